@@ -9,10 +9,12 @@ mod task_container;
 mod event;
 mod task;
 mod api_helpers;
+mod asset_manager;
 pub mod types;
 pub mod math;
 pub mod carbon;
 pub mod natrium;
+pub mod kalium;
 
 use event::{EventFuncTrait, EventFuncTraitWithoutArgs, EventDataTrait};
 use task::{TaskFuncTrait, TaskFuncTraitWithoutArgs, TaskDataTrait};
@@ -266,10 +268,489 @@ pub mod mouse{
     }
 }
 
+/// rigid and soft body physics
+pub mod physics{
+    use compact_str::CompactString;
+    use super::{OE_PHYSICS_, OE_PHYSICS_UPDATE_INFO_, OE_EVENT_HANDLER_};
+    use super::api_helpers;
+    pub use super::kalium::rigidbody::{RigidBody, RigidBodyType, Collider};
+    pub use super::kalium::softbody::SoftBody;
+
+    pub fn add_rigid_body(object_name : &str, body : RigidBody) -> bool {
+        api_helpers::add_rigid_body(object_name, body)
+    }
+    pub fn remove_rigid_body(object_name : &str) -> bool {
+        let mut physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_mut().unwrap().remove_rigid_body(object_name);
+        output
+    }
+    pub fn get_rigid_body(object_name : &str) -> Option<RigidBody> {
+        let physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_ref().unwrap().get_rigid_body(object_name);
+        output
+    }
+    pub fn set_rigid_body(object_name : &str, body : RigidBody) -> bool {
+        let mut physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_mut().unwrap().set_rigid_body(object_name, body);
+        output
+    }
+    pub fn get_contacts(object_name : &str) -> Vec<CompactString> {
+        let physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_ref().unwrap().get_contacts(object_name);
+        output
+    }
+    /// the mesh of the object needs a softbody polygon storage
+    pub fn add_soft_body(object_name : &str, body : SoftBody) -> bool {
+        let mut physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_mut().unwrap().add_soft_body(object_name, body);
+        output
+    }
+    pub fn remove_soft_body(object_name : &str) -> bool {
+        let mut physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_mut().unwrap().remove_soft_body(object_name);
+        output
+    }
+    pub fn get_soft_body(object_name : &str) -> Option<SoftBody> {
+        let physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_ref().unwrap().get_soft_body(object_name);
+        output
+    }
+    pub fn set_soft_body(object_name : &str, body : SoftBody) -> bool {
+        let mut physics = OE_PHYSICS_.lock().unwrap();
+        let output = physics.as_mut().unwrap().set_soft_body(object_name, body);
+        output
+    }
+    pub fn set_gravity(gravity : [f64; 3]){
+        OE_PHYSICS_UPDATE_INFO_.lock().unwrap().as_mut().unwrap().gravity = gravity;
+    }
+    pub fn set_paused(paused : bool){
+        OE_PHYSICS_UPDATE_INFO_.lock().unwrap().as_mut().unwrap().paused = paused;
+    }
+    pub fn set_time_scale(time_scale : f64){
+        OE_PHYSICS_UPDATE_INFO_.lock().unwrap().as_mut().unwrap().time_scale = time_scale;
+    }
+    /// events are named "physics-<object>-collision-begin" and "physics-<object>-collision-end"
+    pub fn get_event(event_name : &str) -> usize {
+        let event_handler = OE_EVENT_HANDLER_.read().unwrap();
+        let output = event_handler.as_ref().unwrap().get_physics_event_id(event_name).unwrap_or(0);
+        output
+    }
+    pub fn get_events() -> Vec<usize> {
+        let event_handler = OE_EVENT_HANDLER_.read().unwrap();
+        let output = event_handler.as_ref().unwrap().get_physics_events();
+        output
+    }
+}
+
 /// scenegraph load elements with associated event
 pub fn load_world_func(filename : &str, func : impl EventFuncTraitWithoutArgs + 'static) -> bool{
     api_helpers::load_world_func_data(filename, move |info: &event::EventInfo, _|{func(info)}, Box::new(0))
 }
 pub fn load_world_func_data(filename : &str, func : impl EventFuncTrait + 'static, data : Box<dyn EventDataTrait>) -> bool{
     api_helpers::load_world_func_data(filename, func, data)
-}
\ No newline at end of file
+}
+
+/// switch the rendered scene, the event "loaded-scene-<name>" happens once it is switched
+pub fn set_loaded_scene(scene_name : &str) -> bool{
+    api_helpers::set_loaded_scene(scene_name)
+}
+/// switch the rendered viewport, the event "loaded-viewport-<name>" happens once it is switched
+pub fn set_loaded_viewport(viewport_name : &str) -> bool{
+    api_helpers::set_loaded_viewport(viewport_name)
+}
+/// remove everything loaded from the file, the event "unloaded-<filename>" happens once it is removed
+pub fn unload_world(filename : &str) -> bool{
+    api_helpers::unload_world(filename)
+}
+
+/// loaded world files are checked for changes every interval seconds, None stops watching
+/// only the changed elements of a file are applied and the event "reloaded-<filename>" happens afterwards
+pub fn set_hot_reload_interval(interval : Option<f32>){
+    OE_FILE_WATCHER_.lock().unwrap().set_interval(interval);
+}
+/// returns false if the file is not loaded
+pub fn reload_world(filename : &str) -> bool{
+    api_helpers::reload_world(filename)
+}
+pub fn set_reload_func(filename : &str, func : impl EventFuncTraitWithoutArgs + 'static) -> bool{
+    api_helpers::set_reload_func_data(filename, move |info: &event::EventInfo, _|{func(info)}, Box::new(0))
+}
+
+/// virtual file system all importers read through
+/// mounted directories, packs and memory buffers are searched by priority, later mounts first on equal priorities
+/// paths no mount contains are read from the host file system
+pub mod vfs{
+    use compact_str::CompactString;
+    use super::OE_VFS_;
+    pub use super::carbon::vfs::{normalize_path, pack_directory, write_pack};
+
+    /// files of the directory appear below the prefix, an empty prefix mounts it at the root
+    pub fn mount_directory(prefix : &str, directory : &str, priority : i32) -> bool {
+        OE_VFS_.write().unwrap().mount_directory(prefix, directory, priority)
+    }
+    /// packs are uncompressed tar archives, returns the number of files or None if the pack cannot be read
+    pub fn mount_pack(prefix : &str, pack : &str, priority : i32) -> Option<usize> {
+        OE_VFS_.write().unwrap().mount_pack(prefix, pack, priority).ok()
+    }
+    pub fn mount_memory(prefix : &str, files : Vec<(CompactString, Vec<u8>)>, priority : i32){
+        OE_VFS_.write().unwrap().mount_memory(prefix, files, priority);
+    }
+    /// removes every mount at the prefix
+    pub fn unmount(prefix : &str) -> bool {
+        OE_VFS_.write().unwrap().unmount(prefix)
+    }
+    pub fn get_mounts() -> Vec<CompactString> {
+        OE_VFS_.read().unwrap().get_mounts()
+    }
+    pub fn read(path : &str) -> Option<Vec<u8>> {
+        OE_VFS_.read().unwrap().read(path)
+    }
+    pub fn read_to_string(path : &str) -> Option<String> {
+        OE_VFS_.read().unwrap().read_to_string(path)
+    }
+    pub fn exists(path : &str) -> bool {
+        OE_VFS_.read().unwrap().exists(path)
+    }
+}
+
+/// images shared by materials, decoded to rgba8 from png, tga and ppm files
+pub mod textures{
+    use super::OE_SCENEGRAPH_;
+    pub use super::carbon::image::{Image, decode_image};
+    pub use super::types::texture::{Texture, TextureSlot, TextureSlotType};
+
+    /// the file is read through the virtual file system, a texture of the same name is replaced
+    pub fn load(name : &str, path : &str, mipmaps : bool) -> bool {
+        let Some(bytes) = super::vfs::read(path) else {return false;};
+        let Ok(image) = decode_image(&bytes) else {return false;};
+        let mut texture = Texture::new();
+        texture.path = path.into();
+        texture.mipmaps = mipmaps;
+        texture.set_image(image);
+        OE_SCENEGRAPH_.lock().unwrap().add_texture(texture, name);
+        true
+    }
+    /// textures loaded on the fly can be used from the next frame on
+    pub fn set_material_texture(material_name : &str, slot : TextureSlotType, texture_name : Option<&str>, uvmap : u8) -> bool {
+        let texture = texture_name.map(|x| TextureSlot{texture : x.into(), uvmap});
+        OE_SCENEGRAPH_.lock().unwrap().set_material_texture(material_name, slot, texture).is_ok()
+    }
+    pub fn get_size(name : &str) -> Option<(u32, u32)> {
+        let texture = OE_SCENEGRAPH_.lock().unwrap().get_texture_by_name(name)?;
+        let size = texture.lock().unwrap().0.get_size();
+        Some(size)
+    }
+}
+
+/// world files loaded by a pool of worker threads
+pub mod assets{
+    use super::{api_helpers, event, EventFuncTraitWithoutArgs, OE_ASSET_MANAGER_};
+    pub use super::asset_manager::{AssetHandle, AssetState, AssetProgress};
+
+    /// number of worker threads, it is only increased once loading started
+    pub fn set_worker_count(count : usize){
+        OE_ASSET_MANAGER_.lock().unwrap().set_worker_count(count);
+    }
+    /// higher priorities are loaded first, loading a path that is already loading or loaded returns the same handle
+    /// the world is unloaded once every clone of the handle is dropped
+    pub fn load(filename : &str, priority : i32) -> AssetHandle {
+        api_helpers::load_asset(filename, priority)
+    }
+    /// the event "loaded-<filename>" happens once the world is added to the scenegraph
+    pub fn load_func(filename : &str, priority : i32, func : impl EventFuncTraitWithoutArgs + 'static) -> AssetHandle {
+        api_helpers::set_load_func_data(filename, move |info: &event::EventInfo, _|{func(info)}, Box::new(0));
+        load(filename, priority)
+    }
+    /// the event "progress-<filename>" happens in every step the progress of the file changed
+    pub fn set_progress_func(filename : &str, func : impl EventFuncTraitWithoutArgs + 'static) -> bool {
+        api_helpers::set_progress_func_data(filename, move |info: &event::EventInfo, _|{func(info)}, Box::new(0))
+    }
+    /// returns false if the file is already loaded or not queued
+    pub fn cancel(filename : &str) -> bool {
+        OE_ASSET_MANAGER_.lock().unwrap().cancel(filename)
+    }
+    pub fn get_progress(filename : &str) -> Option<AssetProgress> {
+        OE_ASSET_MANAGER_.lock().unwrap().get_progress(filename)
+    }
+}
+
+/// keyframe animation of objects, materials and armatures
+pub mod animation{
+    use compact_str::CompactString;
+    use super::{OE_SCENEGRAPH_, OE_EVENT_HANDLER_};
+    use super::api_helpers::{self, with_mesh, with_armature};
+    pub use super::types::armature::{BoneTransform, SkinningMode};
+    pub use super::types::morphtarget::MorphTarget;
+    pub use super::types::animation::{AnimationClip, AnimationPlayer, BoneChannel, BoneKeyframe, PropertyChannel, AnimatedProperty, Interpolation};
+
+    pub fn add_clip(clip : AnimationClip){
+        OE_SCENEGRAPH_.lock().unwrap().animations.add_clip(clip);
+    }
+    pub fn remove_clip(clip_name : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().animations.remove_clip(clip_name)
+    }
+    pub fn get_clips() -> Vec<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().animations.get_clip_names()
+    }
+    /// the target is the name of an object or a material
+    pub fn play(target : &str, clip_name : &str, looping : bool) -> bool {
+        api_helpers::play_animation(target, clip_name, looping, None)
+    }
+    /// blends from the clip currently playing on the target to the new one
+    pub fn crossfade(target : &str, clip_name : &str, looping : bool, duration : f64) -> bool {
+        api_helpers::play_animation(target, clip_name, looping, Some(duration))
+    }
+    pub fn pause(target : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().animations.pause(target)
+    }
+    pub fn resume(target : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().animations.resume(target)
+    }
+    pub fn stop(target : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().animations.stop(target)
+    }
+    pub fn set_speed(target : &str, speed : f64) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().animations.set_speed(target, speed)
+    }
+    pub fn get_player(target : &str) -> Option<AnimationPlayer> {
+        OE_SCENEGRAPH_.lock().unwrap().animations.get_player(target)
+    }
+    /// pauses keyframe and armature animations, independent of physics::set_paused
+    pub fn set_paused(paused : bool){
+        OE_SCENEGRAPH_.lock().unwrap().animations.set_paused(paused);
+    }
+    pub fn is_paused() -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().animations.is_paused()
+    }
+    /// applies to keyframe and armature animations, independent of physics::set_time_scale
+    pub fn set_time_scale(time_scale : f64){
+        OE_SCENEGRAPH_.lock().unwrap().animations.set_time_scale(time_scale);
+    }
+    pub fn get_time_scale() -> f64 {
+        OE_SCENEGRAPH_.lock().unwrap().animations.get_time_scale()
+    }
+    /// events are named "animation-<target>-<clip>-end" and "animation-<target>-<clip>-<marker>",
+    /// they exist after the clip was played on the target once
+    pub fn get_event(event_name : &str) -> usize {
+        let event_handler = OE_EVENT_HANDLER_.read().unwrap();
+        let output = event_handler.as_ref().unwrap().get_animation_event_id(event_name).unwrap_or(0);
+        output
+    }
+    pub fn get_events() -> Vec<usize> {
+        let event_handler = OE_EVENT_HANDLER_.read().unwrap();
+        let output = event_handler.as_ref().unwrap().get_animation_events();
+        output
+    }
+
+
+    pub fn play_armature_animation(object_name : &str, clip_name : &str, looping : bool) -> bool {
+        with_armature(object_name, |armature| armature.play(clip_name, looping)).unwrap_or(false)
+    }
+    pub fn stop_armature_animation(object_name : &str) -> bool {
+        with_armature(object_name, |armature| armature.stop()).is_some()
+    }
+    pub fn set_armature_animation_speed(object_name : &str, speed : f64) -> bool {
+        with_armature(object_name, |armature| armature.player.speed = speed).is_some()
+    }
+    pub fn add_armature_clip(object_name : &str, clip : AnimationClip) -> bool {
+        with_armature(object_name, |armature| armature.add_clip(clip)).is_some()
+    }
+    pub fn get_armature_clips(object_name : &str) -> Vec<CompactString> {
+        with_armature(object_name, |armature| armature.clips.keys().cloned().collect()).unwrap_or_default()
+    }
+    pub fn get_bone_names(object_name : &str) -> Vec<CompactString> {
+        with_armature(object_name, |armature| armature.get_bones().iter().map(|x| x.name.clone()).collect()).unwrap_or_default()
+    }
+    pub fn get_bone_pose(object_name : &str, bone_name : &str) -> Option<BoneTransform> {
+        with_armature(object_name, |armature| armature.get_bone_pose(bone_name))?
+    }
+    /// the pose is relative to the parent bone and is overwritten by a playing animation
+    pub fn set_bone_pose(object_name : &str, bone_name : &str, transform : BoneTransform) -> bool {
+        with_armature(object_name, |armature| armature.set_bone_pose(bone_name, transform)).unwrap_or(false)
+    }
+    pub fn reset_pose(object_name : &str) -> bool {
+        with_armature(object_name, |armature| armature.reset_pose()).is_some()
+    }
+    pub fn set_skinning_mode(object_name : &str, mode : SkinningMode) -> bool {
+        with_armature(object_name, |armature| armature.skinning_mode = mode).is_some()
+    }
+    /// the mesh needs a softbody or dynamic polygon storage
+    pub fn add_morph_target(object_name : &str, target : MorphTarget, weight : f32) -> bool {
+        with_mesh(object_name, |mesh| mesh.add_morph_target(target, weight)).unwrap_or(false)
+    }
+    pub fn get_morph_targets(object_name : &str) -> Vec<CompactString> {
+        with_mesh(object_name, |mesh| Some(mesh.morph_targets_.as_ref()?.get_target_names())).flatten().unwrap_or_default()
+    }
+    pub fn get_morph_weight(object_name : &str, target_name : &str) -> Option<f32> {
+        with_mesh(object_name, |mesh| mesh.morph_targets_.as_ref()?.get_weight(target_name))?
+    }
+    pub fn set_morph_weight(object_name : &str, target_name : &str, weight : f32) -> bool {
+        with_mesh(object_name, |mesh| Some(mesh.morph_targets_.as_mut()?.set_weight(target_name, weight))).flatten().unwrap_or(false)
+    }
+}
+
+/// procedurally generated meshes and on the fly scene content
+pub mod primitives{
+    use super::OE_SCENEGRAPH_;
+    use super::api_helpers::with_mesh;
+    pub use super::types::primitives::*;
+    pub use super::types::polygonstorage::{DynamicPolygonStorage, StaticPolygonStorage};
+    pub use super::types::polygonstoragetrait::{PolygonStorageTrait, PolygonStorageType};
+    pub use super::types::vertexcache::{CacheOptimizationReport, compute_acmr, ACMR_CACHE_SIZE};
+    pub use super::types::quantization::{VertexFormat, PositionFormat, NormalFormat, UvFormat, VertexLayout, QuantizedVertexBuffer};
+    pub use super::types::material::{Material, PbrMaterial, AlphaMode};
+    pub use super::types::mesh::Mesh;
+    pub use super::types::light::{Light, LightType, Attenuation, AreaShape, ShadowSettings};
+    pub use super::types::object_trait::ObjectTrait;
+
+    /// returns false if the scene does not exist, the material is available from the next frame on
+    pub fn add_material(scene_name : &str, material_name : &str, material : Material) -> bool {
+        let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+        if !scenegraph.has_scene(scene_name) {
+            return false;
+        }
+        scenegraph.add_material(material, material_name, scene_name);
+        true
+    }
+    /// None switches the material back to its legacy parameters
+    pub fn set_material_pbr(material_name : &str, pbr : Option<PbrMaterial>) -> bool {
+        let Some(material) = OE_SCENEGRAPH_.lock().unwrap().get_material_by_name(material_name) else {return false;};
+        let mut material = material.lock().unwrap();
+        material.0.pbr = pbr;
+        material.1 = true;
+        true
+    }
+    /// parameters converted from the legacy ones, see PbrMaterial::from_legacy
+    pub fn convert_material_to_pbr(material_name : &str) -> bool {
+        let Some(material) = OE_SCENEGRAPH_.lock().unwrap().get_material_by_name(material_name) else {return false;};
+        let mut material = material.lock().unwrap();
+        material.0.convert_to_pbr();
+        material.1 = true;
+        true
+    }
+    /// returns false if the scene does not exist, the object is available from the next frame on
+    pub fn spawn_object(scene_name : &str, object_name : &str, object : Box<dyn ObjectTrait>) -> bool {
+        let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+        if !scenegraph.has_scene(scene_name) {
+            return false;
+        }
+        scenegraph.add_object(object, object_name, scene_name);
+        true
+    }
+    /// all vertex groups use the given material
+    pub fn spawn_mesh(scene_name : &str, object_name : &str, mut storage : DynamicPolygonStorage, material_name : &str, pos : [f64; 3]) -> bool {
+        for vgroup in storage.get_vgroups_mut(){
+            vgroup.material = Some(material_name.into());
+        }
+        let mut mesh = Mesh::from_storage(storage, PolygonStorageType::Static, object_name);
+        mesh.get_data_mut().pos = pos;
+        spawn_object(scene_name, object_name, Box::new(mesh))
+    }
+    /// shares the polygon storage of the source mesh, returns false if the source does not exist yet or is deformed
+    pub fn spawn_instance(scene_name : &str, object_name : &str, source_name : &str, pos : [f64; 3]) -> bool {
+        let Some(mut mesh) = with_mesh(source_name, |source| source.new_instance()).flatten() else {return false;};
+        mesh.get_data_mut().pos = pos;
+        spawn_object(scene_name, object_name, Box::new(mesh))
+    }
+    /// only meshes with a dynamic polygon storage can be edited, the changes are rendered from the next frame on
+    pub fn edit_mesh<T>(object_name : &str, func : impl FnOnce(&mut DynamicPolygonStorage) -> T) -> Option<T> {
+        with_mesh(object_name, |mesh| Some(func(mesh.get_polygonstorage_unlocked().0.get_dynamic_mut()?)))?
+    }
+}
+
+pub mod prefabs{
+    use compact_str::CompactString;
+    use super::OE_SCENEGRAPH_;
+    pub use super::types::prefab::{Prefab, PrefabInstance, PrefabInstanceOptions, ObjectOverride, prefixed_name};
+    pub use super::types::armature::BoneTransform;
+
+    /// replaces a prefab of the same name, existing instances are not changed
+    pub fn add_prefab(prefab_name : &str, prefab : Prefab){
+        OE_SCENEGRAPH_.lock().unwrap().add_prefab(prefab, prefab_name);
+    }
+    pub fn has_prefab(prefab_name : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().has_prefab(prefab_name)
+    }
+    /// returns false if the scene or prefab does not exist or the instance name is taken
+    /// the elements are named "<instance_name>/<name in the prefab>" and available from the next frame on
+    pub fn instantiate(scene_name : &str, instance_name : &str, prefab_name : &str, options : PrefabInstanceOptions) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().instantiate_prefab(super::types::prefab::PendingPrefabInstance{
+            name : instance_name.into(),
+            prefab : prefab_name.into(),
+            scene : scene_name.into(),
+            options,
+        }).is_ok()
+    }
+    /// removes every object and material of the instance
+    pub fn despawn(instance_name : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().despawn_prefab_instance(instance_name)
+    }
+    pub fn get_instance_objects(instance_name : &str) -> Vec<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().get_prefab_instance(instance_name).map(|x| x.objects.clone()).unwrap_or_default()
+    }
+}
+
+pub mod snapshots{
+    use compact_str::CompactString;
+    use super::OE_SCENEGRAPH_;
+    pub use super::types::snapshot::{Snapshot, ObjectState, LightState, MaterialState, SNAPSHOT_VERSION};
+
+    /// user data is saved with every snapshot, tasks store their own state here
+    pub fn set_user_data(key : &str, value : &str){
+        OE_SCENEGRAPH_.lock().unwrap().set_user_data(key, value);
+    }
+    pub fn get_user_data(key : &str) -> Option<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().get_user_data(key).cloned()
+    }
+    pub fn remove_user_data(key : &str) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().remove_user_data(key)
+    }
+
+    /// object transforms and visibility, light and material parameters, the loaded scene and viewport and the user data
+    pub fn take() -> Snapshot {
+        OE_SCENEGRAPH_.lock().unwrap().take_snapshot()
+    }
+    /// returns false if a world the snapshot was taken with is not loaded
+    pub fn restore(snapshot : &Snapshot) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().restore_snapshot(snapshot).is_ok()
+    }
+    pub fn save(filename : &str) -> bool {
+        std::fs::write(filename, take().to_csl()).is_ok()
+    }
+    /// returns false if the file cannot be read or parsed, has another version or its worlds are not loaded
+    pub fn load(filename : &str) -> bool {
+        let Some(input_str) = super::OE_VFS_.read().unwrap().read_to_string(filename) else {return false;};
+        match Snapshot::from_csl(&input_str) {
+            Ok(snapshot) => restore(&snapshot),
+            Err(_) => false,
+        }
+    }
+}
+
+pub mod transactions{
+    use compact_str::CompactString;
+    use super::OE_SCENEGRAPH_;
+    pub use super::types::transaction::{Transaction, EditOperation};
+
+    /// applies all edits of the transaction or none of them and records how to undo it
+    pub fn apply(transaction : Transaction) -> bool {
+        OE_SCENEGRAPH_.lock().unwrap().apply_transaction(transaction).is_ok()
+    }
+    /// returns the name of the undone transaction
+    pub fn undo() -> Option<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().undo().ok()
+    }
+    /// returns the name of the redone transaction, applying a new transaction clears the redo stack
+    pub fn redo() -> Option<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().redo().ok()
+    }
+    pub fn get_undo_names() -> Vec<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().get_undo_names()
+    }
+    pub fn get_redo_names() -> Vec<CompactString> {
+        OE_SCENEGRAPH_.lock().unwrap().get_redo_names()
+    }
+    pub fn clear_history(){
+        OE_SCENEGRAPH_.lock().unwrap().clear_history();
+    }
+}
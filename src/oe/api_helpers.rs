@@ -12,6 +12,12 @@ use super::event::{EventFuncTrait, EventDataTrait};
 use super::task::{TaskFuncTrait, TaskDataTrait};
 use super::winsys_sdl2::*;
 use super::natrium::renderer_compat::*;
+use super::kalium::physics_world::*;
+use super::kalium::rigidbody::RigidBody;
+use super::types::armature::Armature;
+use super::types::mesh::Mesh;
+use super::types::animation::AnimationEvents;
+use super::asset_manager::AssetHandle;
 
 type TaskEnum = super::task::TaskEnum;
 
@@ -49,6 +55,12 @@ pub fn init(x: u32, y: u32, title: &str) -> bool {
         let mut global_renderer =OE_RENDERER_.lock().unwrap();
         global_renderer.replace(Box::new(renderer_compat));
     }
+
+    let physics_init_info : PhysicsInitInfo = Default::default();
+    {
+        let mut global_physics = OE_PHYSICS_.lock().unwrap();
+        global_physics.replace(Box::new(PhysicsWorld::new(physics_init_info)));
+    }
     if OE_USE_MULTIPLE_THREADS_{
 
         let start_cond = Arc::clone(&OE_START_CONDITION_);
@@ -60,7 +72,9 @@ pub fn init(x: u32, y: u32, title: &str) -> bool {
             while !(OE_DONE_.load(Ordering::Relaxed)) {
                 start_cond.update();
                 update_tasks();
+                update_animations();
                 update_objects();
+                update_physics();
                 end_cond.update();
                 update_scenegraph();
                 update_events();
@@ -75,6 +89,8 @@ pub fn init(x: u32, y: u32, title: &str) -> bool {
     *OE_WINSYS_INIT_INFO_.lock().unwrap() = Some(init_info);
     *OE_WINSYS_UPDATE_INFO_.lock().unwrap() = Some(update_info);
     *OE_RENDERER_UPDATE_INFO_.lock().unwrap() = Some(Default::default());
+    *OE_PHYSICS_INIT_INFO_.lock().unwrap() = Some(physics_init_info);
+    *OE_PHYSICS_UPDATE_INFO_.lock().unwrap() = Some(Default::default());
     true
 }
 
@@ -85,10 +101,45 @@ pub fn set_title(name : &str){
 
 fn update_objects(){
     let scenegraph = OE_SCENEGRAPH_.lock().unwrap();
-    for id in scenegraph.get_object_ids(){
+    let ids = scenegraph.get_object_ids();
+    // instances share polygon storages, so the changed flags are reset before any mesh sets them again
+    for id in &ids{
+        let object_mutexed = scenegraph.get_object(*id);
+        let mut object = object_mutexed.lock().unwrap();
+        if let Some(mesh) = object.0.get_mesh_mut(){
+            mesh.get_polygonstorage_unlocked().1 = false;
+        }
+    }
+    for id in ids{
         let object_mutexed = scenegraph.get_object(id);
         let mut object = object_mutexed.lock().unwrap();
-        object.0.update();
+        if object.0.update(){
+            object.1 = true;
+        }
+    }
+}
+
+fn update_animations(){
+    let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    let events = scenegraph.update_animations();
+    drop(scenegraph);
+
+    for event in &events{
+        super::broadcast_event_by_id(*event);
+    }
+}
+
+fn update_physics(){
+    let update_info = OE_PHYSICS_UPDATE_INFO_.lock().unwrap().unwrap();
+
+    let scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    let mut physics = OE_PHYSICS_.lock().unwrap();
+    let events = physics.as_mut().unwrap().update_data(&scenegraph, update_info);
+    drop(physics);
+    drop(scenegraph);
+
+    for event in &events{
+        super::broadcast_event_by_id(*event);
     }
 }
 
@@ -186,7 +237,9 @@ fn update_tasks(){
 pub fn step() -> bool {
     if !OE_USE_MULTIPLE_THREADS_ {
         update_tasks();
+        update_animations();
         update_objects();
+        update_physics();
     }
     else {
         OE_START_CONDITION_.update();
@@ -216,6 +269,8 @@ pub fn step() -> bool {
         }
     }
     drop(unsync_threads);
+    update_hot_reload();
+    update_assets();
 
     //update winsys events
     OE_WINSYS_.with_borrow_mut(|winsys|winsys.update_events_single_thread());
@@ -281,18 +336,188 @@ pub fn set_event_func_data(event_name : &str, func : impl EventFuncTrait + 'stat
 }
 
 pub fn load_world_func_data(filename : &str, func : impl EventFuncTrait + 'static, data : Box<dyn EventDataTrait>) -> bool{
-    let filename_owned = filename.to_owned();
+    let output = set_load_func_data(filename, func, data);
+    // worlds loaded without a handle stay loaded until they are unloaded explicitly
+    let handle = load_asset(filename, 0);
+    OE_ASSET_MANAGER_.lock().unwrap().pin(handle);
+    output
+}
+
+pub fn load_asset(filename : &str, priority : i32) -> AssetHandle{
+    let load_event = get_or_create_load_event(filename);
+    let progress_event = get_or_create_progress_event(filename);
+    // the scenegraph stays locked, so a world handed over by a worker in the meantime is not loaded twice
+    let scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    let is_loaded = scenegraph.has_world(filename) || scenegraph.has_pending_world(filename);
+    OE_ASSET_MANAGER_.lock().unwrap().load(filename, priority, load_event, progress_event, is_loaded)
+}
+
+pub fn set_load_func_data(filename : &str, func : impl EventFuncTrait + 'static, data : Box<dyn EventDataTrait>) -> bool{
+    let event_id = get_or_create_load_event(filename);
     let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
-    let event_id = event_handler.as_mut().unwrap().create_load_event(filename);
-    let output = event_handler.as_mut().unwrap().set_event_func(&event_id, func, data).unwrap_or(false);
+    event_handler.as_mut().unwrap().set_event_func(&event_id, func, data).unwrap_or(false)
+}
+
+pub fn set_progress_func_data(filename : &str, func : impl EventFuncTrait + 'static, data : Box<dyn EventDataTrait>) -> bool{
+    let event_id = get_or_create_progress_event(filename);
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    event_handler.as_mut().unwrap().set_event_func(&event_id, func, data).unwrap_or(false)
+}
+
+fn get_or_create_progress_event(filename : &str) -> usize{
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    match event_handler.as_ref().unwrap().get_progress_event_id(filename){
+        Some(event_id) => event_id,
+        None => event_handler.as_mut().unwrap().create_progress_event(filename)
+    }
+}
+
+// progress events are broadcast once per step, worlds without handles are unloaded
+fn update_assets(){
+    let (events, freed) = OE_ASSET_MANAGER_.lock().unwrap().update();
+    for event in &events{
+        super::broadcast_event_by_id(*event);
+    }
+    for filename in freed{
+        unload_world(&filename);
+    }
+}
+
+// the load events of scene and viewport switches are created once and reused
+pub fn set_loaded_scene(scene_name : &str) -> bool{
+    let event_id = get_or_create_load_event(&format!("scene-{}", scene_name));
+    let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    scenegraph.set_loaded_scene(scene_name, event_id)
+}
+
+pub fn set_loaded_viewport(viewport_name : &str) -> bool{
+    let event_id = get_or_create_load_event(&format!("viewport-{}", viewport_name));
+    let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    scenegraph.set_loaded_viewport(viewport_name, event_id)
+}
+
+pub fn unload_world(filename : &str) -> bool{
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    let event_id = match event_handler.as_ref().unwrap().get_unload_event_id(filename){
+        Some(event_id) => event_id,
+        None => event_handler.as_mut().unwrap().create_unload_event(filename)
+    };
     drop(event_handler);
+    let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    scenegraph.unload_world(filename, event_id)
+}
+
+fn get_or_create_load_event(event_name : &str) -> usize{
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    match event_handler.as_ref().unwrap().get_load_event_id(event_name){
+        Some(event_id) => event_id,
+        None => event_handler.as_mut().unwrap().create_load_event(event_name)
+    }
+}
+
+// changed world files are interpreted again on an unsync thread
+fn update_hot_reload(){
+    let mut file_watcher = OE_FILE_WATCHER_.lock().unwrap();
+    if !file_watcher.is_due() {
+        return;
+    }
+    let files = OE_SCENEGRAPH_.lock().unwrap().get_world_files();
+    let changed = file_watcher.poll(&files, &OE_VFS_.read().unwrap());
+    drop(file_watcher);
+    for filename in changed{
+        reload_world(&filename);
+    }
+}
+
+pub fn reload_world(filename : &str) -> bool{
+    if !OE_SCENEGRAPH_.lock().unwrap().has_world(filename) {
+        return false;
+    }
+    let event_id = get_or_create_reload_event(filename);
+    let filename_owned = filename.to_owned();
     let handle = thread::spawn(move ||{
-            let new_data = interpret_file(&filename_owned);
+            // files are often saved while they are still being edited
+            let new_data = match interpret_file(&filename_owned){
+                Ok(new_data) => new_data,
+                Err(error) => {
+                    println!("[UNSYNC THREAD] Could not reload world from \"{:?}\": {}", filename_owned, error);
+                    return;
+                }
+            };
             let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
-            scenegraph.add_interpreted(new_data, event_id);
-            println!("[UNSYNC THREAD] Loaded world from \"{:?}\"", filename_owned);
+            scenegraph.add_reloaded(new_data, event_id);
+            println!("[UNSYNC THREAD] Reloaded world from \"{:?}\"", filename_owned);
     });
     let mut threadhandles = OE_UNSYNC_THREADS_.lock().unwrap();
     threadhandles.push((handle, false));
-    output
-}
\ No newline at end of file
+    true
+}
+
+pub fn set_reload_func_data(filename : &str, func : impl EventFuncTrait + 'static, data : Box<dyn EventDataTrait>) -> bool{
+    let event_id = get_or_create_reload_event(filename);
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    event_handler.as_mut().unwrap().set_event_func(&event_id, func, data).unwrap_or(false)
+}
+
+fn get_or_create_reload_event(filename : &str) -> usize{
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    match event_handler.as_ref().unwrap().get_reload_event_id(filename){
+        Some(event_id) => event_id,
+        None => event_handler.as_mut().unwrap().create_reload_event(filename)
+    }
+}
+
+pub fn add_rigid_body(object_name : &str, body : RigidBody) -> bool{
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    let mut events = [0, 0];
+    for (id, suffix) in ["-collision-begin", "-collision-end"].iter().enumerate(){
+        let event_name = object_name.to_string() + suffix;
+        events[id] = match event_handler.as_ref().unwrap().get_physics_event_id(&event_name){
+            Some(event_id) => event_id,
+            None => event_handler.as_mut().unwrap().create_physics_event(&event_name)
+        };
+    }
+    drop(event_handler);
+
+    let mut physics = OE_PHYSICS_.lock().unwrap();
+    physics.as_mut().unwrap().add_rigid_body(object_name, body, (events[0], events[1]))
+}
+
+pub fn with_mesh<T>(object_name : &str, func : impl FnOnce(&mut Mesh) -> T) -> Option<T>{
+    let scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    let object = scenegraph.get_object_by_name(object_name)?;
+    drop(scenegraph);
+    let mut object_unlocked = object.lock().unwrap();
+    Some(func(object_unlocked.0.get_mesh_mut()?))
+}
+
+pub fn with_armature<T>(object_name : &str, func : impl FnOnce(&mut Armature) -> T) -> Option<T>{
+    with_mesh(object_name, |mesh| Some(func(mesh.armature_.as_deref_mut()?)))?
+}
+
+// creates the end and marker events of the clip for this target, then starts it
+pub fn play_animation(target : &str, clip_name : &str, looping : bool, crossfade : Option<f64>) -> bool{
+    let scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    let Some(clip) = scenegraph.animations.get_clip(clip_name) else {return false;};
+    drop(scenegraph);
+
+    let mut event_handler = OE_EVENT_HANDLER_.write().unwrap();
+    let mut get_or_create = |suffix : &str| -> usize{
+        let event_name = format!("{}-{}-{}", target, clip_name, suffix);
+        match event_handler.as_ref().unwrap().get_animation_event_id(&event_name){
+            Some(event_id) => event_id,
+            None => event_handler.as_mut().unwrap().create_animation_event(&event_name)
+        }
+    };
+    let events = AnimationEvents{
+        end : get_or_create("end"),
+        markers : clip.markers.iter().map(|(_, marker)| (marker.clone(), get_or_create(marker))).collect(),
+    };
+    drop(event_handler);
+
+    let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    match crossfade{
+        Some(duration) => scenegraph.animations.crossfade(target, clip_name, looping, duration, events),
+        None => scenegraph.animations.play(target, clip_name, looping, events)
+    }
+}
@@ -0,0 +1,450 @@
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::io::Read;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use compact_str::CompactString;
+
+use super::carbon::interpreter::Interpreter;
+use super::global_variables::{OE_SCENEGRAPH_, OE_VFS_, OE_DONE_};
+
+const READ_CHUNK_SIZE : usize = 1 << 16;
+const DEFAULT_WORKER_COUNT : usize = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AssetState{
+    #[default]
+    Queued,
+    Reading,
+    Interpreting,
+    // handed to the scenegraph, the load event happens on its next update
+    Loaded,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AssetProgress{
+    pub state : AssetState,
+    pub bytes_read : u64,
+    pub total_bytes : u64,
+    pub elements_interpreted : usize,
+}
+
+#[derive(Debug, Default)]
+struct AssetStatus{
+    state : Mutex<AssetState>,
+    cancelled : AtomicBool,
+    bytes_read : AtomicU64,
+    total_bytes : AtomicU64,
+    elements_interpreted : Arc<AtomicUsize>,
+    load_event : usize,
+    progress_event : usize,
+}
+
+impl AssetStatus{
+    fn get_state(&self) -> AssetState{
+        *self.state.lock().unwrap()
+    }
+    fn set_state(&self, state : AssetState){
+        *self.state.lock().unwrap() = state;
+    }
+    fn progress(&self) -> AssetProgress{
+        AssetProgress{
+            state : self.get_state(),
+            bytes_read : self.bytes_read.load(Ordering::Relaxed),
+            total_bytes : self.total_bytes.load(Ordering::Relaxed),
+            elements_interpreted : self.elements_interpreted.load(Ordering::Relaxed),
+        }
+    }
+    // loaded assets cannot be cancelled anymore
+    fn cancel(&self) -> bool{
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, AssetState::Loaded | AssetState::Cancelled | AssetState::Failed) {
+            return false;
+        }
+        self.cancelled.store(true, Ordering::Relaxed);
+        *state = AssetState::Cancelled;
+        true
+    }
+}
+
+#[derive(Debug)]
+struct AssetHandleInner{
+    path : CompactString,
+    freed : Sender<CompactString>,
+}
+
+// the asset manager locks are never taken here, handles can be dropped anywhere
+impl Drop for AssetHandleInner{
+    fn drop(&mut self){
+        let _ = self.freed.send(self.path.clone());
+    }
+}
+
+// the world of the asset is unloaded after the last clone of its handle is dropped
+#[derive(Clone, Debug)]
+pub struct AssetHandle{
+    inner : Arc<AssetHandleInner>,
+}
+
+impl AssetHandle{
+    pub fn path(&self) -> &str{
+        &self.inner.path
+    }
+}
+
+struct AssetJob{
+    priority : i32,
+    order : u64,
+    path : CompactString,
+    status : Arc<AssetStatus>,
+}
+
+// higher priorities first, equal priorities in the order they were queued
+impl AssetJob{
+    fn key(&self) -> (i32, Reverse<u64>){
+        (self.priority, Reverse(self.order))
+    }
+}
+impl PartialEq for AssetJob{
+    fn eq(&self, other : &Self) -> bool{
+        self.key() == other.key()
+    }
+}
+impl Eq for AssetJob{}
+impl PartialOrd for AssetJob{
+    fn partial_cmp(&self, other : &Self) -> Option<CmpOrdering>{
+        Some(self.cmp(other))
+    }
+}
+impl Ord for AssetJob{
+    fn cmp(&self, other : &Self) -> CmpOrdering{
+        self.key().cmp(&other.key())
+    }
+}
+
+#[derive(Default)]
+struct AssetQueue{
+    jobs : Mutex<BinaryHeap<AssetJob>>,
+    available : Condvar,
+}
+
+impl AssetQueue{
+    fn push(&self, job : AssetJob){
+        self.jobs.lock().unwrap().push(job);
+        self.available.notify_one();
+    }
+    fn pop(&self, timeout : Duration) -> Option<AssetJob>{
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.is_empty() {
+            jobs = self.available.wait_timeout(jobs, timeout).unwrap().0;
+        }
+        jobs.pop()
+    }
+    fn raise_priority(&self, path : &str, priority : i32){
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut jobs_vec = std::mem::take(&mut *jobs).into_vec();
+        for job in jobs_vec.iter_mut().filter(|x| x.path == path){
+            job.priority = job.priority.max(priority);
+        }
+        *jobs = jobs_vec.into();
+    }
+    fn remove(&self, path : &str){
+        self.jobs.lock().unwrap().retain(|x| x.path != path);
+    }
+}
+
+struct AssetEntry{
+    handle : Weak<AssetHandleInner>,
+    status : Arc<AssetStatus>,
+    reported : Option<AssetProgress>,
+}
+
+pub struct AssetManager{
+    worker_count : usize,
+    workers : Vec<thread::JoinHandle<()>>,
+    queue : Arc<AssetQueue>,
+    order : u64,
+    assets : HashMap<CompactString, AssetEntry>,
+    pinned : HashMap<CompactString, AssetHandle>,
+    freed_sender : Sender<CompactString>,
+    freed_receiver : Receiver<CompactString>,
+}
+
+impl Default for AssetManager{
+    fn default() -> Self{
+        let (freed_sender, freed_receiver) = channel();
+        AssetManager{
+            worker_count : DEFAULT_WORKER_COUNT,
+            workers : vec![],
+            queue : Default::default(),
+            order : 0,
+            assets : Default::default(),
+            pinned : Default::default(),
+            freed_sender,
+            freed_receiver,
+        }
+    }
+}
+
+impl AssetManager{
+    // workers are started on the first load, changing the count afterwards only adds workers
+    pub fn set_worker_count(&mut self, count : usize){
+        self.worker_count = count.max(1);
+    }
+
+    // loads of a path that is queued, loading or still loaded share one handle
+    pub fn load(&mut self, path : &str, priority : i32, load_event : usize, progress_event : usize, is_loaded : bool) -> AssetHandle{
+        if let Some(entry) = self.assets.get(path){
+            if let Some(inner) = entry.handle.upgrade(){
+                let reusable = match entry.status.get_state(){
+                    AssetState::Queued => {
+                        self.queue.raise_priority(path, priority);
+                        true
+                    }
+                    AssetState::Reading | AssetState::Interpreting => true,
+                    AssetState::Loaded => is_loaded,
+                    AssetState::Cancelled | AssetState::Failed => false,
+                };
+                if !reusable {
+                    let status = self.enqueue(path, priority, load_event, progress_event);
+                    let entry = self.assets.get_mut(path).unwrap();
+                    (entry.status, entry.reported) = (status, None);
+                }
+                return AssetHandle{inner};
+            }
+        }
+        let inner = Arc::new(AssetHandleInner{path : path.into(), freed : self.freed_sender.clone()});
+        let status = self.enqueue(path, priority, load_event, progress_event);
+        self.assets.insert(path.into(), AssetEntry{handle : Arc::downgrade(&inner), status, reported : None});
+        AssetHandle{inner}
+    }
+
+    fn enqueue(&mut self, path : &str, priority : i32, load_event : usize, progress_event : usize) -> Arc<AssetStatus>{
+        while self.workers.len() < self.worker_count {
+            let queue = Arc::clone(&self.queue);
+            self.workers.push(thread::spawn(move || worker_loop(queue)));
+        }
+        let status = Arc::new(AssetStatus{load_event, progress_event, ..Default::default()});
+        self.order += 1;
+        self.queue.push(AssetJob{priority, order : self.order, path : path.into(), status : Arc::clone(&status)});
+        status
+    }
+
+    // the asset stays loaded until it is unloaded explicitly
+    pub fn pin(&mut self, handle : AssetHandle){
+        self.pinned.insert(handle.inner.path.clone(), handle);
+    }
+
+    pub fn cancel(&mut self, path : &str) -> bool{
+        let Some(entry) = self.assets.get(path) else {return false;};
+        if !entry.status.cancel() {
+            return false;
+        }
+        self.queue.remove(path);
+        self.pinned.remove(path);
+        true
+    }
+
+    pub fn get_progress(&self, path : &str) -> Option<AssetProgress>{
+        self.assets.get(path).map(|x| x.status.progress())
+    }
+
+    // returns the progress events of assets whose progress changed since the last update
+    // and the paths of loaded assets without handles, which have to be unloaded
+    pub fn update(&mut self) -> (Vec<usize>, Vec<CompactString>){
+        let mut events = vec![];
+        for entry in self.assets.values_mut(){
+            let progress = entry.status.progress();
+            if entry.reported != Some(progress) {
+                entry.reported = Some(progress);
+                events.push(entry.status.progress_event);
+            }
+        }
+
+        let mut freed = vec![];
+        for path in self.freed_receiver.try_iter(){
+            // the path may have been loaded again in the meantime
+            let Some(entry) = self.assets.get(&path) else {continue;};
+            if entry.handle.strong_count() > 0 {
+                continue;
+            }
+            let entry = self.assets.remove(&path).unwrap();
+            if entry.status.cancel() {
+                self.queue.remove(&path);
+            }
+            else if entry.status.get_state() == AssetState::Loaded {
+                freed.push(path);
+            }
+        }
+        (events, freed)
+    }
+}
+
+fn worker_loop(queue : Arc<AssetQueue>){
+    while !OE_DONE_.load(Ordering::Relaxed) {
+        if let Some(job) = queue.pop(Duration::from_millis(100)){
+            load_asset(job);
+        }
+    }
+}
+
+fn read_file(path : &str, status : &AssetStatus) -> Option<String>{
+    let (mut file, size) = OE_VFS_.read().unwrap().open(path)?;
+    status.total_bytes.store(size, Ordering::Relaxed);
+    let mut bytes = vec![];
+    let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+    loop{
+        if status.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let size = file.read(&mut chunk).ok()?;
+        if size == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..size]);
+        status.bytes_read.fetch_add(size as u64, Ordering::Relaxed);
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn load_asset(job : AssetJob){
+    let status = &job.status;
+    if status.cancelled.load(Ordering::Relaxed) {
+        return;
+    }
+    status.set_state(AssetState::Reading);
+    let Some(input_str) = read_file(&job.path, status) else {
+        if !status.cancelled.load(Ordering::Relaxed) {
+            status.set_state(AssetState::Failed);
+            println!("[ASSET THREAD] Could not read world from \"{:?}\"", job.path);
+        }
+        return;
+    };
+
+    status.set_state(AssetState::Interpreting);
+    let mut new_data = Interpreter::default();
+    new_data.get_data().elements_interpreted = Arc::clone(&status.elements_interpreted);
+    if let Err(error) = new_data.interpret(&input_str) {
+        status.set_state(AssetState::Failed);
+        println!("[ASSET THREAD] Could not interpret world from \"{:?}\": {}", job.path, error);
+        return;
+    }
+    new_data.get_data().source = job.path.clone();
+
+    // the state stays locked during the hand-over, a cancel happens before it or fails
+    let mut scenegraph = OE_SCENEGRAPH_.lock().unwrap();
+    let mut state = status.state.lock().unwrap();
+    if status.cancelled.load(Ordering::Relaxed) {
+        return;
+    }
+    scenegraph.add_interpreted(new_data, status.load_event);
+    *state = AssetState::Loaded;
+    println!("[ASSET THREAD] Loaded world from \"{:?}\"", job.path);
+}
+
+#[cfg(test)]
+pub mod assetmanagertest{
+    use super::*;
+
+    // without workers the jobs stay queued and the states are set by the tests
+    fn manager() -> AssetManager{
+        AssetManager{worker_count : 0, ..Default::default()}
+    }
+
+    fn queued(manager : &AssetManager) -> Vec<CompactString>{
+        manager.queue.jobs.lock().unwrap().iter().map(|x| x.path.clone()).collect()
+    }
+
+    fn set_state(manager : &AssetManager, path : &str, state : AssetState){
+        manager.assets[path].status.set_state(state);
+    }
+
+    #[test]
+    fn priority_order(){
+        let queue = AssetQueue::default();
+        for (order, (path, priority)) in [("a", 0), ("b", 2), ("c", 0), ("d", 1), ("e", 0)].into_iter().enumerate(){
+            queue.push(AssetJob{priority, order : order as u64, path : path.into(), status : Default::default()});
+        }
+        queue.raise_priority("c", 5);
+        queue.raise_priority("b", -1);
+        queue.remove("e");
+        let popped : Vec<_> = std::iter::from_fn(|| queue.pop(Duration::ZERO)).map(|x| x.path).collect();
+        assert_eq!(popped, ["c", "b", "d", "a"]);
+    }
+
+    #[test]
+    fn cancel(){
+        let mut manager = manager();
+        let _handle = manager.load("a.csl", 0, 1, 2, false);
+        assert!(manager.cancel("a.csl"));
+        assert!(!manager.cancel("a.csl"));
+        assert!(!manager.cancel("b.csl"));
+        assert_eq!(manager.get_progress("a.csl").unwrap().state, AssetState::Cancelled);
+        assert!(queued(&manager).is_empty());
+
+        // loading it again queues it again
+        let _handle = manager.load("a.csl", 0, 1, 2, false);
+        assert_eq!(manager.get_progress("a.csl").unwrap().state, AssetState::Queued);
+        assert_eq!(queued(&manager), ["a.csl"]);
+
+        // loaded assets cannot be cancelled anymore
+        set_state(&manager, "a.csl", AssetState::Loaded);
+        assert!(!manager.cancel("a.csl"));
+    }
+
+    #[test]
+    fn shared_handles(){
+        let mut manager = manager();
+        let handle = manager.load("a.csl", 0, 1, 2, false);
+        for state in [AssetState::Queued, AssetState::Reading, AssetState::Interpreting]{
+            set_state(&manager, "a.csl", state);
+            let other = manager.load("a.csl", 1, 1, 2, false);
+            assert!(Arc::ptr_eq(&handle.inner, &other.inner));
+        }
+        // a world that was just handed to the scenegraph is not loaded again
+        set_state(&manager, "a.csl", AssetState::Loaded);
+        drop(manager.load("a.csl", 0, 1, 2, true));
+        assert_eq!(queued(&manager), ["a.csl"]);
+        // but a world that was unloaded is
+        drop(manager.load("a.csl", 0, 1, 2, false));
+        assert_eq!(queued(&manager), ["a.csl", "a.csl"]);
+    }
+
+    #[test]
+    fn refcount(){
+        let mut manager = manager();
+        let first = manager.load("a.csl", 0, 1, 2, false);
+        let second = manager.load("a.csl", 0, 1, 2, false);
+        let copy = second.clone();
+        assert_eq!(queued(&manager), ["a.csl"]);
+        set_state(&manager, "a.csl", AssetState::Loaded);
+
+        // progress events are reported once per change
+        assert_eq!(manager.update(), (vec![2], vec![]));
+        assert_eq!(manager.update(), (vec![], vec![]));
+
+        drop(first);
+        drop(second);
+        assert_eq!(manager.update().1, Vec::<CompactString>::new());
+        drop(copy);
+        assert_eq!(manager.update().1, ["a.csl"]);
+        assert!(manager.get_progress("a.csl").is_none());
+
+        // pinned assets keep their world, queued assets without handles are cancelled
+        let pinned = manager.load("b.csl", 0, 1, 2, false);
+        manager.pin(pinned);
+        let dropped = manager.load("c.csl", 0, 1, 2, false);
+        let status = Arc::clone(&manager.assets["c.csl"].status);
+        drop(dropped);
+        assert_eq!(manager.update().1, Vec::<CompactString>::new());
+        assert!(manager.get_progress("b.csl").is_some());
+        assert_eq!(status.get_state(), AssetState::Cancelled);
+        assert_eq!(queued(&manager), ["a.csl", "b.csl"]);
+    }
+}
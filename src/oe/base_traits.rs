@@ -2,6 +2,9 @@ use compact_str::CompactString;
 
 use super::dummy_structs::*;
 use super::types::globalscenegraphchanged::*;
+use super::types::global_scenegraph::GlobalScenegraph;
+use super::kalium::rigidbody::RigidBody;
+use super::kalium::softbody::SoftBody;
 
 pub trait WinsysBaseTrait{
     fn update_events_single_thread(&mut self);
@@ -40,4 +43,56 @@ impl RendererBaseTrait for DummyRenderer{
     fn get_name(&self) -> CompactString{
         CompactString::new("DummyRenderer")
     }
+}
+
+pub trait PhysicsBaseTrait : Send{
+    // returns the collision events that have to be broadcasted
+    fn update_data(&mut self, scenegraph : &GlobalScenegraph, update_info : PhysicsUpdateInfo) -> Vec<usize>;
+    fn add_rigid_body(&mut self, object_name : &str, body : RigidBody, events : (usize, usize)) -> bool;
+    fn remove_rigid_body(&mut self, object_name : &str) -> bool;
+    fn get_rigid_body(&self, object_name : &str) -> Option<RigidBody>;
+    fn set_rigid_body(&mut self, object_name : &str, body : RigidBody) -> bool;
+    fn get_contacts(&self, object_name : &str) -> Vec<CompactString>;
+    fn add_soft_body(&mut self, object_name : &str, body : SoftBody) -> bool;
+    fn remove_soft_body(&mut self, object_name : &str) -> bool;
+    fn get_soft_body(&self, object_name : &str) -> Option<SoftBody>;
+    fn set_soft_body(&mut self, object_name : &str, body : SoftBody) -> bool;
+    fn get_name(&self) -> CompactString;
+}
+
+pub struct DummyPhysics;
+impl PhysicsBaseTrait for DummyPhysics{
+    fn update_data(&mut self, _ : &GlobalScenegraph, _ : PhysicsUpdateInfo) -> Vec<usize>{
+        vec![]
+    }
+    fn add_rigid_body(&mut self, _ : &str, _ : RigidBody, _ : (usize, usize)) -> bool{
+        false
+    }
+    fn remove_rigid_body(&mut self, _ : &str) -> bool{
+        false
+    }
+    fn get_rigid_body(&self, _ : &str) -> Option<RigidBody>{
+        None
+    }
+    fn set_rigid_body(&mut self, _ : &str, _ : RigidBody) -> bool{
+        false
+    }
+    fn get_contacts(&self, _ : &str) -> Vec<CompactString>{
+        vec![]
+    }
+    fn add_soft_body(&mut self, _ : &str, _ : SoftBody) -> bool{
+        false
+    }
+    fn remove_soft_body(&mut self, _ : &str) -> bool{
+        false
+    }
+    fn get_soft_body(&self, _ : &str) -> Option<SoftBody>{
+        None
+    }
+    fn set_soft_body(&mut self, _ : &str, _ : SoftBody) -> bool{
+        false
+    }
+    fn get_name(&self) -> CompactString{
+        CompactString::new("DummyPhysics")
+    }
 }
\ No newline at end of file
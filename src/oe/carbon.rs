@@ -1,3 +1,7 @@
 pub mod interpreter;
 pub mod parser;
 pub mod lexer;
+pub mod filewatcher;
+pub mod vfs;
+pub mod inflate;
+pub mod image;
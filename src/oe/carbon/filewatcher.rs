@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+use compact_str::CompactString;
+
+use super::vfs::Vfs;
+
+// polls the modification times of files, no notifications from the operating system are needed
+#[derive(Debug)]
+pub struct FileWatcher{
+    // None disables polling
+    interval : Option<Duration>,
+    last_poll : Instant,
+    modified : HashMap<CompactString, SystemTime>,
+}
+
+impl Default for FileWatcher{
+    fn default() -> Self {
+        FileWatcher{interval : None, last_poll : Instant::now(), modified : Default::default()}
+    }
+}
+
+impl FileWatcher{
+    pub fn set_interval(&mut self, interval : Option<f32>){
+        self.interval = interval.map(|x| Duration::from_secs_f32(x.max(0.0)));
+        self.last_poll = Instant::now();
+    }
+
+    pub fn is_due(&mut self) -> bool{
+        let Some(interval) = self.interval else {return false;};
+        if self.last_poll.elapsed() < interval {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        true
+    }
+
+    // returns the files modified since the last poll, files seen for the first time are only remembered
+    pub fn poll(&mut self, files : &[CompactString], vfs : &Vfs) -> Vec<CompactString>{
+        self.modified.retain(|name, _| files.contains(name));
+        files.iter().filter(|name| {
+            let Some(modified) = vfs.modified(name) else {return false;};
+            self.modified.insert((*name).clone(), modified).is_some_and(|old| old != modified)
+        }).cloned().collect()
+    }
+}
@@ -0,0 +1,550 @@
+use super::inflate::zlib_decompress;
+
+const PNG_SIGNATURE : [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+// starting column and row and their steps of the 7 passes of interlaced png images
+const ADAM7_PASSES : [(usize, usize, usize, usize); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+// larger images are rejected before their pixels are allocated
+const MAX_IMAGE_DIMENSION : usize = 16384;
+
+// 8 bit rgba pixels, rows from top to bottom
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Image{
+    pub width : u32,
+    pub height : u32,
+    pub data : Vec<u8>,
+}
+
+impl Image{
+    pub fn new(width : u32, height : u32, data : Vec<u8>) -> Option<Image>{
+        if data.len() != width as usize * height as usize * 4 {
+            return None;
+        }
+        Some(Image{width, height, data})
+    }
+
+    // half the size rounded down, odd rows and columns are averaged into the last pixel
+    pub fn downsample(&self) -> Image{
+        let (width, height) = ((self.width/2).max(1), (self.height/2).max(1));
+        let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height{
+            let rows = if height*2 + 1 == self.height && y == height - 1 {y*2..self.height} else {y*2..(y*2 + 2).min(self.height)};
+            for x in 0..width{
+                let columns = if width*2 + 1 == self.width && x == width - 1 {x*2..self.width} else {x*2..(x*2 + 2).min(self.width)};
+                let mut sum = [0u32; 4];
+                let mut count = 0;
+                for row in rows.clone(){
+                    for column in columns.clone(){
+                        let offset = (row*self.width + column) as usize * 4;
+                        for (channel, value) in sum.iter_mut().enumerate(){
+                            *value += self.data[offset + channel] as u32;
+                        }
+                        count += 1;
+                    }
+                }
+                data.extend(sum.iter().map(|x| ((x + count/2)/count) as u8));
+            }
+        }
+        Image{width, height, data}
+    }
+
+    // the full image followed by every smaller level down to 1x1
+    pub fn generate_mipmaps(self) -> Vec<Image>{
+        let mut output = vec![self];
+        while let Some(last) = output.last().filter(|x| x.width > 1 || x.height > 1){
+            output.push(last.downsample());
+        }
+        output
+    }
+}
+
+// the format is detected from the content, tga images are the fallback since they have no signature
+pub fn decode_image(bytes : &[u8]) -> Result<Image, String>{
+    if bytes.starts_with(&PNG_SIGNATURE) {
+        decode_png(bytes)
+    }
+    else if bytes.len() > 2 && bytes[0] == b'P' && matches!(bytes[1], b'2' | b'3' | b'5' | b'6') && bytes[2].is_ascii_whitespace() {
+        decode_ppm(bytes)
+    }
+    else {
+        decode_tga(bytes)
+    }
+}
+
+// size of the rgba data of an image
+fn rgba_size(width : usize, height : usize) -> Result<usize, String>{
+    if width == 0 || height == 0 {
+        return Err(format!("Image size {}x{} is empty.", width, height));
+    }
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        return Err(format!("Image size {}x{} is larger than {}x{}.", width, height, MAX_IMAGE_DIMENSION, MAX_IMAGE_DIMENSION));
+    }
+    width.checked_mul(height).and_then(|x| x.checked_mul(4)).ok_or(format!("Image size {}x{} is too large.", width, height))
+}
+
+///////////////////////////// PNG /////////////////////////////
+
+fn read_u32_be(bytes : &[u8]) -> u32{
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn paeth(a : u8, b : u8, c : u8) -> u8{
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+}
+
+// reverses the filters of one pass, returns the rows without their filter bytes
+fn unfilter(data : &[u8], stride : usize, rows : usize, pixel_size : usize) -> Result<Vec<u8>, String>{
+    let mut output = vec![0u8; stride*rows];
+    for row in 0..rows{
+        let filter = data[row*(stride + 1)];
+        let input = &data[row*(stride + 1) + 1..(row + 1)*(stride + 1)];
+        let (previous, current) = output.split_at_mut(row*stride);
+        let previous = if row == 0 {None} else {Some(&previous[(row - 1)*stride..])};
+        let current = &mut current[..stride];
+        for i in 0..stride{
+            let a = if i >= pixel_size {current[i - pixel_size]} else {0};
+            let b = previous.map(|x| x[i]).unwrap_or(0);
+            let c = if i >= pixel_size {previous.map(|x| x[i - pixel_size]).unwrap_or(0)} else {0};
+            current[i] = match filter{
+                0 => input[i],
+                1 => input[i].wrapping_add(a),
+                2 => input[i].wrapping_add(b),
+                3 => input[i].wrapping_add(((a as u16 + b as u16)/2) as u8),
+                4 => input[i].wrapping_add(paeth(a, b, c)),
+                _ => return Err(format!("Invalid png filter type {}.", filter)),
+            };
+        }
+    }
+    Ok(output)
+}
+
+struct PngHeader{
+    width : usize,
+    height : usize,
+    bit_depth : u8,
+    color_type : u8,
+    palette : Vec<[u8; 4]>,
+    // raw sample values of fully transparent gray or rgb pixels
+    transparent : Option<[u16; 3]>,
+}
+
+impl PngHeader{
+    fn channels(&self) -> usize{
+        match self.color_type{
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn sample(&self, row : &[u8], index : usize) -> u16{
+        match self.bit_depth{
+            16 => u16::from_be_bytes([row[index*2], row[index*2 + 1]]),
+            8 => row[index] as u16,
+            depth => {
+                let bit = index*depth as usize;
+                let shift = 8 - depth as usize - bit%8;
+                ((row[bit/8] >> shift) & ((1u16 << depth) - 1) as u8) as u16
+            }
+        }
+    }
+
+    fn to_8bit(&self, sample : u16) -> u8{
+        match self.bit_depth{
+            16 => (sample >> 8) as u8,
+            depth => (sample as u32*255/((1u32 << depth) - 1)) as u8,
+        }
+    }
+
+    fn pixel(&self, row : &[u8], x : usize) -> Result<[u8; 4], String>{
+        let channels = self.channels();
+        let samples : Vec<u16> = (0..channels).map(|channel| self.sample(row, x*channels + channel)).collect();
+        let is_transparent = |raw : &[u16]| self.transparent.is_some_and(|x| x[..raw.len()] == *raw);
+        Ok(match self.color_type{
+            0 => {
+                let value = self.to_8bit(samples[0]);
+                [value, value, value, if is_transparent(&samples) {0} else {255}]
+            }
+            2 => {
+                let rgb = [self.to_8bit(samples[0]), self.to_8bit(samples[1]), self.to_8bit(samples[2])];
+                [rgb[0], rgb[1], rgb[2], if is_transparent(&samples) {0} else {255}]
+            }
+            3 => *self.palette.get(samples[0] as usize).ok_or("Png palette index out of range.".to_string())?,
+            4 => {
+                let value = self.to_8bit(samples[0]);
+                [value, value, value, self.to_8bit(samples[1])]
+            }
+            _ => [self.to_8bit(samples[0]), self.to_8bit(samples[1]), self.to_8bit(samples[2]), self.to_8bit(samples[3])],
+        })
+    }
+}
+
+pub fn decode_png(bytes : &[u8]) -> Result<Image, String>{
+    if !bytes.starts_with(&PNG_SIGNATURE) {
+        return Err("Missing png signature.".to_string());
+    }
+    let mut header = None;
+    let mut interlaced = false;
+    let mut compressed = vec![];
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= bytes.len() {
+        let length = read_u32_be(&bytes[pos..]) as usize;
+        let chunk_type = &bytes[pos + 4..pos + 8];
+        let chunk = bytes.get(pos + 8..pos + 8 + length).ok_or("Png chunk ends after the file.".to_string())?;
+        pos += length + 12;
+        match chunk_type{
+            b"IHDR" => {
+                if chunk.len() < 13 {
+                    return Err("Png header is too short.".to_string());
+                }
+                let (bit_depth, color_type) = (chunk[8], chunk[9]);
+                let valid_depths : &[u8] = match color_type{
+                    0 => &[1, 2, 4, 8, 16],
+                    3 => &[1, 2, 4, 8],
+                    2 | 4 | 6 => &[8, 16],
+                    _ => &[],
+                };
+                if !valid_depths.contains(&bit_depth) {
+                    return Err(format!("Png color type {} with bit depth {} is not supported.", color_type, bit_depth));
+                }
+                interlaced = chunk[12] == 1;
+                header = Some(PngHeader{
+                    width : read_u32_be(chunk) as usize,
+                    height : read_u32_be(&chunk[4..]) as usize,
+                    bit_depth, color_type,
+                    palette : vec![],
+                    transparent : None,
+                });
+            }
+            b"PLTE" => {
+                let header = header.as_mut().ok_or("Png palette before the header.".to_string())?;
+                header.palette = chunk.chunks_exact(3).map(|x| [x[0], x[1], x[2], 255]).collect();
+            }
+            b"tRNS" => {
+                let header = header.as_mut().ok_or("Png transparency before the header.".to_string())?;
+                if header.color_type == 3 {
+                    for (entry, alpha) in header.palette.iter_mut().zip(chunk){
+                        entry[3] = *alpha;
+                    }
+                }
+                else {
+                    let values : Vec<u16> = chunk.chunks_exact(2).map(|x| u16::from_be_bytes([x[0], x[1]])).collect();
+                    header.transparent = match values.len(){
+                        1 => Some([values[0], 0, 0]),
+                        3 => Some([values[0], values[1], values[2]]),
+                        _ => None,
+                    };
+                }
+            }
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let header = header.ok_or("Png image without a header.".to_string())?;
+    let data = zlib_decompress(&compressed)?;
+
+    let bits_per_pixel = header.channels()*header.bit_depth as usize;
+    let pixel_size = bits_per_pixel.div_ceil(8);
+    let passes : &[(usize, usize, usize, usize)] = if interlaced {&ADAM7_PASSES} else {&[(0, 0, 1, 1)]};
+    let mut output = vec![0u8; rgba_size(header.width, header.height)?];
+    let mut offset = 0;
+    for (start_x, start_y, step_x, step_y) in passes{
+        let width = header.width.saturating_sub(*start_x).div_ceil(*step_x);
+        let height = header.height.saturating_sub(*start_y).div_ceil(*step_y);
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let stride = (width*bits_per_pixel).div_ceil(8);
+        let pass_data = data.get(offset..offset + (stride + 1)*height).ok_or("Png image data is too short.".to_string())?;
+        offset += (stride + 1)*height;
+        let rows = unfilter(pass_data, stride, height, pixel_size)?;
+        for (y, row) in rows.chunks_exact(stride).enumerate(){
+            for x in 0..width{
+                let target = ((start_y + y*step_y)*header.width + start_x + x*step_x)*4;
+                output[target..target + 4].copy_from_slice(&header.pixel(row, x)?);
+            }
+        }
+    }
+    Ok(Image{width : header.width as u32, height : header.height as u32, data : output})
+}
+
+///////////////////////////// TGA /////////////////////////////
+
+// 16 bit pixels are stored as 5 bits per channel with the attribute bit on top
+fn tga_pixel(bytes : &[u8], depth : u8, has_alpha : bool) -> [u8; 4]{
+    match depth{
+        8 => [bytes[0], bytes[0], bytes[0], 255],
+        15 | 16 => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let scale = |x : u16| ((x & 31) as u32*255/31) as u8;
+            [scale(value >> 10), scale(value >> 5), scale(value), if depth == 16 && has_alpha && value & 0x8000 == 0 {0} else {255}]
+        }
+        24 => [bytes[2], bytes[1], bytes[0], 255],
+        _ => [bytes[2], bytes[1], bytes[0], if has_alpha {bytes[3]} else {255}],
+    }
+}
+
+pub fn decode_tga(bytes : &[u8]) -> Result<Image, String>{
+    if bytes.len() < 18 {
+        return Err("Tga header is too short.".to_string());
+    }
+    let id_length = bytes[0] as usize;
+    let image_type = bytes[2];
+    let (map_first, map_length, map_depth) = (u16::from_le_bytes([bytes[3], bytes[4]]) as usize, u16::from_le_bytes([bytes[5], bytes[6]]) as usize, bytes[7]);
+    let width = u16::from_le_bytes([bytes[12], bytes[13]]) as usize;
+    let height = u16::from_le_bytes([bytes[14], bytes[15]]) as usize;
+    let depth = bytes[16];
+    let descriptor = bytes[17];
+    let has_alpha = descriptor & 15 != 0;
+
+    let is_mapped = matches!(image_type, 1 | 9);
+    let valid = match image_type{
+        1 | 9 => depth == 8 && bytes[1] == 1 && matches!(map_depth, 15 | 16 | 24 | 32),
+        2 | 10 => matches!(depth, 15 | 16 | 24 | 32),
+        3 | 11 => depth == 8,
+        _ => false,
+    };
+    if !valid {
+        return Err(format!("Tga image type {} with {} bits per pixel is not supported.", image_type, depth));
+    }
+    // 8 bit indices can only address the first 256 entries
+    if bytes[1] == 1 && !matches!(map_depth, 15 | 16 | 24 | 32) {
+        return Err(format!("Tga color map with {} bits per entry is not supported.", map_depth));
+    }
+    if is_mapped && map_first + map_length > 256 {
+        return Err(format!("Tga color map entries {} to {} do not fit 8 bit indices.", map_first, map_first + map_length));
+    }
+    let size = rgba_size(width, height)?;
+
+    let mut pos = 18 + id_length;
+    let mut palette = vec![];
+    if bytes[1] == 1 {
+        let entry_size = (map_depth as usize).div_ceil(8);
+        let map = bytes.get(pos..pos + map_length*entry_size).ok_or("Tga color map ends after the file.".to_string())?;
+        palette = map.chunks_exact(entry_size).map(|x| tga_pixel(x, map_depth, has_alpha)).collect();
+        pos += map_length*entry_size;
+    }
+
+    let pixel_size = (depth as usize).div_ceil(8);
+    let mut raw = Vec::with_capacity(width*height*pixel_size);
+    if image_type < 9 {
+        raw.extend_from_slice(bytes.get(pos..pos + width*height*pixel_size).ok_or("Tga image data is too short.".to_string())?);
+    }
+    else {
+        // every packet starts with a byte giving its type and the number of pixels
+        while raw.len() < width*height*pixel_size {
+            let packet = *bytes.get(pos).ok_or("Tga image data is too short.".to_string())?;
+            let count = (packet & 127) as usize + 1;
+            pos += 1;
+            if packet & 128 != 0 {
+                let pixel = bytes.get(pos..pos + pixel_size).ok_or("Tga image data is too short.".to_string())?;
+                for _ in 0..count{
+                    raw.extend_from_slice(pixel);
+                }
+                pos += pixel_size;
+            }
+            else {
+                raw.extend_from_slice(bytes.get(pos..pos + count*pixel_size).ok_or("Tga image data is too short.".to_string())?);
+                pos += count*pixel_size;
+            }
+        }
+        raw.truncate(width*height*pixel_size);
+    }
+
+    // rows are stored from the bottom unless the descriptor says otherwise
+    let (right_to_left, top_to_bottom) = (descriptor & 16 != 0, descriptor & 32 != 0);
+    let mut output = vec![0u8; size];
+    for (index, pixel) in raw.chunks_exact(pixel_size).enumerate(){
+        let (mut x, mut y) = (index%width, index/width);
+        if right_to_left {
+            x = width - 1 - x;
+        }
+        if !top_to_bottom {
+            y = height - 1 - y;
+        }
+        let rgba = if is_mapped {
+            *palette.get((pixel[0] as usize).wrapping_sub(map_first)).ok_or("Tga color map index out of range.".to_string())?
+        } else {tga_pixel(pixel, depth, has_alpha)};
+        output[(y*width + x)*4..(y*width + x + 1)*4].copy_from_slice(&rgba);
+    }
+    Ok(Image{width : width as u32, height : height as u32, data : output})
+}
+
+///////////////////////////// PPM /////////////////////////////
+
+// portable pixmaps and graymaps, in ascii or binary form
+pub fn decode_ppm(bytes : &[u8]) -> Result<Image, String>{
+    let mut pos = 0;
+    let next_token = |pos : &mut usize| -> Result<u32, String>{
+        loop{
+            match bytes.get(*pos){
+                Some(b'#') => while bytes.get(*pos).is_some_and(|x| *x != b'\n') {*pos += 1;},
+                Some(x) if x.is_ascii_whitespace() => *pos += 1,
+                Some(_) => break,
+                None => return Err("Ppm image data is too short.".to_string()),
+            }
+        }
+        let start = *pos;
+        while bytes.get(*pos).is_some_and(|x| x.is_ascii_digit()) {
+            *pos += 1;
+        }
+        std::str::from_utf8(&bytes[start..*pos]).ok().and_then(|x| x.parse().ok()).ok_or("Invalid number in ppm header.".to_string())
+    };
+
+    let format = bytes.get(1).copied().unwrap_or_default();
+    pos += 2;
+    let width = next_token(&mut pos)? as usize;
+    let height = next_token(&mut pos)? as usize;
+    let max_value = next_token(&mut pos)?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(format!("Invalid ppm maximum value {}.", max_value));
+    }
+    let size = rgba_size(width, height)?;
+    let channels = if matches!(format, b'3' | b'6') {3} else {1};
+    let count = size/4*channels;
+
+    let samples : Vec<u32> = if matches!(format, b'2' | b'3') {
+        (0..count).map(|_| next_token(&mut pos)).collect::<Result<_, _>>()?
+    }
+    else {
+        // a single whitespace separates the header from the binary data
+        pos += 1;
+        let sample_size = if max_value > 255 {2} else {1};
+        let data = bytes.get(pos..pos + count*sample_size).ok_or("Ppm image data is too short.".to_string())?;
+        if sample_size == 2 {data.chunks_exact(2).map(|x| u16::from_be_bytes([x[0], x[1]]) as u32).collect()} else {data.iter().map(|x| *x as u32).collect()}
+    };
+
+    let scale = |x : u32| (x.min(max_value)*255/max_value) as u8;
+    let mut output = Vec::with_capacity(size);
+    for pixel in samples.chunks_exact(channels){
+        let rgb = if channels == 3 {[scale(pixel[0]), scale(pixel[1]), scale(pixel[2])]} else {[scale(pixel[0]); 3]};
+        output.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+    }
+    Ok(Image{width : width as u32, height : height as u32, data : output})
+}
+
+#[cfg(test)]
+pub mod imagetest{
+    use super::*;
+
+    // the image data is stored uncompressed, the checksums are not verified
+    fn png(width : u32, height : u32, bit_depth : u8, color_type : u8, interlaced : bool, chunks : &[(&[u8; 4], &[u8])], rows : &[u8]) -> Vec<u8>{
+        let mut header = vec![];
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[bit_depth, color_type, 0, 0, interlaced as u8]);
+        let mut zlib = vec![0x78, 0x01, 1];
+        zlib.extend_from_slice(&(rows.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(rows.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(rows);
+
+        let mut output = PNG_SIGNATURE.to_vec();
+        for (chunk_type, chunk) in [(b"IHDR", header.as_slice())].iter().chain(chunks).chain(&[(b"IDAT", zlib.as_slice()), (b"IEND", &[][..])]){
+            output.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            output.extend_from_slice(*chunk_type);
+            output.extend_from_slice(chunk);
+            output.extend_from_slice(&[0; 4]);
+        }
+        output
+    }
+
+    fn tga(image_type : u8, color_map : Option<(u16, u16, u8)>, size : (u16, u16), depth : u8, descriptor : u8, data : &[u8]) -> Vec<u8>{
+        let (map_first, map_length, map_depth) = color_map.unwrap_or_default();
+        let mut output = vec![0, color_map.is_some() as u8, image_type];
+        output.extend_from_slice(&map_first.to_le_bytes());
+        output.extend_from_slice(&map_length.to_le_bytes());
+        output.extend_from_slice(&[map_depth, 0, 0, 0, 0]);
+        output.extend_from_slice(&size.0.to_le_bytes());
+        output.extend_from_slice(&size.1.to_le_bytes());
+        output.extend_from_slice(&[depth, descriptor]);
+        output.extend_from_slice(data);
+        output
+    }
+
+    #[test]
+    fn png_images(){
+        // the second row uses the sub filter
+        let rows = [0, 10, 20, 30, 255, 40, 50, 60, 128, 1, 1, 2, 3, 4, 5, 5, 5, 5];
+        let image = decode_image(&png(2, 2, 8, 6, false, &[], &rows)).unwrap();
+        assert_eq!(image, Image::new(2, 2, vec![10, 20, 30, 255, 40, 50, 60, 128, 1, 2, 3, 4, 6, 7, 8, 9]).unwrap());
+
+        // 2 bit palette indices, the transparency only covers the first entries
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let image = decode_png(&png(3, 1, 2, 3, false, &[(b"PLTE", &palette), (b"tRNS", &[0, 128])], &[0, 0b10_01_00_00])).unwrap();
+        assert_eq!(image.data, [0, 0, 255, 255, 0, 255, 0, 128, 255, 0, 0, 0]);
+
+        // 16 bit gray with a transparent value, stored in the 7 passes of an interlaced image
+        let samples = |x : [u16; 2]| [x[0].to_be_bytes(), x[1].to_be_bytes()].concat();
+        let rows = [&[0][..], &samples([0xffff, 0])[..2], &[0], &samples([0x8000, 0])[..2], &[0], &samples([0x1234, 0x4000])].concat();
+        let image = decode_png(&png(2, 2, 16, 0, true, &[(b"tRNS", &[0x12, 0x34])], &rows)).unwrap();
+        assert_eq!(image.data, [255, 255, 255, 255, 128, 128, 128, 255, 18, 18, 18, 0, 64, 64, 64, 255]);
+    }
+
+    #[test]
+    fn png_errors(){
+        let rows = [0, 1, 2, 3, 4];
+        assert!(decode_png(&png(1, 1, 8, 6, false, &[], &rows)).is_ok());
+        assert!(decode_png(&png(1, 1, 8, 6, false, &[], &[5, 1, 2, 3, 4])).is_err());
+        assert!(decode_png(&png(1, 1, 4, 6, false, &[], &rows)).is_err());
+        assert!(decode_png(&png(1, 2, 8, 6, false, &[], &rows)).is_err());
+        assert!(decode_png(&png(1, 1, 8, 3, false, &[], &[0, 0])).is_err());
+        // the size is checked before the pixels are allocated
+        assert!(decode_png(&png(MAX_IMAGE_DIMENSION as u32 + 1, 1, 8, 6, false, &[], &rows)).unwrap_err().contains("larger"));
+        assert!(decode_png(&png(u32::MAX, u32::MAX, 8, 6, false, &[], &rows)).is_err());
+        assert!(decode_png(&png(0, 2, 8, 6, false, &[], &[0, 0])).unwrap_err().contains("empty"));
+        assert!(decode_png(&png(1, 1, 8, 6, false, &[], &rows)[..20]).is_err());
+    }
+
+    #[test]
+    fn tga_images(){
+        // rows are stored from the bottom, pixels as bgr
+        let image = decode_image(&tga(2, None, (2, 2), 24, 0, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12])).unwrap();
+        assert_eq!(image.data, [9, 8, 7, 255, 12, 11, 10, 255, 3, 2, 1, 255, 6, 5, 4, 255]);
+
+        // a repeated packet followed by a raw one, from the top right to the left
+        let data = [0x81, 1, 2, 3, 4, 0x00, 5, 6, 7, 8];
+        let image = decode_tga(&tga(10, None, (3, 1), 32, 8 | 16 | 32, &data)).unwrap();
+        assert_eq!(image.data, [7, 6, 5, 8, 3, 2, 1, 4, 3, 2, 1, 4]);
+
+        // indices start at the first entry of the color map
+        let data = [0x00, 0x7c, 0xe0, 0x83, 1, 2];
+        let image = decode_tga(&tga(1, Some((1, 2, 16)), (2, 1), 8, 32 | 1, &data)).unwrap();
+        assert_eq!(image.data, [255, 0, 0, 0, 0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn tga_errors(){
+        let data = [0, 0, 255, 1, 1];
+        assert!(decode_tga(&tga(1, Some((0, 1, 24)), (1, 1), 8, 0, &[0, 0, 255, 0])).is_ok());
+        // an unused color map still needs a valid entry size
+        assert!(decode_tga(&tga(2, Some((0, 1, 0)), (1, 1), 24, 0, &[1, 2, 3])).is_err());
+        assert!(decode_tga(&tga(1, Some((0, 1, 4)), (1, 1), 8, 0, &[0, 0])).is_err());
+        assert!(decode_tga(&tga(1, Some((255, 2, 16)), (1, 1), 8, 0, &data)).is_err());
+        assert!(decode_tga(&tga(1, Some((1, 1, 16)), (1, 1), 8, 0, &[0, 0, 0])).is_err());
+        assert!(decode_tga(&tga(1, Some((0, 2, 16)), (1, 1), 8, 0, &[0, 0])).is_err());
+        assert!(decode_tga(&tga(2, None, (2, 1), 24, 0, &[1, 2, 3])).is_err());
+        assert!(decode_tga(&tga(10, None, (2, 1), 24, 0, &[0x80, 1, 2, 3])).is_err());
+        assert!(decode_tga(&tga(2, None, (1, 1), 12, 0, &[1, 2])).is_err());
+        assert!(decode_tga(&tga(2, None, (u16::MAX, u16::MAX), 24, 0, &[])).unwrap_err().contains("larger"));
+        assert!(decode_tga(&tga(2, None, (0, 2), 24, 0, &[])).unwrap_err().contains("empty"));
+    }
+
+    #[test]
+    fn ppm_images(){
+        let image = decode_image(b"P3\n# comment\n2 1\n15\n15 0 0  0 15 15\n").unwrap();
+        assert_eq!(image.data, [255, 0, 0, 255, 0, 255, 255, 255]);
+        let image = decode_image(b"P6 1 1 255\n\x01\x02\x03").unwrap();
+        assert_eq!(image.data, [1, 2, 3, 255]);
+        let image = decode_ppm(b"P5 2 1 65535\n\xff\xff\x80\x00").unwrap();
+        assert_eq!(image.data, [255, 255, 255, 255, 127, 127, 127, 255]);
+
+        assert!(decode_ppm(b"P6 2 1 255\n\x01\x02\x03").is_err());
+        assert!(decode_ppm(b"P2 1 1 0\n0").is_err());
+        assert!(decode_ppm(b"P2 1 1 x\n0").is_err());
+        assert!(decode_ppm(b"P3 100000 100000 255\n0").unwrap_err().contains("larger"));
+        assert!(decode_ppm(b"P3 18446744073709551615 4 255\n0").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+// deflate and zlib decompression, only what png images need
+
+const MAX_BITS : usize = 15;
+const LENGTH_BASE  : [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA : [u8; 29]  = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE    : [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA   : [u8; 30]  = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER : [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a>{
+    data : &'a [u8],
+    pos : usize,
+    bit_buffer : u32,
+    bit_count : u32,
+}
+
+impl<'a> BitReader<'a>{
+    fn new(data : &'a [u8]) -> BitReader<'a>{
+        BitReader{data, pos : 0, bit_buffer : 0, bit_count : 0}
+    }
+
+    // bits are stored starting with the least significant one
+    fn bits(&mut self, count : u32) -> Result<u32, String>{
+        while self.bit_count < count {
+            let byte = *self.data.get(self.pos).ok_or("Deflate stream ended unexpectedly.".to_string())?;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.pos += 1;
+            self.bit_count += 8;
+        }
+        let output = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(output)
+    }
+
+    fn align_to_byte(&mut self){
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+}
+
+// canonical huffman code given by the code length of every symbol
+struct Huffman{
+    counts : [u16; MAX_BITS + 1],
+    symbols : Vec<u16>,
+}
+
+impl Huffman{
+    fn new(lengths : &[u8]) -> Huffman{
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths{
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS{
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate().filter(|x| *x.1 != 0){
+            symbols[offsets[*length as usize] as usize] = symbol as u16;
+            offsets[*length as usize] += 1;
+        }
+        Huffman{counts, symbols}
+    }
+
+    // codes are stored starting with their most significant bit
+    fn decode(&self, reader : &mut BitReader) -> Result<u16, String>{
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS{
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid huffman code in deflate stream.".to_string())
+    }
+}
+
+fn inflate_block(reader : &mut BitReader, output : &mut Vec<u8>, lengths : &Huffman, distances : &Huffman) -> Result<(), String>{
+    loop{
+        let symbol = lengths.decode(reader)? as usize;
+        match symbol{
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(reader)? as usize;
+                if index >= DIST_BASE.len() {
+                    return Err("Invalid distance in deflate stream.".to_string());
+                }
+                let distance = DIST_BASE[index] as usize + reader.bits(DIST_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err("Distance too far back in deflate stream.".to_string());
+                }
+                // the copied range can overlap with the bytes that are written
+                let start = output.len() - distance;
+                for i in 0..length{
+                    output.push(output[start + i]);
+                }
+            }
+            _ => return Err("Invalid length symbol in deflate stream.".to_string()),
+        }
+    }
+}
+
+fn read_dynamic_tables(reader : &mut BitReader) -> Result<(Huffman, Huffman), String>{
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count){
+        code_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_huffman = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_huffman.decode(reader)?{
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or("Repeated code length without a previous one.".to_string())?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            18 => (0, 11 + reader.bits(7)?),
+            _ => return Err("Invalid code length symbol in deflate stream.".to_string()),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count || lengths[256] == 0 {
+        return Err("Invalid code lengths in deflate stream.".to_string());
+    }
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn fixed_tables() -> (Huffman, Huffman){
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5u8; 30]))
+}
+
+pub fn inflate(data : &[u8]) -> Result<Vec<u8>, String>{
+    let mut reader = BitReader::new(data);
+    let mut output = vec![];
+    loop{
+        let is_last = reader.bits(1)? == 1;
+        match reader.bits(2)?{
+            0 => {
+                reader.align_to_byte();
+                let header = data.get(reader.pos..reader.pos + 4).ok_or("Stored deflate block without a header.".to_string())?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("Stored deflate block with a corrupt length.".to_string());
+                }
+                let start = reader.pos + 4;
+                output.extend_from_slice(data.get(start..start + length as usize).ok_or("Deflate stream ended unexpectedly.".to_string())?);
+                reader.pos = start + length as usize;
+            }
+            1 => {
+                let (lengths, distances) = fixed_tables();
+                inflate_block(&mut reader, &mut output, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = read_dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &lengths, &distances)?;
+            }
+            _ => return Err("Invalid deflate block type.".to_string()),
+        }
+        if is_last {
+            return Ok(output);
+        }
+    }
+}
+
+// the adler checksum at the end is not verified
+pub fn zlib_decompress(data : &[u8]) -> Result<Vec<u8>, String>{
+    if data.len() < 2 || data[0] & 0x0f != 8 || !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31) {
+        return Err("Invalid zlib header.".to_string());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("Zlib streams with a preset dictionary are not supported.".to_string());
+    }
+    inflate(&data[2..])
+}
+
+#[cfg(test)]
+pub mod inflatetest{
+    use super::*;
+
+    fn stored(data : &[u8], is_last : bool) -> Vec<u8>{
+        let length = data.len() as u16;
+        let mut output = vec![is_last as u8];
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(data);
+        output
+    }
+
+    #[test]
+    fn stored_blocks(){
+        let mut data = stored(b"hello ", false);
+        data.extend(stored(b"", false));
+        data.extend(stored(b"world", true));
+        assert_eq!(inflate(&data).unwrap(), b"hello world");
+
+        let mut zlib = vec![0x78, 0x01];
+        zlib.extend(stored(b"png", true));
+        assert_eq!(zlib_decompress(&zlib).unwrap(), b"png");
+
+        data[3] ^= 1;
+        assert!(inflate(&data).is_err());
+        assert!(inflate(&stored(b"hello", true)[..7]).is_err());
+        assert!(zlib_decompress(&[0x78, 0x00, 0x01]).is_err());
+    }
+
+    // streams written by zlib with the fixed and the dynamic huffman codes
+    #[test]
+    fn huffman_blocks(){
+        let fixed = [75, 76, 74, 78, 132, 33, 133, 140, 212, 156, 156, 124, 8, 9, 0];
+        assert_eq!(fixed[0] >> 1 & 3, 1);
+        assert_eq!(inflate(&fixed).unwrap(), b"abcabcabcabc hello hello");
+
+        let dynamic = [37, 136, 193, 13, 0, 48, 16, 130, 102, 21, 220, 127, 134, 218, 28, 124, 8, 38, 208, 6, 187, 146, 116, 30, 68, 252, 115, 197, 3];
+        assert_eq!(dynamic[0] >> 1 & 3, 2);
+        assert_eq!(inflate(&dynamic).unwrap(), b"caabbddabcdaabcbadadaaaaaaabacbcaabcabab");
+
+        assert!(inflate(&fixed[..8]).is_err());
+        assert!(inflate(&dynamic[..12]).is_err());
+        // the reserved block type
+        assert!(inflate(&[0b111]).is_err());
+    }
+}
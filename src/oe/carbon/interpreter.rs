@@ -5,9 +5,45 @@ use std::sync::{Arc, Mutex};
 //use nohash_hasher::IntMap;
 
 use super::parser::*;
+use super::lexer::TokenContent;
 use super::super::types::*;
 use super::super::types::globalscenegraphpending::*;
 
+type PendingScene = (Arc<Mutex<(scene::Scene, bool)>>, CompactString);
+type PendingObject = (Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>>, CompactString);
+
+// name of the element in error messages
+fn element_name(element : &Element) -> &str{
+    element.attributes_ref().get("name").and_then(|x| x.get_str()).unwrap_or("unnamed")
+}
+// required keys, errors name the element and the missing key
+fn attribute<'a, T>(element : &'a Element, key : &str, get : impl Fn(&'a TokenContent) -> Option<T>) -> Result<T, String>{
+    element.attributes_ref().get(key).and_then(get).ok_or_else(|| format!("Element '{}' has a missing or invalid attribute '{}'.", element_name(element), key))
+}
+fn assignment<'a, T>(element : &'a Element, key : &str, get : impl Fn(&'a TokenContent) -> Option<T>) -> Result<T, String>{
+    element.assignments_ref().get(key).and_then(get).ok_or_else(|| format!("Element '{}' has a missing or invalid assignment '{}'.", element_name(element), key))
+}
+// optional keys are only an error if their value has the wrong type
+fn optional_assignment<'a, T>(element : &'a Element, key : &str, get : impl Fn(&'a TokenContent) -> Option<T>) -> Result<Option<T>, String>{
+    element.assignments_ref().get(key).map(|x| get(x).ok_or_else(|| format!("Element '{}' has an invalid assignment '{}'.", element_name(element), key))).transpose()
+}
+// optional attributes naming an enum value, e.g. normal_mode = "smooth"
+fn optional_name<T>(element : &Element, key : &str, from_name : impl Fn(&str) -> Option<T>) -> Result<Option<T>, String>{
+    match element.attributes_ref().get(key).and_then(|x| x.get_str()){
+        Some(name) => from_name(name).map(Some).ok_or_else(|| format!("Element '{}' has an invalid {} '{}'.", element_name(element), key, name)),
+        None => Ok(None),
+    }
+}
+// transform, parent and visibility of every object
+fn process_object_data(element : &Element, data : &mut object_trait::CommonObjectData) -> Result<(), String>{
+    let state = armature::BoneTransform::from_state(assignment(element, "current_state", TokenContent::get_float_list)?)
+        .ok_or_else(|| format!("Element '{}' has an invalid current state.", element_name(element)))?;
+    (data.pos, data.rot, data.sca) = (state.pos, state.rot, state.sca);
+    data.parent = assignment(element, "parent", TokenContent::get_str)?.into();
+    data.visible = attribute(element, "visible", TokenContent::get_int)? != 0;
+    Ok(())
+}
+
 #[derive(Default, Debug)]
 pub struct Interpreter{
     data : GlobalScenegraphPending,
@@ -17,25 +53,44 @@ impl Interpreter{
     pub fn get_data(&mut self) -> &mut GlobalScenegraphPending{
         &mut self.data
     }
-    pub fn interpret(&mut self, input_str: &str){
+    pub fn get_source(&self) -> &str{
+        &self.data.source
+    }
+    // errors name the element that could not be interpreted
+    pub fn interpret(&mut self, input_str: &str) -> Result<(), String>{
         use std::time::Instant;
         let before = Instant::now();
-        let element = parse_string(input_str);
+        let element = parse_string(input_str)?;
         let after = Instant::now();
         println!("[Performance] Time parsing: {:?}", (after-before).as_secs_f64());
 
         let before = Instant::now();
-        self.data.world_ = Some(self.process_world(&element));
+        self.data.world_ = Some(self.process_world(&element)?);
         let after = Instant::now();
         println!("[Performance] Time interpreting: {:?}", (after-before).as_secs_f64());
         //println!("{:?}", self.data);
+        Ok(())
     }
 
-    fn process_world(&mut self, element : &Element) -> world::World{
+    fn process_world(&mut self, element : &Element) -> Result<world::World, String>{
         let mut output: world::World = Default::default();
 
-        for base_e in &element.elements_ref()["Scene"]{
-            let (scene, some_name) = self.process_scene(&base_e.get().unwrap());
+        for prefab_e in element.get_elements("Prefab").iter().filter_map(|x| x.get()){
+            let prefab = self.process_prefab(prefab_e)?;
+            self.data.prefabs.insert(attribute(prefab_e, "name", TokenContent::get_str)?.into(), prefab);
+        }
+
+        // optional, textures are shared by the materials of all scenes
+        for base_e in element.get_elements("Texture").iter().filter_map(|x| x.get()){
+            let (texture, some_name) = self.process_texture(base_e)?;
+            let some_id;{
+                some_id = texture.lock().unwrap().0.id();
+            }
+            self.data.new_texture(some_id, texture, some_name);
+        }
+
+        for base_e in element.get_elements("Scene").iter().filter_map(|x| x.get()){
+            let (scene, some_name) = self.process_scene(base_e)?;
             let some_id;{
                 some_id = scene.lock().unwrap().0.id();
             }
@@ -43,8 +98,8 @@ impl Interpreter{
             output.scenes.insert(some_name);
         }
 
-        for base_e in element.elements_ref().get("ViewportConfig").unwrap_or(&Default::default()){
-            let (viewport, some_name) = self.process_viewport(&base_e.get().unwrap());
+        for base_e in element.get_elements("ViewportConfig").iter().filter_map(|x| x.get()){
+            let (viewport, some_name) = self.process_viewport(base_e)?;
             let some_id;{
                 some_id = viewport.lock().unwrap().0.id();
             }
@@ -53,217 +108,573 @@ impl Interpreter{
             output.viewports.insert(some_name);
         }
         
-        let loaded_scene = element.assignments_ref()["loaded_scene"].get_str().unwrap();
-        let loaded_viewport = element.assignments_ref()["loaded_viewport"].get_str().unwrap();
+        for base_e in element.get_elements("Animation").iter().filter_map(|x| x.get()){
+            let clip = self.process_animation(base_e)?;
+            self.data.animations.push(clip);
+        }
+        
+        let loaded_scene = assignment(element, "loaded_scene", TokenContent::get_str)?;
+        let loaded_viewport = assignment(element, "loaded_viewport", TokenContent::get_str)?;
 
         output.loaded_scene = loaded_scene.into();
         output.loaded_viewport = loaded_viewport.into();
-        output
+        Ok(output)
     }
 
-    fn process_scene(&mut self, element : &Element) -> (Arc<Mutex<(scene::Scene, bool)>>, CompactString){
+    fn process_scene(&mut self, element : &Element) -> Result<PendingScene, String>{
         let output = Arc::new(Mutex::new((scene::Scene::new(), true)));
         let mut output_unlocked = output.lock().unwrap();
-        let scene_name = CompactString::new(element.attributes_ref()["name"].get_str().unwrap());
+        let scene_name = CompactString::new(attribute(element, "name", TokenContent::get_str)?);
         
-        for base_e in element.elements_ref().get("Material").unwrap_or(&Default::default()){
-            let (material, some_name) = self.process_material(&base_e.get().unwrap());
+        for base_e in element.get_elements("Material").iter().filter_map(|x| x.get()){
+            let (material, some_name) = self.process_material(base_e)?;
             let some_id;{
                 some_id = material.lock().unwrap().0.id();
             }
             self.data.new_material(some_id, material.clone(), some_name.clone(), &scene_name);
             output_unlocked.0.materials.insert(some_name);
         }
-        for base_e in element.elements_ref().get("Camera").unwrap_or(&Default::default()){
-            let (obj, some_name) = self.process_camera(&base_e.get().unwrap());
+        for base_e in element.get_elements("Camera").iter().filter_map(|x| x.get()){
+            let (obj, some_name) = self.process_camera(base_e)?;
             let some_id;{
                 some_id = obj.lock().unwrap().0.id();
             }
             self.data.new_object(some_id, obj.clone(), some_name.clone(), &scene_name);
             output_unlocked.0.objects.insert(some_name);
         }
-        for base_e in element.elements_ref().get("Light").unwrap_or(&Default::default()){
-            let (obj, some_name) = self.process_light(&base_e.get().unwrap());
+        for base_e in element.get_elements("Light").iter().filter_map(|x| x.get()){
+            let (obj, some_name) = self.process_light(base_e)?;
             let some_id;{
                 some_id = obj.lock().unwrap().0.id();
             }
             self.data.new_object(some_id, obj.clone(), some_name.clone(), &scene_name);
             output_unlocked.0.objects.insert(some_name);
         }
-        for base_e in element.elements_ref().get("Mesh").unwrap_or(&Default::default()){
-            let (obj, some_name) = self.process_mesh(&base_e.get().unwrap());
+        for base_e in element.get_elements("Mesh").iter().filter_map(|x| x.get()){
+            let (obj, some_name) = self.process_mesh(base_e)?;
             let some_id;{
-                some_id = obj.lock().unwrap().0.id();
+                let mut obj_unlocked = obj.lock().unwrap();
+                some_id = obj_unlocked.0.id();
+                self.check_texture_uvmaps(&some_name, obj_unlocked.0.as_ref())?;
+                self.data.link_vgroup_materials(&some_name, obj_unlocked.0.as_mut());
             }
             self.data.new_object(some_id, obj.clone(), some_name.clone(), &scene_name);
             output_unlocked.0.objects.insert(some_name);
         }
+        // the elements of prefab instances are added to the scene when the instances are resolved
+        for base_e in element.get_elements("PrefabInstance").iter().filter_map(|x| x.get()){
+            let mut instance = self.process_prefab_instance(base_e)?;
+            instance.scene = scene_name.clone();
+            self.data.prefab_instances.push(instance);
+        }
 
         let final_output = output.clone();
-        (final_output, scene_name)
+        Ok((final_output, scene_name))
+    }
+
+    // the textures of the materials that were already interpreted have to use uv maps of the mesh
+    fn check_texture_uvmaps(&self, mesh_name : &str, object : &dyn object_trait::ObjectTrait) -> Result<(), String>{
+        let Some(mesh) = object.get_mesh() else {return Ok(());};
+        let storage = mesh.get_polygonstorage_unlocked();
+        let num_of_uvs = storage.0.get_num_uvs();
+        for vgroup in storage.0.get_vgroups(){
+            let Some(material_name) = &vgroup.material else {continue;};
+            let Some(material) = self.data.materials_.get_id(material_name).and_then(|id| self.data.materials_.get(&id)) else {continue;};
+            let material_unlocked = material.lock().unwrap();
+            for slot in texture::TextureSlotType::ALL{
+                let Some(texture) = material_unlocked.0.get_texture(slot) else {continue;};
+                if texture.uvmap >= num_of_uvs {
+                    return Err(format!("Material '{}' uses uv map {} but mesh '{}' has {} uv maps.", material_name, texture.uvmap, mesh_name, num_of_uvs));
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn process_camera(&mut self, element : &Element) -> (Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>>, CompactString){
+    // prefab elements are templates, they are only added to the scenegraph through instances
+    fn process_prefab(&mut self, element : &Element) -> Result<prefab::Prefab, String>{
+        let mut output = prefab::Prefab::new();
+        let take_object = |obj : Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>>| Arc::try_unwrap(obj).unwrap().into_inner().unwrap().0;
 
-        let ar = element.assignments_ref()["aspect_ratio"].get_float().unwrap() as f32;
-        let fov = element.assignments_ref()["fov"].get_float().unwrap() as f32;
-        let near = element.assignments_ref()["near"].get_float().unwrap() as f32;
-        let far = element.assignments_ref()["far"].get_float().unwrap() as f32;
+        for base_e in element.get_elements("Material").iter().filter_map(|x| x.get()){
+            let (material, some_name) = self.process_material(base_e)?;
+            output.add_material(&some_name, material.lock().unwrap().0.clone());
+        }
+        for base_e in element.get_elements("Camera").iter().filter_map(|x| x.get()){
+            let (obj, some_name) = self.process_camera(base_e)?;
+            output.add_object(&some_name, take_object(obj));
+        }
+        for base_e in element.get_elements("Light").iter().filter_map(|x| x.get()){
+            let (obj, some_name) = self.process_light(base_e)?;
+            output.add_object(&some_name, take_object(obj));
+        }
+        for base_e in element.get_elements("Mesh").iter().filter_map(|x| x.get()){
+            let (obj, some_name) = self.process_mesh(base_e)?;
+            output.add_object(&some_name, take_object(obj));
+        }
+        Ok(output)
+    }
+
+    fn process_prefab_instance(&mut self, element : &Element) -> Result<prefab::PendingPrefabInstance, String>{
+        let name = attribute(element, "name", TokenContent::get_str)?;
+        let get_transform = |element : &Element| optional_assignment(element, "current_state", TokenContent::get_float_list)?.map(|x| {
+            armature::BoneTransform::from_state(x).ok_or_else(|| format!("Prefab instance '{}' has an invalid current state.", name))
+        }).transpose();
+        let mut options = prefab::PrefabInstanceOptions{
+            transform : get_transform(element)?.unwrap_or_default(),
+            ..Default::default()
+        };
+        // optional, changes of single objects of this instance
+        for override_e in element.get_elements("Override").iter().filter_map(|x| x.get()){
+            let object_override = prefab::ObjectOverride{
+                transform : get_transform(override_e)?,
+                visible : override_e.attributes_ref().get("visible").and_then(|x| x.get_int()).map(|x| x != 0),
+            };
+            options.overrides.insert(attribute(override_e, "object", TokenContent::get_str)?.into(), object_override);
+        }
+        // optional, replace the prefab materials of the same name
+        for base_e in element.get_elements("Material").iter().filter_map(|x| x.get()){
+            let (material, some_name) = self.process_material(base_e)?;
+            options.materials.insert(some_name, material.lock().unwrap().0.clone());
+        }
+        Ok(prefab::PendingPrefabInstance{
+            name : name.into(),
+            prefab : attribute(element, "prefab", TokenContent::get_str)?.into(),
+            scene : Default::default(),
+            options,
+        })
+    }
+
+    fn process_camera(&mut self, element : &Element) -> Result<PendingObject, String>{
+
+        let ar = assignment(element, "aspect_ratio", TokenContent::get_float)? as f32;
+        let fov = assignment(element, "fov", TokenContent::get_float)? as f32;
+        let near = assignment(element, "near", TokenContent::get_float)? as f32;
+        let far = assignment(element, "far", TokenContent::get_float)? as f32;
         
 
         let output:  Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>> = Arc::new(Mutex::new((Box::new(camera::Camera::new(ar, fov, near, far)), true)));
         let mut output_unlocked = output.lock().unwrap();
-
-        let cs_v = element.assignments_ref()["current_state"].get_float_list().unwrap();
-        let data = output_unlocked.0.get_data_mut();
-        data.pos = [cs_v[0], cs_v[1], cs_v[2]];
-        data.rot = [cs_v[3], cs_v[4], cs_v[5], cs_v[6]];
-        data.sca = [cs_v[7], cs_v[8], cs_v[9]];
-        
-        let parent = element.assignments_ref()["parent"].get_str().unwrap();
-        data.parent = CompactString::new(parent);
-        
-        let visible = element.attributes_ref()["visible"].get_int().unwrap();
-        data.visible = visible != 0;
+        process_object_data(element, output_unlocked.0.get_data_mut())?;
 
         let final_output = output.clone();
-        (final_output, CompactString::new(element.attributes_ref()["name"].get_str().unwrap()))
+        Ok((final_output, CompactString::new(attribute(element, "name", TokenContent::get_str)?)))
     }
 
-    fn process_light(&mut self, element : &Element) -> (Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>>, CompactString){
+    fn process_light(&mut self, element : &Element) -> Result<PendingObject, String>{
 
-        let ltype = LightType::from(element.assignments_ref()["light_type"].get_int().unwrap());
-        let fov = element.assignments_ref()["fov"].get_float().unwrap() as f32;
-        let range = element.assignments_ref()["range"].get_float().unwrap() as f32;
-        let intensity = element.assignments_ref()["intensity"].get_float().unwrap() as f32;
-        let color : Vec<f32> = element.assignments_ref()["color"].get_float_list().unwrap().iter().map(|x| *x as f32).collect();
+        let ltype = LightType::from(assignment(element, "light_type", TokenContent::get_int)?);
+        let fov = assignment(element, "fov", TokenContent::get_float)? as f32;
+        let range = assignment(element, "range", TokenContent::get_float)? as f32;
+        let intensity = assignment(element, "intensity", TokenContent::get_float)? as f32;
+        let color = assignment(element, "color", TokenContent::get_float_list)?;
+        let color : [f32; 3] = color.iter().map(|x| *x as f32).collect::<Vec<f32>>().try_into()
+            .map_err(|_| format!("Light '{}' needs three color values.", element_name(element)))?;
 
-        let output: Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>> = Arc::new(Mutex::new((Box::new(light::Light::new(ltype, intensity, color.try_into().unwrap(), fov, range)), true)));
+        let output: Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>> = Arc::new(Mutex::new((Box::new(light::Light::new(ltype, intensity, color, fov, range)), true)));
         let mut output_unlocked = output.lock().unwrap();
-
-        let cs_v = element.assignments_ref()["current_state"].get_float_list().unwrap();
-        let data = output_unlocked.0.get_data_mut();
-        data.pos = [cs_v[0], cs_v[1], cs_v[2]];
-        data.rot = [cs_v[3], cs_v[4], cs_v[5], cs_v[6]];
-        data.sca = [cs_v[7], cs_v[8], cs_v[9]];
-        
-        let parent = element.assignments_ref()["parent"].get_str().unwrap();
-        data.parent = CompactString::new(parent);
-        
-        let visible = element.attributes_ref()["visible"].get_int().unwrap();
-        data.visible = visible != 0;
+        self.process_light_parameters(element, output_unlocked.0.get_light_mut().unwrap())?;
+        process_object_data(element, output_unlocked.0.get_data_mut())?;
 
         let final_output = output.clone();
-        (final_output, CompactString::new(element.attributes_ref()["name"].get_str().unwrap()))
+        Ok((final_output, CompactString::new(attribute(element, "name", TokenContent::get_str)?)))
+    }
+
+    // optional, e.g. inner_fov = 0.5 attenuation = "custom" attenuation_quadratic = 0.5 area_shape = "disc" area_radius = 2.0 shadow = 1
+    fn process_light_parameters(&mut self, element : &Element, light : &mut light::Light) -> Result<(), String>{
+        let get_float = |key : &str| Ok::<_, String>(optional_assignment(element, key, TokenContent::get_float)?.map(|x| x as f32));
+        let required_float = |key : &str| Ok::<_, String>(assignment(element, key, TokenContent::get_float)? as f32);
+        let get_int = |key : &str| optional_assignment(element, key, TokenContent::get_int);
+        // negative values are clamped to 0 instead of wrapping around
+        let get_u32 = |key : &str| Ok::<_, String>(get_int(key)?.map(|x| x.max(0) as u32));
+
+        light.inner_fov = get_float("inner_fov")?.unwrap_or(light.fov);
+        // lights of higher priority are kept first when light clusters overflow
+        light.priority = get_u32("priority")?.unwrap_or(0);
+        if let Some(attenuation) = optional_assignment(element, "attenuation", TokenContent::get_str)?{
+            light.attenuation = match attenuation{
+                "none" => light::Attenuation::None,
+                "linear" => light::Attenuation::Linear,
+                "inverse_square" => light::Attenuation::InverseSquare,
+                "custom" => light::Attenuation::Custom{
+                    constant : get_float("attenuation_constant")?.unwrap_or(1.0),
+                    linear : get_float("attenuation_linear")?.unwrap_or(0.0),
+                    quadratic : get_float("attenuation_quadratic")?.unwrap_or(0.0),
+                },
+                other => return Err(format!("Element '{}' has an invalid attenuation '{}'.", element_name(element), other)),
+            };
+        }
+        // shadow = 1 enables shadows, the other shadow_ assignments are optional
+        if get_int("shadow")?.is_some_and(|x| x != 0) {
+            let mut shadow = light::ShadowSettings::default();
+            shadow.resolution = get_u32("shadow_resolution")?.unwrap_or(shadow.resolution);
+            shadow.bias = get_float("shadow_bias")?.unwrap_or(shadow.bias);
+            shadow.normal_bias = get_float("shadow_normal_bias")?.unwrap_or(shadow.normal_bias);
+            shadow.cascades = get_u32("shadow_cascades")?.unwrap_or(shadow.cascades);
+            shadow.split_lambda = get_float("shadow_split_lambda")?.unwrap_or(shadow.split_lambda);
+            shadow.max_distance = get_float("shadow_distance")?.unwrap_or(shadow.max_distance);
+            shadow.near = get_float("shadow_near")?.unwrap_or(shadow.near);
+            light.shadow = Some(shadow);
+        }
+        if let Some(shape) = optional_assignment(element, "area_shape", TokenContent::get_str)?{
+            light.area_shape = match shape{
+                "rectangle" => light::AreaShape::Rectangle{width : required_float("area_width")?, height : required_float("area_height")?},
+                "disc" => light::AreaShape::Disc{radius : required_float("area_radius")?},
+                other => return Err(format!("Element '{}' has an invalid area_shape '{}'.", element_name(element), other)),
+            };
+        }
+        Ok(())
     }
 
-    fn process_mesh(&mut self, element : &Element) -> (Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>>, CompactString){
+    fn process_mesh(&mut self, element : &Element) -> Result<PendingObject, String>{
+
+        let name = attribute(element, "name", TokenContent::get_str)?;
+        // optional, shares the polygon storage of another mesh instead of defining its own geometry
+        let new_mesh = match element.attributes_ref().get("instance_of").and_then(|x| x.get_str()){
+            Some(source) => mesh::Mesh::new_instance_of(source),
+            None => self.process_mesh_geometry(element, name)?,
+        };
+        let output: Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>> = Arc::new(Mutex::new((Box::new(new_mesh), true)));
+        let mut output_unlocked = output.lock().unwrap();
+        process_object_data(element, output_unlocked.0.get_data_mut())?;
+
+        let final_output = output.clone();
+        Ok((final_output, CompactString::new(name)))
+    }
 
-        let name = element.attributes_ref()["name"].get_str().unwrap();
-        let positions = element.assignments_ref()["vertices"].get_float_list().unwrap().iter().map(|x| *x as f32).collect();
-        let normals = element.assignments_ref()["normals"].get_float_list().unwrap().iter().map(|x| *x as f32).collect();
+    fn process_mesh_geometry(&mut self, element : &Element, name : &str) -> Result<mesh::Mesh, String>{
+        let positions : Vec<f32> = assignment(element, "vertices", TokenContent::get_float_list)?.iter().map(|x| *x as f32).collect();
+        let normals = optional_assignment(element, "normals", TokenContent::get_float_list)?.map(|x| x.iter().map(|x| *x as f32).collect());
         let mut vgroups : Vec<VertexGroup> = Default::default();
         let mut uvmaps : Vec<UVMapData> = Default::default();
         
-        for base_e in element.elements_ref().get("VertexGroup").unwrap_or(&Default::default()){
-            let obj = self.process_vgroup(&base_e.get().unwrap(), name);
+        for base_e in element.get_elements("VertexGroup").iter().filter_map(|x| x.get()){
+            let obj = self.process_vgroup(base_e)?;
             vgroups.push(obj);
         }
 
         let mut num_of_uvs = 0;
-        for base_e in element.elements_ref().get("UVMapData").unwrap_or(&Default::default()){
-            let obj = self.process_uvmap_data(&base_e.get().unwrap());
+        for base_e in element.get_elements("UVMapData").iter().filter_map(|x| x.get()){
+            let obj = self.process_uvmap_data(base_e)?;
             uvmaps.push(obj);
             num_of_uvs += 1;
         }
 
-        let num_of_triangles = element.elements_ref().get("Triangle").unwrap_or(&Default::default()).len();
+        let num_of_triangles = element.get_elements("Triangle").len();
         let mut indices = Vec::with_capacity(num_of_triangles*(2+num_of_uvs));
-        for base_e in element.elements_ref().get("Triangle").unwrap_or(&Default::default()){
-            self.process_triangle(&base_e.get_triangle().unwrap(), &mut indices, num_of_uvs);
+        for base_e in element.get_elements("Triangle"){
+            let triangle = base_e.get_triangle().ok_or_else(|| format!("Mesh '{}' has a triangle with attributes.", name))?;
+            self.process_triangle(triangle, &mut indices, num_of_uvs);
         }
-        
 
-        let output: Arc<Mutex<(Box<dyn object_trait::ObjectTrait>, bool)>> = Arc::new(Mutex::new((Box::new(mesh::Mesh::new_static(positions, normals, uvmaps, indices, vgroups, name)), true)));
-        let mut output_unlocked = output.lock().unwrap();
+        // optional, normals are generated if they are missing or a normal mode is given
+        let normal_mode = optional_name(element, "normal_mode", tangentspace::NormalMode::from_name)?;
+        let crease_angle = element.attributes_ref().get("crease_angle").and_then(|x| x.get_float()).map(|x| x as f32);
+        let normals = match (normals, normal_mode){
+            (Some(normals), None) => normals,
+            (_, mode) => {
+                let stride = 2+num_of_uvs;
+                let (normals, normal_ids) = tangentspace::generate_normals(&positions, &indices, stride, mode.unwrap_or_default(), crease_angle);
+                for (corner, id) in normal_ids.into_iter().enumerate(){
+                    indices[corner*stride+1] = id;
+                }
+                normals
+            }
+        };
+
+
+        // optional, meshes deformed by the physics, an armature or morph targets need a softbody polygon storage
+        let armature_e = element.get_elements("Armature").first().and_then(|x| x.get());
+        let morph_targets_e = element.get_elements("MorphTarget");
+        let softbody = element.attributes_ref().get("softbody").and_then(|x| x.get_int()).unwrap_or(0) != 0;
+        let apply_options = |storage : &mut dyn polygonstoragetrait::PolygonStorageTrait| -> Result<(), String>{
+            // optional, id of the uv map the tangents are generated from
+            if let Some(uvmap) = element.attributes_ref().get("tangents").and_then(|x| x.get_int()){
+                if !u8::try_from(uvmap).is_ok_and(|x| storage.set_tangents(Some(x))){
+                    return Err(format!("Mesh '{}' has no uv map {} for its tangents.", name, uvmap));
+                }
+            }
+            // optional, triangle ratios of the generated levels of detail
+            if let Some(ratios) = optional_assignment(element, "lod_ratios", TokenContent::get_float_list)?{
+                let ratios : Vec<f32> = ratios.iter().map(|x| *x as f32).collect();
+                storage.generate_lods(&ratios);
+            }
+            Ok(())
+        };
+        // optional, quantized vertex layout of static meshes
+        let vertex_format = quantization::VertexFormat{
+            position : optional_name(element, "position_format", quantization::PositionFormat::from_name)?.unwrap_or_default(),
+            normal : optional_name(element, "normal_format", quantization::NormalFormat::from_name)?.unwrap_or_default(),
+            uv : optional_name(element, "uv_format", quantization::UvFormat::from_name)?.unwrap_or_default(),
+        };
+
+        let position_count = positions.len() / 3;
+        let mut dynamic_storage = polygonstorage::DynamicPolygonStorage::new(positions, normals, uvmaps, indices, vgroups);
+        let storage : Box<dyn polygonstoragetrait::PolygonStorageTrait> = if softbody || armature_e.is_some() || !morph_targets_e.is_empty() {
+            if !vertex_format.is_float() {
+                return Err(format!("Mesh '{}' is deformed and can not use a quantized vertex format.", name));
+            }
+            let mut storage = Box::new(polygonstorage::SoftbodyPolygonStorage::new(dynamic_storage));
+            apply_options(storage.as_mut())?;
+            storage
+        }
+        else {
+            apply_options(&mut dynamic_storage)?;
+            // optional, reorders the buffers of static meshes for the GPU, the levels of detail are optimized as well
+            let mut storage = if element.attributes_ref().get("optimize").and_then(|x| x.get_int()).unwrap_or(0) != 0 {
+                let storage = polygonstorage::StaticPolygonStorage::new_optimized(dynamic_storage);
+                let report = storage.get_cache_report().unwrap_or_default();
+                println!("[Performance] ACMR of mesh '{}': {:.3} -> {:.3}", name, report.acmr_before, report.acmr_after);
+                storage
+            }
+            else {
+                polygonstorage::StaticPolygonStorage::new(dynamic_storage)
+            };
+            storage.compress(vertex_format);
+            Box::new(storage)
+        };
+        let mut new_mesh = mesh::Mesh::from_polygon_storage(storage, name);
+        if let Some(armature_e) = armature_e{
+            new_mesh.set_armature(self.process_armature(armature_e, position_count)?);
+        }
+        for base_e in morph_targets_e.iter().filter_map(|x| x.get()){
+            let (target, weight) = self.process_morph_target(base_e)?;
+            let target_name = target.name.clone();
+            if !new_mesh.add_morph_target(target, weight){
+                return Err(format!("Morph target '{}' of mesh '{}' has out of range indices.", target_name, name));
+            }
+        }
+        Ok(new_mesh)
+    }
 
-        let cs_v = element.assignments_ref()["current_state"].get_float_list().unwrap();
-        let data = output_unlocked.0.get_data_mut();
-        data.pos = [cs_v[0], cs_v[1], cs_v[2]];
-        data.rot = [cs_v[3], cs_v[4], cs_v[5], cs_v[6]];
-        data.sca = [cs_v[7], cs_v[8], cs_v[9]];
-        
-        let parent = element.assignments_ref()["parent"].get_str().unwrap();
-        data.parent = CompactString::new(parent);
-        
-        let visible = element.attributes_ref()["visible"].get_int().unwrap();
-        data.visible = visible != 0;
+    fn process_morph_target(&mut self, element : &Element) -> Result<(morphtarget::MorphTarget, f32), String>{
+        let name = attribute(element, "name", TokenContent::get_str)?;
+        let to_ids = |key : &str| -> Result<Vec<u32>, String>{
+            Ok(optional_assignment(element, key, TokenContent::get_int_list)?.map(|x| x.iter().map(|x| *x as u32).collect()).unwrap_or_default())
+        };
+        let to_deltas = |key : &str| -> Result<Vec<f32>, String>{
+            Ok(optional_assignment(element, key, TokenContent::get_float_list)?.map(|x| x.iter().map(|x| *x as f32).collect()).unwrap_or_default())
+        };
+        let Some(target) = morphtarget::MorphTarget::new(name, &to_ids("vertices")?, &to_deltas("positions")?, &to_ids("normal_ids")?, &to_deltas("normals")?) else {
+            return Err(format!("Morph target '{}' has mismatching indices and deltas.", name));
+        };
+        let weight = element.assignments_ref().get("weight").and_then(|x| x.get_float()).unwrap_or(0.0) as f32;
+        Ok((target, weight))
+    }
 
-        let final_output = output.clone();
-        (final_output, CompactString::new(element.attributes_ref()["name"].get_str().unwrap()))
+    // bone weights are only accepted for the positions of the mesh
+    fn process_armature(&mut self, element : &Element, position_count : usize) -> Result<armature::Armature, String>{
+        let mut output = armature::Armature::new(attribute(element, "name", TokenContent::get_str)?);
+        output.set_position_count(position_count);
+        if element.assignments_ref().get("skinning").and_then(|x| x.get_str()) == Some("gpu"){
+            output.skinning_mode = armature::SkinningMode::Gpu;
+        }
+
+        // bones may be listed in any order, but parents have to be added first
+        let mut bones : Vec<&Element> = element.get_elements("Bone").iter().filter_map(|x| x.get().map(|x| x.as_ref())).collect();
+        while !bones.is_empty(){
+            let num_of_bones = bones.len();
+            let mut remaining = Vec::with_capacity(num_of_bones);
+            for bone_e in bones{
+                let bone_name = attribute(bone_e, "name", TokenContent::get_str)?;
+                let parent = bone_e.assignments_ref().get("parent").and_then(|x| x.get_str());
+                if parent.is_some_and(|x| output.get_bone_id(x).is_none()){
+                    remaining.push(bone_e);
+                    continue;
+                }
+                let rest = bone_e.assignments_ref().get("rest_state").and_then(|x| x.get_float_list()).and_then(|x| armature::BoneTransform::from_state(x))
+                    .ok_or_else(|| format!("Bone '{}' of armature '{}' has an invalid rest state.", bone_name, output.name))?;
+                if output.add_bone(bone_name, parent, rest).is_none() {
+                    return Err(format!("Armature '{}' has more than one bone named '{}'.", output.name, bone_name));
+                }
+
+                let vertices = optional_assignment(bone_e, "vertices", TokenContent::get_int_list)?;
+                let weights = optional_assignment(bone_e, "weights", TokenContent::get_float_list)?;
+                if let (Some(vertices), Some(weights)) = (vertices, weights){
+                    if vertices.len() != weights.len() {
+                        return Err(format!("Bone '{}' of armature '{}' has {} vertices but {} weights.", bone_name, output.name, vertices.len(), weights.len()));
+                    }
+                    let vertices = vertices.iter().map(|x| u32::try_from(*x).ok().filter(|x| (*x as usize) < position_count)
+                        .ok_or_else(|| format!("Bone '{}' of armature '{}' has the vertex {} but the mesh has {} vertices.", bone_name, output.name, x, position_count)))
+                        .collect::<Result<Vec<u32>, String>>()?;
+                    let weights : Vec<f32> = weights.iter().map(|x| *x as f32).collect();
+                    output.add_weights(bone_name, &vertices, &weights)?;
+                }
+            }
+            if remaining.len() == num_of_bones {
+                let missing : Vec<String> = remaining.iter().map(|x| {
+                    format!("'{}' -> '{}'", element_name(x), x.assignments_ref().get("parent").and_then(|x| x.get_str()).unwrap_or_default())
+                }).collect();
+                return Err(format!("Armature '{}' has bones with missing parents: {}.", output.name, missing.join(", ")));
+            }
+            bones = remaining;
+        }
+
+        for base_e in element.get_elements("Animation").iter().filter_map(|x| x.get()){
+            output.add_clip(self.process_animation(base_e)?);
+        }
+        Ok(output)
+    }
+
+    fn process_animation(&mut self, element : &Element) -> Result<animation::AnimationClip, String>{
+        let mut output = animation::AnimationClip::new(attribute(element, "name", TokenContent::get_str)?);
+        for channel_e in element.get_elements("BoneChannel").iter().filter_map(|x| x.get()){
+            let times = assignment(channel_e, "times", TokenContent::get_float_list)?;
+            let states = assignment(channel_e, "states", TokenContent::get_float_list)?;
+            let bone_name = attribute(channel_e, "bone", TokenContent::get_str)?;
+            // every keyframe has a position, a rotation quaternion and a scale
+            let keyframes : Option<Vec<_>> = times.iter().zip(states.chunks(10)).map(|(time, state)| {
+                Some(animation::BoneKeyframe{time : *time, transform : armature::BoneTransform::from_state(state)?})
+            }).collect();
+            let Some(keyframes) = keyframes.filter(|_| states.len() == times.len()*10) else {
+                return Err(format!("Animation '{}' has a channel of bone '{}' with mismatching times and states.", output.name, bone_name));
+            };
+            output.add_bone_channel(animation::BoneChannel::new(bone_name, keyframes));
+        }
+        for channel_e in element.get_elements("PropertyChannel").iter().filter_map(|x| x.get()){
+            let property_name = attribute(channel_e, "property", TokenContent::get_str)?;
+            let Some(property) = animation::AnimatedProperty::from_name(property_name) else {
+                return Err(format!("Animation '{}' has a channel with an unknown property: \"{}\".", output.name, property_name));
+            };
+            let interpolation = match channel_e.attributes_ref().get("interpolation").and_then(|x| x.get_str()){
+                Some(interpolation_name) => match animation::Interpolation::from_name(interpolation_name){
+                    Some(interpolation) => interpolation,
+                    None => return Err(format!("Animation '{}' has a channel with an unknown interpolation: \"{}\".", output.name, interpolation_name))
+                },
+                None => Default::default()
+            };
+            let times = assignment(channel_e, "times", TokenContent::get_float_list)?;
+            let values = assignment(channel_e, "values", TokenContent::get_float_list)?;
+            let Some(channel) = animation::PropertyChannel::new(property, interpolation, times, values) else {
+                return Err(format!("Animation '{}' has a channel with mismatching times and values.", output.name));
+            };
+            output.add_property_channel(channel);
+        }
+        for marker_e in element.get_elements("Marker").iter().filter_map(|x| x.get()){
+            output.add_marker(assignment(marker_e, "time", TokenContent::get_float)?, attribute(marker_e, "name", TokenContent::get_str)?);
+        }
+        Ok(output)
     }
 
-    fn process_vgroup(&mut self, element : &Element, obj_name : &str) -> VertexGroup{
+    fn process_vgroup(&mut self, element : &Element) -> Result<VertexGroup, String>{
         let mut output : VertexGroup = VertexGroup::new();
 
-        output.name =  CompactString::new(element.attributes_ref()["name"].get_str().unwrap());
-        output.polygons = element.assignments_ref()["polygons"].get_int_list().unwrap().iter().map(|x| *x as u32).collect();
+        output.name =  CompactString::new(attribute(element, "name", TokenContent::get_str)?);
+        output.polygons = assignment(element, "polygons", TokenContent::get_int_list)?.iter().map(|x| *x as u32).collect();
         
         
-        let material_name = element.assignments_ref()["material_id"].get_str().unwrap();
+        let material_name = assignment(element, "material_id", TokenContent::get_str)?;
         //let material_id = self.materials_.get_id(material_name).unwrap();
         output.material = Some(material_name.into());
-        self.data.material2vertexgroup.insert(material_name.into(), (output.name.clone(), obj_name.into()).into());
 
-        output
+        Ok(output)
     }
 
-    fn process_material(&mut self, element : &Element) -> (Arc<Mutex<(material::Material, bool)>>, CompactString){
+    fn process_material(&mut self, element : &Element) -> Result<(Arc<Mutex<(material::Material, bool)>>, CompactString), String>{
         let output: Arc<Mutex<(material::Material, bool)>> = Arc::new(Mutex::new((material::Material::new(), true)));
         let mut output_unlocked = output.lock().unwrap();
         
-        let dif_r = element.assignments_ref()["dif_r"].get_float().unwrap() as f32;
-        let dif_g = element.assignments_ref()["dif_g"].get_float().unwrap() as f32;
-        let dif_b = element.assignments_ref()["dif_b"].get_float().unwrap() as f32;
-        let dif_a = element.assignments_ref()["dif_a"].get_float().unwrap() as f32;
-
-        let scol_r = element.assignments_ref()["scol_r"].get_float().unwrap() as f32;
-        let scol_g = element.assignments_ref()["scol_g"].get_float().unwrap() as f32;
-        let scol_b = element.assignments_ref()["scol_b"].get_float().unwrap() as f32;
-
-        output_unlocked.0.dif_ = [dif_r, dif_g, dif_b, dif_a];
-        output_unlocked.0.scol = [scol_r, scol_g, scol_b];
-        output_unlocked.0.alpha = element.assignments_ref()["alpha"].get_float().unwrap() as f32;
-        output_unlocked.0.translucency = element.assignments_ref()["translucency"].get_float().unwrap() as f32;
-        output_unlocked.0.illuminosity = element.assignments_ref()["illuminosity"].get_float().unwrap() as f32;
-        output_unlocked.0.specular_intensity = element.assignments_ref()["specular_intensity"].get_float().unwrap() as f32;
-        output_unlocked.0.specular_hardness = element.assignments_ref()["specular_hardness"].get_float().unwrap() as f32;
+        // model = "pbr" selects the metallic-roughness parameters instead of the legacy ones
+        if element.assignments_ref().get("model").and_then(|x| x.get_str()) == Some("pbr") {
+            output_unlocked.0.pbr = Some(self.process_pbr_material(element)?);
+        }
+        else {
+            let dif_r = assignment(element, "dif_r", TokenContent::get_float)? as f32;
+            let dif_g = assignment(element, "dif_g", TokenContent::get_float)? as f32;
+            let dif_b = assignment(element, "dif_b", TokenContent::get_float)? as f32;
+            let dif_a = assignment(element, "dif_a", TokenContent::get_float)? as f32;
+
+            let scol_r = assignment(element, "scol_r", TokenContent::get_float)? as f32;
+            let scol_g = assignment(element, "scol_g", TokenContent::get_float)? as f32;
+            let scol_b = assignment(element, "scol_b", TokenContent::get_float)? as f32;
+
+            output_unlocked.0.dif_ = [dif_r, dif_g, dif_b, dif_a];
+            output_unlocked.0.scol = [scol_r, scol_g, scol_b];
+            output_unlocked.0.alpha = assignment(element, "alpha", TokenContent::get_float)? as f32;
+            output_unlocked.0.translucency = assignment(element, "translucency", TokenContent::get_float)? as f32;
+            output_unlocked.0.illuminosity = assignment(element, "illuminosity", TokenContent::get_float)? as f32;
+            output_unlocked.0.specular_intensity = assignment(element, "specular_intensity", TokenContent::get_float)? as f32;
+            output_unlocked.0.specular_hardness = assignment(element, "specular_hardness", TokenContent::get_float)? as f32;
+        }
+
+        // optional, e.g. diffuse_texture = "name" diffuse_uvmap = 0
+        for slot in texture::TextureSlotType::ALL{
+            let Some(texture_name) = element.assignments_ref().get(format!("{}_texture", slot.name()).as_str()).and_then(|x| x.get_str()) else {continue;};
+            let uvmap_key = format!("{}_uvmap", slot.name());
+            let uvmap = optional_assignment(element, &uvmap_key, TokenContent::get_int)?.unwrap_or(0);
+            let uvmap = u8::try_from(uvmap).map_err(|_| format!("Element '{}' has an invalid assignment '{}'.", element_name(element), uvmap_key))?;
+            output_unlocked.0.set_texture(slot, Some(texture::TextureSlot{texture : texture_name.into(), uvmap}));
+        }
 
         let final_output = output.clone();
-        (final_output, CompactString::new(element.attributes_ref()["name"].get_str().unwrap()))
+        Ok((final_output, CompactString::new(attribute(element, "name", TokenContent::get_str)?)))
     }
 
-    fn process_viewport(&mut self, element : &Element) -> (Arc<Mutex<(viewport::ViewPort, bool)>>, CompactString){
+    // base color, metallic and roughness are required
+    fn process_pbr_material(&mut self, element : &Element) -> Result<material::PbrMaterial, String>{
+        let mut output = material::PbrMaterial::default();
+        let get_float = |key : &str| Ok::<_, String>(optional_assignment(element, key, TokenContent::get_float)?.map(|x| x as f32));
+        let required_float = |key : &str| Ok::<_, String>(assignment(element, key, TokenContent::get_float)? as f32);
+
+        for (value, key) in output.base_color.iter_mut().zip(["base_color_r", "base_color_g", "base_color_b", "base_color_a"]){
+            *value = required_float(key)?;
+        }
+        output.metallic = required_float("metallic")?;
+        output.roughness = required_float("roughness")?;
+
+        for (value, key) in output.emissive.iter_mut().zip(["emissive_r", "emissive_g", "emissive_b"]){
+            *value = get_float(key)?.unwrap_or(0.0);
+        }
+        output.occlusion = get_float("occlusion")?.unwrap_or(output.occlusion);
+        if let Some(alpha_mode) = optional_assignment(element, "alpha_mode", TokenContent::get_str)?{
+            output.alpha_mode = material::AlphaMode::from_name(alpha_mode)
+                .ok_or_else(|| format!("Element '{}' has an invalid alpha_mode '{}'.", element_name(element), alpha_mode))?;
+        }
+        output.alpha_cutoff = get_float("alpha_cutoff")?.unwrap_or(output.alpha_cutoff);
+        output.double_sided = optional_assignment(element, "double_sided", TokenContent::get_int)?.is_some_and(|x| x != 0);
+        Ok(output)
+    }
+
+    // images are read through the virtual file system and decoded while interpreting
+    fn process_texture(&mut self, element : &Element) -> Result<(Arc<Mutex<(texture::Texture, bool)>>, CompactString), String>{
+        use super::super::global_variables::OE_VFS_;
+        use super::image::decode_image;
+
+        let name = attribute(element, "name", TokenContent::get_str)?;
+        let mut output = texture::Texture::new();
+        output.path = assignment(element, "path", TokenContent::get_str)?.into();
+        output.mipmaps = element.assignments_ref().get("mipmaps").and_then(|x| x.get_int()).unwrap_or(1) != 0;
+
+        // textures whose image cannot be loaded stay without levels, the rest of the world is still loaded
+        let bytes = OE_VFS_.read().unwrap().read(&output.path);
+        match bytes.ok_or("file could not be read".to_string()).and_then(|x| decode_image(&x)){
+            Ok(image) => output.set_image(image),
+            Err(error) => println!("[INTERPRETER] Texture '{}' could not load '{}': {}", name, output.path, error),
+        }
+        Ok((Arc::new(Mutex::new((output, true))), name.into()))
+    }
+
+    fn process_viewport(&mut self, element : &Element) -> Result<(Arc<Mutex<(viewport::ViewPort, bool)>>, CompactString), String>{
         let output: Arc<Mutex<(viewport::ViewPort, bool)>> = Arc::new(Mutex::new((viewport::ViewPort::new(), true)));
         let mut output_unlocked = output.lock().unwrap();
         
-        output_unlocked.0.split_screen_positions_ = element.assignments_ref()["split_screen_positions"].get_float_list().unwrap().iter().map(|x| *x as f32).collect(); 
-        output_unlocked.0.layer_combine_modes_ = element.assignments_ref()["layer_combine_modes"].get_int_list().unwrap().iter().map(|x| *x as u32).collect(); 
+        output_unlocked.0.split_screen_positions_ = assignment(element, "split_screen_positions", TokenContent::get_float_list)?.iter().map(|x| *x as f32).collect(); 
+        output_unlocked.0.layer_combine_modes_ = assignment(element, "layer_combine_modes", TokenContent::get_int_list)?.iter().map(|x| *x as u32).collect(); 
         
-        let cameras = element.assignments_ref()["cameras"].get_str_list().unwrap();
+        let cameras = assignment(element, "cameras", TokenContent::get_str_list)?;
 
         for cam_name in cameras{
             output_unlocked.0.cameras_.push(cam_name.clone());
         }
         
         let final_output = output.clone();
-        (final_output, CompactString::new(element.attributes_ref()["name"].get_str().unwrap()))
+        Ok((final_output, CompactString::new(attribute(element, "name", TokenContent::get_str)?)))
     }
 
-    fn process_uvmap_data(&mut self, element : &Element) -> UVMapData{
-        let mut output: UVMapData = Default::default();
-        output.elements = element.assignments_ref()["elements"].get_float_list().unwrap().iter().map(|x| *x as f32).collect();
-        output
+    fn process_uvmap_data(&mut self, element : &Element) -> Result<UVMapData, String>{
+        Ok(UVMapData{
+            elements : assignment(element, "elements", TokenContent::get_float_list)?.iter().map(|x| *x as f32).collect()
+        })
     }
 
     fn process_triangle(&mut self, element : &TriangleElement, indices : &mut Vec<u32>, _num_of_uvs : usize){
@@ -280,20 +691,162 @@ impl Interpreter{
 
 }
 
-pub fn interpret(input_str : &str) -> Interpreter{
+pub fn interpret(input_str : &str) -> Result<Interpreter, String>{
     let mut interpreter : Interpreter = Default::default();
-    interpreter.interpret(input_str);
+    interpreter.interpret(input_str)?;
     //println!("{:?}", interpreter);
-    interpreter
+    Ok(interpreter)
 }
 
-pub fn interpret_file(filename : &str) -> Interpreter{
-    use std::fs;
+pub fn interpret_file(filename : &str) -> Result<Interpreter, String>{
     use std::time::Instant;
+    use super::super::global_variables::OE_VFS_;
     let before = Instant::now();
-    let input_str = fs::read_to_string(filename).unwrap();
+    let input_str = OE_VFS_.read().unwrap().read_to_string(filename).ok_or_else(|| format!("Could not read \"{}\".", filename))?;
     let after = Instant::now();
     println!("[Performance] Time reading from file: {:?} secs", (after-before).as_secs_f64());
 
-    interpret(&input_str)
-}
\ No newline at end of file
+    let mut output = interpret(&input_str)?;
+    output.get_data().source = filename.into();
+    Ok(output)
+}
+#[cfg(test)]
+pub mod interpretertest{
+    use super::*;
+
+    fn parse(input_str : &str) -> Box<Element>{
+        parse_string(input_str).unwrap()
+    }
+
+    #[test]
+    fn armature_errors(){
+        let bone = |name : &str, parent : &str| format!("<Bone name = \"{}\"> parent = \"{}\" rest_state = {{0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0}} </Bone>\n", name, parent);
+        // children may come before their parents
+        let element = parse(&format!("<Armature name = \"arm\">\n{}<Bone name = \"root\"> rest_state = {{0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0}} </Bone>\n</Armature>", bone("child", "root")));
+        let armature = Interpreter::default().process_armature(&element, 0).unwrap();
+        assert_eq!(armature.get_bones()[armature.get_bone_id("child").unwrap()].parent, armature.get_bone_id("root"));
+
+        let element = parse(&format!("<Armature name = \"arm\">\n{}</Armature>", bone("child", "missing")));
+        let error = Interpreter::default().process_armature(&element, 0).unwrap_err();
+        assert!(error.contains("'child' -> 'missing'"), "{}", error);
+
+        // weights have to match their vertices, which have to be positions of the mesh
+        let weighted = |vertices : &str, weights : &str| parse(&format!("<Armature name = \"arm\">\n<Bone name = \"root\"> rest_state = {{0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0}} vertices = {{{}}} weights = {{{}}} </Bone>\n</Armature>", vertices, weights));
+        let armature = Interpreter::default().process_armature(&weighted("0; 2", "1.0; 0.5"), 3).unwrap();
+        assert_eq!(armature.skin.weights.len(), 3);
+        for (vertices, weights, message) in [("-1", "1.0", "vertex -1"), ("3", "1.0", "vertex 3"), ("0; 1", "1.0", "2 vertices but 1 weights")]{
+            let error = Interpreter::default().process_armature(&weighted(vertices, weights), 3).unwrap_err();
+            assert!(error.contains(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn animation_errors(){
+        let channel = |property : &str, values : &str| format!("<Animation name = \"anim\">\n<PropertyChannel property = \"{}\"> times = {{0.0; 1.0}} values = {{{}}} </PropertyChannel>\n</Animation>", property, values);
+        let clip = Interpreter::default().process_animation(&parse(&channel("intensity", "0.0; 2.0"))).unwrap();
+        assert_eq!(clip.duration, 1.0);
+        assert_eq!(clip.sample_properties(0.5)[0].1[0], 1.0);
+
+        let error = Interpreter::default().process_animation(&parse(&channel("size", "0.0; 2.0"))).unwrap_err();
+        assert!(error.contains("\"size\""), "{}", error);
+        assert!(Interpreter::default().process_animation(&parse(&channel("intensity", "0.0"))).is_err());
+
+        let error = Interpreter::default().process_animation(&parse(&channel("intensity\" interpolation = \"cubik", "0.0; 2.0"))).unwrap_err();
+        assert!(error.contains("\"cubik\""), "{}", error);
+
+        let bone_channel = |states : &str| format!("<Animation name = \"anim\">\n<BoneChannel bone = \"arm\"> times = {{0.0; 1.0}} states = {{{}}} </BoneChannel>\n</Animation>", states);
+        let state = "0.0; 0.0; 0.0; 1.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0";
+        let clip = Interpreter::default().process_animation(&parse(&bone_channel(&[state, state].join("; ")))).unwrap();
+        assert_eq!(clip.duration, 1.0);
+        let error = Interpreter::default().process_animation(&parse(&bone_channel(state))).unwrap_err();
+        assert!(error.contains("'arm'"), "{}", error);
+        assert!(Interpreter::default().process_animation(&parse(&bone_channel(&[state, state, "0.0"].join("; ")))).is_err());
+    }
+
+    #[test]
+    fn morph_target_errors(){
+        let mesh = |morph_target : &str| format!("<Mesh name = \"quad\">\nvertices = {{0.0; 0.0; 0.0; 1.0; 0.0; 0.0; 0.0; 1.0; 0.0}}\n<Triangle> v1 = {{0; 0}} v2 = {{1; 0}} v3 = {{2; 0}} </Triangle>\n{}\n</Mesh>", morph_target);
+        let element = parse(&mesh("<MorphTarget name = \"lift\"> vertices = {1} positions = {0.0; 0.0; 1.0} weight = 0.5 </MorphTarget>"));
+        let mesh_data = Interpreter::default().process_mesh_geometry(&element, "quad").unwrap();
+        assert_eq!(mesh_data.morph_targets_.as_ref().unwrap().get_weight("lift"), Some(0.5));
+
+        let element = parse(&mesh("<MorphTarget name = \"lift\"> vertices = {3} positions = {0.0; 0.0; 1.0} </MorphTarget>"));
+        let error = Interpreter::default().process_mesh_geometry(&element, "quad").unwrap_err();
+        assert!(error.contains("'lift'") && error.contains("'quad'"), "{}", error);
+
+        let element = parse(&mesh("<MorphTarget name = \"lift\"> vertices = {1; 2} positions = {0.0; 0.0; 1.0} </MorphTarget>"));
+        assert!(Interpreter::default().process_mesh_geometry(&element, "quad").is_err());
+    }
+
+    #[test]
+    fn mesh_errors(){
+        let mesh = |attributes : &str, content : &str| format!("<Mesh name = \"quad\" {}>\nvertices = {{0.0; 0.0; 0.0; 1.0; 0.0; 0.0; 0.0; 1.0; 0.0}}\n<Triangle> v1 = {{0; 0}} v2 = {{1; 0}} v3 = {{2; 0}} </Triangle>\n{}\n</Mesh>", attributes, content);
+        let error = |attributes : &str, content : &str| Interpreter::default().process_mesh_geometry(&parse(&mesh(attributes, content)), "quad").unwrap_err();
+        assert!(Interpreter::default().process_mesh_geometry(&parse(&mesh("normal_mode = \"flat\"", "")), "quad").is_ok());
+
+        assert!(error("normal_mode = \"bent\"", "").contains("'bent'"));
+        assert!(error("position_format = \"int3\"", "").contains("position_format"));
+        assert!(error("tangents = 2", "").contains("uv map 2"));
+        assert!(error("softbody = 1 position_format = \"unorm16\"", "").contains("quantized"));
+        assert!(error("", "lod_ratios = {1; 2}").contains("lod_ratios"));
+
+        // missing keys of objects are errors instead of panics
+        let element = parse("<Camera name = \"camera\" visible = 1> fov = 1.0 near = 0.1 far = 10.0 </Camera>");
+        let error = Interpreter::default().process_camera(&element).unwrap_err();
+        assert!(error.contains("'camera'") && error.contains("'aspect_ratio'"), "{}", error);
+        assert!(interpret("<World> loaded_scene = \"s\" </World>").is_err());
+        assert!(interpret("<World> loaded_scene = \"s\"").is_err());
+    }
+
+    #[test]
+    fn pbr_material_errors(){
+        let source = |assignments : &str| format!("<Material name = \"metal\"> model = \"pbr\" base_color_r = 1.0 base_color_g = 1.0 base_color_b = 1.0 base_color_a = 1.0 metallic = 1.0 roughness = 0.5 {} </Material>", assignments);
+        let material = |assignments : &str| parse(&source(assignments));
+        let output = Interpreter::default().process_material(&material("alpha_mode = \"mask\"")).unwrap().0;
+        assert_eq!(output.lock().unwrap().0.pbr.as_ref().unwrap().alpha_mode, material::AlphaMode::Mask);
+
+        let error = Interpreter::default().process_material(&material("alpha_mode = \"cutout\"")).unwrap_err();
+        assert!(error.contains("'metal'") && error.contains("'cutout'"), "{}", error);
+        assert!(Interpreter::default().process_material(&material("alpha_mode = 1")).is_err());
+
+        let error = Interpreter::default().process_material(&material("diffuse_texture = \"wood\" diffuse_uvmap = 256")).unwrap_err();
+        assert!(error.contains("diffuse_uvmap"), "{}", error);
+        assert!(Interpreter::default().process_material(&material("diffuse_texture = \"wood\" diffuse_uvmap = -1")).is_err());
+
+        // the uv map of a texture has to exist in the meshes that use the material
+        let scene = |uvmap : i32| parse(&format!("<Scene name = \"scene\">\n{}\n<Mesh name = \"quad\" visible = 1> vertices = {{0.0; 0.0; 0.0; 1.0; 0.0; 0.0; 0.0; 1.0; 0.0}} current_state = {{0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0}} parent = \"\"\n<UVMapData> elements = {{0.0; 0.0; 1.0; 0.0; 0.0; 1.0}} </UVMapData>\n<VertexGroup name = \"all\"> polygons = {{0}} material_id = \"metal\" </VertexGroup>\n<Triangle> v1 = {{0; 0; 0}} v2 = {{1; 1; 1}} v3 = {{2; 2; 2}} </Triangle>\n</Mesh>\n</Scene>",
+            source(&format!("diffuse_texture = \"wood\" diffuse_uvmap = {}", uvmap))));
+        assert!(Interpreter::default().process_scene(&scene(0)).is_ok());
+        let error = Interpreter::default().process_scene(&scene(1)).unwrap_err();
+        assert!(error.contains("'metal'") && error.contains("'quad'"), "{}", error);
+    }
+
+    #[test]
+    fn light_errors(){
+        let light = |parameters : &str| parse(&format!("<Light name = \"lamp\" visible = 1> light_type = 1 fov = 1.0 range = 10.0 intensity = 1.0 color = {{1.0; 1.0; 1.0}} current_state = {{0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0}} parent = \"\" {} </Light>", parameters));
+        let process = |parameters : &str| Interpreter::default().process_light(&light(parameters));
+
+        let output = process("attenuation = \"custom\" attenuation_quadratic = 0.5 area_shape = \"disc\" area_radius = 2.0 shadow = 1 shadow_cascades = 2").unwrap().0;
+        let output = output.lock().unwrap().0.get_light().unwrap();
+        assert_eq!(output.attenuation, light::Attenuation::Custom{constant : 1.0, linear : 0.0, quadratic : 0.5});
+        assert_eq!(output.area_shape, light::AreaShape::Disc{radius : 2.0});
+        assert_eq!(output.shadow.unwrap().cascades, 2);
+
+        for (parameters, message) in [
+            ("attenuation = \"cubic\"", "'cubic'"),
+            ("attenuation = 1", "'attenuation'"),
+            ("area_shape = \"triangle\"", "'triangle'"),
+            ("area_shape = \"rectangle\" area_width = 1.0", "'area_height'"),
+            ("area_shape = \"disc\"", "'area_radius'"),
+            ("shadow = 1 shadow_bias = \"small\"", "'shadow_bias'"),
+        ]{
+            let error = process(parameters).unwrap_err();
+            assert!(error.contains("'lamp'") && error.contains(message), "{}", error);
+        }
+
+        // negative values do not wrap around
+        let output = process("priority = -1 shadow = 1 shadow_resolution = -512 shadow_cascades = -2").unwrap().0;
+        let output = output.lock().unwrap().0.get_light().unwrap();
+        assert_eq!((output.priority, output.shadow.unwrap().resolution, output.shadow.unwrap().cascades), (0, 0, 0));
+    }
+}
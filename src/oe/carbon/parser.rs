@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::LazyLock;
 use super::lexer::*;
 use logos::Logos;
 //use std::ffi::CString;
@@ -72,8 +73,14 @@ impl Element {
         if self.elements_ == None {self.elements_ = Some(Default::default());}
         return self.elements_.as_mut().unwrap();
     }
+    // empty if the element has no children
     pub fn elements_ref(&self) -> &ParserMap<Vec<ElementEnum>>{
-        return self.elements_.as_ref().unwrap();
+        static EMPTY : LazyLock<ParserMap<Vec<ElementEnum>>> = LazyLock::new(Default::default);
+        self.elements_.as_ref().unwrap_or(&EMPTY)
+    }
+    // empty if the element has no children of that name
+    pub fn get_elements(&self, name : &str) -> &[ElementEnum]{
+        self.elements_.as_ref().and_then(|x| x.get(name)).map(|x| x.as_slice()).unwrap_or_default()
     }
     pub fn attributes(&mut self) -> &mut ParserMap<TokenContent>{
         if self.attributes_ == None {self.attributes_ = Some(Default::default());}
@@ -82,8 +89,10 @@ impl Element {
     pub fn assignments(&mut self) -> &mut ParserMap<TokenContent>{
         return &mut self.assignments_;
     }
+    // empty if the element has no attributes
     pub fn attributes_ref(&self) -> &ParserMap<TokenContent>{
-        return self.attributes_.as_ref().unwrap();
+        static EMPTY : LazyLock<ParserMap<TokenContent>> = LazyLock::new(Default::default);
+        self.attributes_.as_ref().unwrap_or(&EMPTY)
     }
     pub fn assignments_ref(&self) -> &ParserMap<TokenContent>{
         return &self.assignments_;
@@ -119,47 +128,54 @@ pub struct Parser<T, A> {
 
 impl<'a, A> Parser<Token<'a>, A> where A : Iterator<Item=Result<Token<'a>, LexingError>> {
 
-    fn new(mut tokens : A) -> Parser<Token<'a>, A>{
-        Parser{
-            cur_token_ : tokens.next().unwrap().unwrap(),
+    fn new(tokens : A) -> Result<Parser<Token<'a>, A>, String>{
+        let mut output = Parser{
+            cur_token_ : Token::NewLineDef(0),
             token_it_ : tokens,
-            cur_line_ : 0,
+            cur_line_ : 1,
+        };
+        // skips leading empty lines
+        output.pass_token()?;
+        if output.has_type(&Token::NewLineDef(0)) {
+            return Err("Input is empty.".to_string());
         }
+        Ok(output)
     }
 
-    pub fn parse(&mut self) -> Box<Element> {
-        self.expect_token(&Token::LTDef);
-        self.pass_token();
+    pub fn parse(&mut self) -> Result<Box<Element>, String> {
+        self.require_token(&Token::LTDef)?;
+        self.pass_token()?;
 
-        match self.parse_element(){
-            ElementEnum::NormalElement(s) => s,
-            _ => panic!("wtf")
+        match self.parse_element()?{
+            ElementEnum::NormalElement(s) => Ok(s),
+            ElementEnum::TriangleElement(_) => Err("Root element can not be a triangle.".to_string()),
         }
     }
 
-    fn parse_element(&mut self) -> ElementEnum{
+    fn parse_element(&mut self) -> Result<ElementEnum, String>{
         let mut result : Element = Default::default();
 
         // parse opening tag
-        self.expect_token(&Token::IdentifierDef(None)).unwrap();
+        self.require_token(&Token::IdentifierDef(None))?;
 
-        let el_name = self.get_token_content();
-        self.pass_token();
+        let el_name = self.get_token_content()?;
+        self.pass_token()?;
 
         while self.has_type(&Token::IdentifierDef(None)) {
-            let att_name = CompactString::new(self.get_token_content().get_str().unwrap());
+            let att_name = self.get_identifier()?;
 
-            self.pass_token();
-            self.expect_token(&Token::EqualDef).unwrap();
-            self.pass_token();
-            result.attributes().insert(att_name, self.parse_single_assignment());
+            self.pass_token()?;
+            self.require_token(&Token::EqualDef)?;
+            self.pass_token()?;
+            self.expect_value().ok_or_else(|| self.error("expected a value"))?;
+            result.attributes().insert(att_name, self.parse_single_assignment()?);
         }
 
-        self.expect_token(&Token::GTDef).unwrap();
-        self.pass_token();
+        self.require_token(&Token::GTDef)?;
+        self.pass_token()?;
 
-        if el_name.get_str().unwrap() == "Triangle" && result.attributes().len() == 0{
-            return ElementEnum::TriangleElement(self.parse_triangle_element());
+        if el_name.get_str() == Some("Triangle") && result.attributes().is_empty(){
+            return Ok(ElementEnum::TriangleElement(self.parse_triangle_element()?));
         }
 
         // parse element content
@@ -167,22 +183,19 @@ impl<'a, A> Parser<Token<'a>, A> where A : Iterator<Item=Result<Token<'a>, Lexin
             //println!("blabl {:?}", self.get_token());
             if self.has_type(&Token::IdentifierDef(None)) {
 
-                let as_name = CompactString::new(self.get_token_content().get_str().unwrap());
-                self.pass_token();
+                let as_name = self.get_identifier()?;
+                self.pass_token()?;
 
-                self.expect_token(&Token::EqualDef).unwrap();
-                self.pass_token();
+                self.require_token(&Token::EqualDef)?;
+                self.pass_token()?;
 
-                result.assignments().insert(as_name, self.parse_assigment());
+                result.assignments().insert(as_name, self.parse_assigment()?);
             }
             else if self.has_type(&Token::LTDef) {
-                self.pass_token();
-                let sub_el_name = CompactString::new(self.get_token_content().get_str().unwrap());
-                if ! result.elements().contains_key(&sub_el_name){
-                    result.elements().insert(sub_el_name.clone(), Default::default());
-                }
-                result.elements().get_mut(&sub_el_name).unwrap().push(self.parse_element())
-
+                self.pass_token()?;
+                let sub_el_name = self.get_identifier()?;
+                let sub_element = self.parse_element()?;
+                result.elements().entry(sub_el_name).or_default().push(sub_element);
             }
             else {
                 break;
@@ -190,165 +203,126 @@ impl<'a, A> Parser<Token<'a>, A> where A : Iterator<Item=Result<Token<'a>, Lexin
         }
 
         // parse closing tag
-        self.expect_tokens(&[Token::LtSlashDef, Token::LTDef]).unwrap();
-        self.pass_token();
+        self.require_token(&Token::LtSlashDef)?;
+        self.pass_token()?;
 
-        self.expect_token(&Token::IdentifierDef(None)).unwrap();
-        if el_name != self.get_token_content(){
-            panic!("Closing tag identifier does not match opening tag identifier.");
+        self.require_token(&Token::IdentifierDef(None))?;
+        if el_name != self.get_token_content()?{
+            return Err(self.error("closing tag identifier does not match opening tag identifier"));
         }
 
-        self.pass_token();
-        self.expect_token(&Token::GTDef).unwrap();
-        self.pass_token();
+        self.pass_token()?;
+        self.require_token(&Token::GTDef)?;
+        self.pass_token()?;
 
         result.elements().shrink_to_fit();
         result.assignments().shrink_to_fit();
         result.attributes().shrink_to_fit();
-        ElementEnum::NormalElement(Box::new(result))
+        Ok(ElementEnum::NormalElement(Box::new(result)))
     }
 
-    fn parse_triangle_element(&mut self) -> TriangleElement{
+    fn parse_triangle_element(&mut self) -> Result<TriangleElement, String>{
         let mut output : TriangleElement = Default::default();
         // parse element content
-        loop {
-            //println!("blabl {:?}", self.get_token());
-            if self.has_type(&Token::IdentifierDef(None)) {
-
-                let as_name = CompactString::new(self.get_token_content().get_str().unwrap());
-                self.pass_token();
-
-                self.expect_token(&Token::EqualDef).unwrap();
-                self.pass_token();
-
-
-                let assignment = self.parse_assigment();
-                if as_name == "v1" {
-                    let triangle_indices = assignment.get_int_list().unwrap();
-                    output.num_of_uvs = triangle_indices.len() as u8;
-                    for id in 0..triangle_indices.len(){
-                        output.v1[id] = triangle_indices[id];
-                    }
-                }
-                else if as_name == "v2" {
-                    let triangle_indices = assignment.get_int_list().unwrap();
-                    for id in 0..triangle_indices.len(){
-                        output.v2[id] = triangle_indices[id];
-                    }
-                }
-                else if as_name == "v3" {
-                    let triangle_indices = assignment.get_int_list().unwrap();
-                    for id in 0..triangle_indices.len(){
-                        output.v3[id] = triangle_indices[id];
-                    }
-                }
-                else {
-                }
+        while self.has_type(&Token::IdentifierDef(None)) {
+            let as_name = self.get_identifier()?;
+            self.pass_token()?;
+
+            self.require_token(&Token::EqualDef)?;
+            self.pass_token()?;
+
+            let assignment = self.parse_assigment()?;
+            let corner = match as_name.as_str(){
+                "v1" => &mut output.v1,
+                "v2" => &mut output.v2,
+                "v3" => &mut output.v3,
+                _ => continue,
+            };
+            let triangle_indices = assignment.get_int_list().ok_or_else(|| self.error("triangle corners have to be integer lists"))?;
+            if triangle_indices.len() > corner.len() {
+                return Err(self.error("triangle corner has too many indices"));
             }
-            else {
-                break;
+            corner[..triangle_indices.len()].copy_from_slice(triangle_indices);
+            if as_name == "v1" {
+                output.num_of_uvs = triangle_indices.len() as u8;
             }
         }
 
         // parse closing tag
-        self.expect_tokens(&[Token::LtSlashDef, Token::LTDef]).unwrap();
-        self.pass_token();
+        self.require_token(&Token::LtSlashDef)?;
+        self.pass_token()?;
 
-        self.expect_token(&Token::IdentifierDef(None)).unwrap();
-        if "Triangle" != self.get_token_content().get_str().unwrap(){
-            panic!("Closing tag identifier does not match opening tag identifier.");
+        self.require_token(&Token::IdentifierDef(None))?;
+        if Some("Triangle") != self.get_token_content()?.get_str(){
+            return Err(self.error("closing tag identifier does not match opening tag identifier"));
         }
 
-        self.pass_token();
-        self.expect_token(&Token::GTDef).unwrap();
-        self.pass_token();
+        self.pass_token()?;
+        self.require_token(&Token::GTDef)?;
+        self.pass_token()?;
 
-        output
+        Ok(output)
     }
 
-    fn get_token(&self) -> Token {
-        self.cur_token_.clone()
+    // errors name the line of the current token
+    fn error(&self, message : &str) -> String{
+        format!("Line {}: {}, found {:?}.", self.cur_line_, message, self.cur_token_)
+    }
+    fn get_token_content(&self) -> Result<TokenContent, String>{
+        self.cur_token_.get_content().ok_or_else(|| self.error("expected a value"))
     }
-    fn get_token_content(&self) -> TokenContent{
-        let content = self.cur_token_.get_content().unwrap();
-        //println!("{:?}", content);
-        content
+    fn get_identifier(&self) -> Result<CompactString, String>{
+        let content = self.get_token_content()?;
+        content.get_str().map(CompactString::new).ok_or_else(|| self.error("expected an identifier"))
     }
 
-    fn parse_assigment(&mut self) -> TokenContent{
-        self.expect_tokens(&[Token::IntegerDef(0), Token::FloatDef(0.0), Token::IdentifierDef(None), Token::OpenBraceDef, Token::StringDef(None)]).unwrap();
+    fn parse_assigment(&mut self) -> Result<TokenContent, String>{
+        self.expect_tokens(&[Token::IntegerDef(0), Token::FloatDef(0.0), Token::IdentifierDef(None), Token::OpenBraceDef, Token::StringDef(None)])
+            .ok_or_else(|| self.error("expected a value or a list"))?;
 
         if self.has_type(&Token::OpenBraceDef){
-            self.pass_token();
-            self.parse_list_assignment().unwrap()
+            self.pass_token()?;
+            self.parse_list_assignment()
         }
         else {
             self.parse_single_assignment()
         }
     }
 
-    fn parse_single_assignment(&mut self) -> TokenContent {
-        let output = self.get_token_content();
-        self.pass_token();
-        output
+    fn parse_single_assignment(&mut self) -> Result<TokenContent, String> {
+        let output = self.get_token_content()?;
+        self.pass_token()?;
+        Ok(output)
     }
-    fn parse_list_assignment(&mut self) -> Option<TokenContent> {
-
-        let is_int = self.is_int();
-        let is_float = self.is_float();
-        let is_str = self.is_str();
-
-
-        if is_float{
-            let mut result : Vec<f64> = Vec::with_capacity(4);
-                result.push(self.get_token_content().get_float().unwrap());
-
-                self.pass_token();
-                while self.has_type(&Token::SemiColonDef){
-                    self.pass_token();
-                    self.expect_value().unwrap();
-
-                    result.push(self.get_token_content().get_float().unwrap());
-                    self.pass_token();
-                }
-            self.expect_token(&Token::CloseBraceDef).unwrap();
-            self.pass_token();
-            Some(TokenContent::FloatList(Box::new(result)))
+    // every value of a list has the type of the first one
+    fn parse_list_value<T>(&mut self, result : &mut Vec<T>, get : impl Fn(&TokenContent) -> Option<T>) -> Result<(), String>{
+        let value = get(&self.get_token_content()?).ok_or_else(|| self.error("list values have to be of the same type"))?;
+        result.push(value);
+        self.pass_token()
+    }
+    fn parse_list_values<T>(&mut self, get : impl Fn(&TokenContent) -> Option<T>) -> Result<Vec<T>, String>{
+        let mut result : Vec<T> = Vec::with_capacity(4);
+        self.parse_list_value(&mut result, &get)?;
+        while self.has_type(&Token::SemiColonDef){
+            self.pass_token()?;
+            self.parse_list_value(&mut result, &get)?;
         }
-        else if is_int{
-            let mut result : Vec<i32> = Vec::with_capacity(4);
-                result.push(self.get_token_content().get_int().unwrap());
-
-                self.pass_token();
-                while self.has_type(&Token::SemiColonDef){
-                    self.pass_token();
-                    self.expect_value().unwrap();
-
-                    result.push(self.get_token_content().get_int().unwrap());
-                    self.pass_token();
-                }
-            self.expect_token(&Token::CloseBraceDef).unwrap();
-            self.pass_token();
-            Some(TokenContent::IntList(Box::new(result)))
+        self.require_token(&Token::CloseBraceDef)?;
+        self.pass_token()?;
+        Ok(result)
+    }
+    fn parse_list_assignment(&mut self) -> Result<TokenContent, String> {
+        if self.is_float(){
+            Ok(TokenContent::FloatList(Box::new(self.parse_list_values(|x| x.get_float())?)))
+        }
+        else if self.is_int(){
+            Ok(TokenContent::IntList(Box::new(self.parse_list_values(|x| x.get_int())?)))
         }
-        else if is_str{
-            let mut result : Vec<CompactString> = Vec::with_capacity(4);
-                result.push(CompactString::new(self.get_token_content().get_str().unwrap()));
-
-                self.pass_token();
-                while self.has_type(&Token::SemiColonDef){
-                    self.pass_token();
-                    self.expect_value().unwrap();
-
-                    result.push(CompactString::new(self.get_token_content().get_str().unwrap()));
-                    self.pass_token();
-                }
-            self.expect_token(&Token::CloseBraceDef).unwrap();
-            self.pass_token();
-            Some(TokenContent::StringList(Box::new(result)))
+        else if self.is_str(){
+            Ok(TokenContent::StringList(Box::new(self.parse_list_values(|x| x.get_str().map(CompactString::new))?)))
         }
         else {
-            None
+            Err(self.error("expected a list of numbers or strings"))
         }
     }
 
@@ -370,26 +344,31 @@ impl<'a, A> Parser<Token<'a>, A> where A : Iterator<Item=Result<Token<'a>, Lexin
             None
         }
     }
+    fn require_token(&self, comparison : &Token) -> Result<(), String>{
+        self.expect_token(comparison).map(|_| ()).ok_or_else(|| self.error(&format!("expected {:?}", comparison)))
+    }
 
     fn expect_value(&self) -> Option<bool>{
         self.expect_tokens(&[Token::IntegerDef(4), Token::FloatDef(0.0), Token::IdentifierDef(None), Token::StringDef(None)])
     }
 
-    fn pass_token(&mut self){
+    // the last token stays the current one at the end of the input
+    fn pass_token(&mut self) -> Result<(), String>{
         match self.token_it_.next(){
-            Some(s) => {self.cur_token_ = s.unwrap();}
-            None => {return;}
+            Some(s) => {self.cur_token_ = s.map_err(|error| format!("Line {}: invalid token ({:?}).", self.cur_line_, error))?;}
+            None => {return Ok(());}
         }
         while self.has_type(&Token::NewLineDef(0)){
-            match self.token_it_.next(){
-                Some(s) => {self.cur_token_ = s.unwrap();}
-                None => {return;}
-            }
             self.cur_line_ = match self.cur_token_ {
-                Token::NewLineDef(s) => s,
+                Token::NewLineDef(s) => s + 1,
                 _ => self.cur_line_,
+            };
+            match self.token_it_.next(){
+                Some(s) => {self.cur_token_ = s.map_err(|error| format!("Line {}: invalid token ({:?}).", self.cur_line_, error))?;}
+                None => {return Ok(());}
             }
         }
+        Ok(())
     }
     fn is_int(&self) -> bool {
         self.expect_token(&Token::IntegerDef(4)).unwrap_or(false)
@@ -405,11 +384,51 @@ impl<'a, A> Parser<Token<'a>, A> where A : Iterator<Item=Result<Token<'a>, Lexin
     }
 }
 
-pub fn parse_string(input_str : &str) -> Box<Element> {
+// errors name the line that could not be parsed
+pub fn parse_string(input_str : &str) -> Result<Box<Element>, String> {
     let tokens: _ = Token::lexer(input_str).spanned().map(|x| x.0).into_iter();
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens)?;
 
     parser.parse()
 }
 
+#[cfg(test)]
+pub mod parsertest{
+    use super::*;
+
+    #[test]
+    fn parse_elements(){
+        let input = "\n\n<World name = \"w\">\n  value = 1\n  list = {1.0; 2.5}\n  <Scene name = \"s\"> names = {\"a\"; \"b\"} </Scene>\n  <Triangle> v1 = {0; 1} v2 = {1; 2} v3 = {2; 3} </Triangle>\n</World>\n";
+        let element = parse_string(input).unwrap();
+        assert_eq!(element.attributes_ref()["name"].get_str(), Some("w"));
+        assert_eq!(element.assignments_ref()["value"].get_int(), Some(1));
+        assert_eq!(element.assignments_ref()["list"].get_float_list().unwrap().as_slice(), [1.0, 2.5]);
+        let scene = element.get_elements("Scene")[0].get().unwrap();
+        assert_eq!(scene.assignments_ref()["names"].get_str_list().unwrap().as_slice(), ["a", "b"]);
+        assert!(scene.get_elements("Mesh").is_empty() && scene.elements_ref().is_empty());
+        let ElementEnum::TriangleElement(triangle) = &element.get_elements("Triangle")[0] else {panic!("expected a triangle")};
+        assert_eq!((triangle.num_of_uvs, triangle.v3[..2].to_vec()), (2, vec![2, 3]));
+    }
 
+    // malformed input is reported with its line instead of panicking
+    #[test]
+    fn reject_malformed_input(){
+        let error = |input : &str| parse_string(input).unwrap_err();
+        assert_eq!(error(""), "Input is empty.");
+        assert_eq!(error("\n\n"), "Input is empty.");
+        assert!(error("World </World>").contains("expected LTDef"));
+        assert!(error("<World>\n a = 1\n</Scene>").starts_with("Line 3: closing tag identifier does not match"));
+        assert!(error("<World> a = 1").contains("expected LtSlashDef"));
+        assert!(error("<World> a = </World>").contains("expected a value or a list"));
+        assert!(error("<World> a 1 </World>").contains("expected EqualDef"));
+        assert!(error("<World name = > </World>").contains("expected a value"));
+        assert!(error("<World> a = {1; 2.0} </World>").contains("same type"));
+        assert!(error("<World> a = {1; 2 </World>").contains("expected CloseBraceDef"));
+        assert!(error("<World> a = {} </World>").contains("expected a list"));
+        assert!(error("<World> a = 1 ? </World>").contains("invalid token"));
+        assert!(error("<Triangle> v1 = {0} </Triangle>").contains("triangle"));
+        assert!(error("<World> <Triangle> v1 = {0; 1; 2; 3; 4; 5; 6; 7; 8} </Triangle> </World>").contains("too many indices"));
+        assert!(error("<World> <Triangle> v1 = {0.5} </Triangle> </World>").contains("integer lists"));
+        assert!(error("<World> <Triangle> v1 = {0} </Mesh> </World>").contains("closing tag"));
+    }
+}
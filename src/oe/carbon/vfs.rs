@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use compact_str::CompactString;
+
+// packs are uncompressed tar archives, so they can also be created with any tar tool
+const TAR_BLOCK_SIZE : u64 = 512;
+
+pub trait VfsReadTrait : Read + Send {}
+impl<T : Read + Send> VfsReadTrait for T {}
+
+// removes empty and "." components, resolves ".." and uses "/" as separator
+pub fn normalize_path(path : &str) -> CompactString{
+    let mut components : Vec<&str> = vec![];
+    for component in path.split(['/', '\\']){
+        match component{
+            "" | "." => {}
+            ".." => {components.pop();}
+            other => components.push(other),
+        }
+    }
+    components.join("/").into()
+}
+
+#[derive(Debug, Clone)]
+struct PackEntry{
+    offset : u64,
+    size : u64,
+}
+
+#[derive(Debug)]
+enum MountSource{
+    Directory(PathBuf),
+    Pack{path : PathBuf, entries : HashMap<CompactString, PackEntry>},
+    Memory(HashMap<CompactString, Arc<Vec<u8>>>),
+}
+
+#[derive(Debug)]
+struct MountPoint{
+    // normalized virtual directory the files appear in, empty for the root
+    prefix : CompactString,
+    priority : i32,
+    source : MountSource,
+}
+
+impl MountPoint{
+    // path inside the mount if the virtual path is below its prefix
+    fn relative<'a>(&self, path : &'a str) -> Option<&'a str>{
+        if self.prefix.is_empty() {
+            return Some(path);
+        }
+        path.strip_prefix(self.prefix.as_str()).and_then(|x| if x.is_empty() {Some(x)} else {x.strip_prefix('/')})
+    }
+
+    fn open(&self, path : &str) -> Option<(Box<dyn VfsReadTrait>, u64)>{
+        match &self.source{
+            MountSource::Directory(directory) => {
+                let file = File::open(directory.join(path)).ok()?;
+                let metadata = file.metadata().ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                Some((Box::new(file), metadata.len()))
+            }
+            MountSource::Pack{path : pack_path, entries} => {
+                let entry = entries.get(path)?;
+                let mut file = File::open(pack_path).ok()?;
+                file.seek(SeekFrom::Start(entry.offset)).ok()?;
+                Some((Box::new(file.take(entry.size)), entry.size))
+            }
+            MountSource::Memory(files) => {
+                let data = Arc::clone(files.get(path)?);
+                let size = data.len() as u64;
+                Some((Box::new(Cursor::new(ArcBytes(data))), size))
+            }
+        }
+    }
+
+    fn contains(&self, path : &str) -> bool{
+        match &self.source{
+            MountSource::Directory(directory) => directory.join(path).is_file(),
+            MountSource::Pack{entries, ..} => entries.contains_key(path),
+            MountSource::Memory(files) => files.contains_key(path),
+        }
+    }
+}
+
+// lets cursors read from buffers shared with the mount
+struct ArcBytes(Arc<Vec<u8>>);
+impl AsRef<[u8]> for ArcBytes{
+    fn as_ref(&self) -> &[u8]{
+        &self.0
+    }
+}
+
+// files are looked up in the mounts with the highest priority first, the latest mount wins on equal priorities
+// paths no mount contains are read from the host file system
+#[derive(Debug, Default)]
+pub struct Vfs{
+    mounts : Vec<MountPoint>,
+}
+
+impl Vfs{
+    fn mount(&mut self, prefix : &str, priority : i32, source : MountSource){
+        let position = self.mounts.iter().position(|x| x.priority <= priority).unwrap_or(self.mounts.len());
+        self.mounts.insert(position, MountPoint{prefix : normalize_path(prefix), priority, source});
+    }
+
+    pub fn mount_directory(&mut self, prefix : &str, directory : &str, priority : i32) -> bool{
+        if !Path::new(directory).is_dir() {
+            return false;
+        }
+        self.mount(prefix, priority, MountSource::Directory(directory.into()));
+        true
+    }
+
+    // returns the number of files in the pack
+    pub fn mount_pack(&mut self, prefix : &str, pack : &str, priority : i32) -> Result<usize, String>{
+        let file = File::open(pack).map_err(|x| format!("Could not open pack \"{}\": {}", pack, x))?;
+        let entries = read_pack_index(file).map_err(|x| format!("Could not read pack \"{}\": {}", pack, x))?;
+        let count = entries.len();
+        self.mount(prefix, priority, MountSource::Pack{path : pack.into(), entries});
+        Ok(count)
+    }
+
+    pub fn mount_memory(&mut self, prefix : &str, files : Vec<(CompactString, Vec<u8>)>, priority : i32){
+        let files = files.into_iter().map(|(name, data)| (normalize_path(&name), Arc::new(data))).collect();
+        self.mount(prefix, priority, MountSource::Memory(files));
+    }
+
+    // removes every mount at the prefix
+    pub fn unmount(&mut self, prefix : &str) -> bool{
+        let prefix = normalize_path(prefix);
+        let count = self.mounts.len();
+        self.mounts.retain(|x| x.prefix != prefix);
+        self.mounts.len() != count
+    }
+
+    pub fn get_mounts(&self) -> Vec<CompactString>{
+        self.mounts.iter().map(|x| x.prefix.clone()).collect()
+    }
+
+    fn find(&self, path : &str) -> Option<(&MountPoint, CompactString)>{
+        let path = normalize_path(path);
+        self.mounts.iter().find_map(|mount| {
+            let relative = mount.relative(&path)?;
+            mount.contains(relative).then(|| (mount, relative.into()))
+        })
+    }
+
+    // returns the reader and the size of the file
+    pub fn open(&self, path : &str) -> Option<(Box<dyn VfsReadTrait>, u64)>{
+        if let Some((mount, relative)) = self.find(path){
+            return mount.open(&relative);
+        }
+        let file = File::open(path).ok()?;
+        let size = file.metadata().ok()?.len();
+        Some((Box::new(file), size))
+    }
+
+    pub fn read(&self, path : &str) -> Option<Vec<u8>>{
+        let (mut reader, size) = self.open(path)?;
+        let mut output = Vec::with_capacity(size as usize);
+        reader.read_to_end(&mut output).ok()?;
+        Some(output)
+    }
+
+    pub fn read_to_string(&self, path : &str) -> Option<String>{
+        String::from_utf8(self.read(path)?).ok()
+    }
+
+    pub fn exists(&self, path : &str) -> bool{
+        self.find(path).is_some() || Path::new(path).is_file()
+    }
+
+    // files in packs and memory mounts never change
+    pub fn modified(&self, path : &str) -> Option<SystemTime>{
+        let real_path = match self.find(path){
+            Some((MountPoint{source : MountSource::Directory(directory), ..}, relative)) => directory.join(relative.as_str()),
+            Some(_) => return None,
+            None => path.into(),
+        };
+        std::fs::metadata(real_path).and_then(|x| x.modified()).ok()
+    }
+}
+
+fn parse_octal(field : &[u8]) -> u64{
+    field.iter().skip_while(|x| **x == b' ').take_while(|x| (b'0'..=b'7').contains(*x)).fold(0, |acc, x| acc * 8 + (x - b'0') as u64)
+}
+fn parse_name(field : &[u8]) -> String{
+    String::from_utf8_lossy(field.split(|x| *x == 0).next().unwrap_or_default()).into_owned()
+}
+fn padded_size(size : u64) -> u64{
+    size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE
+}
+
+fn invalid_pack(message : String) -> std::io::Error{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+// old tar tools sum signed bytes, so both sums are accepted
+fn has_valid_checksum(header : &[u8]) -> bool{
+    let checksum = parse_octal(&header[148..156]);
+    let field = |i : usize, x : u8| if (148..156).contains(&i) {b' '} else {x};
+    let unsigned : u64 = header.iter().enumerate().map(|(i, x)| field(i, *x) as u64).sum();
+    let signed : i64 = header.iter().enumerate().map(|(i, x)| field(i, *x) as i8 as i64).sum();
+    checksum == unsigned || checksum as i64 == signed
+}
+
+// regular files of ustar and gnu tar archives, gnu long names are supported
+fn read_pack_index(mut file : File) -> std::io::Result<HashMap<CompactString, PackEntry>>{
+    let pack_size = file.metadata()?.len();
+    let mut output = HashMap::new();
+    let mut header = [0u8; TAR_BLOCK_SIZE as usize];
+    let mut offset = 0;
+    let mut long_name = None;
+    // archives without the end marker are accepted if they end after a complete entry
+    while offset < pack_size{
+        if offset + TAR_BLOCK_SIZE > pack_size {
+            return Err(invalid_pack(format!("The header at {} is truncated.", offset)));
+        }
+        file.read_exact(&mut header)?;
+        if header.iter().all(|x| *x == 0) {
+            break;
+        }
+        if !has_valid_checksum(&header) {
+            return Err(invalid_pack(format!("The header at {} has an invalid checksum.", offset)));
+        }
+        offset += TAR_BLOCK_SIZE;
+        let size = parse_octal(&header[124..136]);
+        if offset.checked_add(padded_size(size)).is_none_or(|end| end > pack_size) {
+            return Err(invalid_pack(format!("The entry at {} with {} bytes is truncated.", offset - TAR_BLOCK_SIZE, size)));
+        }
+        let mut name = parse_name(&header[0..100]);
+        if &header[257..262] == b"ustar" && header[345] != 0 {
+            name = parse_name(&header[345..500]) + "/" + &name;
+        }
+        match header[156]{
+            b'L' => {
+                let mut data = vec![0u8; size as usize];
+                file.read_exact(&mut data)?;
+                long_name = Some(parse_name(&data));
+                file.seek(SeekFrom::Current((padded_size(size) - size) as i64))?;
+                offset += padded_size(size);
+                continue;
+            }
+            b'0' | 0 => {
+                let name = long_name.take().unwrap_or(name);
+                output.insert(normalize_path(&name), PackEntry{offset, size});
+            }
+            _ => {long_name = None;}
+        }
+        file.seek(SeekFrom::Current(padded_size(size) as i64))?;
+        offset += padded_size(size);
+    }
+    Ok(output)
+}
+
+fn write_header(writer : &mut impl Write, name : &[u8], size : u64, type_flag : u8) -> std::io::Result<()>{
+    let mut header = [0u8; TAR_BLOCK_SIZE as usize];
+    header[..name.len().min(100)].copy_from_slice(&name[..name.len().min(100)]);
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = type_flag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // the checksum is computed with the checksum field filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum : u32 = header.iter().map(|x| *x as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    writer.write_all(&header)
+}
+
+fn write_padding(writer : &mut impl Write, size : u64) -> std::io::Result<()>{
+    writer.write_all(&vec![0u8; (padded_size(size) - size) as usize])
+}
+
+pub fn write_pack(pack : &str, files : &[(CompactString, Vec<u8>)]) -> std::io::Result<()>{
+    let mut writer = std::io::BufWriter::new(File::create(pack)?);
+    for (name, data) in files{
+        let name = normalize_path(name);
+        if name.len() > 100 {
+            write_header(&mut writer, b"././@LongLink", name.len() as u64 + 1, b'L')?;
+            writer.write_all(name.as_bytes())?;
+            writer.write_all(&[0])?;
+            write_padding(&mut writer, name.len() as u64 + 1)?;
+        }
+        write_header(&mut writer, name.as_bytes(), data.len() as u64, b'0')?;
+        writer.write_all(data)?;
+        write_padding(&mut writer, data.len() as u64)?;
+    }
+    writer.write_all(&[0u8; 2 * TAR_BLOCK_SIZE as usize])?;
+    writer.flush()
+}
+
+fn collect_files(directory : &Path, prefix : &str, output : &mut Vec<(CompactString, Vec<u8>)>) -> std::io::Result<()>{
+    let mut entries = std::fs::read_dir(directory)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|x| x.file_name());
+    for entry in entries{
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &name, output)?;
+        }
+        else {
+            output.push((normalize_path(&name), std::fs::read(entry.path())?));
+        }
+    }
+    Ok(())
+}
+
+// returns the number of packed files
+pub fn pack_directory(directory : &str, pack : &str) -> Result<usize, String>{
+    let mut files = vec![];
+    collect_files(Path::new(directory), "", &mut files).map_err(|x| format!("Could not read directory \"{}\": {}", directory, x))?;
+    write_pack(pack, &files).map_err(|x| format!("Could not write pack \"{}\": {}", pack, x))?;
+    Ok(files.len())
+}
+
+#[cfg(test)]
+pub mod vfstest{
+
+    use super::*;
+
+    #[test]
+    fn test_vfs_mounts(){
+        assert_eq!(normalize_path("./worlds\\a/../b//c.csl"), "worlds/b/c.csl");
+
+        let mut vfs = Vfs::default();
+        vfs.mount_memory("assets", vec![("worlds/a.csl".into(), b"low".to_vec()), ("b.csl".into(), b"only low".to_vec())], 0);
+        vfs.mount_memory("assets/worlds", vec![("a.csl".into(), b"high".to_vec())], 1);
+        assert_eq!(vfs.read_to_string("assets/worlds/a.csl").as_deref(), Some("high"));
+        assert_eq!(vfs.read_to_string("/assets/./worlds/../b.csl").as_deref(), Some("only low"));
+        assert!(!vfs.exists("assets/c.csl"));
+        assert!(vfs.modified("assets/b.csl").is_none());
+
+        let pack = std::env::temp_dir().join("oe_vfs_test.pack");
+        let pack = pack.to_str().unwrap();
+        let long_name : CompactString = format!("{}/a.csl", "d".repeat(120)).into();
+        write_pack(pack, &[("a.csl".into(), b"packed".to_vec()), (long_name.clone(), vec![7; 600])]).unwrap();
+        assert_eq!(vfs.mount_pack("assets/worlds", pack, 1), Ok(2));
+        assert_eq!(vfs.read_to_string("assets/worlds/a.csl").as_deref(), Some("packed"));
+        assert_eq!(vfs.read(&format!("assets/worlds/{}", long_name)), Some(vec![7; 600]));
+
+        assert!(vfs.unmount("assets/worlds/"));
+        assert_eq!(vfs.read_to_string("assets/worlds/a.csl").as_deref(), Some("low"));
+        std::fs::remove_file(pack).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_packs(){
+        let pack = std::env::temp_dir().join("oe_vfs_invalid_test.pack");
+        let pack = pack.to_str().unwrap();
+        let long_name : CompactString = format!("{}/a.csl", "d".repeat(120)).into();
+        write_pack(pack, &[("a.csl".into(), vec![1; 600]), (long_name, b"long".to_vec())]).unwrap();
+        let data = std::fs::read(pack).unwrap();
+        let mount = |data : &[u8]| {
+            std::fs::write(pack, data).unwrap();
+            Vfs::default().mount_pack("assets", pack, 0)
+        };
+        assert_eq!(mount(&data), Ok(2));
+        // without the end marker
+        assert_eq!(mount(&data[..7 * TAR_BLOCK_SIZE as usize]), Ok(2));
+
+        // the data of the file, the long name, the header and the padding are cut off
+        assert!(mount(&data[..2 * TAR_BLOCK_SIZE as usize]).is_err());
+        assert!(mount(&data[..4 * TAR_BLOCK_SIZE as usize]).is_err());
+        assert!(mount(&data[..5 * TAR_BLOCK_SIZE as usize - 1]).is_err());
+        assert!(mount(&data[..6 * TAR_BLOCK_SIZE as usize + 4]).is_err());
+
+        let mut corrupted = data.clone();
+        corrupted[0] = b'b';
+        let error = mount(&corrupted).unwrap_err();
+        assert!(error.contains("checksum"), "{}", error);
+        std::fs::remove_file(pack).unwrap();
+    }
+}
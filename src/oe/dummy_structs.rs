@@ -123,4 +123,31 @@ pub struct RendererUpdateInfo{
     pub use_z_prepass : bool,
     pub restart_renderer : bool,
     pub shading_mode : RendererShadingMode,
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct PhysicsInitInfo{
+    pub fixed_timestep : f64,
+    pub max_substeps : u32,
+}
+
+impl Default for PhysicsInitInfo {
+    fn default() -> Self {PhysicsInitInfo{ fixed_timestep : 1.0/60.0,
+                            max_substeps : 8,
+                        }}
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct PhysicsUpdateInfo{
+    pub gravity : [f64; 3],
+    pub time_scale : f64,
+    pub paused : bool,
+}
+
+impl Default for PhysicsUpdateInfo {
+    // CSL worlds are exported from Blender, so Z points up
+    fn default() -> Self {PhysicsUpdateInfo{ gravity : [0.0, 0.0, -9.81],
+                            time_scale : 1.0,
+                            paused : false,
+                        }}
 }
\ No newline at end of file
@@ -43,6 +43,8 @@ pub enum EventEnum {
     Keyboard,
     Mouse,
     Internal,
+    Physics,
+    Animation,
 }
 
 pub struct EventMutexedProperties<'a> {
@@ -73,6 +73,41 @@ impl<'a> EventHandler<'a> {
         id
     }
 
+    pub fn create_unload_event(&mut self, event_name : &str) -> usize {
+        let id =Arc::get_mut(&mut self.events_).unwrap().insert_no_overwrite(&("unloaded-".to_string() + event_name), Box::new(&event_default_fn), EventEnum::User);
+        let happened_events_counter = Arc::get_mut(&mut Arc::get_mut(&mut self.events_).unwrap().happened_events_counter_).unwrap();
+        happened_events_counter.insert(id, AtomicU32::new(0));
+        id
+    }
+
+    pub fn create_reload_event(&mut self, event_name : &str) -> usize {
+        let id =Arc::get_mut(&mut self.events_).unwrap().insert_no_overwrite(&("reloaded-".to_string() + event_name), Box::new(&event_default_fn), EventEnum::User);
+        let happened_events_counter = Arc::get_mut(&mut Arc::get_mut(&mut self.events_).unwrap().happened_events_counter_).unwrap();
+        happened_events_counter.insert(id, AtomicU32::new(0));
+        id
+    }
+
+    pub fn create_progress_event(&mut self, event_name : &str) -> usize {
+        let id =Arc::get_mut(&mut self.events_).unwrap().insert_no_overwrite(&("progress-".to_string() + event_name), Box::new(&event_default_fn), EventEnum::User);
+        let happened_events_counter = Arc::get_mut(&mut Arc::get_mut(&mut self.events_).unwrap().happened_events_counter_).unwrap();
+        happened_events_counter.insert(id, AtomicU32::new(0));
+        id
+    }
+
+    pub fn create_physics_event(&mut self, event_name : &str) -> usize {
+        let id =Arc::get_mut(&mut self.events_).unwrap().insert_no_overwrite(&("physics-".to_string() + event_name), Box::new(&event_default_fn), EventEnum::Physics);
+        let happened_events_counter = Arc::get_mut(&mut Arc::get_mut(&mut self.events_).unwrap().happened_events_counter_).unwrap();
+        happened_events_counter.insert(id, AtomicU32::new(0));
+        id
+    }
+
+    pub fn create_animation_event(&mut self, event_name : &str) -> usize {
+        let id =Arc::get_mut(&mut self.events_).unwrap().insert_no_overwrite(&("animation-".to_string() + event_name), Box::new(&event_default_fn), EventEnum::Animation);
+        let happened_events_counter = Arc::get_mut(&mut Arc::get_mut(&mut self.events_).unwrap().happened_events_counter_).unwrap();
+        happened_events_counter.insert(id, AtomicU32::new(0));
+        id
+    }
+
     pub fn create_keyboard_event(&mut self, event_name : &str) -> usize {
         let id =Arc::get_mut(&mut self.events_).unwrap().insert_no_overwrite(&("keyboard-".to_string() + event_name), Box::new(&event_default_fn), EventEnum::Keyboard);
         let happened_events_counter = Arc::get_mut(&mut Arc::get_mut(&mut self.events_).unwrap().happened_events_counter_).unwrap();
@@ -114,6 +149,19 @@ impl<'a> EventHandler<'a> {
         output
     }
 
+    pub fn get_load_event_id(&self, event_name : &str) -> Option<usize> {
+        self.events_.get_id(&("loaded-".to_string() + event_name))
+    }
+    pub fn get_unload_event_id(&self, event_name : &str) -> Option<usize> {
+        self.events_.get_id(&("unloaded-".to_string() + event_name))
+    }
+    pub fn get_reload_event_id(&self, event_name : &str) -> Option<usize> {
+        self.events_.get_id(&("reloaded-".to_string() + event_name))
+    }
+    pub fn get_progress_event_id(&self, event_name : &str) -> Option<usize> {
+        self.events_.get_id(&("progress-".to_string() + event_name))
+    }
+
     pub fn get_keyboard_event_id(&self, event_name : &str) -> Option<usize> {
         self.events_.get_id(&("keyboard-".to_string() + event_name))
     }
@@ -140,6 +188,33 @@ impl<'a> EventHandler<'a> {
         }
         output
     }
+    pub fn get_physics_event_id(&self, event_name : &str) -> Option<usize> {
+        self.events_.get_id(&("physics-".to_string() + event_name))
+    }
+    pub fn get_physics_events(&self) -> Vec<usize> {
+        let mut output : Vec<usize> = vec![];
+
+        for event in self.events_.ids() {
+            if self.events_[&event].get_type() == EventEnum::Physics{
+                output.push(event);
+            }
+        }
+        output
+    }
+    
+    pub fn get_animation_event_id(&self, event_name : &str) -> Option<usize> {
+        self.events_.get_id(&("animation-".to_string() + event_name))
+    }
+    pub fn get_animation_events(&self) -> Vec<usize> {
+        let mut output : Vec<usize> = vec![];
+
+        for event in self.events_.ids() {
+            if self.events_[&event].get_type() == EventEnum::Animation{
+                output.push(event);
+            }
+        }
+        output
+    }
     
     pub fn get_internal_event_id(&self, event_name : &str) -> Option<usize> {
         self.events_.get_id(&("internal-".to_string() + event_name))
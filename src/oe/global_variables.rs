@@ -5,6 +5,9 @@ use std::thread;
 use std::sync::RwLock;
 
 use super::types::global_scenegraph::GlobalScenegraph;
+use super::carbon::filewatcher::FileWatcher;
+use super::carbon::vfs::Vfs;
+use super::asset_manager::AssetManager;
 
 //use no_deadlocks::Mutex;
 use super::base_traits::*;
@@ -42,10 +45,13 @@ thread_local!{
 pub static OE_EVENT_HANDLER_ : LazyLock<UltimateWrapper<EventHandler>> = LazyLock::new(||{new_ultimate_wrapper(None)});
 //pub static OE_TASK_MANAGER_ : LazyLock<UltimateWrapper<TaskManager>> = LazyLock::new(||{new_ultimate_wrapper(None)});
 pub static OE_TASK_MANAGERS_ : LazyLock<TaskManagerList<TaskManager>> = LazyLock::new(||{new_task_manager_list()});
+pub static OE_VFS_ : LazyLock<RwLock<Vfs>> = LazyLock::new(||{Default::default()});
+pub static OE_FILE_WATCHER_ : LazyLock<Mutex<FileWatcher>> = LazyLock::new(||{Default::default()});
+pub static OE_ASSET_MANAGER_ : LazyLock<Mutex<AssetManager>> = LazyLock::new(||{Default::default()});
 pub static OE_UNSYNC_THREADS_ : LazyLock<Arc<Mutex<Vec<(std::thread::JoinHandle<()>, bool)>>>> = LazyLock::new(||{Default::default()});
 
 pub static OE_RENDERER_   : TraitWrapper<dyn RendererBaseTrait> = Mutex::new(None);
-//pub static OE_PHYSICS_    : TraitWrapper<dyn OE_PhysicsBaseTrait> = Mutex::new(None);
+pub static OE_PHYSICS_    : TraitWrapper<dyn PhysicsBaseTrait> = Mutex::new(None);
 //pub static OE_NETWORKING_ : TraitWrapper<dyn OE_NetworkingBaseTrait> = Mutex::new(None);
 
 pub static OE_DONE_ : AtomicBool = AtomicBool::new(false);  
@@ -59,6 +65,6 @@ pub static OE_RENDERER_UPDATE_INFO_ : LazyLock<Mutex<Option<RendererUpdateInfo>>
 //pub static OE_RENDERER_UPDATE_INFO_: Mutex<Option<oe_renderer_update_info_t>> = Mutex::new(None);
 
 //pub static OE_NETWORKING_INIT_INFO_ : Mutex<Option<oe_networking_init_info_t>> = Mutex::new(None);
-//pub static OE_PHYSICS_INIT_INFO_ : Mutex<Option<oe_physics_init_info_t>> = Mutex::new(None);
+pub static OE_PHYSICS_INIT_INFO_ : LazyLock<Mutex<Option<PhysicsInitInfo>>> = LazyLock::new(||{Mutex::new(None)});
      
-//pub static OE_PHYSICS_UPDATE_INFO_ : Mutex<Option<oe_physics_update_info_t>> = Mutex::new(None);
\ No newline at end of file
+pub static OE_PHYSICS_UPDATE_INFO_ : LazyLock<Mutex<Option<PhysicsUpdateInfo>>> = LazyLock::new(||{Mutex::new(None)});
\ No newline at end of file
@@ -0,0 +1,4 @@
+pub mod rigidbody;
+pub mod softbody;
+pub mod collision;
+pub mod physics_world;
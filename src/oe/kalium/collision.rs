@@ -0,0 +1,485 @@
+use nalgebra_glm as glm;
+
+use super::rigidbody::Collider;
+
+const GJK_MAX_ITERATIONS : usize = 64;
+const EPA_MAX_ITERATIONS : usize = 64;
+const EPA_TOLERANCE : f64 = 1e-6;
+
+// AXIS ALIGNED BOUNDING BOX
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aabb{
+    pub min : glm::DVec3,
+    pub max : glm::DVec3,
+}
+
+impl Aabb{
+    pub fn overlaps(&self, other : &Aabb) -> bool{
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+        self.min.y <= other.max.y && self.max.y >= other.min.y &&
+        self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+    pub fn from_points<'a>(points : impl Iterator<Item=&'a glm::DVec3>) -> Aabb{
+        let mut output = Aabb{min : glm::DVec3::repeat(f64::MAX), max : glm::DVec3::repeat(f64::MIN)};
+        for point in points{
+            output.min = glm::min2(&output.min, point);
+            output.max = glm::max2(&output.max, point);
+        }
+        output
+    }
+}
+
+// CONTACT
+#[derive(Clone, Copy, Debug)]
+pub struct Contact{
+    // points from the first shape towards the second one
+    pub normal : glm::DVec3,
+    pub depth : f64,
+    pub point : glm::DVec3,
+}
+
+// CONVEX SHAPE IN WORLD SPACE
+// triangle meshes are decomposed into single triangles before narrowphase
+#[derive(Clone, Debug)]
+pub enum ConvexKind<'a>{
+    Sphere(f64),
+    Box(glm::DVec3),
+    Points(&'a [glm::DVec3]),
+    Triangle([glm::DVec3; 3]),
+}
+
+#[derive(Clone, Debug)]
+pub struct ConvexShape<'a>{
+    pub kind : ConvexKind<'a>,
+    pub pos : glm::DVec3,
+    pub rot : glm::DQuat,
+}
+
+impl<'a> ConvexShape<'a>{
+    pub fn new(kind : ConvexKind<'a>, pos : glm::DVec3, rot : glm::DQuat) -> ConvexShape<'a>{
+        ConvexShape{kind, pos, rot}
+    }
+
+    pub fn support(&self, dir : &glm::DVec3) -> glm::DVec3{
+        let local_dir = glm::quat_rotate_vec3(&glm::quat_conjugate(&self.rot), dir);
+        let local_point = match &self.kind{
+            ConvexKind::Sphere(radius) => {
+                let length = glm::length(&local_dir);
+                if length < 1e-12 {glm::DVec3::zeros()} else {local_dir * (*radius / length)}
+            }
+            ConvexKind::Box(half_extents) => {
+                glm::DVec3::new(half_extents.x.copysign(local_dir.x), half_extents.y.copysign(local_dir.y), half_extents.z.copysign(local_dir.z))
+            }
+            ConvexKind::Points(points) => furthest_point(points.iter(), &local_dir),
+            ConvexKind::Triangle(points) => furthest_point(points.iter(), &local_dir),
+        };
+        glm::quat_rotate_vec3(&self.rot, &local_point) + self.pos
+    }
+
+    pub fn aabb(&self) -> Aabb{
+        let mut output : Aabb = Default::default();
+        for axis in 0..3{
+            let mut dir = glm::DVec3::zeros();
+            dir[axis] = 1.0;
+            output.max[axis] = self.support(&dir)[axis];
+            output.min[axis] = self.support(&-dir)[axis];
+        }
+        output
+    }
+}
+
+fn furthest_point<'a>(points : impl Iterator<Item=&'a glm::DVec3>, dir : &glm::DVec3) -> glm::DVec3{
+    let mut best = glm::DVec3::zeros();
+    let mut best_dot = f64::MIN;
+    for point in points{
+        let value = glm::dot(point, dir);
+        if value > best_dot{
+            best_dot = value;
+            best = *point;
+        }
+    }
+    best
+}
+
+// collider converted to local space convex data, scale already applied
+#[derive(Clone, Debug)]
+pub enum ScaledCollider{
+    Sphere(f64),
+    Box(glm::DVec3),
+    ConvexHull(Vec<glm::DVec3>),
+    TriangleMesh(Vec<glm::DVec3>, Vec<[u32; 3]>),
+}
+
+impl ScaledCollider{
+    pub fn new(collider : &Collider, sca : &[f64; 3]) -> ScaledCollider{
+        let scale = glm::DVec3::new(sca[0], sca[1], sca[2]);
+        match collider{
+            Collider::Sphere{radius} => ScaledCollider::Sphere(radius * scale.abs().max()),
+            Collider::Box{half_extents} => ScaledCollider::Box(glm::DVec3::new(half_extents[0], half_extents[1], half_extents[2]).component_mul(&scale.abs())),
+            Collider::ConvexHull{points} => ScaledCollider::ConvexHull(points.iter().map(|p| glm::DVec3::new(p[0], p[1], p[2]).component_mul(&scale)).collect()),
+            Collider::TriangleMesh{positions, indices} => ScaledCollider::TriangleMesh(positions.iter().map(|p| glm::DVec3::new(p[0], p[1], p[2]).component_mul(&scale)).collect(), indices.clone()),
+        }
+    }
+
+    pub fn as_convex(&self, pos : glm::DVec3, rot : glm::DQuat) -> Option<ConvexShape<'_>>{
+        match self{
+            ScaledCollider::Sphere(radius) => Some(ConvexShape::new(ConvexKind::Sphere(*radius), pos, rot)),
+            ScaledCollider::Box(half_extents) => Some(ConvexShape::new(ConvexKind::Box(*half_extents), pos, rot)),
+            ScaledCollider::ConvexHull(points) => Some(ConvexShape::new(ConvexKind::Points(points), pos, rot)),
+            ScaledCollider::TriangleMesh(_, _) => None,
+        }
+    }
+
+    pub fn aabb(&self, pos : glm::DVec3, rot : glm::DQuat) -> Aabb{
+        match self.as_convex(pos, rot){
+            Some(shape) => shape.aabb(),
+            None => match self{
+                ScaledCollider::TriangleMesh(positions, _) => {
+                    let transformed : Vec<glm::DVec3> = positions.iter().map(|p| glm::quat_rotate_vec3(&rot, p) + pos).collect();
+                    Aabb::from_points(transformed.iter())
+                }
+                _ => Default::default()
+            }
+        }
+    }
+}
+
+// MINKOWSKI DIFFERENCE VERTEX
+#[derive(Clone, Copy, Debug)]
+struct SupportPoint{
+    v : glm::DVec3,
+    a : glm::DVec3,
+}
+
+fn minkowski_support(a : &ConvexShape, b : &ConvexShape, dir : &glm::DVec3) -> SupportPoint{
+    let point_a = a.support(dir);
+    let point_b = b.support(&-dir);
+    SupportPoint{v : point_a - point_b, a : point_a}
+}
+
+fn same_direction(a : &glm::DVec3, b : &glm::DVec3) -> bool{
+    glm::dot(a, b) > 0.0
+}
+
+// GJK
+fn gjk(a : &ConvexShape, b : &ConvexShape) -> Option<Vec<SupportPoint>>{
+    let mut dir = b.pos - a.pos;
+    if glm::length2(&dir) < 1e-12 {
+        dir = glm::DVec3::x();
+    }
+    let mut simplex = vec![minkowski_support(a, b, &dir)];
+    dir = -simplex[0].v;
+
+    for _ in 0..GJK_MAX_ITERATIONS{
+        if glm::length2(&dir) < 1e-20 {
+            // origin lies on the simplex, fill it up to a tetrahedron
+            return complete_simplex(a, b, simplex);
+        }
+        let new_point = minkowski_support(a, b, &dir);
+        if glm::dot(&new_point.v, &dir) < 0.0 {
+            return None;
+        }
+        simplex.push(new_point);
+        if next_simplex(&mut simplex, &mut dir){
+            return Some(simplex);
+        }
+    }
+    None
+}
+
+fn complete_simplex(a : &ConvexShape, b : &ConvexShape, mut simplex : Vec<SupportPoint>) -> Option<Vec<SupportPoint>>{
+    let axes = [glm::DVec3::x(), glm::DVec3::y(), glm::DVec3::z(), -glm::DVec3::x(), -glm::DVec3::y(), -glm::DVec3::z()];
+    for axis in axes.iter(){
+        if simplex.len() == 4 {break;}
+        let candidate = minkowski_support(a, b, axis);
+        if simplex.iter().all(|p| glm::distance2(&p.v, &candidate.v) > 1e-12){
+            simplex.push(candidate);
+        }
+    }
+    if simplex.len() == 4 {Some(simplex)} else {None}
+}
+
+fn next_simplex(simplex : &mut Vec<SupportPoint>, dir : &mut glm::DVec3) -> bool{
+    match simplex.len(){
+        2 => {line_case(simplex, dir); false}
+        3 => {triangle_case(simplex, dir); false}
+        4 => tetrahedron_case(simplex, dir),
+        _ => false
+    }
+}
+
+// the newest point is always the last one
+fn line_case(simplex : &mut Vec<SupportPoint>, dir : &mut glm::DVec3){
+    let a = simplex[1];
+    let b = simplex[0];
+    let ab = b.v - a.v;
+    let ao = -a.v;
+    if same_direction(&ab, &ao){
+        *dir = glm::cross(&glm::cross(&ab, &ao), &ab);
+    }
+    else {
+        *simplex = vec![a];
+        *dir = ao;
+    }
+}
+
+fn triangle_case(simplex : &mut Vec<SupportPoint>, dir : &mut glm::DVec3){
+    let a = simplex[2];
+    let b = simplex[1];
+    let c = simplex[0];
+    let ab = b.v - a.v;
+    let ac = c.v - a.v;
+    let ao = -a.v;
+    let abc = glm::cross(&ab, &ac);
+
+    if same_direction(&glm::cross(&abc, &ac), &ao){
+        if same_direction(&ac, &ao){
+            *simplex = vec![c, a];
+            *dir = glm::cross(&glm::cross(&ac, &ao), &ac);
+        }
+        else {
+            *simplex = vec![b, a];
+            line_case(simplex, dir);
+        }
+    }
+    else if same_direction(&glm::cross(&ab, &abc), &ao){
+        *simplex = vec![b, a];
+        line_case(simplex, dir);
+    }
+    else if same_direction(&abc, &ao){
+        *dir = abc;
+    }
+    else {
+        *simplex = vec![b, c, a];
+        *dir = -abc;
+    }
+}
+
+fn tetrahedron_case(simplex : &mut Vec<SupportPoint>, dir : &mut glm::DVec3) -> bool{
+    let a = simplex[3];
+    let b = simplex[2];
+    let c = simplex[1];
+    let d = simplex[0];
+    let ab = b.v - a.v;
+    let ac = c.v - a.v;
+    let ad = d.v - a.v;
+    let ao = -a.v;
+
+    let abc = glm::cross(&ab, &ac);
+    let acd = glm::cross(&ac, &ad);
+    let adb = glm::cross(&ad, &ab);
+
+    if same_direction(&abc, &ao){
+        *simplex = vec![c, b, a];
+        triangle_case(simplex, dir);
+        return false;
+    }
+    if same_direction(&acd, &ao){
+        *simplex = vec![d, c, a];
+        triangle_case(simplex, dir);
+        return false;
+    }
+    if same_direction(&adb, &ao){
+        *simplex = vec![b, d, a];
+        triangle_case(simplex, dir);
+        return false;
+    }
+    true
+}
+
+// EPA
+fn face_normal(points : &[SupportPoint], face : &[usize; 3]) -> Option<(glm::DVec3, f64)>{
+    let a = points[face[0]].v;
+    let normal = glm::cross(&(points[face[1]].v - a), &(points[face[2]].v - a));
+    let length = glm::length(&normal);
+    if length < 1e-12 {
+        return None;
+    }
+    let mut normal = normal / length;
+    let mut distance = glm::dot(&normal, &a);
+    if distance < 0.0 {
+        normal = -normal;
+        distance = -distance;
+    }
+    Some((normal, distance))
+}
+
+// index, normal and distance of the polytope face closest to the origin
+fn closest_face(points : &[SupportPoint], faces : &[[usize; 3]]) -> Option<(usize, glm::DVec3, f64)>{
+    let mut output : Option<(usize, glm::DVec3, f64)> = None;
+    for (id, face) in faces.iter().enumerate(){
+        if let Some((normal, distance)) = face_normal(points, face){
+            if output.is_none_or(|x| distance < x.2){
+                output = Some((id, normal, distance));
+            }
+        }
+    }
+    output
+}
+
+fn epa(a : &ConvexShape, b : &ConvexShape, simplex : Vec<SupportPoint>) -> Option<Contact>{
+    let mut points = simplex;
+    let mut faces : Vec<[usize; 3]> = vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+
+    for _ in 0..EPA_MAX_ITERATIONS{
+        let (_, best_normal, best_distance) = closest_face(&points, &faces)?;
+
+        let new_point = minkowski_support(a, b, &best_normal);
+        if glm::dot(&new_point.v, &best_normal) - best_distance < EPA_TOLERANCE{
+            break;
+        }
+
+        // remove all faces visible from the new point and stitch the horizon
+        let mut horizon : Vec<(usize, usize)> = vec![];
+        let mut kept_faces = Vec::with_capacity(faces.len());
+        for face in faces.iter(){
+            let visible = match face_normal(&points, face){
+                Some((normal, _)) => glm::dot(&normal, &(new_point.v - points[face[0]].v)) > 0.0,
+                None => true
+            };
+            if visible{
+                for edge in [(face[0], face[1]), (face[1], face[2]), (face[2], face[0])]{
+                    if let Some(pos) = horizon.iter().position(|e| *e == (edge.1, edge.0)){
+                        horizon.remove(pos);
+                    }
+                    else {
+                        horizon.push(edge);
+                    }
+                }
+            }
+            else {
+                kept_faces.push(*face);
+            }
+        }
+        if horizon.is_empty(){
+            break;
+        }
+        let new_id = points.len();
+        points.push(new_point);
+        for (e0, e1) in horizon{
+            kept_faces.push([e0, e1, new_id]);
+        }
+        faces = kept_faces;
+    }
+
+    // the polytope may have grown after the last search when the iteration limit was hit
+    let (best_face, best_normal, best_distance) = closest_face(&points, &faces)?;
+
+    // contact point from barycentric coordinates of the origin projection
+    let face = faces[best_face];
+    let projected = best_normal * best_distance;
+    let (u, v, w) = barycentric(&projected, &points[face[0]].v, &points[face[1]].v, &points[face[2]].v);
+    let point = points[face[0]].a * u + points[face[1]].a * v + points[face[2]].a * w;
+
+    Some(Contact{normal : best_normal, depth : best_distance, point})
+}
+
+fn barycentric(p : &glm::DVec3, a : &glm::DVec3, b : &glm::DVec3, c : &glm::DVec3) -> (f64, f64, f64){
+    let v0 = b - a;
+    let v1 = c - a;
+    let v2 = p - a;
+    let d00 = glm::dot(&v0, &v0);
+    let d01 = glm::dot(&v0, &v1);
+    let d11 = glm::dot(&v1, &v1);
+    let d20 = glm::dot(&v2, &v0);
+    let d21 = glm::dot(&v2, &v1);
+    let denom = d00 * d11 - d01 * d01;
+    if denom.abs() < 1e-12 {
+        return (1.0, 0.0, 0.0);
+    }
+    let v = (d11 * d20 - d01 * d21) / denom;
+    let w = (d00 * d21 - d01 * d20) / denom;
+    (1.0 - v - w, v, w)
+}
+
+// NARROWPHASE
+pub fn collide_convex(a : &ConvexShape, b : &ConvexShape) -> Option<Contact>{
+    // analytic path for the most common case, EPA converges slowly on curved shapes
+    if let (ConvexKind::Sphere(ra), ConvexKind::Sphere(rb)) = (&a.kind, &b.kind){
+        let delta = b.pos - a.pos;
+        let distance = glm::length(&delta);
+        if distance >= ra + rb {
+            return None;
+        }
+        let normal = if distance > 1e-12 {delta / distance} else {glm::DVec3::z()};
+        return Some(Contact{normal, depth : ra + rb - distance, point : a.pos + normal * *ra});
+    }
+    let simplex = gjk(a, b)?;
+    epa(a, b, simplex)
+}
+
+pub fn collide(a : &ScaledCollider, pos_a : glm::DVec3, rot_a : glm::DQuat, b : &ScaledCollider, pos_b : glm::DVec3, rot_b : glm::DQuat) -> Vec<Contact>{
+    match (a.as_convex(pos_a, rot_a), b.as_convex(pos_b, rot_b)){
+        (Some(shape_a), Some(shape_b)) => collide_convex(&shape_a, &shape_b).into_iter().collect(),
+        (Some(shape_a), None) => collide_mesh(&shape_a, b, pos_b, rot_b).into_iter().map(|c| Contact{normal : -c.normal, ..c}).collect(),
+        (None, Some(shape_b)) => collide_mesh(&shape_b, a, pos_a, rot_a),
+        (None, None) => vec![]
+    }
+}
+
+// returns contacts pointing from the mesh towards the convex shape
+fn collide_mesh(convex : &ConvexShape, mesh : &ScaledCollider, pos : glm::DVec3, rot : glm::DQuat) -> Vec<Contact>{
+    let mut output = vec![];
+    let bounds = convex.aabb();
+    if let ScaledCollider::TriangleMesh(positions, indices) = mesh{
+        for tri in indices{
+            let points = [
+                glm::quat_rotate_vec3(&rot, &positions[tri[0] as usize]) + pos,
+                glm::quat_rotate_vec3(&rot, &positions[tri[1] as usize]) + pos,
+                glm::quat_rotate_vec3(&rot, &positions[tri[2] as usize]) + pos,
+            ];
+            if !Aabb::from_points(points.iter()).overlaps(&bounds){
+                continue;
+            }
+            let triangle = ConvexShape::new(ConvexKind::Triangle(points), glm::DVec3::zeros(), glm::DQuat::identity());
+            if let Some(contact) = collide_convex(&triangle, convex){
+                output.push(contact);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+pub mod collisiontest{
+    use super::*;
+
+    fn assert_contact(contact : Option<Contact>, depth : f64, normal : glm::DVec3, tolerance : f64){
+        let contact = contact.expect("shapes should overlap");
+        assert!((contact.depth - depth).abs() < tolerance, "depth {} instead of {}", contact.depth, depth);
+        assert!(glm::distance(&contact.normal, &normal) < tolerance, "normal {:?} instead of {:?}", contact.normal, normal);
+    }
+
+    #[test]
+    fn sphere_sphere(){
+        let a = ConvexShape::new(ConvexKind::Sphere(1.0), glm::DVec3::zeros(), glm::DQuat::identity());
+        let b = ConvexShape::new(ConvexKind::Sphere(0.5), glm::DVec3::new(0.0, 1.2, 0.0), glm::DQuat::identity());
+        assert_contact(collide_convex(&a, &b), 0.3, glm::DVec3::y(), 1e-9);
+        let far = ConvexShape::new(ConvexKind::Sphere(0.5), glm::DVec3::new(0.0, 1.6, 0.0), glm::DQuat::identity());
+        assert!(collide_convex(&a, &far).is_none());
+    }
+
+    #[test]
+    fn box_box(){
+        let a = ConvexShape::new(ConvexKind::Box(glm::DVec3::repeat(1.0)), glm::DVec3::zeros(), glm::DQuat::identity());
+        let b = ConvexShape::new(ConvexKind::Box(glm::DVec3::repeat(1.0)), glm::DVec3::new(1.5, 0.2, -0.1), glm::DQuat::identity());
+        assert_contact(collide_convex(&a, &b), 0.5, glm::DVec3::x(), 1e-6);
+        assert_contact(collide_convex(&b, &a), 0.5, -glm::DVec3::x(), 1e-6);
+
+        // a box rotated by 45 degrees around z resting with its edge in a flat box
+        let rot = glm::quat_angle_axis(std::f64::consts::FRAC_PI_4, &glm::DVec3::z());
+        let c = ConvexShape::new(ConvexKind::Box(glm::DVec3::repeat(0.5)), glm::DVec3::new(0.0, 0.6, 0.0), rot);
+        let ground = ConvexShape::new(ConvexKind::Box(glm::DVec3::new(5.0, 0.5, 5.0)), glm::DVec3::new(0.0, -0.5, 0.0), glm::DQuat::identity());
+        assert_contact(collide_convex(&ground, &c), 0.5f64.sqrt() - 0.6, glm::DVec3::y(), 1e-6);
+
+        let far = ConvexShape::new(ConvexKind::Box(glm::DVec3::repeat(1.0)), glm::DVec3::new(2.1, 0.0, 0.0), glm::DQuat::identity());
+        assert!(collide_convex(&a, &far).is_none());
+    }
+
+    #[test]
+    fn sphere_box(){
+        let a = ConvexShape::new(ConvexKind::Box(glm::DVec3::repeat(1.0)), glm::DVec3::zeros(), glm::DQuat::identity());
+        let b = ConvexShape::new(ConvexKind::Sphere(0.5), glm::DVec3::new(0.0, 0.0, 1.3), glm::DQuat::identity());
+        assert_contact(collide_convex(&a, &b), 0.2, glm::DVec3::z(), 1e-3);
+    }
+}
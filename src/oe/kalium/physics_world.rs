@@ -0,0 +1,402 @@
+use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::time;
+
+use compact_str::CompactString;
+use nalgebra_glm as glm;
+
+use super::super::base_traits::PhysicsBaseTrait;
+use super::super::dummy_structs::{PhysicsInitInfo, PhysicsUpdateInfo};
+use super::super::types::global_scenegraph::GlobalScenegraph;
+use super::super::types::elementcontainer::SingleElement;
+use super::super::types::object_trait::ObjectTrait;
+use super::super::types::polygonstoragetrait::PolygonStorageTrait;
+use super::rigidbody::*;
+use super::softbody::*;
+use super::collision::*;
+
+const SOLVER_ITERATIONS : usize = 4;
+const PENETRATION_SLOP : f64 = 0.005;
+const PENETRATION_CORRECTION : f64 = 0.4;
+// below this approach speed contacts do not bounce, avoids jitter on resting bodies
+const RESTING_SPEED : f64 = 0.5;
+
+type ContactPair = (CompactString, CompactString);
+
+#[derive(Debug)]
+struct BodyEntry{
+    body : RigidBody,
+    // (collision begin, collision end)
+    events : (usize, usize),
+    // kinematic bodies only, position written by the last step to detect moves by tasks
+    last_pos : Option<[f64; 3]>,
+}
+
+// per frame simulation state of a body whose object is currently loaded
+struct SimBody{
+    name : CompactString,
+    object : Arc<SingleElement<Box<dyn ObjectTrait>>>,
+    collider : ScaledCollider,
+    pos : glm::DVec3,
+    rot : glm::DQuat,
+    vel : glm::DVec3,
+    ang_vel : glm::DVec3,
+    inv_mass : f64,
+    inv_inertia : glm::DVec3,
+    restitution : f64,
+    friction : f64,
+    dynamic : bool,
+    kinematic : bool,
+}
+
+impl SimBody{
+    fn inv_inertia_world(&self) -> glm::DMat3{
+        let rot_mat = glm::quat_to_mat3(&self.rot);
+        rot_mat * glm::DMat3::from_diagonal(&self.inv_inertia) * rot_mat.transpose()
+    }
+}
+
+#[derive(Debug)]
+struct SoftBodyEntry{
+    body : SoftBody,
+    // created on the first step, when the polygon storage is available
+    state : Option<SoftBodyState>,
+    masses_changed : bool,
+}
+
+#[derive(Debug)]
+pub struct PhysicsWorld{
+    bodies : HashMap<CompactString, BodyEntry>,
+    soft_bodies : HashMap<CompactString, SoftBodyEntry>,
+    contacts : HashSet<ContactPair>,
+    init_info : PhysicsInitInfo,
+    accumulator : f64,
+    last_update : Option<time::Instant>,
+    // scaled time since the last step, kinematic bodies moved by tasks get velocity = distance / time
+    time_since_step : f64,
+}
+
+impl PhysicsWorld{
+    pub fn new(init_info : PhysicsInitInfo) -> PhysicsWorld{
+        PhysicsWorld{
+            bodies : Default::default(),
+            soft_bodies : Default::default(),
+            contacts : Default::default(),
+            init_info,
+            accumulator : 0.0,
+            last_update : None,
+            time_since_step : 0.0,
+        }
+    }
+
+    fn gather_bodies(&self, scenegraph : &GlobalScenegraph) -> Vec<SimBody>{
+        let mut output = Vec::with_capacity(self.bodies.len());
+        for (name, entry) in &self.bodies{
+            let Some(object) = scenegraph.get_object_by_name(name) else {continue;};
+            let object_unlocked = object.lock().unwrap();
+            let data = object_unlocked.0.get_data();
+            let body = &entry.body;
+            let dynamic = body.is_dynamic();
+            let kinematic = body.body_type == RigidBodyType::Kinematic;
+            let inv_mass = if dynamic {1.0/body.mass} else {0.0};
+            let unit_inertia = body.collider.unit_inertia();
+            let inv_inertia = glm::DVec3::new(
+                inverse_or_zero(unit_inertia[0]*body.mass),
+                inverse_or_zero(unit_inertia[1]*body.mass),
+                inverse_or_zero(unit_inertia[2]*body.mass)
+            ) * if dynamic {1.0} else {0.0};
+            let pos = glm::make_vec3(&data.pos);
+            let mut vel = glm::make_vec3(&body.linear_velocity);
+            if let Some(last_pos) = entry.last_pos.filter(|_| kinematic && self.time_since_step > 0.0){
+                vel += (pos - glm::make_vec3(&last_pos)) / self.time_since_step;
+            }
+
+            output.push(SimBody{
+                name : name.clone(),
+                object : Arc::clone(&object),
+                collider : ScaledCollider::new(&body.collider, &data.sca),
+                pos,
+                rot : glm::quat_normalize(&glm::make_quat(&data.rot)),
+                vel,
+                ang_vel : glm::make_vec3(&body.angular_velocity),
+                inv_mass,
+                inv_inertia,
+                restitution : body.restitution,
+                friction : body.friction,
+                dynamic,
+                kinematic,
+            });
+        }
+        output
+    }
+
+    fn step(&self, sim : &mut [SimBody], update_info : &PhysicsUpdateInfo, dt : f64, contacts_found : &mut HashSet<ContactPair>){
+        let gravity = glm::make_vec3(&update_info.gravity);
+
+        // integrate forces
+        for body in sim.iter_mut(){
+            if !body.dynamic {continue;}
+            let entry = &self.bodies[&body.name].body;
+            body.vel += gravity * entry.gravity_scale * dt;
+            body.vel /= 1.0 + dt*entry.linear_damping;
+            body.ang_vel /= 1.0 + dt*entry.angular_damping;
+        }
+
+        // broadphase: sweep and prune along the x axis
+        let aabbs : Vec<Aabb> = sim.iter().map(|b| b.collider.aabb(b.pos, b.rot)).collect();
+        let mut order : Vec<usize> = (0..sim.len()).collect();
+        order.sort_by(|a, b| aabbs[*a].min.x.total_cmp(&aabbs[*b].min.x));
+
+        let mut manifolds : Vec<(usize, usize, Contact)> = vec![];
+        for (pos_i, &i) in order.iter().enumerate(){
+            for &j in &order[pos_i+1..]{
+                if aabbs[j].min.x > aabbs[i].max.x {break;}
+                if !(sim[i].dynamic || sim[j].dynamic) || !aabbs[i].overlaps(&aabbs[j]) {continue;}
+
+                let contacts = collide(&sim[i].collider, sim[i].pos, sim[i].rot, &sim[j].collider, sim[j].pos, sim[j].rot);
+                if !contacts.is_empty(){
+                    contacts_found.insert(contact_pair(&sim[i].name, &sim[j].name));
+                }
+                for contact in contacts{
+                    manifolds.push((i, j, contact));
+                }
+            }
+        }
+
+        // collision response
+        for iteration in 0..SOLVER_ITERATIONS{
+            for (i, j, contact) in &manifolds{
+                resolve_contact(sim, *i, *j, contact, iteration == 0);
+            }
+        }
+
+        // integrate velocities, kinematic bodies only move by the velocity they were given
+        for body in sim.iter_mut(){
+            if body.kinematic {
+                let entry = &self.bodies[&body.name].body;
+                body.pos += glm::make_vec3(&entry.linear_velocity) * dt;
+                let ang_vel = glm::make_vec3(&entry.angular_velocity);
+                let spin = glm::DQuat::new(0.0, ang_vel.x, ang_vel.y, ang_vel.z);
+                body.rot = glm::quat_normalize(&(body.rot + spin * body.rot * (0.5*dt)));
+                continue;
+            }
+            if !body.dynamic {continue;}
+            body.pos += body.vel * dt;
+            let spin = glm::DQuat::new(0.0, body.ang_vel.x, body.ang_vel.y, body.ang_vel.z);
+            body.rot = glm::quat_normalize(&(body.rot + spin * body.rot * (0.5*dt)));
+        }
+
+        // positional correction
+        for (i, j, contact) in &manifolds{
+            let total_inv_mass = sim[*i].inv_mass + sim[*j].inv_mass;
+            if total_inv_mass <= 0.0 {continue;}
+            let correction = contact.normal * ((contact.depth - PENETRATION_SLOP).max(0.0) / total_inv_mass * PENETRATION_CORRECTION);
+            let inv_mass_i = sim[*i].inv_mass;
+            let inv_mass_j = sim[*j].inv_mass;
+            sim[*i].pos -= correction * inv_mass_i;
+            sim[*j].pos += correction * inv_mass_j;
+        }
+    }
+
+    fn write_back(&mut self, sim : &[SimBody]){
+        self.time_since_step = 0.0;
+        for body in sim{
+            if !body.dynamic && !body.kinematic {continue;}
+            let entry = self.bodies.get_mut(&body.name).unwrap();
+            if body.kinematic {
+                entry.last_pos = Some([body.pos.x, body.pos.y, body.pos.z]);
+            }
+            else {
+                entry.body.linear_velocity = [body.vel.x, body.vel.y, body.vel.z];
+                entry.body.angular_velocity = [body.ang_vel.x, body.ang_vel.y, body.ang_vel.z];
+            }
+
+            let mut object = body.object.lock().unwrap();
+            let data = object.0.get_data_mut();
+            data.pos = [body.pos.x, body.pos.y, body.pos.z];
+            data.rot = [body.rot.coords.x, body.rot.coords.y, body.rot.coords.z, body.rot.coords.w];
+            object.1 = true;
+        }
+    }
+
+    fn step_soft_bodies(&mut self, scenegraph : &GlobalScenegraph, update_info : &PhysicsUpdateInfo, dt : f64, substeps : usize){
+        for (name, entry) in self.soft_bodies.iter_mut(){
+            let Some(object) = scenegraph.get_object_by_name(name) else {continue;};
+            let mut object_unlocked = object.lock().unwrap();
+            let data = object_unlocked.0.get_data();
+
+            // gravity in object space
+            let rot = glm::quat_normalize(&glm::make_quat(&data.rot));
+            let sca = glm::make_vec3(&data.sca).map(|x| if x.abs() > 1e-12 {x} else {1.0});
+            let gravity = glm::quat_rotate_vec3(&glm::quat_inverse(&rot), &glm::make_vec3(&update_info.gravity));
+            let gravity = gravity.component_div(&sca);
+
+            let Some(mesh) = object_unlocked.0.get_mesh_mut() else {continue;};
+            let mut polygons = mesh.get_polygonstorage_unlocked();
+            let Some(storage) = polygons.0.get_softbody_mut() else {continue;};
+
+            if entry.state.as_ref().map(|x| x.num_of_vertices()*3 != storage.positions.len()).unwrap_or(true){
+                entry.state = Some(SoftBodyState::new(&entry.body, storage));
+                entry.masses_changed = false;
+            }
+            let state = entry.state.as_mut().unwrap();
+            if entry.masses_changed {
+                state.update_masses(&entry.body, storage);
+                entry.masses_changed = false;
+            }
+
+            for _ in 0..substeps{
+                state.step(&entry.body, &gravity, dt);
+            }
+            state.write_to_storage(storage);
+            storage.recompute_normals();
+            storage.regenerate_data();
+            polygons.1 = true;
+            drop(polygons);
+            object_unlocked.1 = true;
+        }
+    }
+}
+
+fn inverse_or_zero(value : f64) -> f64{
+    if value > 0.0 {1.0/value} else {0.0}
+}
+
+fn contact_pair(a : &CompactString, b : &CompactString) -> ContactPair{
+    if a < b {(a.clone(), b.clone())} else {(b.clone(), a.clone())}
+}
+
+fn resolve_contact(sim : &mut [SimBody], i : usize, j : usize, contact : &Contact, apply_restitution : bool){
+    let normal = contact.normal;
+    let ra = contact.point - sim[i].pos;
+    let rb = contact.point - sim[j].pos;
+    let inv_inertia_a = sim[i].inv_inertia_world();
+    let inv_inertia_b = sim[j].inv_inertia_world();
+
+    let relative_vel = (sim[j].vel + glm::cross(&sim[j].ang_vel, &rb)) - (sim[i].vel + glm::cross(&sim[i].ang_vel, &ra));
+    let normal_vel = glm::dot(&relative_vel, &normal);
+    if normal_vel > 0.0 {
+        return;
+    }
+
+    let effective_mass = |dir : &glm::DVec3| -> f64{
+        sim[i].inv_mass + sim[j].inv_mass
+        + glm::dot(dir, &glm::cross(&(inv_inertia_a * glm::cross(&ra, dir)), &ra))
+        + glm::dot(dir, &glm::cross(&(inv_inertia_b * glm::cross(&rb, dir)), &rb))
+    };
+    let normal_mass = effective_mass(&normal);
+    if normal_mass <= 0.0 {
+        return;
+    }
+
+    let restitution = if apply_restitution && -normal_vel > RESTING_SPEED {sim[i].restitution.max(sim[j].restitution)} else {0.0};
+    let normal_impulse = -(1.0 + restitution) * normal_vel / normal_mass;
+    let mut impulse = normal * normal_impulse;
+
+    // coulomb friction
+    let tangent_vel = relative_vel - normal * normal_vel;
+    let tangent_length = glm::length(&tangent_vel);
+    if tangent_length > 1e-9 {
+        let tangent = tangent_vel / tangent_length;
+        let tangent_mass = effective_mass(&tangent);
+        if tangent_mass > 0.0 {
+            let friction = (sim[i].friction * sim[j].friction).sqrt();
+            let tangent_impulse = (-tangent_length / tangent_mass).clamp(-friction*normal_impulse, friction*normal_impulse);
+            impulse += tangent * tangent_impulse;
+        }
+    }
+
+    let inv_mass_a = sim[i].inv_mass;
+    let inv_mass_b = sim[j].inv_mass;
+    sim[i].vel -= impulse * inv_mass_a;
+    sim[i].ang_vel -= inv_inertia_a * glm::cross(&ra, &impulse);
+    sim[j].vel += impulse * inv_mass_b;
+    sim[j].ang_vel += inv_inertia_b * glm::cross(&rb, &impulse);
+}
+
+impl PhysicsBaseTrait for PhysicsWorld{
+    fn update_data(&mut self, scenegraph : &GlobalScenegraph, update_info : PhysicsUpdateInfo) -> Vec<usize>{
+        let now = time::Instant::now();
+        let elapsed = match self.last_update.replace(now){
+            Some(before) => (now - before).as_secs_f64(),
+            None => 0.0
+        };
+        if update_info.paused || (self.bodies.is_empty() && self.soft_bodies.is_empty()){
+            self.accumulator = 0.0;
+            // moves during a pause are teleports
+            self.bodies.values_mut().for_each(|x| x.last_pos = None);
+            return vec![];
+        }
+
+        let dt = self.init_info.fixed_timestep;
+        self.time_since_step += elapsed*update_info.time_scale;
+        self.accumulator = (self.accumulator + elapsed*update_info.time_scale).min(dt*self.init_info.max_substeps as f64);
+        if self.accumulator < dt {
+            return vec![];
+        }
+
+        let mut sim = self.gather_bodies(scenegraph);
+        let mut contacts_found = HashSet::new();
+        let mut substeps = 0;
+        while self.accumulator >= dt{
+            self.step(&mut sim, &update_info, dt, &mut contacts_found);
+            self.accumulator -= dt;
+            substeps += 1;
+        }
+        self.write_back(&sim);
+        self.step_soft_bodies(scenegraph, &update_info, dt, substeps);
+
+        // collision events
+        let mut events = vec![];
+        for (a, b) in contacts_found.difference(&self.contacts){
+            events.extend([a, b].iter().filter_map(|name| Some(self.bodies.get(*name)?.events.0)));
+        }
+        for (a, b) in self.contacts.difference(&contacts_found){
+            events.extend([a, b].iter().filter_map(|name| Some(self.bodies.get(*name)?.events.1)));
+        }
+        self.contacts = contacts_found;
+        events
+    }
+
+    fn add_rigid_body(&mut self, object_name : &str, body : RigidBody, events : (usize, usize)) -> bool{
+        self.bodies.insert(object_name.into(), BodyEntry{body, events, last_pos : None}).is_none()
+    }
+    fn remove_rigid_body(&mut self, object_name : &str) -> bool{
+        self.contacts.retain(|(a, b)| a != object_name && b != object_name);
+        self.bodies.remove(object_name).is_some()
+    }
+    fn get_rigid_body(&self, object_name : &str) -> Option<RigidBody>{
+        Some(self.bodies.get(object_name)?.body.clone())
+    }
+    fn set_rigid_body(&mut self, object_name : &str, body : RigidBody) -> bool{
+        match self.bodies.get_mut(object_name){
+            Some(entry) => {entry.body = body; true}
+            None => false
+        }
+    }
+    fn get_contacts(&self, object_name : &str) -> Vec<CompactString>{
+        self.contacts.iter().filter_map(|(a, b)| {
+            if a == object_name {Some(b.clone())}
+            else if b == object_name {Some(a.clone())}
+            else {None}
+        }).collect()
+    }
+    fn add_soft_body(&mut self, object_name : &str, body : SoftBody) -> bool{
+        self.soft_bodies.insert(object_name.into(), SoftBodyEntry{body, state : None, masses_changed : false}).is_none()
+    }
+    fn remove_soft_body(&mut self, object_name : &str) -> bool{
+        self.soft_bodies.remove(object_name).is_some()
+    }
+    fn get_soft_body(&self, object_name : &str) -> Option<SoftBody>{
+        Some(self.soft_bodies.get(object_name)?.body.clone())
+    }
+    fn set_soft_body(&mut self, object_name : &str, body : SoftBody) -> bool{
+        match self.soft_bodies.get_mut(object_name){
+            Some(entry) => {entry.body = body; entry.masses_changed = true; true}
+            None => false
+        }
+    }
+    fn get_name(&self) -> CompactString{
+        CompactString::new("PhysicsWorld")
+    }
+}
@@ -0,0 +1,102 @@
+use super::super::types::mesh::Mesh;
+
+#[derive(Clone, Debug)]
+pub enum Collider{
+    Sphere{radius : f64},
+    Box{half_extents : [f64; 3]},
+    ConvexHull{points : Vec<[f64; 3]>},
+    // only usable by static bodies
+    TriangleMesh{positions : Vec<[f64; 3]>, indices : Vec<[u32; 3]>},
+}
+
+impl Collider{
+    pub fn convex_hull_from_mesh(mesh : &Mesh) -> Collider{
+        let polygons = mesh.get_polygonstorage_unlocked();
+        let points = polygons.0.get_positions().chunks(3).map(|x| [x[0] as f64, x[1] as f64, x[2] as f64]).collect();
+        Collider::ConvexHull{points}
+    }
+    pub fn triangle_mesh_from_mesh(mesh : &Mesh) -> Collider{
+        let polygons = mesh.get_polygonstorage_unlocked();
+        let positions = polygons.0.get_positions().chunks(3).map(|x| [x[0] as f64, x[1] as f64, x[2] as f64]).collect();
+        let mut indices = vec![];
+        for id in 0..polygons.0.get_vgroups().len(){
+            indices.extend(polygons.0.get_index_buffer(id).chunks(3).map(|x| [x[0], x[1], x[2]]));
+        }
+        Collider::TriangleMesh{positions, indices}
+    }
+
+    // diagonal of the inertia tensor in local space for a body of unit mass
+    pub fn unit_inertia(&self) -> [f64; 3]{
+        match self{
+            Collider::Sphere{radius} => [0.4*radius*radius; 3],
+            Collider::Box{half_extents} => box_inertia(half_extents),
+            Collider::ConvexHull{points} => {
+                let mut half_extents = [0.0f64; 3];
+                for point in points{
+                    for axis in 0..3{
+                        half_extents[axis] = half_extents[axis].max(point[axis].abs());
+                    }
+                }
+                box_inertia(&half_extents)
+            }
+            Collider::TriangleMesh{..} => [0.0; 3],
+        }
+    }
+}
+
+fn box_inertia(half_extents : &[f64; 3]) -> [f64; 3]{
+    let [x, y, z] = half_extents.map(|h| 4.0*h*h);
+    [(y + z)/12.0, (x + z)/12.0, (x + y)/12.0]
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RigidBodyType{
+    #[default]
+    Dynamic,
+    // moved only by tasks, pushes dynamic bodies but is never pushed back
+    Kinematic,
+    Static,
+}
+
+#[derive(Clone, Debug)]
+pub struct RigidBody{
+    pub body_type : RigidBodyType,
+    pub collider : Collider,
+    pub mass : f64,
+    pub restitution : f64,
+    pub friction : f64,
+    pub linear_damping : f64,
+    pub angular_damping : f64,
+    pub gravity_scale : f64,
+    pub linear_velocity : [f64; 3],
+    pub angular_velocity : [f64; 3],
+}
+
+impl RigidBody{
+    pub fn new(body_type : RigidBodyType, collider : Collider, mass : f64) -> RigidBody{
+        // triangle meshes have no volume, so they can never be simulated
+        let body_type = match collider{
+            Collider::TriangleMesh{..} => RigidBodyType::Static,
+            _ => body_type
+        };
+        RigidBody{
+            body_type,
+            collider,
+            mass,
+            restitution : 0.2,
+            friction : 0.5,
+            linear_damping : 0.05,
+            angular_damping : 0.05,
+            gravity_scale : 1.0,
+            linear_velocity : [0.0; 3],
+            angular_velocity : [0.0; 3],
+        }
+    }
+    pub fn new_static(collider : Collider) -> RigidBody{
+        RigidBody::new(RigidBodyType::Static, collider, 0.0)
+    }
+    pub fn is_dynamic(&self) -> bool{
+        self.body_type == RigidBodyType::Dynamic && self.mass > 0.0
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use compact_str::CompactString;
+use nalgebra_glm as glm;
+
+use super::super::types::polygonstorage::SoftbodyPolygonStorage;
+
+#[derive(Clone, Debug)]
+pub struct SoftBody{
+    // 0.0 - 1.0, independent of the number of iterations
+    pub stiffness : f64,
+    pub bending_stiffness : f64,
+    // fraction of the velocity lost per step
+    pub damping : f64,
+    // spread evenly over all vertices
+    pub mass : f64,
+    pub gravity_scale : f64,
+    pub iterations : u32,
+    // vertices of these vertex groups are kept at their rest position
+    pub pinned_vgroups : Vec<CompactString>,
+}
+
+impl SoftBody{
+    pub fn new(stiffness : f64, damping : f64) -> SoftBody{
+        SoftBody{
+            stiffness,
+            bending_stiffness : stiffness*0.1,
+            damping,
+            mass : 1.0,
+            gravity_scale : 1.0,
+            iterations : 8,
+            pinned_vgroups : vec![],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DistanceConstraint{
+    a : usize,
+    b : usize,
+    rest_length : f64,
+    bending : bool,
+}
+
+// simulation state, the simulation runs in object space so pinned vertices follow the object
+#[derive(Clone, Debug)]
+pub struct SoftBodyState{
+    positions : Vec<glm::DVec3>,
+    previous : Vec<glm::DVec3>,
+    rest_positions : Vec<glm::DVec3>,
+    inv_masses : Vec<f64>,
+    constraints : Vec<DistanceConstraint>,
+}
+
+impl SoftBodyState{
+    pub fn new(body : &SoftBody, storage : &SoftbodyPolygonStorage) -> SoftBodyState{
+        let positions : Vec<glm::DVec3> = storage.positions.chunks(3).map(|x| glm::DVec3::new(x[0] as f64, x[1] as f64, x[2] as f64)).collect();
+
+        // structural constraints along every edge, bending constraints across every pair of adjacent triangles
+        let mut edges : HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for tri in storage.get_position_triangles(){
+            let tri = tri.map(|x| x as usize);
+            for corner in 0..3{
+                let (a, b, opposite) = (tri[corner], tri[(corner+1)%3], tri[(corner+2)%3]);
+                if a == b {continue;}
+                edges.entry((a.min(b), a.max(b))).or_default().push(opposite);
+            }
+        }
+        let mut constraints = vec![];
+        for ((a, b), opposites) in edges{
+            constraints.push(DistanceConstraint{a, b, rest_length : glm::distance(&positions[a], &positions[b]), bending : false});
+            for (i, c) in opposites.iter().enumerate(){
+                for d in &opposites[i+1..]{
+                    if c == d {continue;}
+                    constraints.push(DistanceConstraint{a : *c, b : *d, rest_length : glm::distance(&positions[*c], &positions[*d]), bending : true});
+                }
+            }
+        }
+
+        let mut output = SoftBodyState{
+            previous : positions.clone(),
+            rest_positions : positions.clone(),
+            positions,
+            inv_masses : vec![],
+            constraints,
+        };
+        output.update_masses(body, storage);
+        output
+    }
+
+    pub fn update_masses(&mut self, body : &SoftBody, storage : &SoftbodyPolygonStorage){
+        let num_vertices = self.positions.len().max(1) as f64;
+        let inv_mass = if body.mass > 0.0 {num_vertices/body.mass} else {0.0};
+        self.inv_masses = vec![inv_mass; self.positions.len()];
+        for vgroup in &body.pinned_vgroups{
+            for id in storage.get_vgroup_positions(vgroup){
+                let id = id as usize;
+                self.inv_masses[id] = 0.0;
+                self.positions[id] = self.rest_positions[id];
+                self.previous[id] = self.rest_positions[id];
+            }
+        }
+    }
+
+    // gravity has to be given in object space
+    pub fn step(&mut self, body : &SoftBody, gravity : &glm::DVec3, dt : f64){
+        let damping = 1.0 - body.damping.clamp(0.0, 1.0);
+        let acceleration = gravity * body.gravity_scale * dt * dt;
+        for id in 0..self.positions.len(){
+            if self.inv_masses[id] == 0.0 {continue;}
+            let velocity = (self.positions[id] - self.previous[id]) * damping;
+            self.previous[id] = self.positions[id];
+            self.positions[id] += velocity + acceleration;
+        }
+
+        let iterations = body.iterations.max(1);
+        let stiffness = iteration_stiffness(body.stiffness, iterations);
+        let bending_stiffness = iteration_stiffness(body.bending_stiffness, iterations);
+        for _ in 0..iterations{
+            for constraint in &self.constraints{
+                let (w_a, w_b) = (self.inv_masses[constraint.a], self.inv_masses[constraint.b]);
+                if w_a + w_b == 0.0 {continue;}
+                let delta = self.positions[constraint.b] - self.positions[constraint.a];
+                let length = glm::length(&delta);
+                if length < 1e-12 {continue;}
+
+                let k = if constraint.bending {bending_stiffness} else {stiffness};
+                let correction = delta * ((length - constraint.rest_length) / (length * (w_a + w_b)) * k);
+                self.positions[constraint.a] += correction * w_a;
+                self.positions[constraint.b] -= correction * w_b;
+            }
+        }
+    }
+
+    pub fn write_to_storage(&self, storage : &mut SoftbodyPolygonStorage){
+        for (id, position) in self.positions.iter().enumerate(){
+            storage.positions[id*3] = position.x as f32;
+            storage.positions[id*3+1] = position.y as f32;
+            storage.positions[id*3+2] = position.z as f32;
+        }
+    }
+
+    pub fn num_of_vertices(&self) -> usize{
+        self.positions.len()
+    }
+}
+
+// makes the stiffness after all iterations match the requested one
+fn iteration_stiffness(stiffness : f64, iterations : u32) -> f64{
+    1.0 - (1.0 - stiffness.clamp(0.0, 1.0)).powf(1.0/iterations as f64)
+}
+
+#[cfg(test)]
+pub mod softbodytest{
+    use super::*;
+
+    // two vertices joined by one constraint of rest length 1
+    fn pair_state(positions : [glm::DVec3; 2], inv_masses : [f64; 2]) -> SoftBodyState{
+        SoftBodyState{
+            positions : positions.to_vec(),
+            previous : positions.to_vec(),
+            rest_positions : positions.to_vec(),
+            inv_masses : inv_masses.to_vec(),
+            constraints : vec![DistanceConstraint{a : 0, b : 1, rest_length : 1.0, bending : false}],
+        }
+    }
+
+    #[test]
+    fn iteration_stiffness_compounds(){
+        for iterations in [1, 4, 16]{
+            let k = iteration_stiffness(0.7, iterations);
+            assert!(((1.0 - k).powi(iterations as i32) - 0.3).abs() < 1e-12);
+        }
+        assert_eq!(iteration_stiffness(1.0, 8), 1.0);
+        assert_eq!(iteration_stiffness(0.0, 8), 0.0);
+    }
+
+    #[test]
+    fn distance_constraint_solve(){
+        let mut body = SoftBody::new(1.0, 0.0);
+        body.iterations = 1;
+        let zero = glm::DVec3::zeros();
+
+        // stretched to 3, equal masses move symmetrically back to the rest length
+        let mut state = pair_state([zero, glm::DVec3::new(3.0, 0.0, 0.0)], [1.0, 1.0]);
+        state.step(&body, &zero, 0.01);
+        assert!((glm::distance(&state.positions[0], &state.positions[1]) - 1.0).abs() < 1e-12);
+        assert!((state.positions[0].x - 1.0).abs() < 1e-12 && (state.positions[1].x - 2.0).abs() < 1e-12);
+
+        // a pinned vertex never moves, the free one is pulled up to it
+        let mut state = pair_state([zero, glm::DVec3::new(0.0, -1.0, 0.0)], [0.0, 1.0]);
+        for _ in 0..100{
+            state.step(&body, &glm::DVec3::new(0.0, -9.81, 0.0), 0.01);
+        }
+        assert_eq!(state.positions[0], zero);
+        assert!((glm::distance(&state.positions[0], &state.positions[1]) - 1.0).abs() < 1e-9);
+
+        // half the stiffness closes half the error in one iteration
+        body.stiffness = 0.5;
+        let mut state = pair_state([zero, glm::DVec3::new(3.0, 0.0, 0.0)], [1.0, 1.0]);
+        state.step(&body, &zero, 0.01);
+        assert!((glm::distance(&state.positions[0], &state.positions[1]) - 2.0).abs() < 1e-12);
+    }
+}
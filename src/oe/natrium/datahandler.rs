@@ -1,3 +1,5 @@
 pub mod datahandler;
 pub mod render_data;
 pub mod render_datacontainer;
+pub mod light_clusters;
+pub mod shadows;
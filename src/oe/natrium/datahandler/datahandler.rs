@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use crate::oe::math::Mat4x4;
 
 use super::super::super::types::globalscenegraphchanged::GlobalScenegraphChanged;
@@ -5,13 +7,17 @@ use super::super::super::types::globalscenegraphchanged::GlobalScenegraphChanged
 use super::super::super::types::viewport::ViewPort;
 use super::super::super::types::scene::*;
 use super::super::super::types::mesh::*;
+use super::super::super::types::armature::SkinningMode;
 use super::super::super::types::material::*;
+use super::super::super::types::texture::Texture;
 use super::super::super::types::camera::*;
 use super::super::super::types::light::*;
 use super::super::super::math;
 use super::super::super::types::object_trait::{ChangedObjectEnum, ObjectTrait};
-use super::super::super::types::polygonstoragetrait::VertexGroup;
+use super::super::super::types::polygonstoragetrait::PolygonStorageTrait;
 use super::render_datacontainer::RenderDataContainer;
+use super::light_clusters::*;
+use super::shadows::*;
 
 use super::render_data::*;
 
@@ -19,18 +25,37 @@ use super::render_data::*;
 pub struct DataHandler{
     pub cameras : RenderDataContainer<CameraRenderData>,
     pub materials : RenderDataContainer<MaterialRenderData>,
+    pub textures : RenderDataContainer<TextureRenderData>,
     pub meshes : RenderDataContainer<MeshRenderData>,
 
     pub dir_lights : RenderDataContainer<DirectionalLightRenderData>,
     pub pt_lights : RenderDataContainer<PointLightRenderData>,
+    pub spot_lights : RenderDataContainer<SpotLightRenderData>,
+    pub area_lights : RenderDataContainer<AreaLightRenderData>,
 
     pub scenes : RenderDataContainer<SceneRenderData>,
     pub viewports : RenderDataContainer<ViewportRenderData>,
+    // rebuilt every frame, gpu skinned meshes are never batched
+    pub instanced_batches : Vec<InstancedBatchRenderData>,
+    // one per camera of the loaded viewport, rebuilt every frame with light indexed rendering
+    pub light_clusters : Vec<LightClusterRenderData>,
+    // shadow maps of the sun and point lights seen by the cameras of the loaded viewport, rebuilt every frame
+    pub shadow_atlas : ShadowAtlasRenderData,
 
     pub has_dir_lights_changed : bool,
     pub has_pt_lights_changed : bool,
+    pub has_spot_lights_changed : bool,
+    pub has_area_lights_changed : bool,
     pub load_spheres_or_bboxes : bool,
     pub loaded_viewport : usize,
+    // larger values switch to simpler levels of detail earlier
+    pub lod_bias : f32,
+    // GLES2 class targets can not draw 32 bit indices
+    pub use_16bit_indices : bool,
+    pub use_light_indexed_rendering : bool,
+    pub cluster_settings : ClusterSettings,
+    // width and height of the shadow atlas in texels, rounded up to a power of two
+    pub shadow_atlas_size : u32,
 
     pub elements : GlobalScenegraphChanged,
 }
@@ -41,15 +66,28 @@ impl DataHandler{
         DataHandler { 
             cameras:RenderDataContainer::new(), 
             materials: RenderDataContainer::new(), 
+            textures: RenderDataContainer::new(),
             meshes: RenderDataContainer::new(), 
             dir_lights: RenderDataContainer::new(), 
             pt_lights: RenderDataContainer::new(), 
+            spot_lights: RenderDataContainer::new(), 
+            area_lights: RenderDataContainer::new(), 
             scenes: RenderDataContainer::new(), 
             viewports: RenderDataContainer::new(), 
+            instanced_batches: vec![],
+            light_clusters: vec![],
+            shadow_atlas: Default::default(),
             has_dir_lights_changed: false, 
             has_pt_lights_changed: false, 
+            has_spot_lights_changed: false, 
+            has_area_lights_changed: false, 
             load_spheres_or_bboxes: false, 
             loaded_viewport: 0,
+            lod_bias: 1.0,
+            use_16bit_indices: false,
+            use_light_indexed_rendering: false,
+            cluster_settings: Default::default(),
+            shadow_atlas_size: 4096,
             elements : Default::default() }
     }
 
@@ -63,7 +101,10 @@ impl DataHandler{
         self.cameras.update(restart_renderer);
         self.dir_lights.update(restart_renderer);
         self.materials.update(restart_renderer);
+        self.textures.update(restart_renderer);
         self.pt_lights.update(restart_renderer);
+        self.spot_lights.update(restart_renderer);
+        self.area_lights.update(restart_renderer);
         self.meshes.update(restart_renderer);
         self.viewports.update(restart_renderer);
 
@@ -75,12 +116,17 @@ impl DataHandler{
             println!("RUNS DATA HANDLER");
         }
 
-        // first handle materials
+        // first handle textures, then the materials using them
+        for (id, name, texture) in &self.elements.textures_.take_data(){
+            self.handle_texture_data(id, texture, name);
+        }
         for (id, name, material) in &self.elements.materials_.take_data(){
             self.handle_material_data(id, material, name);
         }
         self.has_dir_lights_changed = false;
         self.has_pt_lights_changed = false;
+        self.has_spot_lights_changed = false;
+        self.has_area_lights_changed = false;
 
         // then meshes and lights
         let new_objects = self.elements.objects_.take_data();
@@ -109,14 +155,15 @@ impl DataHandler{
         }
         // then viewport
         for (id, name, viewport) in &self.elements.viewports_.take_data(){
-            let loaded_viewport = &self.elements.world_.as_ref().unwrap().loaded_viewport;
-            self.loaded_viewport = self.viewports.get_id(loaded_viewport).unwrap();
             self.handle_viewport_data(id, viewport, name);
         }
 
         for name in &self.elements.materials_.take_deleted(){
             self.materials.remove_by_name(name);
         }
+        for name in &self.elements.textures_.take_deleted(){
+            self.textures.remove_by_name(name);
+        }
         for name in &self.elements.scenes_.take_deleted(){
             self.scenes.remove_by_name(name);
         }
@@ -127,13 +174,8 @@ impl DataHandler{
             else if self.cameras.contains_name(name){
                 self.cameras.remove_by_name(name);
             }
-            else if self.pt_lights.contains_name(name){
-                self.pt_lights.remove_by_name(name);
-                self.has_pt_lights_changed = true;
-            }
-            else if self.dir_lights.contains_name(name){
-                self.dir_lights.remove_by_name(name);
-                self.has_dir_lights_changed = true;
+            else {
+                self.remove_light(name);
             }
         }
 
@@ -146,9 +188,152 @@ impl DataHandler{
             }
             self.viewports.remove_by_name(name);
         }
+        // the world can switch to another viewport without any viewport changing
+        if let Some(world) = &self.elements.world_{
+            if let Some(id) = self.viewports.get_id(&world.loaded_viewport){
+                self.loaded_viewport = id;
+            }
+        }
         if is_elements_empty{
             println!("{:?}", self);
         }
+        self.update_lods();
+        self.update_instanced_batches();
+        self.update_light_clusters();
+        self.update_shadows();
+    }
+
+    // sun lights get cascades for every camera they are seen by, point lights one cube map
+    fn update_shadows(&mut self){
+        if !self.viewports.contains(&self.loaded_viewport) {
+            self.shadow_atlas = ShadowAtlasRenderData{size : self.shadow_atlas_size, ..Default::default()};
+            return;
+        }
+        let position = |model_mat : &math::DMat4x4| [model_mat[(0, 3)], model_mat[(1, 3)], model_mat[(2, 3)]];
+        let mut requests = vec![];
+        let mut point_ids = vec![];
+        for camera_id in self.viewports[self.loaded_viewport].cameras_.iter().filter(|id| self.cameras.contains(id)){
+            let mut sun_ids = vec![];
+            for (_, scene) in &self.scenes{
+                if scene.cameras.contains(camera_id) {
+                    sun_ids.extend(scene.dirlights.iter().filter(|id| self.dir_lights.contains(id)));
+                    point_ids.extend(scene.pt_lights.iter().filter(|id| self.pt_lights.contains(id)));
+                }
+            }
+            sun_ids.sort();
+            sun_ids.dedup();
+            for id in sun_ids{
+                let light = &self.dir_lights[id];
+                let Some(settings) = light.shadow else {continue;};
+                let direction = [light.direction[0] as f64, light.direction[1] as f64, light.direction[2] as f64];
+                let light = ShadowLight{id, position : position(&light.model_mat), direction, range : 0.0, priority : light.priority, settings};
+                requests.push(ShadowRequest{light, camera : Some((*camera_id, &self.cameras[*camera_id]))});
+            }
+        }
+        point_ids.sort();
+        point_ids.dedup();
+        for id in point_ids{
+            let light = &self.pt_lights[id];
+            let Some(settings) = light.shadow else {continue;};
+            let light = ShadowLight{id, position : position(&light.model_mat), direction : [0.0; 3], range : light.range, priority : light.priority, settings};
+            requests.push(ShadowRequest{light, camera : None});
+        }
+        self.shadow_atlas = build_shadow_atlas(self.shadow_atlas_size, &requests);
+    }
+
+    // point and spot lights of the scenes the camera is in
+    fn update_light_clusters(&mut self){
+        self.light_clusters.clear();
+        if !self.use_light_indexed_rendering || !self.viewports.contains(&self.loaded_viewport) {
+            return;
+        }
+        for camera_id in self.viewports[self.loaded_viewport].cameras_.iter().filter(|id| self.cameras.contains(id)){
+            let (mut point_ids, mut spot_ids) = (vec![], vec![]);
+            for (_, scene) in &self.scenes{
+                if scene.cameras.contains(camera_id) {
+                    point_ids.extend(scene.pt_lights.iter().filter(|id| self.pt_lights.contains(id)));
+                    spot_ids.extend(scene.spot_lights.iter().filter(|id| self.spot_lights.contains(id)));
+                }
+            }
+            for ids in [&mut point_ids, &mut spot_ids]{
+                ids.sort();
+                ids.dedup();
+            }
+            let position = |model_mat : &math::DMat4x4| [model_mat[(0, 3)], model_mat[(1, 3)], model_mat[(2, 3)]];
+            let point_lights : Vec<ClusterLight> = point_ids.into_iter().map(|id| {
+                let light = &self.pt_lights[id];
+                ClusterLight{id, position : position(&light.model_mat), range : light.range, priority : light.priority}
+            }).collect();
+            let spot_lights : Vec<ClusterLight> = spot_ids.into_iter().map(|id| {
+                let light = &self.spot_lights[id];
+                ClusterLight{id, position : position(&light.model_mat), range : light.range, priority : light.priority}
+            }).collect();
+            self.light_clusters.push(build_light_clusters(*camera_id, &self.cameras[*camera_id], &point_lights, &spot_lights, &self.cluster_settings));
+        }
+    }
+
+    // groups the visible meshes of the scenes seen by the loaded viewport by polygon storage and selected level of detail
+    // batches keep the order of the mesh ids
+    fn update_instanced_batches(&mut self){
+        self.instanced_batches.clear();
+        if !self.viewports.contains(&self.loaded_viewport) {
+            return;
+        }
+        let cameras = &self.viewports[self.loaded_viewport].cameras_;
+        let mut ids = vec![];
+        for (_, scene) in &self.scenes{
+            if cameras.iter().any(|x| scene.cameras.contains(x)) {
+                ids.extend(scene.meshes.iter().filter(|id| self.meshes.contains(id)));
+            }
+        }
+        ids.sort();
+        ids.dedup();
+        let mut batch_ids : HashMap<(usize, usize), usize> = HashMap::new();
+        for id in ids{
+            let mesh = &self.meshes[id];
+            if !mesh.visible || mesh.bones != 0 {
+                continue;
+            }
+            let batch_id = *batch_ids.entry((mesh.storage_id, mesh.lod)).or_insert_with(|| {
+                self.instanced_batches.push(InstancedBatchRenderData{storage_id : mesh.storage_id, lod : mesh.lod, ..Default::default()});
+                self.instanced_batches.len() - 1
+            });
+            let batch = &mut self.instanced_batches[batch_id];
+            batch.instances.push(id);
+            batch.model_mats.extend(mesh.model_mat.get_f32_vec());
+        }
+    }
+
+    // picks the level of detail of every mesh from its largest projected size in the cameras of the loaded viewport
+    fn update_lods(&mut self){
+        if !self.viewports.contains(&self.loaded_viewport) {
+            return;
+        }
+        let cameras : Vec<([f64; 3], f64)> = self.viewports[self.loaded_viewport].cameras_.iter().filter(|id| self.cameras.contains(id)).map(|id| {
+            let camera = &self.cameras[*id];
+            ([camera.model_mat[(0, 3)], camera.model_mat[(1, 3)], camera.model_mat[(2, 3)]], camera.perspective_mat[(1, 1)] as f64)
+        }).collect();
+        if cameras.is_empty() {
+            return;
+        }
+
+        for id in self.meshes.ids(){
+            let mesh = self.meshes.get_mut(&id).unwrap();
+            if mesh.lod_screen_sizes.is_empty() {
+                mesh.lod = 0;
+                continue;
+            }
+            let center : Vec<f64> = (0..3).map(|axis| (mesh.min_vec[axis] + mesh.max_vec[axis]) as f64/2.0).collect();
+            let world_center = mesh.model_mat.clone() * math::DVec4::new(&[center[0], center[1], center[2], 1.0]);
+            let scale = (0..3).map(|col| (0..3).map(|row| mesh.model_mat[(row, col)].powi(2)).sum::<f64>().sqrt()).fold(0.0, f64::max);
+            let radius = (0..3).map(|axis| ((mesh.max_vec[axis] - mesh.min_vec[axis]) as f64/2.0).powi(2)).sum::<f64>().sqrt()*scale;
+
+            let screen_size = cameras.iter().map(|(pos, focal)| {
+                let distance = (0..3).map(|axis| (world_center[axis] - pos[axis]).powi(2)).sum::<f64>().sqrt();
+                if distance <= radius {f64::MAX} else {radius*focal/distance}
+            }).fold(0.0, f64::max);
+            mesh.lod = mesh.lod_screen_sizes.iter().filter(|x| screen_size <= (**x*self.lod_bias) as f64).count();
+        }
     }
 
     ////////// HANDLE ELEMENTS ////////////////
@@ -159,33 +344,61 @@ impl DataHandler{
         if self.meshes.contains(id){
             mesh_render_data = self.meshes[*id].clone();
             mesh_render_data.model_mat = mesh.get_model_mat();
+            mesh_render_data.visible = mesh.get_visible();
             let polygons_unlocked = mesh.get_polygonstorage_unlocked();
             if polygons_unlocked.1{
-                mesh_render_data.vgroups = self.handle_vgroup_data(polygons_unlocked.0.get_vgroups());
+                (mesh_render_data.vgroups, mesh_render_data.copied_vertices) = self.handle_vgroup_data(polygons_unlocked.0.as_ref());
+                mesh_render_data.tangents = polygons_unlocked.0.has_tangents();
+                mesh_render_data.vertex_layout = polygons_unlocked.0.get_vertex_layout();
+                (mesh_render_data.min_vec, mesh_render_data.max_vec) = polygons_unlocked.0.get_bounds();
+                mesh_render_data.lod_screen_sizes = polygons_unlocked.0.get_lods().iter().map(|x| x.max_screen_size).collect();
                 mesh_render_data.mesh = mesh.polygon_storage_.1.clone();
+                mesh_render_data.storage_id = polygons_unlocked.0.id();
                 mesh_render_data.vao_initialized = false;
             }
         }
         else{
             let polygons_unlocked = mesh.get_polygonstorage_unlocked();
+            let (vgroups, copied_vertices) = self.handle_vgroup_data(polygons_unlocked.0.as_ref());
             mesh_render_data = MeshRenderData { 
                 common_data: CommonRenderData::new(*id), 
                 model_mat: mesh.get_model_mat(), 
+                visible: mesh.get_visible(),
                 uvmaps: polygons_unlocked.0.get_num_uvs(), 
+                tangents: polygons_unlocked.0.has_tangents(),
+                vertex_layout: polygons_unlocked.0.get_vertex_layout(),
                 bones: 0, 
+                bone_mats: Default::default(),
+                skin: None,
                 vbo: 0, 
                 vbo_size: 0, 
                 vao: 0, 
                 ubo: UniformBufferData::new(), 
                 vao_initialized: false, 
                 vao_input: Default::default(), 
-                max_vec: Default::default(), 
-                min_vec: Default::default(), 
+                max_vec: polygons_unlocked.0.get_bounds().1, 
+                min_vec: polygons_unlocked.0.get_bounds().0, 
+                lod: 0,
+                lod_screen_sizes: polygons_unlocked.0.get_lods().iter().map(|x| x.max_screen_size).collect(),
                 mesh: mesh.polygon_storage_.1.clone(), 
-                vgroups: self.handle_vgroup_data(polygons_unlocked.0.get_vgroups()),
+                storage_id: polygons_unlocked.0.id(),
+                vgroups,
+                copied_vertices,
             };
             drop(polygons_unlocked);
         }
+        match &mesh.armature_{
+            Some(armature) if armature.skinning_mode == SkinningMode::Gpu => {
+                mesh_render_data.bones = armature.num_of_bones().min(u8::MAX as usize) as u8;
+                mesh_render_data.bone_mats = armature.get_render_bone_mats();
+                mesh_render_data.skin = Some(armature.skin.clone());
+            }
+            _ => {
+                mesh_render_data.bones = 0;
+                mesh_render_data.bone_mats.clear();
+                mesh_render_data.skin = None;
+            }
+        }
         mesh_render_data.common_data.set_changed(true);
         self.meshes.insert(*id, mesh_render_data, name);
     }
@@ -199,14 +412,41 @@ impl DataHandler{
         else{
             material_render_data = MaterialRenderData { 
                 common_data: CommonRenderData::new(*id), 
+                textures: Default::default(),
+                pbr: false,
                 ubo: UniformBufferData::new()
             }
         }
         material_render_data.common_data.data = material.get_renderer_data();
+        material_render_data.pbr = material.is_pbr();
+        material_render_data.textures = material.textures.clone().map(|slot| {
+            let slot = slot?;
+            Some(MaterialTextureRenderData{texture : (self.textures.get_id(&slot.texture)?, slot.texture), uvmap : slot.uvmap})
+        });
         material_render_data.common_data.set_changed(true);
         self.materials.insert(*id, material_render_data, name);
     }
 
+    fn handle_texture_data(&mut self, id : &usize, texture : &Texture, name : &str){
+        let mut texture_render_data : TextureRenderData;
+        if self.textures.contains(id){
+            texture_render_data = self.textures[*id].clone();
+        }
+        else{
+            texture_render_data = TextureRenderData {
+                common_data: CommonRenderData::new(*id),
+                width: 0,
+                height: 0,
+                levels: Default::default(),
+                texture: 0,
+            }
+        }
+        (texture_render_data.width, texture_render_data.height) = texture.get_size();
+        texture_render_data.levels = texture.get_levels();
+        texture_render_data.common_data.set_changed(true);
+        self.textures.insert(*id, texture_render_data, name);
+    }
+
     fn handle_camera_data(&mut self, id : &usize, camera : &Camera, name : &str){
         
         let mut camera_render_data: CameraRenderData;
@@ -236,48 +476,205 @@ impl DataHandler{
     }
 
     fn handle_light_data(&mut self, id : &usize, light : &Light, name : &str){
+        // a light that changed its type moves to another container
+        let is_known = match light.get_type(){
+            LightType::Sun => self.dir_lights.contains(id),
+            LightType::Point => self.pt_lights.contains(id),
+            LightType::Spot | LightType::Lamp => self.spot_lights.contains(id),
+            LightType::Area => self.area_lights.contains(id),
+            _ => false,
+        };
+        if !is_known {
+            self.remove_light(name);
+        }
+        let model_mat = light.get_model_mat();
+        let mut data = model_mat.get_f32_vec();
+        data[15] = light.range;
+        data.extend_from_slice(&light.attenuation.get_renderer_data());
+
         match light.get_type(){
+            LightType::Sun => {
+                let direction = light.get_direction().map(|x| x as f32);
+                let color = light.color.map(|x| x as f64);
+                let mut light_render_data;
+                if self.dir_lights.contains(id) {
+                    light_render_data = self.dir_lights[*id].clone();
+                    light_render_data.model_mat = model_mat;
+                    light_render_data.direction = math::Vec3::new(&direction);
+                    light_render_data.color = math::DVec3::new(&color);
+                    light_render_data.intensity = light.intensity;
+                    light_render_data.priority = light.priority;
+                    light_render_data.shadow = light.shadow;
+                    light_render_data.common_data.set_changed(true);
+                }
+                else{
+                    light_render_data = DirectionalLightRenderData {
+                        common_data: CommonRenderData::new(*id),
+                        model_mat,
+                        direction: math::Vec3::new(&direction),
+                        color: math::DVec3::new(&color),
+                        intensity: light.intensity,
+                        priority: light.priority,
+                        shadow: light.shadow,
+                        ubo: UniformBufferData::new()
+                    }
+                }
+                self.has_dir_lights_changed = true;
+                // sun lights have no range or attenuation, the model matrix is followed by the direction
+                data.truncate(16);
+                data[15] = 1.0;
+                data.extend_from_slice(&direction);
+                data.push(0.0);
+                light_render_data.common_data.data = data;
+                self.dir_lights.insert(*id, light_render_data, name);
+            }
             LightType::Point => {
                 
                 let mut light_render_data;
                 if self.pt_lights.contains(id) {
                     light_render_data = self.pt_lights[*id].clone();
-                    light_render_data.model_mat = light.get_model_mat();
+                    light_render_data.model_mat = model_mat;
                     light_render_data.color = math::Vec3::new(&light.color);
                     light_render_data.intensity = light.intensity;
                     light_render_data.range = light.range;
+                    light_render_data.attenuation = light.attenuation;
+                    light_render_data.priority = light.priority;
+                    light_render_data.shadow = light.shadow;
                     light_render_data.common_data.set_changed(true);
                 }
                 else{
                     light_render_data = PointLightRenderData { 
                         common_data: CommonRenderData::new(*id), 
-                        model_mat: light.get_model_mat(), 
+                        model_mat, 
                         color: math::Vec3::new(&light.color), 
                         intensity: light.intensity, 
                         range: light.range, 
+                        attenuation: light.attenuation,
+                        priority: light.priority,
+                        shadow: light.shadow,
                         ubo: UniformBufferData::new()
                     }
                 }
                 self.has_pt_lights_changed = true;
-                light_render_data.common_data.data = light_render_data.model_mat.get_f32_vec();
-                light_render_data.common_data.data[15] = light.range; 
+                // model matrix with the range in its last element, then the attenuation
+                light_render_data.common_data.data = data;
                 self.pt_lights.insert(*id, light_render_data, name);
             }
+            LightType::Spot | LightType::Lamp => {
+                let direction = light.get_direction().map(|x| x as f32);
+                let (inner_cone, outer_cone) = light.get_cone_cosines();
+                let mut light_render_data;
+                if self.spot_lights.contains(id) {
+                    light_render_data = self.spot_lights[*id].clone();
+                    light_render_data.model_mat = model_mat;
+                    light_render_data.direction = math::Vec3::new(&direction);
+                    light_render_data.color = math::Vec3::new(&light.color);
+                    light_render_data.intensity = light.intensity;
+                    light_render_data.range = light.range;
+                    light_render_data.attenuation = light.attenuation;
+                    (light_render_data.inner_cone, light_render_data.outer_cone) = (inner_cone, outer_cone);
+                    light_render_data.priority = light.priority;
+                    light_render_data.common_data.set_changed(true);
+                }
+                else{
+                    light_render_data = SpotLightRenderData {
+                        common_data: CommonRenderData::new(*id),
+                        model_mat,
+                        direction: math::Vec3::new(&direction),
+                        color: math::Vec3::new(&light.color),
+                        intensity: light.intensity,
+                        range: light.range,
+                        attenuation: light.attenuation,
+                        inner_cone,
+                        outer_cone,
+                        priority: light.priority,
+                        ubo: UniformBufferData::new()
+                    }
+                }
+                self.has_spot_lights_changed = true;
+                // like point lights, followed by the direction, the inner and the outer cone
+                data.extend_from_slice(&direction);
+                data.extend_from_slice(&[inner_cone, outer_cone, 0.0, 0.0, 0.0]);
+                light_render_data.common_data.data = data;
+                self.spot_lights.insert(*id, light_render_data, name);
+            }
+            LightType::Area => {
+                let mut light_render_data;
+                if self.area_lights.contains(id) {
+                    light_render_data = self.area_lights[*id].clone();
+                    light_render_data.model_mat = model_mat;
+                    light_render_data.color = math::Vec3::new(&light.color);
+                    light_render_data.intensity = light.intensity;
+                    light_render_data.range = light.range;
+                    light_render_data.attenuation = light.attenuation;
+                    light_render_data.shape = light.area_shape;
+                    light_render_data.common_data.set_changed(true);
+                }
+                else{
+                    light_render_data = AreaLightRenderData {
+                        common_data: CommonRenderData::new(*id),
+                        model_mat,
+                        color: math::Vec3::new(&light.color),
+                        intensity: light.intensity,
+                        range: light.range,
+                        attenuation: light.attenuation,
+                        shape: light.area_shape,
+                        ubo: UniformBufferData::new()
+                    }
+                }
+                self.has_area_lights_changed = true;
+                // like point lights, followed by the shape and its size
+                data.extend_from_slice(&match light.area_shape{
+                    AreaShape::Rectangle{width, height} => [0.0, width, height, 0.0],
+                    AreaShape::Disc{radius} => [1.0, radius, radius, 0.0],
+                });
+                light_render_data.common_data.data = data;
+                self.area_lights.insert(*id, light_render_data, name);
+            }
             _ => {}
         }
     }
 
-    fn handle_vgroup_data(&mut self, vgroups : &Vec<VertexGroup>) -> Vec<VertexGroupRenderData>{
+    // returns false if there is no light of this name
+    fn remove_light(&mut self, name : &str) -> bool{
+        if self.pt_lights.contains_name(name){
+            self.pt_lights.remove_by_name(name);
+            self.has_pt_lights_changed = true;
+        }
+        else if self.spot_lights.contains_name(name){
+            self.spot_lights.remove_by_name(name);
+            self.has_spot_lights_changed = true;
+        }
+        else if self.area_lights.contains_name(name){
+            self.area_lights.remove_by_name(name);
+            self.has_area_lights_changed = true;
+        }
+        else if self.dir_lights.contains_name(name){
+            self.dir_lights.remove_by_name(name);
+            self.has_dir_lights_changed = true;
+        }
+        else {
+            return false;
+        }
+        true
+    }
+
+    // also returns the vertices the 16 bit draw ranges need after the vertex buffer
+    fn handle_vgroup_data(&mut self, polygons : &dyn PolygonStorageTrait) -> (Vec<VertexGroupRenderData>, Arc<[u32]>){
+        let vgroups = polygons.get_vgroups();
+        let index_data = if self.use_16bit_indices {polygons.get_16bit_index_data()} else {Default::default()};
         let mut output = Vec::with_capacity(vgroups.len());
-        for vgroup in vgroups{
+        for (id, vgroup) in vgroups.iter().enumerate(){
             output.push(VertexGroupRenderData { 
                 common_data: CommonRenderData::new(0), 
                 bone_mat: Mat4x4::new_identity(), ibo: 0, 
                 material: (self.materials.get_id(vgroup.material.as_ref().unwrap()).unwrap(), vgroup.material.clone().unwrap()), 
-                offset: 0, size: 0 
+                offset: 0, size: 0,
+                draw_ranges: index_data.draw_ranges.iter().map(|x| x.get(&id).cloned().unwrap_or_default()).collect(),
+                indices_16bit: index_data.index_buffers.iter().map(|x| x.get(&id).map(|x| x.as_slice().into()).unwrap_or_default()).collect(),
             })
         }
-        output
+        (output, index_data.copied_vertices.as_slice().into())
     }
 
     fn handle_scene_data(&mut self, id : &usize, scene : &Scene, name : &str){
@@ -296,6 +693,12 @@ impl DataHandler{
             else if self.pt_lights.contains_name(name){
                 scene_render_data.pt_lights.insert(self.pt_lights.get_id(name).unwrap());
             }
+            else if self.spot_lights.contains_name(name){
+                scene_render_data.spot_lights.insert(self.spot_lights.get_id(name).unwrap());
+            }
+            else if self.area_lights.contains_name(name){
+                scene_render_data.area_lights.insert(self.area_lights.get_id(name).unwrap());
+            }
             else if self.dir_lights.contains_name(name){
                 scene_render_data.dirlights.insert(self.dir_lights.get_id(name).unwrap());
             }
@@ -325,4 +728,55 @@ impl DataHandler{
     fn delete_mesh(&mut self, name : &str, _elements : &GlobalScenegraphChanged){
         
     }*/
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub mod datahandlertest{
+    use super::*;
+    use compact_str::CompactString;
+    use super::super::super::super::types::polygonstorage::DynamicPolygonStorage;
+
+    fn empty_mesh() -> Mesh{
+        Mesh::from_polygon_storage(Box::new(DynamicPolygonStorage::new(vec![], vec![], vec![], vec![], vec![])), "empty")
+    }
+
+    #[test]
+    fn instanced_batches(){
+        let mut data = DataHandler::new();
+        let (first, second) = (empty_mesh(), empty_mesh());
+        // meshes 1 to 3 share a storage, 2 is hidden and 4 has its own storage, 5 is in a scene no camera sees
+        let mut meshes = [(1, first.new_instance().unwrap()), (2, first.new_instance().unwrap()), (3, first), (4, second.new_instance().unwrap()), (5, second)];
+        for (id, mesh) in meshes.iter_mut(){
+            mesh.set_visible(*id != 2);
+            let transform = mesh.get_data_mut();
+            (transform.pos, transform.rot, transform.sca) = ([*id as f64, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0], [1.0; 3]);
+            data.handle_mesh_data(id, mesh, &format!("mesh{}", id));
+        }
+        data.handle_camera_data(&10, &Camera::new(1.0, 1.0, 0.1, 100.0), "camera");
+
+        let mut seen = Scene::new();
+        seen.objects.extend(["camera", "mesh1", "mesh2", "mesh3", "mesh4"].map(CompactString::from));
+        data.handle_scene_data(&20, &seen, "seen");
+        let mut unseen = Scene::new();
+        unseen.objects.insert("mesh5".into());
+        data.handle_scene_data(&21, &unseen, "unseen");
+
+        // without a loaded viewport nothing is drawn
+        data.update_instanced_batches();
+        assert!(data.instanced_batches.is_empty());
+
+        let mut viewport = ViewPort::new();
+        viewport.cameras_.push("camera".into());
+        data.handle_viewport_data(&30, &viewport, "viewport");
+        data.loaded_viewport = 30;
+        data.update_instanced_batches();
+        let batches : Vec<Vec<usize>> = data.instanced_batches.iter().map(|x| x.instances.clone()).collect();
+        assert_eq!(batches, vec![vec![1, 3], vec![4]]);
+
+        // one model matrix per instance, in the order of the instances
+        let batch = &data.instanced_batches[0];
+        assert_eq!(batch.model_mats.len(), 32);
+        assert_eq!((batch.model_mats[12], batch.model_mats[28]), (1.0, 3.0));
+        assert_eq!(batch.storage_id, data.meshes[3].storage_id);
+        assert_ne!(batch.storage_id, data.instanced_batches[1].storage_id);
+    }
+}
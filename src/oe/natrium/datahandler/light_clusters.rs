@@ -0,0 +1,275 @@
+use super::render_data::CameraRenderData;
+use super::super::super::math::Vec4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClusterSettings{
+    // tiles in x and y, slices in depth
+    pub grid : [u32; 3],
+    pub max_lights_per_cluster : usize,
+    // visible lights beyond it are dropped before the assignment
+    pub max_lights : usize,
+}
+
+impl Default for ClusterSettings{
+    fn default() -> Self{
+        ClusterSettings{grid : [16, 9, 24], max_lights_per_cluster : 32, max_lights : 1024}
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClusterStatistics{
+    // lights touching at least one cluster
+    pub visible_lights : usize,
+    // visible lights over the max_lights limit
+    pub dropped_lights : usize,
+    pub light_indices : usize,
+    pub occupied_clusters : usize,
+    pub overflowed_clusters : usize,
+    // light and cluster pairs that did not fit into their cluster
+    pub dropped_assignments : usize,
+    pub max_cluster_lights : usize,
+}
+
+// point or spot light, culled by the bounding sphere of its range
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClusterLight{
+    pub id : usize,
+    pub position : [f64; 3],
+    pub range : f32,
+    pub priority : u32,
+}
+
+// froxel grid of a camera, the tiles are uniform in screen space and the slices exponential in depth
+#[derive(Clone, Debug, Default)]
+pub struct LightClusterRenderData{
+    pub camera : usize,
+    pub grid : [u32; 3],
+    pub near : f32,
+    pub far : f32,
+    // ids of the lights the indices refer to, point lights before spot lights
+    pub lights : Vec<usize>,
+    pub point_light_count : usize,
+    // offset into light_indices and light count of every cluster, x first, then y, then the depth slice
+    pub grid_data : Vec<u32>,
+    pub light_indices : Vec<u32>,
+    pub statistics : ClusterStatistics,
+}
+
+impl LightClusterRenderData{
+    pub fn get_cluster_count(&self) -> usize{
+        self.grid.iter().product::<u32>() as usize
+    }
+    pub fn get_cluster_index(&self, x : u32, y : u32, z : u32) -> usize{
+        ((z * self.grid[1] + y) * self.grid[0] + x) as usize
+    }
+    // slice of a positive view space depth, None in front of the near or behind the far plane
+    pub fn get_slice(&self, depth : f32) -> Option<u32>{
+        if depth < self.near || depth > self.far {
+            return None;
+        }
+        let slice = ((depth / self.near).ln() / (self.far / self.near).ln() * self.grid[2] as f32) as u32;
+        Some(slice.min(self.grid[2] - 1))
+    }
+    pub fn get_slice_depth(&self, slice : u32) -> f32{
+        self.near * (self.far / self.near).powf(slice as f32 / self.grid[2] as f32)
+    }
+    // indices into lights
+    pub fn get_cluster_lights(&self, index : usize) -> &[u32]{
+        let (offset, count) = (self.grid_data[index * 2] as usize, self.grid_data[index * 2 + 1] as usize);
+        &self.light_indices[offset..offset + count]
+    }
+}
+
+// view space sphere of a light with the range of clusters it can touch
+struct ClusterCandidate{
+    index : usize,
+    center : [f32; 3],
+    radius : f32,
+    priority : u32,
+    distance : f32,
+    tiles : [(u32, u32); 3],
+}
+
+fn tile_range(min : f32, max : f32, count : u32) -> Option<(u32, u32)>{
+    if max < -1.0 || min > 1.0 {
+        return None;
+    }
+    let to_tile = |x : f32| (((x + 1.0) * 0.5 * count as f32) as i64).clamp(0, count as i64 - 1) as u32;
+    Some((to_tile(min), to_tile(max)))
+}
+
+// squared distance of a point to an axis aligned box
+fn box_distance_squared(point : &[f32; 3], min : &[f32; 3], max : &[f32; 3]) -> f32{
+    (0..3).map(|i| (min[i] - point[i]).max(0.0).max(point[i] - max[i])).map(|x| x * x).sum()
+}
+
+// lights are taken by descending priority and then by their distance to the camera,
+// so full clusters and the max_lights limit drop the least important ones
+pub fn build_light_clusters(camera_id : usize, camera : &CameraRenderData, point_lights : &[ClusterLight], spot_lights : &[ClusterLight], settings : &ClusterSettings) -> LightClusterRenderData{
+    let mut output = LightClusterRenderData{
+        camera : camera_id,
+        grid : settings.grid.map(|x| x.max(1)),
+        near : camera.near.max(f32::EPSILON),
+        far : camera.far.max(camera.near + f32::EPSILON),
+        ..Default::default()
+    };
+    let (scale_x, scale_y) = (camera.perspective_mat[(0, 0)], camera.perspective_mat[(1, 1)]);
+
+    let mut candidates = vec![];
+    for (index, light) in point_lights.iter().chain(spot_lights.iter()).enumerate(){
+        let position = Vec4::new(&[light.position[0] as f32, light.position[1] as f32, light.position[2] as f32, 1.0]);
+        let view = camera.view_mat.clone() * position;
+        let (center, radius) = ([view[0], view[1], view[2]], light.range);
+        let depth = -center[2];
+        let (min_depth, max_depth) = ((depth - radius).max(output.near), (depth + radius).min(output.far));
+        if min_depth > max_depth {
+            continue;
+        }
+        // the sphere is widest on screen at the depth closest to the camera
+        let ndc = |x : f32, scale : f32| [x * scale / min_depth, x * scale / max_depth];
+        let (x_min, x_max) = (ndc(center[0] - radius, scale_x), ndc(center[0] + radius, scale_x));
+        let (y_min, y_max) = (ndc(center[1] - radius, scale_y), ndc(center[1] + radius, scale_y));
+        let Some(tiles_x) = tile_range(x_min[0].min(x_min[1]), x_max[0].max(x_max[1]), output.grid[0]) else {continue;};
+        let Some(tiles_y) = tile_range(y_min[0].min(y_min[1]), y_max[0].max(y_max[1]), output.grid[1]) else {continue;};
+        let slices = (output.get_slice(min_depth).unwrap(), output.get_slice(max_depth).unwrap());
+        let distance = center.iter().map(|x| x * x).sum::<f32>().sqrt();
+        candidates.push(ClusterCandidate{index, center, radius, priority : light.priority, distance, tiles : [tiles_x, tiles_y, slices]});
+    }
+
+    let slice_depths : Vec<f32> = (0..=output.grid[2]).map(|x| output.get_slice_depth(x)).collect();
+    let mut clusters : Vec<Vec<u32>> = vec![vec![]; output.get_cluster_count()];
+    let mut is_visible = vec![false; candidates.len()];
+    let mut order : Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (&candidates[*a], &candidates[*b]);
+        b.priority.cmp(&a.priority).then(a.distance.total_cmp(&b.distance))
+    });
+    for candidate_index in order.iter().copied(){
+        let candidate = &candidates[candidate_index];
+        let ([(x0, x1), (y0, y1), (z0, z1)], grid) = (candidate.tiles, output.grid);
+        for z in z0..=z1{
+            let (near, far) = (slice_depths[z as usize], slice_depths[z as usize + 1]);
+            for y in y0..=y1{
+                for x in x0..=x1{
+                    // view space bounds of the cluster, tiles widen with the depth
+                    let bounds = |tile : u32, count : u32, scale : f32| {
+                        let (ndc0, ndc1) = (tile as f32 / count as f32 * 2.0 - 1.0, (tile + 1) as f32 / count as f32 * 2.0 - 1.0);
+                        let values = [ndc0 * near, ndc0 * far, ndc1 * near, ndc1 * far].map(|v| v / scale);
+                        (values.iter().cloned().fold(f32::MAX, f32::min), values.iter().cloned().fold(f32::MIN, f32::max))
+                    };
+                    let ((min_x, max_x), (min_y, max_y)) = (bounds(x, grid[0], scale_x), bounds(y, grid[1], scale_y));
+                    if box_distance_squared(&candidate.center, &[min_x, min_y, -far], &[max_x, max_y, -near]) > candidate.radius * candidate.radius {
+                        continue;
+                    }
+                    is_visible[candidate_index] = true;
+                    clusters[output.get_cluster_index(x, y, z)].push(candidate.index as u32);
+                }
+            }
+        }
+    }
+
+    // the max_lights limit keeps the first visible lights in priority order
+    let mut kept = vec![false; point_lights.len() + spot_lights.len()];
+    let visible : Vec<usize> = order.into_iter().filter(|x| is_visible[*x]).collect();
+    output.statistics.visible_lights = visible.len();
+    output.statistics.dropped_lights = visible.len().saturating_sub(settings.max_lights);
+    for candidate_index in visible.into_iter().take(settings.max_lights){
+        kept[candidates[candidate_index].index] = true;
+    }
+    // indices of the kept lights in the output order
+    let mut remap = vec![u32::MAX; kept.len()];
+    for (index, light) in point_lights.iter().chain(spot_lights.iter()).enumerate().filter(|x| kept[x.0]){
+        remap[index] = output.lights.len() as u32;
+        output.lights.push(light.id);
+        if index < point_lights.len() {
+            output.point_light_count += 1;
+        }
+    }
+
+    output.grid_data.reserve(clusters.len() * 2);
+    for cluster in clusters{
+        let offset = output.light_indices.len();
+        let indices : Vec<u32> = cluster.into_iter().map(|x| remap[x as usize]).filter(|x| *x != u32::MAX).collect();
+        if indices.len() > settings.max_lights_per_cluster {
+            output.statistics.overflowed_clusters += 1;
+            output.statistics.dropped_assignments += indices.len() - settings.max_lights_per_cluster;
+        }
+        output.light_indices.extend(indices.into_iter().take(settings.max_lights_per_cluster));
+        let count = output.light_indices.len() - offset;
+        if count > 0 {
+            output.statistics.occupied_clusters += 1;
+        }
+        output.statistics.max_cluster_lights = output.statistics.max_cluster_lights.max(count);
+        output.grid_data.push(offset as u32);
+        output.grid_data.push(count as u32);
+    }
+    output.statistics.light_indices = output.light_indices.len();
+    output
+}
+
+#[cfg(test)]
+pub mod lightclusterstest{
+    use super::*;
+    use super::super::super::super::math;
+
+    // at the origin looking down -z, with a 90 degree field of view the tiles cover [-depth, depth] in x and y
+    fn camera() -> CameraRenderData{
+        let mut camera = CameraRenderData::new(1);
+        camera.perspective_mat = math::perspective(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 100.0);
+        camera.view_mat = math::Mat4x4::new_identity();
+        (camera.near, camera.far) = (1.0, 100.0);
+        camera
+    }
+
+    fn light(id : usize, position : [f64; 3]) -> ClusterLight{
+        ClusterLight{id, position, range : 1.0, priority : 0}
+    }
+
+    // clusters containing the light, as x, y and depth slice
+    fn clusters_of(output : &LightClusterRenderData, id : usize) -> Vec<[u32; 3]>{
+        let index = output.lights.iter().position(|x| *x == id).unwrap() as u32;
+        let mut clusters = vec![];
+        for z in 0..output.grid[2]{
+            for y in 0..output.grid[1]{
+                for x in 0..output.grid[0]{
+                    if output.get_cluster_lights(output.get_cluster_index(x, y, z)).contains(&index) {
+                        clusters.push([x, y, z]);
+                    }
+                }
+            }
+        }
+        clusters
+    }
+
+    #[test]
+    fn assign_point_lights(){
+        let settings = ClusterSettings{grid : [4, 4, 4], ..Default::default()};
+        // the slices end at depths 3.16, 10, 31.6 and 100
+        let output = build_light_clusters(1, &camera(), &[light(10, [0.0, 0.0, -10.0]), light(11, [6.0, 0.0, -20.0])], &[], &settings);
+        assert_eq!(output.lights, [10, 11]);
+        assert_eq!(output.get_slice(10.5), Some(2));
+
+        // the centered light touches the four middle tiles on both sides of the slice boundary at depth 10
+        let expected : Vec<[u32; 3]> = (1..3).flat_map(|z| (1..3).flat_map(move |y| (1..3).map(move |x| [x, y, z]))).collect();
+        assert_eq!(clusters_of(&output, 10), expected);
+        // the light to the right only touches the third column, in the middle rows of its slice
+        assert_eq!(clusters_of(&output, 11), [[2, 1, 2], [2, 2, 2]]);
+        assert_eq!(output.statistics.occupied_clusters, 8);
+        assert_eq!(output.statistics.light_indices, 10);
+    }
+
+    #[test]
+    fn cull_lights_outside_the_frustum(){
+        let settings = ClusterSettings{grid : [4, 4, 4], ..Default::default()};
+        // behind the camera, beside the frustum and beyond the far plane
+        let culled = [light(20, [0.0, 0.0, 10.0]), light(21, [100.0, 0.0, -10.0]), light(22, [0.0, 0.0, -200.0])];
+        let output = build_light_clusters(1, &camera(), &culled, &[light(23, [0.0, 5.0, -10.0])], &settings);
+        assert_eq!(output.lights, [23]);
+        assert_eq!((output.point_light_count, output.statistics.visible_lights, output.statistics.dropped_lights), (0, 1, 0));
+        assert!(output.light_indices.iter().all(|x| *x == 0));
+
+        let output = build_light_clusters(1, &camera(), &culled, &[], &settings);
+        assert!(output.lights.is_empty() && output.light_indices.is_empty());
+        assert_eq!(output.grid_data.len(), 4 * 4 * 4 * 2);
+    }
+}
@@ -3,6 +3,12 @@ use compact_str::CompactString;
 use nohash_hasher::IntSet;
 
 use super::super::super::types::polygonstoragetrait::*;
+use super::super::super::types::armature::SkinData;
+use super::super::super::types::drawrange::DrawRange;
+use super::super::super::types::quantization::VertexLayout;
+use super::super::super::types::texture::TEXTURE_SLOTS;
+use super::super::super::types::light::{Attenuation, AreaShape, ShadowSettings};
+use super::super::super::carbon::image::Image;
 use super::super::super::math::*;
 
 #[derive(Clone, Debug)]
@@ -86,12 +92,33 @@ impl CameraRenderData{
     }
 }
 
+// texture of a material slot and the uv map it is sampled with
+#[derive(Clone, Debug)]
+pub struct MaterialTextureRenderData{
+    pub texture : (usize, CompactString),
+    pub uvmap : u8,
+}
+
 #[derive(Clone, Debug)]
 pub struct MaterialRenderData{
     pub common_data : CommonRenderData,
+    // indexed by TextureSlotType
+    pub textures : [Option<MaterialTextureRenderData>; TEXTURE_SLOTS],
+    // layout of the data, metallic-roughness or the legacy parameters
+    pub pbr : bool,
     pub ubo : UniformBufferData
 }
 
+#[derive(Clone, Debug)]
+pub struct TextureRenderData{
+    pub common_data : CommonRenderData,
+    pub width : u32,
+    pub height : u32,
+    // rgba8 images, the full image followed by its mipmaps
+    pub levels : Arc<Vec<Image>>,
+    pub texture : usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct VertexGroupRenderData{
     pub common_data : CommonRenderData,
@@ -100,14 +127,26 @@ pub struct VertexGroupRenderData{
     pub material : (usize, CompactString),
     pub offset : u32,
     pub size : usize,
+    // per level of detail, only filled for targets without 32 bit indices
+    pub draw_ranges : Vec<Vec<DrawRange>>,
+    // per level of detail, the draw ranges point into these
+    pub indices_16bit : Vec<Arc<[u16]>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct MeshRenderData{
     pub common_data : CommonRenderData,
     pub model_mat : DMat4x4,
+    pub visible : bool,
     pub uvmaps :u8,
+    // 4 floats after the uvs of every vertex
+    pub tangents : bool,
+    // attributes of the vertex buffer, quantized for compressed meshes
+    pub vertex_layout : VertexLayout,
     pub bones : u8,
+    // only filled for gpu skinning
+    pub bone_mats : Vec<Mat4x4>,
+    pub skin : Option<Arc<SkinData>>,
 
     pub vbo : usize,
     pub vbo_size : usize,
@@ -121,8 +160,27 @@ pub struct MeshRenderData{
     pub max_vec : [f32 ; 3],
     pub min_vec : [f32 ; 3],
 
+    // currently selected level of detail, 0 is the full mesh
+    pub lod : usize,
+    pub lod_screen_sizes : Vec<f32>,
+
     pub mesh : Arc<Mutex<(Box<dyn PolygonStorageTrait>, bool)>>,
-    pub vgroups : Vec<VertexGroupRenderData>
+    // equal for instances sharing the polygon storage
+    pub storage_id : usize,
+    pub vgroups : Vec<VertexGroupRenderData>,
+    // vertices to append to the vertex buffer for the 16 bit draw ranges, only filled for targets without 32 bit indices
+    pub copied_vertices : Arc<[u32]>,
+}
+
+// meshes sharing a polygon storage and level of detail, drawn with one instanced draw call
+#[derive(Clone, Debug, Default)]
+pub struct InstancedBatchRenderData{
+    pub storage_id : usize,
+    pub lod : usize,
+    // the buffers of the first instance are used for the whole batch
+    pub instances : Vec<usize>,
+    // 16 floats per instance
+    pub model_mats : Vec<f32>,
 }
 
 #[derive(Clone, Debug)]
@@ -132,6 +190,37 @@ pub struct PointLightRenderData{
     pub color : Vec3,
     pub intensity : f32,
     pub range : f32,
+    pub attenuation : Attenuation,
+    pub priority : u32,
+    pub shadow : Option<ShadowSettings>,
+    pub ubo : UniformBufferData,
+}
+
+#[derive(Clone, Debug)]
+pub struct SpotLightRenderData{
+    pub common_data : CommonRenderData,
+    pub model_mat : DMat4x4,
+    pub direction : Vec3,
+    pub color : Vec3,
+    pub intensity : f32,
+    pub range : f32,
+    pub attenuation : Attenuation,
+    // cosines of the half angles, fully lit inside the inner cone
+    pub inner_cone : f32,
+    pub outer_cone : f32,
+    pub priority : u32,
+    pub ubo : UniformBufferData,
+}
+
+#[derive(Clone, Debug)]
+pub struct AreaLightRenderData{
+    pub common_data : CommonRenderData,
+    pub model_mat : DMat4x4,
+    pub color : Vec3,
+    pub intensity : f32,
+    pub range : f32,
+    pub attenuation : Attenuation,
+    pub shape : AreaShape,
     pub ubo : UniformBufferData,
 }
 
@@ -139,8 +228,11 @@ pub struct PointLightRenderData{
 pub struct DirectionalLightRenderData{
     pub common_data : CommonRenderData,
     pub model_mat : DMat4x4,
+    pub direction : Vec3,
     pub color : DVec3,
     pub intensity : f32,
+    pub priority : u32,
+    pub shadow : Option<ShadowSettings>,
     pub ubo : UniformBufferData,
 }
 
@@ -151,6 +243,8 @@ pub struct SceneRenderData{
     pub meshes : IntSet<usize>,
     pub dirlights : IntSet<usize>,
     pub pt_lights : IntSet<usize>,
+    pub spot_lights : IntSet<usize>,
+    pub area_lights : IntSet<usize>,
     pub materials : IntSet<usize>,
 }
 
@@ -162,6 +256,8 @@ impl SceneRenderData{
             meshes: Default::default(), 
             dirlights: Default::default(), 
             pt_lights: Default::default(), 
+            spot_lights: Default::default(), 
+            area_lights: Default::default(), 
             materials: Default::default() 
         }
     }
@@ -0,0 +1,368 @@
+use nalgebra_glm as glm;
+
+use super::render_data::CameraRenderData;
+use super::super::super::math::{Mat4x4, Vec4};
+use super::super::super::types::light::ShadowSettings;
+
+// lights that do not fit at this size are dropped
+pub const MIN_SHADOW_MAP_SIZE : u32 = 64;
+const MAX_CASCADES : u32 = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShadowStatistics{
+    pub shadowed_lights : usize,
+    pub shadow_maps : usize,
+    // lights whose maps were halved until they fit into the atlas
+    pub downscaled_lights : usize,
+    // lights without space left even at the minimal map size
+    pub dropped_lights : usize,
+    pub used_texels : u64,
+}
+
+// shadow casting sun or point light
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowLight{
+    pub id : usize,
+    pub position : [f64; 3],
+    // only used by sun lights
+    pub direction : [f64; 3],
+    // far plane of the cube faces of point lights
+    pub range : f32,
+    pub priority : u32,
+    pub settings : ShadowSettings,
+}
+
+// sun lights get cascades fit to the camera, point lights without a camera get a cube map
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowRequest<'a>{
+    pub light : ShadowLight,
+    pub camera : Option<(usize, &'a CameraRenderData)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShadowMapRenderData{
+    // x, y and size of the square tile in the atlas in texels
+    pub tile : [u32; 3],
+    // from world space to the clip space of the map, depth from -1 to 1 like the camera matrices
+    pub light_space_mat : Mat4x4,
+    // world size of a texel, for cube faces at a distance of 1 from the light
+    pub texel_size : f32,
+    // cascades end at this view depth of the camera, 0 for cube faces
+    pub split_depth : f32,
+}
+
+impl ShadowMapRenderData{
+    // atlas coordinates and depth from 0 to 1, None outside of the map
+    pub fn project(&self, position : &[f32; 3], atlas_size : u32) -> Option<([f32; 2], f32)>{
+        let clip = self.light_space_mat.clone() * Vec4::new(&[position[0], position[1], position[2], 1.0]);
+        if clip[3] <= 0.0 {
+            return None;
+        }
+        let ndc = [clip[0] / clip[3], clip[1] / clip[3], clip[2] / clip[3]];
+        if ndc.iter().any(|x| x.abs() > 1.0) {
+            return None;
+        }
+        let [x, y, size] = self.tile.map(|x| x as f32);
+        let to_atlas = |offset : f32, ndc : f32| (offset + (ndc * 0.5 + 0.5) * size) / atlas_size as f32;
+        Some(([to_atlas(x, ndc[0]), to_atlas(y, ndc[1])], ndc[2] * 0.5 + 0.5))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LightShadowRenderData{
+    pub light : usize,
+    // camera the cascades are fit to, None for cube maps
+    pub camera : Option<usize>,
+    // size of every map after fitting into the atlas
+    pub resolution : u32,
+    pub bias : f32,
+    // in texels, multiplied with the texel_size of the sampled map
+    pub normal_bias : f32,
+    pub near : f32,
+    pub far : f32,
+    // cascades from near to far or the cube faces +x, -x, +y, -y, +z, -z
+    pub maps : Vec<ShadowMapRenderData>,
+}
+
+impl LightShadowRenderData{
+    pub fn is_cube(&self) -> bool{
+        self.camera.is_none()
+    }
+    // first cascade containing the view depth, None behind the last one
+    pub fn get_cascade(&self, view_depth : f32) -> Option<usize>{
+        self.maps.iter().position(|x| view_depth <= x.split_depth)
+    }
+    // cube face of a direction pointing away from the light
+    pub fn get_cube_face(direction : &[f32; 3]) -> usize{
+        let axis = (0..3).max_by(|a, b| direction[*a].abs().total_cmp(&direction[*b].abs())).unwrap();
+        axis * 2 + (direction[axis] < 0.0) as usize
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShadowAtlasRenderData{
+    pub size : u32,
+    pub shadows : Vec<LightShadowRenderData>,
+    pub statistics : ShadowStatistics,
+}
+
+// buddy allocator of square power of two tiles
+#[derive(Clone, Debug)]
+struct ShadowAtlas{
+    free : Vec<[u32; 3]>,
+}
+
+impl ShadowAtlas{
+    fn new(size : u32) -> ShadowAtlas{
+        ShadowAtlas{free : vec![[0, 0, size]]}
+    }
+
+    // the smallest free tile that fits is split into quarters until it has the requested size
+    fn allocate(&mut self, size : u32) -> Option<[u32; 3]>{
+        let index = self.free.iter().enumerate().filter(|x| x.1[2] >= size).min_by_key(|x| (x.1[2], x.1[1], x.1[0])).map(|x| x.0)?;
+        let [x, y, mut tile_size] = self.free.swap_remove(index);
+        while tile_size > size {
+            tile_size /= 2;
+            self.free.extend([[x + tile_size, y, tile_size], [x, y + tile_size, tile_size], [x + tile_size, y + tile_size, tile_size]]);
+        }
+        Some([x, y, size])
+    }
+}
+
+// end depths of the cascades, lambda blends the uniform and the logarithmic split
+fn get_cascade_splits(near : f32, far : f32, count : u32, lambda : f32) -> Vec<f32>{
+    (1..=count).map(|i| {
+        let t = i as f32 / count as f32;
+        lambda * near * (far / near).powf(t) + (1.0 - lambda) * (near + (far - near) * t)
+    }).collect()
+}
+
+fn get_up_vector(direction : &glm::Vec3) -> glm::Vec3{
+    if direction.y.abs() > 0.99 {glm::vec3(0.0, 0.0, 1.0)} else {glm::vec3(0.0, 1.0, 0.0)}
+}
+
+// orthographic projection around the bounding sphere of the frustum slice, the sphere keeps the size
+// constant while the camera rotates and its center is snapped to texels so the edges do not flicker
+fn fit_cascade(inverse_view : &glm::Mat4, scale : [f32; 2], near : f32, far : f32, direction : &glm::Vec3, resolution : u32) -> (Mat4x4, f32){
+    let mut corners = Vec::with_capacity(8);
+    for depth in [near, far]{
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]{
+            corners.push((inverse_view * glm::vec4(x * depth / scale[0], y * depth / scale[1], -depth, 1.0)).xyz());
+        }
+    }
+    let center = corners.iter().sum::<glm::Vec3>() / 8.0;
+    let radius = corners.iter().map(|x| glm::distance(x, &center)).fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+    let texel_size = radius * 2.0 / resolution as f32;
+
+    let up = get_up_vector(direction);
+    let rotation = glm::look_at_rh(&glm::Vec3::zeros(), direction, &up);
+    let mut light_center = (rotation * glm::vec4(center.x, center.y, center.z, 1.0)).xyz();
+    light_center.x = (light_center.x / texel_size).floor() * texel_size;
+    light_center.y = (light_center.y / texel_size).floor() * texel_size;
+    let center = (rotation.transpose() * glm::vec4(light_center.x, light_center.y, light_center.z, 1.0)).xyz();
+
+    // casters up to a radius in front of the slice still reach the map
+    let eye = center - direction * radius * 2.0;
+    let view = glm::look_at_rh(&eye, &center, &up);
+    let projection = glm::ortho_rh_no(-radius, radius, -radius, radius, 0.0, radius * 3.0);
+    (to_mat4x4(&(projection * view)), texel_size)
+}
+
+fn to_mat4x4(mat : &glm::Mat4) -> Mat4x4{
+    Mat4x4::new(mat.as_slice().try_into().unwrap())
+}
+
+fn build_cascades(light : &ShadowLight, camera : &CameraRenderData, cascades : u32, tiles : &[[u32; 3]]) -> (Vec<ShadowMapRenderData>, f32, f32){
+    let settings = &light.settings;
+    let near = camera.near.max(f32::EPSILON);
+    let far = camera.far.min(settings.max_distance).max(near + f32::EPSILON);
+    let view : glm::Mat4 = glm::make_mat4(&camera.view_mat.get_f32_vec());
+    let inverse_view = glm::inverse(&view);
+    let scale = [camera.perspective_mat[(0, 0)], camera.perspective_mat[(1, 1)]];
+    let direction = glm::normalize(&glm::vec3(light.direction[0] as f32, light.direction[1] as f32, light.direction[2] as f32));
+
+    let splits = get_cascade_splits(near, far, cascades, settings.split_lambda.clamp(0.0, 1.0));
+    let mut maps = Vec::with_capacity(tiles.len());
+    let mut start = near;
+    for (tile, end) in tiles.iter().zip(splits){
+        let (light_space_mat, texel_size) = fit_cascade(&inverse_view, scale, start, end, &direction, tile[2]);
+        maps.push(ShadowMapRenderData{tile : *tile, light_space_mat, texel_size, split_depth : end});
+        start = end;
+    }
+    (maps, near, far)
+}
+
+fn build_cube_faces(light : &ShadowLight, tiles : &[[u32; 3]]) -> (Vec<ShadowMapRenderData>, f32, f32){
+    let near = light.settings.near.max(f32::EPSILON);
+    let far = light.range.max(near + f32::EPSILON);
+    let position = glm::vec3(light.position[0] as f32, light.position[1] as f32, light.position[2] as f32);
+    let projection = glm::perspective_rh_no(1.0, std::f32::consts::FRAC_PI_2, near, far);
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+    ];
+    let maps = tiles.iter().zip(faces).map(|(tile, (direction, up))| {
+        let view = glm::look_at_rh(&position, &(position + glm::make_vec3(&direction)), &glm::make_vec3(&up));
+        ShadowMapRenderData{tile : *tile, light_space_mat : to_mat4x4(&(projection * view)), texel_size : 2.0 / tile[2] as f32, split_depth : 0.0}
+    }).collect();
+    (maps, near, far)
+}
+
+// requests are placed by descending priority, sun cascades before cube maps, a light that does not fit
+// is retried with all its maps at half the size
+pub fn build_shadow_atlas(size : u32, requests : &[ShadowRequest]) -> ShadowAtlasRenderData{
+    let size = size.max(MIN_SHADOW_MAP_SIZE).next_power_of_two();
+    let mut output = ShadowAtlasRenderData{size, ..Default::default()};
+    let mut atlas = ShadowAtlas::new(size);
+
+    let mut order : Vec<&ShadowRequest> = requests.iter().collect();
+    order.sort_by(|a, b| {
+        b.light.priority.cmp(&a.light.priority)
+            .then(b.camera.is_some().cmp(&a.camera.is_some()))
+            .then(a.light.id.cmp(&b.light.id))
+            .then(a.camera.map(|x| x.0).cmp(&b.camera.map(|x| x.0)))
+    });
+    for request in order{
+        let settings = &request.light.settings;
+        let map_count = match request.camera{
+            Some(_) => settings.cascades.clamp(1, MAX_CASCADES),
+            None => 6,
+        };
+        let requested = settings.resolution.clamp(MIN_SHADOW_MAP_SIZE, size).next_power_of_two();
+        let mut resolution = requested;
+        let tiles = loop{
+            let mut attempt = atlas.clone();
+            let tiles : Option<Vec<[u32; 3]>> = (0..map_count).map(|_| attempt.allocate(resolution)).collect();
+            if let Some(tiles) = tiles {
+                atlas = attempt;
+                break Some(tiles);
+            }
+            resolution /= 2;
+            if resolution < MIN_SHADOW_MAP_SIZE {
+                break None;
+            }
+        };
+        let Some(tiles) = tiles else {
+            output.statistics.dropped_lights += 1;
+            continue;
+        };
+        if resolution < requested {
+            output.statistics.downscaled_lights += 1;
+        }
+
+        let (maps, near, far) = match request.camera{
+            Some((_, camera)) => build_cascades(&request.light, camera, map_count, &tiles),
+            None => build_cube_faces(&request.light, &tiles),
+        };
+        output.statistics.shadowed_lights += 1;
+        output.statistics.shadow_maps += maps.len();
+        output.statistics.used_texels += maps.len() as u64 * resolution as u64 * resolution as u64;
+        output.shadows.push(LightShadowRenderData{
+            light : request.light.id,
+            camera : request.camera.map(|x| x.0),
+            resolution,
+            bias : settings.bias,
+            normal_bias : settings.normal_bias,
+            near,
+            far,
+            maps,
+        });
+    }
+    output
+}
+
+#[cfg(test)]
+pub mod shadowstest{
+    use super::*;
+    use super::super::super::super::math;
+
+    fn overlaps(a : &[u32; 3], b : &[u32; 3]) -> bool{
+        a[0] < b[0] + b[2] && b[0] < a[0] + a[2] && a[1] < b[1] + b[2] && b[1] < a[1] + a[2]
+    }
+
+    fn sun(resolution : u32, cascades : u32) -> ShadowLight{
+        let settings = ShadowSettings{resolution, cascades, split_lambda : 1.0, ..Default::default()};
+        ShadowLight{id : 1, position : [0.0; 3], direction : [0.0, -1.0, 0.0], range : 10.0, priority : 0, settings}
+    }
+
+    // at the origin looking down -z
+    fn camera() -> CameraRenderData{
+        let mut camera = CameraRenderData::new(2);
+        camera.perspective_mat = math::perspective(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 50.0);
+        camera.view_mat = Mat4x4::new_identity();
+        (camera.near, camera.far) = (1.0, 50.0);
+        camera
+    }
+
+    #[test]
+    fn atlas_allocation(){
+        let mut atlas = ShadowAtlas::new(256);
+        assert_eq!(atlas.allocate(512), None);
+        let mut tiles = vec![atlas.allocate(128).unwrap()];
+        assert_eq!(tiles[0], [0, 0, 128]);
+        // the smallest free tile is split, so the large ones stay available
+        tiles.push(atlas.allocate(64).unwrap());
+        assert_eq!(tiles[1], [128, 0, 64]);
+        tiles.extend([atlas.allocate(128).unwrap(), atlas.allocate(128).unwrap()]);
+        assert_eq!(atlas.allocate(128), None);
+        tiles.extend((0..3).map(|_| atlas.allocate(64).unwrap()));
+        assert_eq!(atlas.allocate(64), None);
+
+        assert_eq!(tiles.iter().map(|x| x[2] * x[2]).sum::<u32>(), 256 * 256);
+        for (index, tile) in tiles.iter().enumerate(){
+            assert!(tile[0] + tile[2] <= 256 && tile[1] + tile[2] <= 256);
+            assert!(tiles[index + 1..].iter().all(|x| !overlaps(tile, x)), "{:?}", tiles);
+        }
+    }
+
+    #[test]
+    fn cascade_splits(){
+        assert_eq!(get_cascade_splits(1.0, 100.0, 2, 1.0), [10.0, 100.0]);
+        assert_eq!(get_cascade_splits(1.0, 100.0, 2, 0.0), [50.5, 100.0]);
+        assert_eq!(get_cascade_splits(1.0, 100.0, 2, 0.5), [30.25, 100.0]);
+        let splits = get_cascade_splits(0.1, 50.0, 4, 0.75);
+        assert!(splits.windows(2).all(|x| x[0] < x[1]));
+        assert!((splits[3] - 50.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn project(){
+        let map = ShadowMapRenderData{tile : [64, 0, 64], light_space_mat : Mat4x4::new_identity(), texel_size : 1.0, split_depth : 0.0};
+        assert_eq!(map.project(&[0.0, 0.0, 0.0], 256), Some(([0.375, 0.125], 0.5)));
+        assert_eq!(map.project(&[-1.0, 1.0, 1.0], 256), Some(([0.25, 0.25], 1.0)));
+        assert_eq!(map.project(&[1.5, 0.0, 0.0], 256), None);
+        let behind = ShadowMapRenderData{light_space_mat : Mat4x4::new_from_value(0.0), ..map};
+        assert_eq!(behind.project(&[0.0, 0.0, 0.0], 256), None);
+
+        // points in front of the camera land in the tile of their cascade
+        let camera = camera();
+        let atlas = build_shadow_atlas(1024, &[ShadowRequest{light : sun(256, 2), camera : Some((2, &camera))}]);
+        let shadow = &atlas.shadows[0];
+        assert_eq!((shadow.maps.len(), shadow.resolution, shadow.far), (2, 256, 50.0));
+        for (depth, cascade) in [(2.0, 0), (20.0, 1)]{
+            assert_eq!(shadow.get_cascade(depth), Some(cascade));
+            let map = &shadow.maps[cascade];
+            let (position, depth) = map.project(&[0.5, 0.5, -depth], atlas.size).unwrap();
+            for (value, offset) in position.iter().zip(&map.tile[..2]){
+                let texel = value * atlas.size as f32;
+                assert!(texel >= *offset as f32 && texel <= (offset + map.tile[2]) as f32, "{:?} {:?}", position, map.tile);
+            }
+            assert!((0.0..=1.0).contains(&depth));
+        }
+        assert_eq!(shadow.get_cascade(60.0), None);
+    }
+
+    #[test]
+    fn invalid_resolutions(){
+        let camera = camera();
+        let atlas = build_shadow_atlas(1024, &[ShadowRequest{light : sun(u32::MAX, 1), camera : Some((2, &camera))}]);
+        assert_eq!(atlas.shadows[0].resolution, 1024);
+        let atlas = build_shadow_atlas(1024, &[ShadowRequest{light : sun(0, 1), camera : Some((2, &camera))}]);
+        assert_eq!(atlas.shadows[0].resolution, MIN_SHADOW_MAP_SIZE);
+    }
+}
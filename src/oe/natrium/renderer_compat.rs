@@ -33,6 +33,8 @@ impl RendererBaseTrait for RendererCompat{
     }
     fn update_data(&mut self, new_data : GlobalScenegraphChanged, update_info : RendererUpdateInfo, winsys_output : WinsysOutput){
         self.data.set_changed(new_data);
+        self.data.use_16bit_indices = matches!(winsys_output.backend, WinsysBackend::Gles2 | WinsysBackend::Angle);
+        self.data.use_light_indexed_rendering = update_info.use_light_indexed_rendering;
         self.renderer_update_info = update_info;
         self.winsys_data = winsys_output;
         //println!("{:?}", &self.new_data);
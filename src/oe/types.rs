@@ -9,6 +9,20 @@ pub mod light;
 pub mod camera;
 pub mod viewport;
 pub mod material;
+pub mod texture;
+pub mod armature;
+pub mod animation;
+pub mod morphtarget;
+pub mod prefab;
+pub mod snapshot;
+pub mod transaction;
+pub mod worlddiff;
+pub mod primitives;
+pub mod tangentspace;
+pub mod simplification;
+pub mod vertexcache;
+pub mod drawrange;
+pub mod quantization;
 pub mod polygonstorage;
 pub mod polygonstoragetrait;
 pub mod basecontainer;
@@ -0,0 +1,607 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::time;
+use compact_str::CompactString;
+use nalgebra_glm as glm;
+
+use super::armature::BoneTransform;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoneKeyframe{
+    pub time : f64,
+    pub transform : BoneTransform,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BoneChannel{
+    pub bone : CompactString,
+    // sorted by time
+    pub keyframes : Vec<BoneKeyframe>,
+}
+
+impl BoneChannel{
+    pub fn new(bone : &str, mut keyframes : Vec<BoneKeyframe>) -> BoneChannel{
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        BoneChannel{bone : bone.into(), keyframes}
+    }
+
+    pub fn sample(&self, time : f64) -> Option<BoneTransform>{
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(first.transform);
+        }
+        if time >= last.time {
+            return Some(last.transform);
+        }
+        let next = self.keyframes.partition_point(|x| x.time <= time);
+        let (a, b) = (&self.keyframes[next-1], &self.keyframes[next]);
+        let factor = (time - a.time) / (b.time - a.time);
+        Some(a.transform.interpolate(&b.transform, factor))
+    }
+
+    pub fn duration(&self) -> f64{
+        self.keyframes.last().map(|x| x.time).unwrap_or(0.0)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation{
+    #[default]
+    Linear,
+    Step,
+    Cubic,
+}
+
+impl Interpolation{
+    pub fn from_name(name : &str) -> Option<Interpolation>{
+        match name{
+            "linear" => Some(Interpolation::Linear),
+            "step" => Some(Interpolation::Step),
+            "cubic" => Some(Interpolation::Cubic),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AnimatedProperty{
+    Position,
+    Rotation,
+    Scale,
+    LightIntensity,
+    LightColor,
+    CameraFov,
+    MaterialDiffuse,
+    MaterialSpecular,
+    MaterialAlpha,
+}
+
+impl AnimatedProperty{
+    // same names as the CSL assignments
+    pub fn from_name(name : &str) -> Option<AnimatedProperty>{
+        match name{
+            "pos" => Some(AnimatedProperty::Position),
+            "rot" => Some(AnimatedProperty::Rotation),
+            "sca" => Some(AnimatedProperty::Scale),
+            "intensity" => Some(AnimatedProperty::LightIntensity),
+            "color" => Some(AnimatedProperty::LightColor),
+            "fov" => Some(AnimatedProperty::CameraFov),
+            "dif" => Some(AnimatedProperty::MaterialDiffuse),
+            "scol" => Some(AnimatedProperty::MaterialSpecular),
+            "alpha" => Some(AnimatedProperty::MaterialAlpha),
+            _ => None
+        }
+    }
+    pub fn num_of_components(&self) -> usize{
+        match self{
+            AnimatedProperty::Rotation | AnimatedProperty::MaterialDiffuse => 4,
+            AnimatedProperty::Position | AnimatedProperty::Scale | AnimatedProperty::LightColor | AnimatedProperty::MaterialSpecular => 3,
+            AnimatedProperty::LightIntensity | AnimatedProperty::CameraFov | AnimatedProperty::MaterialAlpha => 1,
+        }
+    }
+    pub fn is_material(&self) -> bool{
+        matches!(self, AnimatedProperty::MaterialDiffuse | AnimatedProperty::MaterialSpecular | AnimatedProperty::MaterialAlpha)
+    }
+
+    // slerp for rotations, lerp for everything else
+    pub fn blend(&self, a : &[f64; 4], b : &[f64; 4], factor : f64) -> [f64; 4]{
+        if *self == AnimatedProperty::Rotation {
+            let rot_a = glm::quat_normalize(&glm::make_quat(a));
+            let mut rot_b = glm::quat_normalize(&glm::make_quat(b));
+            if glm::quat_dot(&rot_a, &rot_b) < 0.0 {
+                rot_b = -rot_b;
+            }
+            let rot = glm::quat_slerp(&rot_a, &rot_b, factor);
+            return [rot.coords.x, rot.coords.y, rot.coords.z, rot.coords.w];
+        }
+        let mut output = [0.0; 4];
+        for id in 0..4{
+            output[id] = a[id] + (b[id] - a[id])*factor;
+        }
+        output
+    }
+}
+
+pub type AnimatedValues = Vec<(AnimatedProperty, [f64; 4])>;
+
+#[derive(Clone, Debug)]
+pub struct PropertyChannel{
+    pub property : AnimatedProperty,
+    pub interpolation : Interpolation,
+    // sorted by time, one value per keyframe
+    pub times : Vec<f64>,
+    pub values : Vec<[f64; 4]>,
+}
+
+impl PropertyChannel{
+    // values are flattened, with as many components per keyframe as the property has
+    pub fn new(property : AnimatedProperty, interpolation : Interpolation, times : &[f64], values : &[f64]) -> Option<PropertyChannel>{
+        let components = property.num_of_components();
+        if times.is_empty() || values.len() != times.len()*components {
+            return None;
+        }
+        let mut keyframes : Vec<(f64, [f64; 4])> = times.iter().zip(values.chunks(components)).map(|(time, value)| {
+            let mut output = [0.0; 4];
+            output[..components].copy_from_slice(value);
+            (*time, output)
+        }).collect();
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // neighbouring rotations in the same hemisphere, so that interpolation takes the shortest path
+        if property == AnimatedProperty::Rotation {
+            for id in 1..keyframes.len(){
+                let previous = keyframes[id-1].1;
+                let current = &mut keyframes[id].1;
+                if (0..4).map(|x| previous[x]*current[x]).sum::<f64>() < 0.0 {
+                    current.iter_mut().for_each(|x| *x = -*x);
+                }
+            }
+        }
+        Some(PropertyChannel{
+            property,
+            interpolation,
+            times : keyframes.iter().map(|x| x.0).collect(),
+            values : keyframes.iter().map(|x| x.1).collect(),
+        })
+    }
+
+    pub fn sample(&self, time : f64) -> [f64; 4]{
+        let last = self.times.len()-1;
+        if time <= self.times[0] {
+            return self.values[0];
+        }
+        if time >= self.times[last] {
+            return self.values[last];
+        }
+        let next = self.times.partition_point(|x| *x <= time);
+        let (a, b) = (next-1, next);
+        let span = self.times[b] - self.times[a];
+        let factor = (time - self.times[a]) / span;
+
+        match self.interpolation{
+            Interpolation::Step => self.values[a],
+            Interpolation::Linear => self.property.blend(&self.values[a], &self.values[b], factor),
+            Interpolation::Cubic => {
+                // hermite spline with catmull-rom tangents
+                let tangent = |id : usize, component : usize| -> f64{
+                    let (prev, next) = (id.saturating_sub(1), (id+1).min(last));
+                    if prev == next {return 0.0;}
+                    (self.values[next][component] - self.values[prev][component]) / (self.times[next] - self.times[prev])
+                };
+                let (t2, t3) = (factor*factor, factor*factor*factor);
+                let mut output = [0.0; 4];
+                for (component, value) in output.iter_mut().enumerate(){
+                    *value = (2.0*t3 - 3.0*t2 + 1.0)*self.values[a][component]
+                            + (t3 - 2.0*t2 + factor)*span*tangent(a, component)
+                            + (-2.0*t3 + 3.0*t2)*self.values[b][component]
+                            + (t3 - t2)*span*tangent(b, component);
+                }
+                if self.property == AnimatedProperty::Rotation {
+                    let length = output.iter().map(|x| x*x).sum::<f64>().sqrt();
+                    if length > 1e-12 {
+                        output.iter_mut().for_each(|x| *x /= length);
+                    }
+                }
+                output
+            }
+        }
+    }
+
+    pub fn duration(&self) -> f64{
+        self.times.last().copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnimationClip{
+    pub name : CompactString,
+    pub duration : f64,
+    pub bone_channels : Vec<BoneChannel>,
+    pub property_channels : Vec<PropertyChannel>,
+    // (time, name)
+    pub markers : Vec<(f64, CompactString)>,
+}
+
+impl AnimationClip{
+    pub fn new(name : &str) -> AnimationClip{
+        AnimationClip{name : name.into(), ..Default::default()}
+    }
+    pub fn add_bone_channel(&mut self, channel : BoneChannel){
+        self.duration = self.duration.max(channel.duration());
+        self.bone_channels.push(channel);
+    }
+    pub fn add_property_channel(&mut self, channel : PropertyChannel){
+        self.duration = self.duration.max(channel.duration());
+        self.property_channels.push(channel);
+    }
+    pub fn add_marker(&mut self, time : f64, name : &str){
+        self.duration = self.duration.max(time);
+        self.markers.push((time, name.into()));
+    }
+    pub fn sample_properties(&self, time : f64) -> AnimatedValues{
+        self.property_channels.iter().map(|channel| (channel.property, channel.sample(time))).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlaybackStep{
+    pub time : f64,
+    pub previous_time : f64,
+    // the clip wrapped around at least once
+    pub looped : bool,
+    // the clip reached its end and stopped
+    pub finished : bool,
+}
+
+impl PlaybackStep{
+    pub fn has_passed(&self, marker_time : f64) -> bool{
+        if self.looped {
+            marker_time > self.previous_time || marker_time <= self.time
+        }
+        else if self.time >= self.previous_time {
+            marker_time > self.previous_time && marker_time <= self.time
+        }
+        else {
+            marker_time < self.previous_time && marker_time >= self.time
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer{
+    pub clip : Option<CompactString>,
+    pub time : f64,
+    pub speed : f64,
+    pub looping : bool,
+    pub playing : bool,
+}
+
+impl Default for AnimationPlayer{
+    fn default() -> Self {
+        AnimationPlayer{clip : None, time : 0.0, speed : 1.0, looping : false, playing : false}
+    }
+}
+
+impl AnimationPlayer{
+    pub fn play(&mut self, clip_name : &str, looping : bool){
+        self.clip = Some(clip_name.into());
+        self.time = 0.0;
+        self.looping = looping;
+        self.playing = true;
+    }
+    pub fn stop(&mut self){
+        self.playing = false;
+    }
+    pub fn resume(&mut self){
+        self.playing = self.clip.is_some();
+    }
+
+    // advances the time by the given frame time in seconds
+    pub fn advance(&mut self, elapsed : f64, get_duration : impl Fn(&str) -> Option<f64>) -> Option<PlaybackStep>{
+        if !self.playing {
+            return None;
+        }
+        let duration = get_duration(self.clip.as_ref()?)?;
+        let mut output = PlaybackStep{previous_time : self.time, ..Default::default()};
+        self.time += elapsed*self.speed;
+        if self.looping && duration > 0.0 {
+            output.looped = self.time >= duration || self.time < 0.0;
+            self.time = self.time.rem_euclid(duration);
+        }
+        else if self.time >= duration || self.time < 0.0 {
+            self.time = self.time.clamp(0.0, duration);
+            self.playing = false;
+            output.finished = true;
+        }
+        output.time = self.time;
+        Some(output)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnimationEvents{
+    // broadcasted when the clip ends or loops
+    pub end : usize,
+    pub markers : HashMap<CompactString, usize>,
+}
+
+#[derive(Clone, Debug)]
+struct TargetPlayback{
+    player : AnimationPlayer,
+    events : AnimationEvents,
+    // clip that is faded out, with the time the fade has been running and its duration
+    fade_out : Option<(AnimationPlayer, f64, f64)>,
+}
+
+// plays clips on objects and materials, the targets are identified by name
+#[derive(Debug)]
+pub struct AnimationManager{
+    clips : HashMap<CompactString, Arc<AnimationClip>>,
+    targets : HashMap<CompactString, TargetPlayback>,
+    last_frame : Option<time::Instant>,
+    // independent of the physics simulation, applies to every target and armature
+    time_scale : f64,
+    paused : bool,
+}
+
+impl Default for AnimationManager{
+    fn default() -> Self {
+        AnimationManager{clips : HashMap::new(), targets : HashMap::new(), last_frame : None, time_scale : 1.0, paused : false}
+    }
+}
+
+impl AnimationManager{
+    pub fn add_clip(&mut self, clip : AnimationClip){
+        self.clips.insert(clip.name.clone(), Arc::new(clip));
+    }
+    pub fn remove_clip(&mut self, name : &str) -> bool{
+        self.targets.retain(|_, playback| playback.player.clip.as_deref() != Some(name));
+        self.clips.remove(name).is_some()
+    }
+    pub fn get_clip(&self, name : &str) -> Option<Arc<AnimationClip>>{
+        self.clips.get(name).cloned()
+    }
+    pub fn get_clip_names(&self) -> Vec<CompactString>{
+        self.clips.keys().cloned().collect()
+    }
+
+    pub fn play(&mut self, target : &str, clip_name : &str, looping : bool, events : AnimationEvents) -> bool{
+        if !self.clips.contains_key(clip_name) {
+            return false;
+        }
+        let mut player : AnimationPlayer = Default::default();
+        player.play(clip_name, looping);
+        if let Some(old) = self.targets.get(target){
+            player.speed = old.player.speed;
+        }
+        self.targets.insert(target.into(), TargetPlayback{player, events, fade_out : None});
+        true
+    }
+    // blends from the currently playing clip to the new one over the given duration in seconds
+    pub fn crossfade(&mut self, target : &str, clip_name : &str, looping : bool, duration : f64, events : AnimationEvents) -> bool{
+        let old = self.targets.get(target).map(|x| x.player.clone());
+        if !self.play(target, clip_name, looping, events) {
+            return false;
+        }
+        if let Some(old) = old{
+            if duration > 0.0 {
+                self.targets.get_mut(target).unwrap().fade_out = Some((old, 0.0, duration));
+            }
+        }
+        true
+    }
+    pub fn pause(&mut self, target : &str) -> bool{
+        let Some(playback) = self.targets.get_mut(target) else {return false;};
+        playback.player.stop();
+        if let Some((old, _, _)) = playback.fade_out.as_mut(){
+            old.stop();
+        }
+        true
+    }
+    pub fn resume(&mut self, target : &str) -> bool{
+        let Some(playback) = self.targets.get_mut(target) else {return false;};
+        playback.player.resume();
+        if let Some((old, _, _)) = playback.fade_out.as_mut(){
+            old.resume();
+        }
+        true
+    }
+    pub fn stop(&mut self, target : &str) -> bool{
+        self.targets.remove(target).is_some()
+    }
+    pub fn set_speed(&mut self, target : &str, speed : f64) -> bool{
+        let Some(playback) = self.targets.get_mut(target) else {return false;};
+        playback.player.speed = speed;
+        true
+    }
+    pub fn get_player(&self, target : &str) -> Option<AnimationPlayer>{
+        Some(self.targets.get(target)?.player.clone())
+    }
+
+    pub fn set_paused(&mut self, paused : bool){
+        self.paused = paused;
+    }
+    pub fn is_paused(&self) -> bool{
+        self.paused
+    }
+    pub fn set_time_scale(&mut self, time_scale : f64){
+        self.time_scale = time_scale;
+    }
+    pub fn get_time_scale(&self) -> f64{
+        self.time_scale
+    }
+
+    // real time since the last call multiplied by the time scale, nothing passes while the animations are paused
+    pub fn get_frame_delta(&mut self) -> f64{
+        let now = time::Instant::now();
+        let elapsed = match self.last_frame.replace(now){
+            Some(before) => (now - before).as_secs_f64(),
+            None => 0.0
+        };
+        if self.paused {0.0} else {elapsed*self.time_scale}
+    }
+
+    // advances every target by the frame time in seconds,
+    // returns the sampled values of every animated target and the events that have to be broadcasted
+    pub fn update(&mut self, elapsed : f64) -> (Vec<(CompactString, AnimatedValues)>, Vec<usize>){
+        let mut values = vec![];
+        let mut events = vec![];
+        let mut finished = vec![];
+        let clips = &self.clips;
+        let get_duration = |name : &str| clips.get(name).map(|x| x.duration);
+
+        for (target, playback) in self.targets.iter_mut(){
+            let Some(clip) = playback.player.clip.as_ref().and_then(|x| clips.get(x)) else {continue;};
+            let Some(step) = playback.player.advance(elapsed, get_duration) else {continue;};
+
+            for (time, marker) in &clip.markers{
+                if step.has_passed(*time) {
+                    events.extend(playback.events.markers.get(marker));
+                }
+            }
+            if step.looped || step.finished {
+                events.push(playback.events.end);
+            }
+
+            let mut sampled = clip.sample_properties(step.time);
+            if let Some((old, fade_time, duration)) = playback.fade_out.as_mut(){
+                *fade_time += elapsed;
+                let factor = *fade_time / *duration;
+                let old_clip = old.clip.as_ref().and_then(|x| clips.get(x));
+                match (factor < 1.0, old_clip){
+                    (true, Some(old_clip)) => {
+                        old.advance(elapsed, get_duration);
+                        let old_sampled = old_clip.sample_properties(old.time);
+                        for (property, value) in sampled.iter_mut(){
+                            if let Some((_, old_value)) = old_sampled.iter().find(|x| x.0 == *property){
+                                *value = property.blend(old_value, value, factor);
+                            }
+                        }
+                    }
+                    _ => {playback.fade_out = None;}
+                }
+            }
+            if step.finished && playback.fade_out.is_none() {
+                finished.push(target.clone());
+            }
+            values.push((target.clone(), sampled));
+        }
+        for target in finished{
+            self.targets.remove(&target);
+        }
+        events.retain(|x| *x != 0);
+        (values, events)
+    }
+}
+
+#[cfg(test)]
+pub mod animationtest{
+    use super::*;
+
+    fn assert_close(a : &[f64], b : &[f64]){
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9), "{:?} != {:?}", a, b);
+    }
+
+    fn clip(name : &str, values : &[f64]) -> AnimationClip{
+        let mut output = AnimationClip::new(name);
+        output.add_property_channel(PropertyChannel::new(AnimatedProperty::LightIntensity, Interpolation::Linear, &[0.0, 1.0], values).unwrap());
+        output
+    }
+
+    #[test]
+    fn property_interpolation(){
+        assert!(PropertyChannel::new(AnimatedProperty::Position, Interpolation::Linear, &[0.0, 1.0], &[0.0; 5]).is_none());
+        assert!(PropertyChannel::new(AnimatedProperty::Position, Interpolation::Linear, &[], &[]).is_none());
+
+        // keyframes are sorted by time
+        let times = [2.0, 0.0, 1.0];
+        let values = [4.0, 0.0, 2.0];
+        let linear = PropertyChannel::new(AnimatedProperty::LightIntensity, Interpolation::Linear, &times, &values).unwrap();
+        assert_eq!(linear.duration(), 2.0);
+        assert_close(&linear.sample(-1.0), &[0.0; 4]);
+        assert_close(&linear.sample(0.25), &[0.5, 0.0, 0.0, 0.0]);
+        assert_close(&linear.sample(1.5), &[3.0, 0.0, 0.0, 0.0]);
+        assert_close(&linear.sample(3.0), &[4.0, 0.0, 0.0, 0.0]);
+
+        let step = PropertyChannel::new(AnimatedProperty::LightIntensity, Interpolation::Step, &times, &values).unwrap();
+        assert_close(&step.sample(0.99), &[0.0; 4]);
+        assert_close(&step.sample(1.5), &[2.0, 0.0, 0.0, 0.0]);
+
+        // evenly spaced keyframes on a line stay on it, and every keyframe is hit
+        let cubic = PropertyChannel::new(AnimatedProperty::LightIntensity, Interpolation::Cubic, &times, &values).unwrap();
+        for time in [0.0, 0.3, 1.0, 1.7, 2.0]{
+            assert_close(&cubic.sample(time)[..1], &[time*2.0]);
+        }
+    }
+
+    #[test]
+    fn rotation_interpolation(){
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        // the second keyframe is a 90 degree rotation around z given in the other hemisphere
+        let channel = PropertyChannel::new(AnimatedProperty::Rotation, Interpolation::Linear, &[0.0, 1.0], &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, -half, -half]).unwrap();
+        let angle = std::f64::consts::FRAC_PI_8;
+        assert_close(&channel.sample(0.5), &[0.0, 0.0, angle.sin(), angle.cos()]);
+
+        let bone = BoneChannel::new("bone", vec![
+            BoneKeyframe{time : 1.0, transform : BoneTransform{pos : [2.0, 0.0, 0.0], ..Default::default()}},
+            BoneKeyframe{time : 0.0, transform : Default::default()},
+        ]);
+        assert_eq!(bone.duration(), 1.0);
+        assert_close(&bone.sample(0.25).unwrap().pos, &[0.5, 0.0, 0.0]);
+        assert_close(&bone.sample(2.0).unwrap().pos, &[2.0, 0.0, 0.0]);
+        assert!(BoneChannel::new("bone", vec![]).sample(0.0).is_none());
+    }
+
+    #[test]
+    fn playback(){
+        let get_duration = |_ : &str| Some(2.0);
+        let mut player : AnimationPlayer = Default::default();
+        assert!(player.advance(1.0, get_duration).is_none());
+
+        player.play("clip", true);
+        let step = player.advance(1.5, get_duration).unwrap();
+        assert!(!step.looped && step.has_passed(1.0) && !step.has_passed(1.6));
+        let step = player.advance(1.0, get_duration).unwrap();
+        assert!(step.looped && (step.time - 0.5).abs() < 1e-12);
+        assert!(step.has_passed(1.8) && step.has_passed(0.2) && !step.has_passed(1.0));
+
+        player.stop();
+        assert!(player.advance(1.0, get_duration).is_none());
+        player.resume();
+        player.looping = false;
+        player.speed = -1.0;
+        let step = player.advance(1.0, get_duration).unwrap();
+        assert!(step.finished && step.time == 0.0 && !player.playing);
+    }
+
+    #[test]
+    fn crossfade(){
+        let mut manager : AnimationManager = Default::default();
+        manager.add_clip(clip("low", &[0.0, 0.0]));
+        manager.add_clip(clip("high", &[1.0, 1.0]));
+        assert!(!manager.crossfade("light", "missing", true, 1.0, Default::default()));
+        assert!(manager.play("light", "low", true, Default::default()));
+        assert!(manager.crossfade("light", "high", true, 2.0, Default::default()));
+
+        let sample = |manager : &mut AnimationManager, elapsed : f64| manager.update(elapsed).0[0].1[0].1[0];
+        assert_eq!(sample(&mut manager, 0.5), 0.25);
+        // the blend does not progress while the target is paused
+        manager.pause("light");
+        assert!(manager.update(10.0).0.is_empty());
+        manager.resume("light");
+        assert_eq!(sample(&mut manager, 0.5), 0.5);
+        assert_eq!(sample(&mut manager, 1.0), 1.0);
+        assert_eq!(sample(&mut manager, 0.1), 1.0);
+
+        // nothing passes while the animations are paused
+        assert!(!manager.is_paused() && manager.get_time_scale() == 1.0);
+        manager.get_frame_delta();
+        manager.set_paused(true);
+        assert_eq!(manager.get_frame_delta(), 0.0);
+        manager.set_paused(false);
+        manager.set_time_scale(0.0);
+        assert_eq!(manager.get_frame_delta(), 0.0);
+    }
+}
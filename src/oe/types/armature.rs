@@ -0,0 +1,394 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+use compact_str::CompactString;
+use nalgebra_glm as glm;
+
+use super::animation::*;
+use super::polygonstorage::SoftbodyPolygonStorage;
+use crate::oe::math::Mat4x4;
+
+pub const MAX_BONE_INFLUENCES : usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoneTransform{
+    pub pos : [f64; 3],
+    pub rot : [f64; 4],
+    pub sca : [f64; 3],
+}
+
+impl Default for BoneTransform{
+    fn default() -> Self {
+        BoneTransform{pos : [0.0; 3], rot : [0.0, 0.0, 0.0, 1.0], sca : [1.0; 3]}
+    }
+}
+
+impl BoneTransform{
+    // same layout as current_state in CSL files
+    pub fn from_state(state : &[f64]) -> Option<BoneTransform>{
+        if state.len() < 10 {
+            return None;
+        }
+        Some(BoneTransform{
+            pos : [state[0], state[1], state[2]],
+            rot : [state[3], state[4], state[5], state[6]],
+            sca : [state[7], state[8], state[9]],
+        })
+    }
+
+    pub fn to_state(&self) -> [f64; 10]{
+        [self.pos[0], self.pos[1], self.pos[2], self.rot[0], self.rot[1], self.rot[2], self.rot[3], self.sca[0], self.sca[1], self.sca[2]]
+    }
+
+    pub fn to_mat(&self) -> glm::DMat4{
+        let rot = glm::quat_normalize(&glm::make_quat(&self.rot));
+        glm::translation(&glm::make_vec3(&self.pos)) * glm::quat_to_mat4(&rot) * glm::scaling(&glm::make_vec3(&self.sca))
+    }
+
+    pub fn interpolate(&self, other : &BoneTransform, factor : f64) -> BoneTransform{
+        let lerp = |a : f64, b : f64| a + (b - a)*factor;
+        let rot_a = glm::quat_normalize(&glm::make_quat(&self.rot));
+        let mut rot_b = glm::quat_normalize(&glm::make_quat(&other.rot));
+        // take the shortest path
+        if glm::quat_dot(&rot_a, &rot_b) < 0.0 {
+            rot_b = -rot_b;
+        }
+        let rot = glm::quat_slerp(&rot_a, &rot_b, factor);
+        BoneTransform{
+            pos : [lerp(self.pos[0], other.pos[0]), lerp(self.pos[1], other.pos[1]), lerp(self.pos[2], other.pos[2])],
+            rot : [rot.coords.x, rot.coords.y, rot.coords.z, rot.coords.w],
+            sca : [lerp(self.sca[0], other.sca[0]), lerp(self.sca[1], other.sca[1]), lerp(self.sca[2], other.sca[2])],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Bone{
+    pub name : CompactString,
+    // parents always come before their children
+    pub parent : Option<usize>,
+    // relative to the parent bone
+    pub rest : BoneTransform,
+    inverse_bind : glm::DMat4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SkinningMode{
+    // the vertex buffer is rewritten on every pose change
+    #[default]
+    Cpu,
+    // only the bone matrices are sent to the renderer
+    Gpu,
+}
+
+// per vertex data that stays the same after loading, shared between all copies of the armature
+#[derive(Clone, Debug, Default)]
+pub struct SkinData{
+    // per position
+    pub bone_ids : Vec<[u16; MAX_BONE_INFLUENCES]>,
+    pub weights : Vec<[f32; MAX_BONE_INFLUENCES]>,
+
+    // captured from the polygon storage when binding
+    pub rest_positions : Vec<f32>,
+    pub rest_normals : Vec<f32>,
+    normal_positions : Vec<u32>,
+    vertex_positions : Vec<u32>,
+}
+
+impl SkinData{
+    // keeps the strongest influences, the position has to be below the position count
+    fn add_weight(&mut self, position : usize, bone_id : u16, weight : f32){
+        let weights = &mut self.weights[position];
+        let (weakest, weakest_weight) = weights.iter().enumerate().fold((0, f32::MAX), |acc, (id, w)| if *w < acc.1 {(id, *w)} else {acc});
+        if weight > weakest_weight {
+            weights[weakest] = weight;
+            self.bone_ids[position][weakest] = bone_id;
+        }
+    }
+
+    fn normalize(&mut self){
+        for weights in self.weights.iter_mut(){
+            let sum : f32 = weights.iter().sum();
+            if sum > 0.0 {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            }
+        }
+    }
+
+    // 4 bone ids followed by 4 weights for every vertex in the vertex buffer
+    pub fn get_vertex_data(&self) -> Vec<f32>{
+        let mut output = Vec::with_capacity(self.vertex_positions.len()*MAX_BONE_INFLUENCES*2);
+        for position in &self.vertex_positions{
+            let position = *position as usize;
+            if position < self.weights.len(){
+                output.extend(self.bone_ids[position].iter().map(|x| *x as f32));
+                output.extend_from_slice(&self.weights[position]);
+            }
+            else {
+                output.extend_from_slice(&[0.0; MAX_BONE_INFLUENCES*2]);
+            }
+        }
+        output
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Armature{
+    pub name : CompactString,
+    bones : Vec<Bone>,
+    bone_ids : HashMap<CompactString, usize>,
+    // current local transforms, relative to the parent bone
+    pose : Vec<BoneTransform>,
+    pose_changed : bool,
+    pub skin : Arc<SkinData>,
+    pub skinning_mode : SkinningMode,
+
+    pub clips : HashMap<CompactString, Arc<AnimationClip>>,
+    pub player : AnimationPlayer,
+}
+
+impl Armature{
+    pub fn new(name : &str) -> Armature{
+        Armature{
+            name : name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_bone(&mut self, name : &str, parent : Option<&str>, rest : BoneTransform) -> Option<usize>{
+        if self.bone_ids.contains_key(name) {
+            return None;
+        }
+        let parent = match parent{
+            Some(parent_name) => Some(*self.bone_ids.get(parent_name)?),
+            None => None
+        };
+        let parent_mat = parent.map(|id| glm::inverse(&self.bones[id].inverse_bind)).unwrap_or(glm::DMat4::identity());
+        let id = self.bones.len();
+        self.bones.push(Bone{
+            name : name.into(),
+            parent,
+            rest,
+            inverse_bind : glm::inverse(&(parent_mat * rest.to_mat())),
+        });
+        self.bone_ids.insert(name.into(), id);
+        self.pose.push(rest);
+        self.pose_changed = true;
+        Some(id)
+    }
+
+    // weights can only be added to positions below the count, existing weights are kept
+    pub fn set_position_count(&mut self, count : usize){
+        let skin = Arc::make_mut(&mut self.skin);
+        skin.bone_ids.resize(count, [0; MAX_BONE_INFLUENCES]);
+        skin.weights.resize(count, [0.0; MAX_BONE_INFLUENCES]);
+    }
+
+    // nothing is added if the bone is unknown, the lengths differ or a vertex is out of range
+    pub fn add_weights(&mut self, bone_name : &str, vertices : &[u32], weights : &[f32]) -> Result<(), String>{
+        let bone_id = *self.bone_ids.get(bone_name).ok_or_else(|| format!("Armature '{}' has no bone named '{}'.", self.name, bone_name))?;
+        let bone_id = u16::try_from(bone_id).map_err(|_| format!("Armature '{}' has more than {} bones.", self.name, u16::MAX as usize + 1))?;
+        if vertices.len() != weights.len() {
+            return Err(format!("Bone '{}' of armature '{}' has {} vertices but {} weights.", bone_name, self.name, vertices.len(), weights.len()));
+        }
+        let position_count = self.skin.weights.len();
+        if let Some(vertex) = vertices.iter().find(|x| **x as usize >= position_count) {
+            return Err(format!("Bone '{}' of armature '{}' has the vertex {} but there are only {} vertices.", bone_name, self.name, vertex, position_count));
+        }
+        let skin = Arc::make_mut(&mut self.skin);
+        for (vertex, weight) in vertices.iter().zip(weights){
+            skin.add_weight(*vertex as usize, bone_id, *weight);
+        }
+        Ok(())
+    }
+
+    // captures the rest geometry, has to be called after all weights are added
+    pub fn bind(&mut self, storage : &SoftbodyPolygonStorage){
+        let skin = Arc::make_mut(&mut self.skin);
+        skin.normalize();
+        skin.rest_positions = storage.positions.clone();
+        skin.rest_normals = storage.normals.clone();
+        skin.normal_positions = storage.get_normal_positions();
+        skin.vertex_positions = storage.get_vertex_positions();
+        self.pose_changed = true;
+    }
+
+    pub fn get_bone_id(&self, name : &str) -> Option<usize>{
+        self.bone_ids.get(name).copied()
+    }
+    pub fn get_bones(&self) -> &Vec<Bone>{
+        &self.bones
+    }
+    pub fn num_of_bones(&self) -> usize{
+        self.bones.len()
+    }
+
+    pub fn get_bone_pose(&self, name : &str) -> Option<BoneTransform>{
+        Some(self.pose[self.get_bone_id(name)?])
+    }
+    pub fn set_bone_pose(&mut self, name : &str, transform : BoneTransform) -> bool{
+        let Some(id) = self.get_bone_id(name) else {return false;};
+        self.pose[id] = transform;
+        self.pose_changed = true;
+        true
+    }
+    pub fn reset_pose(&mut self){
+        for (bone, pose) in self.bones.iter().zip(self.pose.iter_mut()){
+            *pose = bone.rest;
+        }
+        self.pose_changed = true;
+    }
+
+    pub fn add_clip(&mut self, clip : AnimationClip){
+        self.clips.insert(clip.name.clone(), Arc::new(clip));
+    }
+    pub fn play(&mut self, clip_name : &str, looping : bool) -> bool{
+        if !self.clips.contains_key(clip_name) {
+            return false;
+        }
+        self.player.play(clip_name, looping);
+        true
+    }
+    pub fn stop(&mut self){
+        self.player.stop();
+    }
+
+    // advances the animation by the frame time in seconds
+    pub fn advance(&mut self, elapsed : f64){
+        let time = self.player.advance(elapsed, |name| self.clips.get(name).map(|x| x.duration)).map(|x| x.time);
+        if let (Some(time), Some(clip_name)) = (time, self.player.clip.as_ref()){
+            let clip = Arc::clone(&self.clips[clip_name]);
+            for channel in &clip.bone_channels{
+                let Some(id) = self.bone_ids.get(&channel.bone) else {continue;};
+                if let Some(transform) = channel.sample(time){
+                    self.pose[*id] = transform;
+                    self.pose_changed = true;
+                }
+            }
+        }
+    }
+    // returns true if the pose changed since the last update
+    pub fn update(&mut self) -> bool{
+        std::mem::replace(&mut self.pose_changed, false)
+    }
+
+    // transforms from bind space to posed space for every bone
+    pub fn get_skinning_matrices(&self) -> Vec<glm::DMat4>{
+        let mut global : Vec<glm::DMat4> = Vec::with_capacity(self.bones.len());
+        for (id, bone) in self.bones.iter().enumerate(){
+            let local = self.pose[id].to_mat();
+            global.push(match bone.parent{
+                Some(parent) => global[parent] * local,
+                None => local
+            });
+        }
+        global.iter().zip(self.bones.iter()).map(|(mat, bone)| mat * bone.inverse_bind).collect()
+    }
+
+    pub fn get_render_bone_mats(&self) -> Vec<Mat4x4>{
+        self.get_skinning_matrices().iter().map(|mat| {
+            let mat : glm::Mat4 = glm::convert(*mat);
+            Mat4x4::new(&glm::value_ptr(&mat).try_into().unwrap())
+        }).collect()
+    }
+
+    // the source geometry is usually the rest geometry of the skin, or a morphed copy of it
+    pub fn skin_vertices(&self, source_positions : &[f32], source_normals : &[f32], positions : &mut [f32], normals : &mut [f32]){
+        let matrices = self.get_skinning_matrices();
+        let skin = &self.skin;
+        let blend = |position : usize| -> Option<glm::DMat4>{
+            if position >= skin.weights.len() || skin.weights[position].iter().all(|w| *w == 0.0) {
+                return None;
+            }
+            let mut output = glm::DMat4::zeros();
+            for (bone_id, weight) in skin.bone_ids[position].iter().zip(skin.weights[position].iter()){
+                if *weight > 0.0 {
+                    output += matrices[*bone_id as usize] * (*weight as f64);
+                }
+            }
+            Some(output)
+        };
+
+        for (position, rest) in source_positions.chunks(3).enumerate(){
+            let Some(mat) = blend(position) else {
+                positions[position*3..position*3+3].copy_from_slice(rest);
+                continue;
+            };
+            let skinned = mat * glm::DVec4::new(rest[0] as f64, rest[1] as f64, rest[2] as f64, 1.0);
+            positions[position*3] = skinned.x as f32;
+            positions[position*3+1] = skinned.y as f32;
+            positions[position*3+2] = skinned.z as f32;
+        }
+        for (normal, rest) in source_normals.chunks(3).enumerate(){
+            let Some(mat) = skin.normal_positions.get(normal).and_then(|x| blend(*x as usize)) else {
+                normals[normal*3..normal*3+3].copy_from_slice(rest);
+                continue;
+            };
+            let skinned = glm::normalize(&(glm::mat4_to_mat3(&mat) * glm::DVec3::new(rest[0] as f64, rest[1] as f64, rest[2] as f64)));
+            normals[normal*3] = skinned.x as f32;
+            normals[normal*3+1] = skinned.y as f32;
+            normals[normal*3+2] = skinned.z as f32;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod armaturetest{
+    use super::*;
+
+    fn transform(pos : [f64; 3], rot : [f64; 4]) -> BoneTransform{
+        BoneTransform{pos, rot, ..Default::default()}
+    }
+    fn apply(mat : &glm::DMat4, point : [f64; 3]) -> glm::DVec3{
+        (mat * glm::DVec4::new(point[0], point[1], point[2], 1.0)).xyz()
+    }
+
+    #[test]
+    fn bone_hierarchy(){
+        let mut armature = Armature::new("arm");
+        assert_eq!(armature.add_bone("root", None, transform([0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0])), Some(0));
+        assert_eq!(armature.add_bone("child", Some("root"), transform([0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0])), Some(1));
+        assert_eq!(armature.add_bone("orphan", Some("missing"), Default::default()), None);
+        assert_eq!(armature.add_bone("child", Some("root"), Default::default()), None);
+        assert_eq!(armature.get_bones()[1].parent, Some(0));
+
+        // the rest pose does not move anything
+        for mat in armature.get_skinning_matrices(){
+            assert!((mat - glm::DMat4::identity()).abs().max() < 1e-12);
+        }
+
+        // rotating the root by 90 degrees around z carries the child with it
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert!(armature.set_bone_pose("root", transform([0.0, 1.0, 0.0], [0.0, 0.0, half, half])));
+        let matrices = armature.get_skinning_matrices();
+        assert!(glm::distance(&apply(&matrices[1], [0.0, 2.0, 0.0]), &glm::DVec3::new(-1.0, 1.0, 0.0)) < 1e-12);
+        assert!(glm::distance(&apply(&matrices[1], [0.0, 3.0, 0.0]), &glm::DVec3::new(-2.0, 1.0, 0.0)) < 1e-12);
+        assert!(glm::distance(&apply(&matrices[0], [0.0, 1.0, 0.0]), &glm::DVec3::new(0.0, 1.0, 0.0)) < 1e-12);
+
+        armature.reset_pose();
+        assert_eq!(armature.get_bone_pose("root"), Some(transform([0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0])));
+    }
+
+    #[test]
+    fn skinning(){
+        let mut armature = Armature::new("arm");
+        armature.add_bone("root", None, Default::default());
+        armature.add_bone("child", Some("root"), transform([0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]));
+        // vertex 0 follows the child, vertex 1 both bones by half, vertex 2 has no weights
+        armature.set_position_count(3);
+        assert!(armature.add_weights("child", &[0, 1], &[1.0, 0.5]).is_ok());
+        assert!(armature.add_weights("root", &[1], &[0.5]).is_ok());
+        assert!(armature.add_weights("missing", &[2], &[1.0]).is_err());
+        // invalid weights are rejected without growing the skin
+        assert!(armature.add_weights("root", &[2, u32::MAX], &[1.0, 1.0]).unwrap_err().contains("4294967295"));
+        assert!(armature.add_weights("root", &[2], &[1.0, 1.0]).unwrap_err().contains("1 vertices but 2 weights"));
+        assert_eq!((armature.skin.weights.len(), armature.skin.weights[2]), (3, [0.0; MAX_BONE_INFLUENCES]));
+
+        armature.set_bone_pose("child", transform([2.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]));
+        let rest = [0.0, 2.0, 0.0, 0.0, 2.0, 0.0, 5.0, 5.0, 5.0];
+        let mut positions = [0.0; 9];
+        armature.skin_vertices(&rest, &[0.0, 1.0, 0.0], &mut positions, &mut [0.0; 3]);
+        let expected = [2.0, 2.0, 0.0, 1.0, 2.0, 0.0, 5.0, 5.0, 5.0];
+        assert!(positions.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", positions);
+    }
+}
@@ -29,6 +29,7 @@ impl Camera {
 
 impl ObjectTrait for Camera {
     fn get_camera(&self) -> Option<Camera> {Some(self.clone())}
+    fn get_camera_mut(&mut self) -> Option<&mut Camera> {Some(self)}
     fn get_light(&self) -> Option<Light> {None}
     fn get_mesh(&self) -> Option<Mesh> {None}
     fn get_mesh_mut(&mut self) -> Option<&mut Mesh> {None}
@@ -38,7 +39,7 @@ impl ObjectTrait for Camera {
     fn get_data_mut(&mut self) -> &mut CommonObjectData {
         &mut self.data_
     }
-    fn update(&mut self){
-        
+    fn update(&mut self) -> bool{
+        false
     }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use nohash_hasher::IntMap;
+
+use super::polygonstoragetrait::PolygonStorageData;
+
+// number of vertices a 16 bit index can address
+pub const MAX_16BIT_VERTICES : usize = 65536;
+
+// part of a vertex group that can be drawn with 16 bit indices
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DrawRange{
+    // into the 16 bit index buffer of the vertex group
+    pub first_index : u32,
+    pub num_of_indices : u32,
+    // window of the vertex buffer the indices are relative to
+    pub first_vertex : u32,
+    pub num_of_vertices : u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct IndexData16Bit{
+    // vertices whose triangles do not fit into any window of the vertex buffer, copies of them belong after the vertex buffer in this order
+    pub copied_vertices : Vec<u32>,
+    // per level of detail, level 0 is the full mesh, and per vertex group
+    pub index_buffers : Vec<IntMap<usize, Vec<u16>>>,
+    pub draw_ranges : Vec<IntMap<usize, Vec<DrawRange>>>,
+}
+
+fn push_window(indices : &[u32], first_vertex : u32, last_vertex : u32, output : &mut Vec<u16>, ranges : &mut Vec<DrawRange>){
+    if indices.is_empty() {
+        return;
+    }
+    ranges.push(DrawRange{
+        first_index : output.len() as u32,
+        num_of_indices : indices.len() as u32,
+        first_vertex,
+        num_of_vertices : last_vertex - first_vertex + 1,
+    });
+    output.extend(indices.iter().map(|x| (x - first_vertex) as u16));
+}
+
+// triangles are kept in order as long as they fit into a window of the vertex buffer, vertices ordered by first use keep the windows small
+fn split_index_buffer(indices : &[u32], num_of_vertices : usize, max_vertices : usize, copied_vertices : &mut Vec<u32>) -> (Vec<u16>, Vec<DrawRange>){
+    let mut output = Vec::with_capacity(indices.len());
+    let mut ranges = vec![];
+    let mut window : Vec<u32> = vec![];
+    let mut copied : Vec<u32> = vec![];
+    let (mut min, mut max) = (u32::MAX, 0);
+    for tri in indices.chunks(3){
+        let (tri_min, tri_max) = (*tri.iter().min().unwrap(), *tri.iter().max().unwrap());
+        if (tri_max - tri_min) as usize >= max_vertices {
+            copied.extend_from_slice(tri);
+            continue;
+        }
+        if (max.max(tri_max) - min.min(tri_min)) as usize >= max_vertices {
+            push_window(&window, min, max, &mut output, &mut ranges);
+            window.clear();
+            (min, max) = (tri_min, tri_max);
+        }
+        else {
+            (min, max) = (min.min(tri_min), max.max(tri_max));
+        }
+        window.extend_from_slice(tri);
+    }
+    push_window(&window, min, max, &mut output, &mut ranges);
+
+    // the remaining triangles get windows of copied vertices
+    window.clear();
+    let mut local_ids : HashMap<u32, u32> = HashMap::new();
+    let next_vertex = |copied_vertices : &Vec<u32>| (num_of_vertices + copied_vertices.len()) as u32;
+    let mut first_vertex = next_vertex(copied_vertices);
+    for tri in copied.chunks(3){
+        if local_ids.len() + 3 > max_vertices {
+            push_window(&window, first_vertex, next_vertex(copied_vertices) - 1, &mut output, &mut ranges);
+            window.clear();
+            local_ids.clear();
+            first_vertex = next_vertex(copied_vertices);
+        }
+        for vertex in tri{
+            let new_id = next_vertex(copied_vertices);
+            let id = *local_ids.entry(*vertex).or_insert_with(|| {
+                copied_vertices.push(*vertex);
+                new_id
+            });
+            window.push(id);
+        }
+    }
+    if !window.is_empty() {
+        push_window(&window, first_vertex, next_vertex(copied_vertices) - 1, &mut output, &mut ranges);
+    }
+    (output, ranges)
+}
+
+// splits the index buffers of all vertex groups and levels of detail for targets without 32 bit indices
+pub fn split_16bit(data : &PolygonStorageData, num_of_vertices : usize, max_vertices : usize) -> IndexData16Bit{
+    let max_vertices = max_vertices.clamp(3, MAX_16BIT_VERTICES);
+    let mut output = IndexData16Bit::default();
+    let levels = std::iter::once(&data.index_buffers_).chain(data.lods.iter().map(|x| &x.index_buffers));
+    for level in levels{
+        let mut vgroup_ids : Vec<usize> = level.keys().copied().collect();
+        vgroup_ids.sort();
+        let mut index_buffers : IntMap<usize, Vec<u16>> = Default::default();
+        let mut draw_ranges : IntMap<usize, Vec<DrawRange>> = Default::default();
+        for id in vgroup_ids{
+            let (indices, ranges) = split_index_buffer(&level[&id], num_of_vertices, max_vertices, &mut output.copied_vertices);
+            index_buffers.insert(id, indices);
+            draw_ranges.insert(id, ranges);
+        }
+        output.index_buffers.push(index_buffers);
+        output.draw_ranges.push(draw_ranges);
+    }
+    output
+}
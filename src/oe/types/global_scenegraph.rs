@@ -1,5 +1,5 @@
 use std::sync::{Arc, Mutex, LazyLock};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
     use std::iter::Iterator;
 
 use compact_str::CompactString;
@@ -9,17 +9,33 @@ use super::object_trait::*;
 use super::world::*;
 use super::scene::*;
 use super::material::*;
+use super::texture::*;
 use super::viewport::*;
+use super::animation::*;
 //use super::polygonstoragetrait::*;
 use super::basecontainer::*;
 use super::super::carbon::interpreter::Interpreter;
 use super::elementcontainer::*;
 use super::globalscenegraphchanged::*;
 use super::globalscenegraphpending::*;
+use super::prefab::*;
+use super::mesh::Mesh;
+use super::snapshot::*;
+use super::armature::BoneTransform;
+use super::transaction::*;
+use super::worlddiff::*;
 
 pub type InterpreterElementWrapper<T> = BaseContainer<Arc<SingleElement<T>>>;
 type GlobalVar<T> = LazyLock<Arc<Mutex<T>>>;
 
+// changes of the world that are applied at the start of the next frame
+#[derive(Debug)]
+enum WorldChange{
+    LoadedScene(CompactString),
+    LoadedViewport(CompactString),
+    Unload(CompactString),
+}
+
 
 #[derive(Default)]
 pub struct GlobalScenegraph{
@@ -29,16 +45,40 @@ pub struct GlobalScenegraph{
     //polygons_  : ElementContainer<Box<dyn PolygonStorageTrait>>,
     materials_ : ElementContainer<Material>,
     viewports_ : ElementContainer<ViewPort>,
+    textures_  : ElementContainer<Texture>,
 
     object2viewport      : HashMultiMap<CompactString, CompactString>,
     object2scene         : HashMultiMap<CompactString, CompactString>,
     object2object        : HashMultiMap<CompactString, CompactString>,
     material2scene       : HashMultiMap<CompactString, CompactString>,
     material2vertexgroup : HashMultiMap<CompactString, VertexGroupMeshKey>,
+    texture2material     : HashMultiMap<CompactString, CompactString>,
+
+    pub animations : AnimationManager,
+
+    prefabs_          : HashMap<CompactString, Prefab>,
+    prefab_instances_ : HashMap<CompactString, PrefabInstance>,
+
+    // placeholder meshes by the name of the mesh they are an instance of, resolved once it is added
+    unresolved_instances_ : HashMultiMap<CompactString, CompactString>,
+
+    // elements of every loaded file by file name
+    worlds_ : HashMap<CompactString, WorldElements>,
+    // file that defined the current world, unloading it removes the world
+    world_source_ : CompactString,
+
+    // saved and restored with snapshots
+    user_data_ : HashMap<CompactString, CompactString>,
+
+    // transactions with the edits that revert them
+    undo_stack_ : Vec<Transaction>,
+    redo_stack_ : Vec<Transaction>,
 
     pending_events        : Vec<usize>,
     pending_elements      : GlobalScenegraphPending,
     pending_interpreters_ : Vec<(Interpreter, usize)>,
+    pending_world_changes_ : Vec<(WorldChange, usize)>,
+    pending_reloads_       : Vec<(Interpreter, usize)>,
 }
 
 impl GlobalScenegraph{
@@ -48,6 +88,305 @@ impl GlobalScenegraph{
     pub fn get_object(&self, id : usize) -> Arc<SingleElement<Box<dyn ObjectTrait>>>{
         self.objects_[id].clone()
     }
+    pub fn get_object_by_name(&self, name : &str) -> Option<Arc<SingleElement<Box<dyn ObjectTrait>>>>{
+        Some(self.objects_[self.objects_.get_id(name)?].clone())
+    }
+    pub fn get_material_by_name(&self, name : &str) -> Option<Arc<SingleElement<Material>>>{
+        Some(self.materials_[self.materials_.get_id(name)?].clone())
+    }
+    pub fn get_texture_by_name(&self, name : &str) -> Option<Arc<SingleElement<Texture>>>{
+        Some(self.textures_[self.textures_.get_id(name)?].clone())
+    }
+    pub fn has_scene(&self, name : &str) -> bool{
+        self.scenes_.contains_name(name)
+    }
+
+    // elements created on the fly are added at the start of the next frame
+    pub fn has_viewport(&self, name : &str) -> bool{
+        self.viewports_.contains_name(name)
+    }
+    pub fn has_world(&self, name : &str) -> bool{
+        self.worlds_.contains_key(name)
+    }
+    // interpreted by a worker, it is added on the next update
+    pub fn has_pending_world(&self, name : &str) -> bool{
+        self.pending_interpreters_.iter().any(|(x, _)| x.get_source() == name)
+    }
+    pub fn get_world_files(&self) -> Vec<CompactString>{
+        self.worlds_.keys().cloned().collect()
+    }
+    pub fn get_world(&self) -> Option<&World>{
+        self.world_.as_ref()
+    }
+
+    // the event is emitted once the change is applied at the start of the next frame
+    pub fn set_loaded_scene(&mut self, name : &str, event : usize) -> bool{
+        if self.world_.is_none() || !self.has_scene(name) {
+            return false;
+        }
+        self.pending_world_changes_.push((WorldChange::LoadedScene(name.into()), event));
+        true
+    }
+    pub fn set_loaded_viewport(&mut self, name : &str, event : usize) -> bool{
+        if self.world_.is_none() || !self.has_viewport(name) {
+            return false;
+        }
+        self.pending_world_changes_.push((WorldChange::LoadedViewport(name.into()), event));
+        true
+    }
+    pub fn unload_world(&mut self, name : &str, event : usize) -> bool{
+        if !self.has_world(name) {
+            return false;
+        }
+        self.pending_world_changes_.push((WorldChange::Unload(name.into()), event));
+        true
+    }
+
+    pub fn set_user_data(&mut self, key : &str, value : &str){
+        self.user_data_.insert(key.into(), value.into());
+    }
+    pub fn get_user_data(&self, key : &str) -> Option<&CompactString>{
+        self.user_data_.get(key)
+    }
+    pub fn remove_user_data(&mut self, key : &str) -> bool{
+        self.user_data_.remove(key).is_some()
+    }
+
+    // elements are sorted by name, so the same state always gives the same file
+    pub fn take_snapshot(&self) -> Snapshot{
+        let mut output = Snapshot{
+            worlds : self.worlds_.keys().cloned().collect(),
+            loaded_scene : self.world_.as_ref().map(|x| x.loaded_scene.clone()),
+            loaded_viewport : self.world_.as_ref().map(|x| x.loaded_viewport.clone()),
+            user_data : self.user_data_.iter().map(|(a, b)| (a.clone(), b.clone())).collect(),
+            ..Default::default()
+        };
+        output.worlds.sort();
+        output.user_data.sort();
+
+        let mut object_names = self.objects_.names();
+        object_names.sort();
+        for name in object_names{
+            let object = self.objects_[self.objects_.get_id(&name).unwrap()].lock().unwrap();
+            let data = object.0.get_data();
+            output.objects.push((name.clone(), ObjectState{
+                transform : BoneTransform{pos : data.pos, rot : data.rot, sca : data.sca},
+                visible : data.visible,
+            }));
+            if let Some(light) = object.0.get_light(){
+                output.lights.push((name, LightState::new(&light)));
+            }
+        }
+        let mut material_names = self.materials_.names();
+        material_names.sort();
+        for name in material_names{
+            let material = self.materials_[self.materials_.get_id(&name).unwrap()].lock().unwrap();
+            output.materials.push((name.clone(), MaterialState::new(&material.0)));
+        }
+        output
+    }
+    // elements that do not exist anymore are skipped, restored elements are sent to the renderer in the next frame
+    pub fn restore_snapshot(&mut self, snapshot : &Snapshot) -> Result<u8, String>{
+        if let Some(world) = snapshot.worlds.iter().find(|x| !self.worlds_.contains_key(*x)){
+            return Err(String::from("Snapshot needs world: \"") + world + "\" to be loaded.");
+        }
+
+        for (name, state) in &snapshot.objects{
+            let Some(id) = self.objects_.get_id(name) else {continue;};
+            let mut object = self.objects_[id].lock().unwrap();
+            let data = object.0.get_data_mut();
+            (data.pos, data.rot, data.sca) = (state.transform.pos, state.transform.rot, state.transform.sca);
+            data.visible = state.visible;
+            object.1 = true;
+        }
+        for (name, state) in &snapshot.lights{
+            let Some(id) = self.objects_.get_id(name) else {continue;};
+            let mut object = self.objects_[id].lock().unwrap();
+            let Some(light) = object.0.get_light_mut() else {continue;};
+            state.apply(light);
+            object.1 = true;
+        }
+        for (name, state) in &snapshot.materials{
+            let Some(id) = self.materials_.get_id(name) else {continue;};
+            let mut material = self.materials_[id].lock().unwrap();
+            state.apply(&mut material.0);
+            material.1 = true;
+        }
+
+        if let Some(world) = self.world_.as_mut(){
+            if let Some(scene) = snapshot.loaded_scene.as_ref().filter(|x| self.scenes_.contains_name(x)){
+                world.loaded_scene = scene.clone();
+            }
+            if let Some(viewport) = snapshot.loaded_viewport.as_ref().filter(|x| self.viewports_.contains_name(x)){
+                world.loaded_viewport = viewport.clone();
+            }
+        }
+        self.user_data_ = snapshot.user_data.iter().cloned().collect();
+        Ok(5)
+    }
+
+    // TRANSACTIONS
+    // nothing is changed if one of the edits fails, the changes are sent to the renderer in the next frame
+    pub fn apply_transaction(&mut self, transaction : Transaction) -> Result<u8, String>{
+        let reverted = self.apply_edits(transaction.operations)?;
+        self.undo_stack_.push(Transaction{name : transaction.name, operations : reverted});
+        self.redo_stack_.clear();
+        Ok(5)
+    }
+    // returns the name of the reverted transaction, a failed undo keeps the transaction on the undo stack
+    pub fn undo(&mut self) -> Result<CompactString, String>{
+        let transaction = self.undo_stack_.pop().ok_or("Nothing to undo.".to_string())?;
+        match self.apply_edits(transaction.operations.clone()){
+            Ok(reverted) => {
+                self.redo_stack_.push(Transaction{name : transaction.name.clone(), operations : reverted});
+                Ok(transaction.name)
+            }
+            Err(error) => {
+                self.undo_stack_.push(transaction);
+                Err(error)
+            }
+        }
+    }
+    pub fn redo(&mut self) -> Result<CompactString, String>{
+        let transaction = self.redo_stack_.pop().ok_or("Nothing to redo.".to_string())?;
+        match self.apply_edits(transaction.operations.clone()){
+            Ok(reverted) => {
+                self.undo_stack_.push(Transaction{name : transaction.name.clone(), operations : reverted});
+                Ok(transaction.name)
+            }
+            Err(error) => {
+                self.redo_stack_.push(transaction);
+                Err(error)
+            }
+        }
+    }
+    // the most recent transaction is last
+    pub fn get_undo_names(&self) -> Vec<CompactString>{
+        self.undo_stack_.iter().map(|x| x.name.clone()).collect()
+    }
+    pub fn get_redo_names(&self) -> Vec<CompactString>{
+        self.redo_stack_.iter().map(|x| x.name.clone()).collect()
+    }
+    pub fn clear_history(&mut self){
+        self.undo_stack_.clear();
+        self.redo_stack_.clear();
+    }
+
+    pub fn add_object(&mut self, mut object : Box<dyn ObjectTrait>, name : &str, scene_name : &str){
+        self.pending_elements.link_vgroup_materials(name, object.as_mut());
+        let id = object.id();
+        self.pending_elements.new_object(id, Arc::new(Mutex::new((object, true))), name.into(), scene_name);
+    }
+    pub fn add_material(&mut self, material : Material, name : &str, scene_name : &str){
+        self.pending_elements.new_material(material.id(), Arc::new(Mutex::new((material, true))), name.into(), scene_name);
+    }
+    // replaces the texture of the same name, materials using it stay linked
+    pub fn add_texture(&mut self, texture : Texture, name : &str){
+        self.pending_elements.new_texture(texture.id(), Arc::new(Mutex::new((texture, true))), name.into());
+    }
+    // None removes the texture from the slot
+    pub fn set_material_texture(&mut self, material_name : &str, slot : TextureSlotType, texture : Option<TextureSlot>) -> Result<u8, String>{
+        let empty_data = GlobalScenegraphPending::default();
+        self.check_texture_validity(texture.iter().map(|x| &x.texture), &empty_data, "Material")?;
+        let material = self.get_material_by_name(material_name).ok_or(String::from("Material does not exist: \"") + material_name + "\". ")?;
+        let mut material_unlocked = material.lock().unwrap();
+        let old = material_unlocked.0.get_texture(slot).cloned();
+        if let Some(new) = &texture{
+            self.texture2material.insert(new.texture.clone(), material_name.into());
+        }
+        material_unlocked.0.set_texture(slot, texture);
+        // other slots of the material can still use the old texture
+        if let Some(old) = old.filter(|x| !material_unlocked.0.get_texture_names().any(|y| *y == x.texture)){
+            self.texture2material.remove(&old.texture, material_name);
+        }
+        material_unlocked.1 = true;
+        Ok(5)
+    }
+
+    // PREFABS
+    pub fn add_prefab(&mut self, prefab : Prefab, name : &str){
+        self.prefabs_.insert(name.into(), prefab);
+    }
+    pub fn has_prefab(&self, name : &str) -> bool{
+        self.prefabs_.contains_key(name)
+    }
+    pub fn get_prefab_instance(&self, name : &str) -> Option<&PrefabInstance>{
+        self.prefab_instances_.get(name)
+    }
+    // the elements of the instance are added at the start of the next frame
+    pub fn instantiate_prefab(&mut self, instance : PendingPrefabInstance) -> Result<u8, String>{
+        if !self.scenes_.contains_name(&instance.scene) {
+            return Err(String::from("Prefab instance belongs in non-existent scene: \"") + &instance.scene + "\". ");
+        }
+        let mut pending_elements = std::mem::take(&mut self.pending_elements);
+        let output = self.new_prefab_instance(instance, &mut pending_elements);
+        self.pending_elements = pending_elements;
+        output
+    }
+    // removes all objects and materials of the instance, returns false if there is no such instance
+    pub fn despawn_prefab_instance(&mut self, name : &str) -> bool{
+        let Some(instance) = self.prefab_instances_.remove(name) else {return false;};
+        for object_name in &instance.objects{
+            self.pending_elements.objects_.remove_by_name(object_name);
+            self.remove_object(object_name);
+        }
+        for material_name in &instance.materials{
+            self.pending_elements.materials_.remove_by_name(material_name);
+            self.remove_material(material_name);
+        }
+        true
+    }
+
+    // writes the animated values into their objects and materials, returns the events that have to be broadcasted
+    pub fn update_animations(&mut self) -> Vec<usize>{
+        let elapsed = self.animations.get_frame_delta();
+        for id in self.get_object_ids(){
+            let object = self.get_object(id);
+            let mut object_unlocked = object.lock().unwrap();
+            if let Some(armature) = object_unlocked.0.get_mesh_mut().and_then(|x| x.armature_.as_deref_mut()){
+                armature.advance(elapsed);
+            }
+        }
+        let (values, events) = self.animations.update(elapsed);
+        for (target, properties) in values{
+            if let Some(material) = self.get_material_by_name(&target){
+                let mut material_unlocked = material.lock().unwrap();
+                for (property, value) in properties.iter().filter(|x| x.0.is_material()){
+                    let value = value.map(|x| x as f32);
+                    match property{
+                        AnimatedProperty::MaterialDiffuse => {material_unlocked.0.dif_ = value;}
+                        AnimatedProperty::MaterialSpecular => {material_unlocked.0.scol = [value[0], value[1], value[2]];}
+                        AnimatedProperty::MaterialAlpha => {material_unlocked.0.alpha = value[0];}
+                        _ => {}
+                    }
+                }
+                material_unlocked.1 = true;
+            }
+            if let Some(object) = self.get_object_by_name(&target){
+                let mut object_unlocked = object.lock().unwrap();
+                for (property, value) in properties.iter().filter(|x| !x.0.is_material()){
+                    match property{
+                        AnimatedProperty::Position => {object_unlocked.0.get_data_mut().pos = [value[0], value[1], value[2]];}
+                        AnimatedProperty::Rotation => {object_unlocked.0.get_data_mut().rot = *value;}
+                        AnimatedProperty::Scale => {object_unlocked.0.get_data_mut().sca = [value[0], value[1], value[2]];}
+                        AnimatedProperty::LightIntensity => {
+                            if let Some(light) = object_unlocked.0.get_light_mut(){light.intensity = value[0] as f32;}
+                        }
+                        AnimatedProperty::LightColor => {
+                            if let Some(light) = object_unlocked.0.get_light_mut(){light.color = [value[0] as f32, value[1] as f32, value[2] as f32];}
+                        }
+                        AnimatedProperty::CameraFov => {
+                            if let Some(camera) = object_unlocked.0.get_camera_mut(){camera.fov = value[0] as f32;}
+                        }
+                        _ => {}
+                    }
+                }
+                object_unlocked.1 = true;
+            }
+        }
+        events
+    }
+
     pub fn update(&mut self, changed : bool) -> (GlobalScenegraphChanged, Vec<usize>) {
 
         // output
@@ -58,12 +397,14 @@ impl GlobalScenegraph{
             //polygons_ : self.polygons_.get_changed_and_reset(changed),
             materials_ : self.materials_.get_changed_and_reset(changed),
             viewports_ : self.viewports_.get_changed_and_reset(changed),
+            textures_ : self.textures_.get_changed_and_reset(changed),
         };
 
         self.scenes_.update();
         self.materials_.update();
         self.objects_.update();
         self.viewports_.update();
+        self.textures_.update();
 
         // delete everything that is necessary
         for name in std::mem::take(self.scenes_.get_deleted()){
@@ -78,6 +419,9 @@ impl GlobalScenegraph{
         for name in std::mem::take(self.viewports_.get_deleted()){
             self.remove_viewport(&name);
         }
+        for name in std::mem::take(self.textures_.get_deleted()){
+            self.remove_texture(&name);
+        }
 
         // NEW FRAME STARTS HERE
         
@@ -95,6 +439,20 @@ impl GlobalScenegraph{
             events.push(event);
         }
 
+        // reloaded files only change what is different
+        for (mut inter, event) in std::mem::take(&mut self.pending_reloads_){
+            if self.reload_world(inter.get_data()) {
+                events.push(event);
+            }
+        }
+
+        // switch or unload the world after everything new is added
+        for (change, event) in std::mem::take(&mut self.pending_world_changes_){
+            if self.apply_world_change(change) {
+                events.push(event);
+            }
+        }
+
         (output, events)
     }
     fn consume_pending_elements(&mut self, data : &mut GlobalScenegraphPending){
@@ -108,10 +466,45 @@ impl GlobalScenegraph{
             for name in self.scenes_.names(){
                 self.remove_scene(&name);
             }
+            for name in self.textures_.names(){
+                self.remove_texture(&name);
+            }
             self.world_ = data.world_.clone();
+            self.world_source_ = data.source.clone();
+            self.worlds_.clear();
         }
         
 
+        for clip in std::mem::take(&mut data.animations){
+            self.animations.add_clip(clip);
+        }
+
+        // prefab instances become regular pending elements
+        for (name, prefab) in std::mem::take(&mut data.prefabs){
+            self.add_prefab(prefab, &name);
+        }
+        let mut prefab_instances = vec![];
+        for instance in std::mem::take(&mut data.prefab_instances){
+            let name = instance.name.clone();
+            match self.new_prefab_instance(instance, data){
+                Ok(_) => prefab_instances.push(name),
+                Err(error) => println!("[SCENEGRAPH] Skipped prefab instance \"{}\": {}", name, error),
+            }
+        }
+
+        // remember what the file contains, so it can be unloaded again
+        // reloads add the unchanged elements to the record afterwards
+        if !data.source.is_empty() {
+            self.worlds_.insert(data.source.clone(), WorldElements{
+                scenes : data.scenes_.names().right_values().cloned().collect(),
+                objects : data.objects_.names().right_values().cloned().collect(),
+                materials : data.materials_.names().right_values().cloned().collect(),
+                viewports : data.viewports_.names().right_values().cloned().collect(),
+                textures : data.textures_.names().right_values().cloned().collect(),
+                prefab_instances,
+            });
+        }
+
         // extend hashmaps for linked elements
         self.object2viewport.extend(std::mem::take(&mut data.object2viewport).mappings().into_iter().map(|(a, b)| (a.clone(), b.clone())));
         self.object2scene.extend(std::mem::take(&mut data.object2scene).mappings().into_iter().map(|(a, b)| (a.clone(), b.clone())));
@@ -119,27 +512,322 @@ impl GlobalScenegraph{
         self.material2scene.extend(std::mem::take(&mut data.material2scene).mappings().into_iter().map(|(a, b)| (a.clone(), b.clone())));
         self.material2vertexgroup.extend(std::mem::take(&mut data.material2vertexgroup).mappings().into_iter().map(|(a, b)| (a.clone(), b.clone())));
         
-        // update elements
+        // update elements, materials need their textures
+        for (id, name, obj) in &data.textures_ {
+            self.new_texture(*id, name, obj.clone()).unwrap_or_else(|_| panic!("Texture : \"{}\" could not be added.", name));
+        }
         for (id, name, element) in &data.scenes_ {
-            self.new_scene(*id, name, element.clone(), data).expect(&(CompactString::new("Scene : \"") + &name + &"\" could not be added."));
+            self.new_scene(*id, name, element.clone(), data).unwrap_or_else(|_| panic!("Scene : \"{}\" could not be added.", name));
         }
         for (id, name, obj) in &data.objects_ {
-            self.new_object(*id, name, obj.clone(), data).expect(&(CompactString::new("Object : \"") + &name + &"\" could not be added."));
+            self.new_object(*id, name, obj.clone(), data).unwrap_or_else(|_| panic!("Object : \"{}\" could not be added.", name));
         }
         for (id, name, obj) in &data.materials_ {
-            self.new_material(*id, name, obj.clone(), data).expect(&(CompactString::new("Material : \"") + &name + &"\" could not be added."));
+            self.new_material(*id, name, obj.clone(), data).unwrap_or_else(|_| panic!("Material : \"{}\" could not be added.", name));
         }
         for (id, name, obj) in &data.viewports_ {
-            self.new_viewport(*id, name, obj.clone(), data).expect(&(CompactString::new("Viewport : \"") + &name + &"\" could not be added."));
+            self.new_viewport(*id, name, obj.clone(), data).unwrap_or_else(|_| panic!("Viewport : \"{}\" could not be added.", name));
         }
         
         std::mem::take(data);
     }
 
+    fn apply_world_change(&mut self, change : WorldChange) -> bool{
+        match change{
+            WorldChange::LoadedScene(name) => {
+                if !self.scenes_.contains_name(&name) {return false;}
+                let Some(world) = self.world_.as_mut() else {return false;};
+                world.loaded_scene = name;
+                true
+            }
+            WorldChange::LoadedViewport(name) => {
+                if !self.viewports_.contains_name(&name) {return false;}
+                let Some(world) = self.world_.as_mut() else {return false;};
+                world.loaded_viewport = name;
+                true
+            }
+            WorldChange::Unload(name) => {
+                let Some(elements) = self.worlds_.remove(&name) else {return false;};
+                self.remove_world(elements);
+                // files that only added elements leave the world of another file alone
+                if name == self.world_source_ {
+                    self.world_ = None;
+                    self.world_source_.clear();
+                }
+                true
+            }
+        }
+    }
+    // removed elements are reported as deleted in the next frame, so the renderer frees them
+    fn remove_world(&mut self, elements : WorldElements){
+        for name in &elements.prefab_instances{
+            self.despawn_prefab_instance(name);
+        }
+        for name in &elements.viewports{
+            self.remove_viewport(name);
+        }
+        for name in &elements.scenes{
+            self.remove_scene(name);
+        }
+        for name in &elements.objects{
+            self.remove_object(name);
+        }
+        for name in &elements.materials{
+            self.remove_material(name);
+        }
+        for name in &elements.textures{
+            self.remove_texture(name);
+        }
+        let scenes = &self.scenes_;
+        self.prefab_instances_.retain(|_, instance| scenes.contains_name(&instance.scene));
+    }
+
+    // elements missing in the new version are removed and unchanged ones are left alone
+    fn reload_world(&mut self, data : &mut GlobalScenegraphPending) -> bool{
+        let Some(old) = self.worlds_.remove(&data.source) else {return false;};
+        let new_world = data.world_.take();
+
+        // prefab instances are instantiated again
+        for name in &old.prefab_instances{
+            self.despawn_prefab_instance(name);
+        }
+        for name in old.viewports.iter().filter(|x| !data.viewports_.contains_name(x)){
+            self.remove_viewport(name);
+        }
+        for name in old.scenes.iter().filter(|x| !data.scenes_.contains_name(x)){
+            self.remove_scene(name);
+        }
+        for name in old.objects.iter().filter(|x| !data.objects_.contains_name(x)){
+            self.remove_object(name);
+        }
+        for name in old.materials.iter().filter(|x| !data.materials_.contains_name(x)){
+            self.remove_material(name);
+        }
+        for name in old.textures.iter().filter(|x| !data.textures_.contains_name(x)){
+            self.remove_texture(name);
+        }
+
+        // materials are changed in place, so vertex groups and scenes stay linked to them
+        let mut unchanged = WorldElements::default();
+        let material_names : Vec<CompactString> = data.materials_.names().right_values().cloned().collect();
+        for name in material_names{
+            let Some(id) = self.materials_.get_id(&name) else {continue;};
+            let pending_id = data.materials_.get_id(&name).unwrap();
+            let new_material = data.materials_.pop(pending_id).lock().unwrap().0.clone();
+            let material = self.materials_[id].clone();
+            let mut material = material.lock().unwrap();
+            if !same_material(&material.0, &new_material) {
+                MaterialState::new(&new_material).apply(&mut material.0);
+                for texture_name in material.0.get_texture_names(){
+                    self.texture2material.remove(texture_name, &name);
+                }
+                for texture_name in new_material.get_texture_names(){
+                    self.texture2material.insert(texture_name.clone(), name.clone());
+                }
+                material.0.textures = new_material.textures;
+                material.1 = true;
+            }
+            unchanged.materials.push(name);
+        }
+        unchanged.scenes = take_unchanged(&mut data.scenes_, &self.scenes_, same_scene);
+        unchanged.objects = take_unchanged(&mut data.objects_, &self.objects_, |a, b| same_object(a.as_ref(), b.as_ref()));
+        unchanged.viewports = take_unchanged(&mut data.viewports_, &self.viewports_, same_viewport);
+        unchanged.textures = take_unchanged(&mut data.textures_, &self.textures_, same_texture);
+
+        let source = data.source.clone();
+        self.consume_pending_elements(data);
+        let elements = self.worlds_.entry(source.clone()).or_default();
+        elements.scenes.append(&mut unchanged.scenes);
+        elements.objects.append(&mut unchanged.objects);
+        elements.materials.append(&mut unchanged.materials);
+        elements.viewports.append(&mut unchanged.viewports);
+        elements.textures.append(&mut unchanged.textures);
+
+        // the loaded scene and viewport stay if they still exist
+        if let Some(mut world) = new_world {
+            if let Some(current) = &self.world_ {
+                if self.scenes_.contains_name(&current.loaded_scene) {
+                    world.loaded_scene = current.loaded_scene.clone();
+                }
+                if self.viewports_.contains_name(&current.loaded_viewport) {
+                    world.loaded_viewport = current.loaded_viewport.clone();
+                }
+            }
+            self.world_ = Some(world);
+            self.world_source_ = source;
+        }
+        true
+    }
+
+    // returns the edits that revert all applied ones in the order they have to be applied
+    fn apply_edits(&mut self, operations : Vec<EditOperation>) -> Result<Vec<EditOperation>, String>{
+        let mut reverted : Vec<EditOperation> = vec![];
+        for operation in operations{
+            match self.apply_edit(operation){
+                Ok(mut output) => {
+                    output.append(&mut reverted);
+                    reverted = output;
+                }
+                Err(error) => {
+                    self.revert_edits(reverted).map_err(|revert_error| error.clone() + " Reverting the applied edits failed: " + &revert_error)?;
+                    return Err(error);
+                }
+            }
+        }
+        Ok(reverted)
+    }
+    // every edit is tried, so as much as possible is restored
+    fn revert_edits(&mut self, reverted : Vec<EditOperation>) -> Result<(), String>{
+        let errors : Vec<String> = reverted.into_iter().filter_map(|operation| self.apply_edit(operation).err()).collect();
+        if errors.is_empty() {Ok(())} else {Err(errors.join(" "))}
+    }
+    fn apply_edit(&mut self, operation : EditOperation) -> Result<Vec<EditOperation>, String>{
+        let empty_data = GlobalScenegraphPending::default();
+        match operation{
+            EditOperation::AddObject{name, scenes, object} => {
+                if self.objects_.contains_name(&name) {
+                    return Err(String::from("Object: \"") + &name + "\" already exists.");
+                }
+                if !self.scenes_.contains_names(scenes.iter()) {
+                    return Err(String::from("Object: \"") + &name + "\" belongs in non-existent scene.");
+                }
+                let (id, vgroup_materials) = {
+                    let mut object_unlocked = object.lock().unwrap();
+                    object_unlocked.1 = true;
+                    let vgroup_materials : Vec<(CompactString, CompactString)> = object_unlocked.0.get_mesh().map(|mesh| {
+                        mesh.get_polygonstorage_unlocked().0.get_vgroups().iter().filter_map(|x| Some((x.material.clone()?, x.name.clone()))).collect()
+                    }).unwrap_or_default();
+                    (object_unlocked.0.id(), vgroup_materials)
+                };
+                self.check_material_validity(vgroup_materials.iter().map(|x| &x.0), &empty_data, "Object")?;
+
+                for scene in &scenes{
+                    self.object2scene.insert(name.clone(), scene.clone());
+                }
+                if let Err(error) = self.new_object(id, &name, object, &empty_data){
+                    self.object2scene.remove_key(&name);
+                    return Err(error);
+                }
+                for (material, vgroup) in vgroup_materials{
+                    self.material2vertexgroup.insert(material, (vgroup, name.clone()).into());
+                }
+                Ok(vec![EditOperation::RemoveObject{name}])
+            }
+            EditOperation::RemoveObject{name} => {
+                let id = self.objects_.get_id(&name).ok_or(String::from("Object: \"") + &name + "\" does not exist.")?;
+                let object = self.objects_[id].clone();
+
+                // children are removed first and added again after their parent
+                let children : Vec<CompactString> = (&self.objects_).into_iter()
+                    .filter(|(_, _, child)| child.lock().unwrap().0.get_parent() == name)
+                    .map(|(_, child_name, _)| CompactString::from(child_name)).collect();
+                let mut children_reverted = vec![];
+                for child in children{
+                    match self.apply_edit(EditOperation::RemoveObject{name : child}){
+                        Ok(mut output) => children_reverted.append(&mut output),
+                        Err(error) => {
+                            self.revert_edits(children_reverted)?;
+                            return Err(error);
+                        }
+                    }
+                }
+
+                let scenes : Vec<CompactString> = self.object2scene.get(&name).map(|x| x.iter().cloned().collect()).unwrap_or_default();
+                self.remove_object(&name);
+                for scene in &scenes{
+                    self.scenes_[self.scenes_.get_id(scene).unwrap()].lock().unwrap().1 = true;
+                }
+                let mut output = vec![EditOperation::AddObject{name, scenes, object}];
+                output.append(&mut children_reverted);
+                Ok(output)
+            }
+            EditOperation::SetTransform{name, transform} => {
+                let id = self.objects_.get_id(&name).ok_or(String::from("Object: \"") + &name + "\" does not exist.")?;
+                let mut object = self.objects_[id].lock().unwrap();
+                let data = object.0.get_data_mut();
+                let old_transform = BoneTransform{pos : data.pos, rot : data.rot, sca : data.sca};
+                (data.pos, data.rot, data.sca) = (transform.pos, transform.rot, transform.sca);
+                object.1 = true;
+                Ok(vec![EditOperation::SetTransform{name, transform : old_transform}])
+            }
+            EditOperation::SetMaterial{mesh, vgroup, material} => {
+                self.check_material_validity(material.iter(), &empty_data, "Vertex group")?;
+                let id = self.objects_.get_id(&mesh).ok_or(String::from("Object: \"") + &mesh + "\" does not exist.")?;
+                let element = self.objects_[id].clone();
+                let mut object = element.lock().unwrap();
+                let Some(mesh_data) = object.0.get_mesh_mut() else {
+                    return Err(String::from("Object: \"") + &mesh + "\" is not a mesh.");
+                };
+                let old_material = {
+                    let mut polygons = mesh_data.get_polygonstorage_unlocked();
+                    let Some(vgroup_data) = polygons.0.get_vgroups_mut().iter_mut().find(|x| x.name == vgroup) else {
+                        return Err(String::from("Mesh: \"") + &mesh + "\" has no vertex group: \"" + &vgroup + "\".");
+                    };
+                    std::mem::replace(&mut vgroup_data.material, material.clone())
+                };
+                mesh_data.set_vgroups_changed();
+                let storage = mesh_data.polygon_storage_.1.clone();
+                object.1 = true;
+                drop(object);
+
+                // instances share the vertex groups
+                let mut meshes = vec![mesh.clone()];
+                for other_id in self.objects_.ids().into_iter().filter(|x| *x != id){
+                    let mut other = self.objects_[other_id].lock().unwrap();
+                    let Some(other_mesh) = other.0.get_mesh_mut() else {continue;};
+                    if Arc::ptr_eq(&other_mesh.polygon_storage_.1, &storage) {
+                        other_mesh.set_vgroups_changed();
+                        other.1 = true;
+                        meshes.push(self.objects_.get_name(&other_id).unwrap().into());
+                    }
+                }
+                for name in meshes{
+                    let key : VertexGroupMeshKey = (vgroup.clone(), name).into();
+                    if let Some(old_material) = &old_material{
+                        self.material2vertexgroup.remove(old_material, &key);
+                    }
+                    if let Some(material) = &material{
+                        self.material2vertexgroup.insert(material.clone(), key);
+                    }
+                }
+                Ok(vec![EditOperation::SetMaterial{mesh, vgroup, material : old_material}])
+            }
+            EditOperation::SetViewport{name, viewport} => {
+                let id = self.viewports_.get_id(&name).ok_or(String::from("Viewport: \"") + &name + "\" does not exist.")?;
+                self.check_object_validity(viewport.cameras_.iter(), &empty_data, "Viewport")?;
+                let mut viewport_unlocked = self.viewports_[id].lock().unwrap();
+                let old_viewport = viewport_unlocked.0.clone();
+                viewport_unlocked.0.layers_ = viewport.layers_;
+                viewport_unlocked.0.cameras_ = viewport.cameras_;
+                viewport_unlocked.0.layer_combine_modes_ = viewport.layer_combine_modes_;
+                viewport_unlocked.0.split_screen_positions_ = viewport.split_screen_positions_;
+                viewport_unlocked.1 = true;
+                for camera in &old_viewport.cameras_{
+                    self.object2viewport.remove(camera, &name);
+                }
+                for camera in &viewport_unlocked.0.cameras_{
+                    self.object2viewport.insert(camera.clone(), name.clone());
+                }
+                drop(viewport_unlocked);
+                Ok(vec![EditOperation::SetViewport{name, viewport : old_viewport}])
+            }
+        }
+    }
+
     // HANDLE INDIVIDUAL OBJECTS
     fn new_object(&mut self, id : usize, name : &str, element: Arc<SingleElement<Box<dyn ObjectTrait>>>, data : &GlobalScenegraphPending) -> Result<u8, String> {
         
-        let object_unlocked = element.lock().unwrap();
+        let mut object_unlocked = element.lock().unwrap();
+        if let Err(error) = self.resolve_instance(name, object_unlocked.0.as_mut(), data) {
+            println!("[SCENEGRAPH] Skipped mesh \"{}\": {}", name, error);
+            for scene_name in self.object2scene.remove_key(name).unwrap_or_default(){
+                if let Some(scene_id) = self.scenes_.get_id(&scene_name) {
+                    self.scenes_[scene_id].lock().unwrap().0.objects.remove(name);
+                }
+            }
+            return Ok(5);
+        }
+        let is_mesh = object_unlocked.0.get_mesh().is_some();
         
         if let Some(old_id) = self.objects_.get_id(&name){
             let old_object = self.objects_.remove_now(&old_id).unwrap();
@@ -160,16 +848,104 @@ impl GlobalScenegraph{
         if let Some(names) = self.object2scene.get(name){
             if self.scenes_.contains_names(names.iter()){
                 self.objects_.insert(id, element, name);
+                self.link_to_scenes(names.iter(), name, false);
             }
             else {
                 return Err("Object belongs in non-existent scene.".to_string());
             }
         }
+        if is_mesh {
+            self.resolve_waiting_instances(name);
+        }
+        Ok(5)
+    }
+    fn new_prefab_instance(&mut self, instance : PendingPrefabInstance, data : &mut GlobalScenegraphPending) -> Result<u8, String> {
+        let prefab = self.prefabs_.get(&instance.prefab).ok_or(String::from("Prefab instance of non-existent prefab: \"") + &instance.prefab + "\". ")?;
+        if self.prefab_instances_.contains_key(&instance.name) {
+            return Err(String::from("Prefab instance already exists: \"") + &instance.name + "\". ");
+        }
+        let (objects, materials) = prefab.instantiate(&instance.name, &instance.options);
+        let mut output = PrefabInstance{prefab : instance.prefab, scene : instance.scene, ..Default::default()};
+        for (name, material) in materials{
+            data.new_material(material.id(), Arc::new(Mutex::new((material, true))), name.clone(), &output.scene);
+            output.materials.push(name);
+        }
+        for (name, mut object) in objects{
+            data.link_vgroup_materials(&name, object.as_mut());
+            data.new_object(object.id(), Arc::new(Mutex::new((object, true))), name.clone(), &output.scene);
+            output.objects.push(name);
+        }
+        self.prefab_instances_.insert(instance.name, output);
+        Ok(5)
+    }
+
+    // meshes that reference another mesh by name share its polygon storage
+    // placeholders of meshes that are not loaded yet are kept and resolved once the mesh is added
+    fn resolve_instance(&mut self, name : &str, object : &mut dyn ObjectTrait, data : &GlobalScenegraphPending) -> Result<u8, String> {
+        let Some(mesh) = object.get_mesh_mut() else {return Ok(5);};
+        let Some(source_name) = mesh.instance_of_.clone() else {return Ok(5);};
+        if source_name == name {
+            return Err(String::from("Mesh can not be an instance of itself."));
+        }
+        let source = match data.objects_.get_id(&source_name){
+            Some(id) => data.objects_[id].clone(),
+            None => match self.get_object_by_name(&source_name){
+                Some(source) => source,
+                None => {
+                    self.unresolved_instances_.insert(source_name, name.into());
+                    return Ok(5);
+                }
+            }
+        };
+        self.resolve_instance_from(name, mesh, &source, &source_name)
+    }
+    fn resolve_instance_from(&mut self, name : &str, mesh : &mut Mesh, source : &SingleElement<Box<dyn ObjectTrait>>, source_name : &str) -> Result<u8, String> {
+        let source_unlocked = source.lock().unwrap();
+        let Some(source_mesh) = source_unlocked.0.get_mesh() else {
+            return Err(String::from("Mesh is an instance of non-mesh object: \"") + source_name + "\". ");
+        };
+        if source_mesh.instance_of_.is_some() {
+            return Err(String::from("Mesh is an instance of another instance: \"") + source_name + "\". ");
+        }
+        if !mesh.resolve_instance(&source_mesh) {
+            return Err(String::from("Mesh is an instance of deformed mesh: \"") + source_name + "\". ");
+        }
+        for vgroup in mesh.get_polygonstorage_unlocked().0.get_vgroups(){
+            if let Some(material) = &vgroup.material{
+                self.material2vertexgroup.insert(material.clone(), (vgroup.name.clone(), name.into()).into());
+            }
+        }
         Ok(5)
     }
+    // placeholders that can not be resolved against the new mesh are removed
+    fn resolve_waiting_instances(&mut self, source_name : &str){
+        let Some(source) = self.get_object_by_name(source_name) else {return;};
+        for name in self.unresolved_instances_.remove_key(source_name).unwrap_or_default(){
+            let Some(object) = self.get_object_by_name(&name) else {continue;};
+            let mut object_unlocked = object.lock().unwrap();
+            let Some(mesh) = object_unlocked.0.get_mesh_mut() else {continue;};
+            if mesh.instance_of_.as_deref() != Some(source_name) {continue;}
+            let mut resolved = mesh.clone();
+            match self.resolve_instance_from(&name, &mut resolved, &source, source_name){
+                Ok(_) => {
+                    resolved.set_vgroups_changed();
+                    *mesh = resolved;
+                    object_unlocked.1 = true;
+                }
+                Err(error) => {
+                    println!("[SCENEGRAPH] Removed mesh \"{}\": {}", name, error);
+                    drop(object_unlocked);
+                    self.remove_object(&name);
+                }
+            }
+        }
+    }
     fn remove_object(&mut self, name : &str){
         if let Some(old_id) = self.objects_.get_id(&name){
-            self.objects_.remove_now(&old_id);
+            let old_object = self.objects_.remove_now(&old_id).unwrap();
+            if let Some(source_name) = old_object.0.lock().unwrap().0.get_mesh().and_then(|x| x.instance_of_) {
+                self.unresolved_instances_.remove(&source_name, name);
+            }
 
             for scenekey in self.object2scene.remove_key(name).unwrap_or_default(){
                 let scene_id = self.scenes_.get_id(&scenekey).unwrap();
@@ -243,11 +1019,17 @@ impl GlobalScenegraph{
             }
         }
     }
-    fn new_material(&mut self, id : usize, name : &str,  element: Arc<SingleElement<Material>>, _ : &GlobalScenegraphPending) -> Result<u8, String> {
+    fn new_material(&mut self, id : usize, name : &str,  element: Arc<SingleElement<Material>>, data : &GlobalScenegraphPending) -> Result<u8, String> {
         //TODO : IF VALID LINKS EXIST
         if let Some(names) = self.material2scene.get(name){
             if self.scenes_.contains_names(names.iter()){
+                let texture_names : Vec<CompactString> = element.lock().unwrap().0.get_texture_names().cloned().collect();
+                self.check_texture_validity(texture_names.iter(), data, "Material")?;
+                for texture_name in texture_names{
+                    self.texture2material.insert(texture_name, name.into());
+                }
                 self.materials_.insert(id, element, name);
+                self.link_to_scenes(names.iter(), name, true);
             }
             else {
                 return Err("Material belongs to non-existent scene.".to_string());
@@ -257,7 +1039,10 @@ impl GlobalScenegraph{
     }
     fn remove_material(&mut self, name : &str){
         if let Some(old_id) = self.materials_.get_id(&name){
-            self.materials_.remove_now(&old_id);
+            let old_material = self.materials_.remove_now(&old_id).unwrap();
+            for texture_name in old_material.0.lock().unwrap().0.get_texture_names(){
+                self.texture2material.remove(texture_name, name);
+            }
 
             for vgroupmeshkey in self.material2vertexgroup.remove_key(name).unwrap_or_default(){
                 let obj_id = self.objects_.get_id(&vgroupmeshkey.mesh);
@@ -309,12 +1094,56 @@ impl GlobalScenegraph{
             self.viewports_.remove_now(&old_id);
        }
     }
+    // a replaced texture keeps its materials, they are sent to the renderer again
+    fn new_texture(&mut self, id : usize, name : &str, element: Arc<SingleElement<Texture>>) -> Result<u8, String> {
+        if let Some(old_id) = self.textures_.get_id(&name){
+            self.textures_.remove_now(&old_id);
+        }
+        self.textures_.insert(id, element, name);
+        for material_name in self.texture2material.get(name).cloned().unwrap_or_default(){
+            if let Some(material) = self.get_material_by_name(&material_name){
+                material.lock().unwrap().1 = true;
+            }
+        }
+        Ok(5)
+    }
+    fn remove_texture(&mut self, name : &str){
+        if let Some(old_id) = self.textures_.get_id(&name){
+            self.textures_.remove_now(&old_id);
+
+            for material_name in self.texture2material.remove_key(name).unwrap_or_default(){
+                let Some(material) = self.get_material_by_name(&material_name) else {continue;};
+                let mut material_unlocked = material.lock().unwrap();
+                for slot in material_unlocked.0.textures.iter_mut(){
+                    if slot.as_ref().is_some_and(|x| x.texture == name){
+                        *slot = None;
+                    }
+                }
+                material_unlocked.1 = true;
+            }
+        }
+    }
     pub fn add_interpreted(&mut self, new_data : Interpreter, event : usize){
         self.pending_interpreters_.push((new_data, event));
     }
+    // the file has to be loaded already, otherwise the reload is ignored
+    pub fn add_reloaded(&mut self, new_data : Interpreter, event : usize){
+        self.pending_reloads_.push((new_data, event));
+    }
 
     /////////////////////////////////////////////////////////
     // internal specific functions
+
+    // elements added on the fly are not yet listed in their scenes
+    fn link_to_scenes<'a>(&self, scene_names : impl Iterator<Item=&'a CompactString>, name : &str, is_material : bool){
+        for scene_name in scene_names{
+            let mut scene = self.scenes_[self.scenes_.get_id(scene_name).unwrap()].lock().unwrap();
+            let inserted = if is_material {scene.0.materials.insert(name.into())} else {scene.0.objects.insert(name.into())};
+            if inserted {
+                scene.1 = true;
+            }
+        }
+    }
     fn check_object_validity<'a>(&self, objects_list : impl Iterator<Item=&'a CompactString>, data : &GlobalScenegraphPending, component_name : &str) -> Result<u8, String>{
         for obj in objects_list{
             if ! (self.objects_.contains_name(obj) || data.objects_.contains_name(obj)) {
@@ -328,6 +1157,14 @@ impl GlobalScenegraph{
         }
         Ok(5)
     }
+    fn check_texture_validity<'a>(&self, textures_list : impl Iterator<Item=&'a CompactString>, data : &GlobalScenegraphPending, component_name : &str) -> Result<u8, String>{
+        for obj in textures_list{
+            if ! (self.textures_.contains_name(obj) || data.textures_.contains_name(obj)) {
+                return Err(String::from(component_name) + " does not contain texture: \"" + obj + &"\". ");
+            }
+        }
+        Ok(5)
+    }
     fn check_material_validity<'a>(&self, materials_list : impl Iterator<Item=&'a CompactString>, data : &GlobalScenegraphPending, component_name : &str) -> Result<u8, String>{
         for obj in materials_list{
             if ! (self.materials_.contains_name(obj) || data.materials_.contains_name(obj)) {
@@ -338,6 +1175,18 @@ impl GlobalScenegraph{
     }
 }
 
+// drops the pending elements that equal the current ones and returns their names
+fn take_unchanged<T>(pending : &mut InterpreterElementWrapper<T>, current : &ElementContainer<T>, same : impl Fn(&T, &T) -> bool) -> Vec<CompactString>{
+    let unchanged : Vec<CompactString> = pending.names().iter().filter(|(pending_id, name)| {
+        let Some(id) = current.get_id(name) else {return false;};
+        same(&current[id].lock().unwrap().0, &pending.get(pending_id).unwrap().lock().unwrap().0)
+    }).map(|(_, name)| name.clone()).collect();
+    for name in &unchanged{
+        pending.remove_by_name(name);
+    }
+    unchanged
+}
+
 fn test_global_scenegraph(){
 
 }
@@ -352,13 +1201,196 @@ impl std::fmt::Debug for GlobalScenegraph{
         output_string.push_str(&format!("___objects_ {:?}\n", &self.objects_));
         output_string.push_str(&format!("___materials_ {:?}\n", &self.materials_));
         output_string.push_str(&format!("___viewports_ {:?}\n", &self.viewports_));
+        output_string.push_str(&format!("___textures_ {:?}\n", &self.textures_));
         output_string.push_str(&format!("___object2viewport {:?}\n", &self.object2viewport));
         output_string.push_str(&format!("___object2scene {:?}\n", &self.object2scene));
         output_string.push_str(&format!("___object2object {:?}\n", &self.object2object));
         output_string.push_str(&format!("___material2scene {:?}\n", &self.material2scene));
         output_string.push_str(&format!("___material2vertexgroup {:?}\n", &self.material2vertexgroup));
+        output_string.push_str(&format!("___texture2material {:?}\n", &self.texture2material));
+        output_string.push_str(&format!("___animations {:?}\n", &self.animations));
+        output_string.push_str(&format!("___worlds {:?}\n", &self.worlds_));
+        output_string.push_str(&format!("___world_source {:?}\n", &self.world_source_));
+        output_string.push_str(&format!("___user_data {:?}\n", &self.user_data_));
+        output_string.push_str(&format!("___undo_stack {:?}\n", &self.undo_stack_));
+        output_string.push_str(&format!("___redo_stack {:?}\n", &self.redo_stack_));
         output_string.push_str(&format!("___pending_interpreters {:?}\n", &self.pending_interpreters_));
+        output_string.push_str(&format!("___pending_world_changes {:?}\n", &self.pending_world_changes_));
+        output_string.push_str(&format!("___pending_reloads {:?}\n", &self.pending_reloads_));
         output_string.push_str(&format!("___pending_events {:?}\n", &self.pending_elements));
          write!(f, "Global Scenegraph\n{}", output_string)
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+pub mod globalscenegraphtest{
+    use super::*;
+    use super::super::super::carbon::interpreter::interpret;
+
+    // meshes without triangles, the attributes are added to the opening tag
+    fn mesh(name : &str, attributes : &str) -> String{
+        format!("<Mesh name = \"{}\" visible = 1 {}> vertices = {{0.0; 0.0; 0.0}} current_state = {{0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0}} parent = \"\" </Mesh>\n", name, attributes)
+    }
+    fn world(scene : &str, meshes : &[String]) -> String{
+        format!("<World> loaded_scene = \"{}\" loaded_viewport = \"\"\n<Scene name = \"{}\">\n{}</Scene>\n</World>", scene, scene, meshes.concat())
+    }
+    fn load(scenegraph : &mut GlobalScenegraph, interpreter : Interpreter){
+        scenegraph.add_interpreted(interpreter, 0);
+        scenegraph.update(false);
+    }
+    // files without a world only add their elements to the current one
+    fn load_file(scenegraph : &mut GlobalScenegraph, input : &str, source : &str, has_world : bool){
+        let mut interpreter = interpret(input).unwrap();
+        interpreter.get_data().source = source.into();
+        if !has_world {
+            interpreter.get_data().world_ = None;
+        }
+        load(scenegraph, interpreter);
+    }
+    fn get_mesh(scenegraph : &GlobalScenegraph, name : &str) -> Option<Mesh>{
+        scenegraph.get_object_by_name(name)?.lock().unwrap().0.get_mesh()
+    }
+
+    #[test]
+    fn resolve_instances(){
+        let mut scenegraph = GlobalScenegraph::default();
+        load(&mut scenegraph, interpret(&world("scene", &[
+            mesh("source", ""),
+            mesh("copy", "instance_of = \"source\""),
+            mesh("early", "instance_of = \"late\""),
+            mesh("itself", "instance_of = \"itself\""),
+        ])).unwrap());
+
+        // instances of meshes in the same file share their polygon storage
+        let source = get_mesh(&scenegraph, "source").unwrap();
+        let copy = get_mesh(&scenegraph, "copy").unwrap();
+        assert!(copy.instance_of_.is_none());
+        assert!(Arc::ptr_eq(&source.polygon_storage_.1, &copy.polygon_storage_.1));
+
+        // invalid instances are skipped instead of stopping the whole file
+        assert!(get_mesh(&scenegraph, "itself").is_none());
+        assert!(!scenegraph.scenes_[scenegraph.scenes_.get_id("scene").unwrap()].lock().unwrap().0.objects.contains("itself"));
+
+        // the placeholder waits for the mesh it is an instance of
+        let early = get_mesh(&scenegraph, "early").unwrap();
+        assert_eq!(early.instance_of_.as_deref(), Some("late"));
+        scenegraph.add_object(Box::new(source.duplicate()), "late", "scene");
+        scenegraph.update(false);
+        let late = get_mesh(&scenegraph, "late").unwrap();
+        let early = get_mesh(&scenegraph, "early").unwrap();
+        assert!(early.instance_of_.is_none());
+        assert!(Arc::ptr_eq(&late.polygon_storage_.1, &early.polygon_storage_.1));
+        assert!(scenegraph.unresolved_instances_.get("late").is_none());
+
+        // removed placeholders are no longer resolved
+        scenegraph.add_object(Box::new(Mesh::new_instance_of("missing")), "waiting", "scene");
+        scenegraph.update(false);
+        assert!(scenegraph.unresolved_instances_.get("missing").is_some());
+        scenegraph.remove_object("waiting");
+        assert!(scenegraph.unresolved_instances_.get("missing").is_none_or(|x| x.is_empty()));
+    }
+
+    #[test]
+    fn skip_invalid_prefab_instances(){
+        let mut scenegraph = GlobalScenegraph::default();
+        let input = format!("<World> loaded_scene = \"scene\" loaded_viewport = \"\"\n<Prefab name = \"prefab\">\n{}</Prefab>\n<Scene name = \"scene\">\n{}{}{}</Scene>\n</World>",
+            mesh("part", ""),
+            "<PrefabInstance name = \"first\" prefab = \"prefab\"> </PrefabInstance>\n",
+            "<PrefabInstance name = \"first\" prefab = \"prefab\"> </PrefabInstance>\n",
+            "<PrefabInstance name = \"unknown\" prefab = \"missing\"> </PrefabInstance>\n");
+        load(&mut scenegraph, interpret(&input).unwrap());
+
+        // the duplicate and the instance of an unknown prefab are skipped, the valid one is kept
+        assert_eq!(scenegraph.get_prefab_instance("first").unwrap().objects.len(), 1);
+        assert!(scenegraph.get_prefab_instance("unknown").is_none());
+        assert!(get_mesh(&scenegraph, "first/part").is_some());
+    }
+
+    #[test]
+    fn unload_file_without_world(){
+        let mut scenegraph = GlobalScenegraph::default();
+        load_file(&mut scenegraph, &world("scene", &[mesh("first", "")]), "world.csl", true);
+        load_file(&mut scenegraph, &world("extra", &[mesh("second", "")]), "extra.csl", false);
+        assert!(scenegraph.has_world("world.csl") && scenegraph.has_world("extra.csl"));
+
+        // only the elements of the unloaded file are removed, the world stays
+        assert!(scenegraph.unload_world("extra.csl", 0));
+        scenegraph.update(false);
+        assert_eq!(scenegraph.get_world().unwrap().loaded_scene, "scene");
+        assert!(get_mesh(&scenegraph, "first").is_some());
+        assert!(get_mesh(&scenegraph, "second").is_none());
+        assert!(!scenegraph.scenes_.contains_name("extra"));
+
+        assert!(scenegraph.unload_world("world.csl", 0));
+        scenegraph.update(false);
+        assert!(scenegraph.get_world().is_none());
+    }
+
+    #[test]
+    fn failed_transaction(){
+        let mut scenegraph = GlobalScenegraph::default();
+        let child = mesh("child", "").replace("parent = \"\"", "parent = \"parent\"");
+        load(&mut scenegraph, interpret(&world("scene", &[mesh("first", ""), mesh("parent", ""), child])).unwrap());
+        let transform = |scenegraph : &GlobalScenegraph, name : &str| scenegraph.get_object_by_name(name).unwrap().lock().unwrap().0.get_data().pos;
+        let scene_objects = |scenegraph : &GlobalScenegraph| scenegraph.scenes_[scenegraph.scenes_.get_id("scene").unwrap()].lock().unwrap().0.objects.len();
+
+        // the last edit fails, so the transform and the removed objects are restored
+        let mut transaction = Transaction::new("failing");
+        transaction.set_transform("first", BoneTransform{pos : [1.0, 2.0, 3.0], ..Default::default()});
+        transaction.remove_object("parent");
+        transaction.add_object("scene", "first", Box::new(get_mesh(&scenegraph, "first").unwrap().duplicate()));
+        assert!(scenegraph.apply_transaction(transaction).is_err());
+        assert_eq!(transform(&scenegraph, "first"), [0.0; 3]);
+        assert!(get_mesh(&scenegraph, "parent").is_some());
+        assert_eq!(get_mesh(&scenegraph, "child").unwrap().get_parent(), "parent");
+        assert_eq!(scene_objects(&scenegraph), 3);
+        assert!(scenegraph.get_undo_names().is_empty());
+
+        // children are removed with their parent and come back when it is undone
+        let mut transaction = Transaction::new("remove");
+        transaction.remove_object("parent");
+        scenegraph.apply_transaction(transaction).unwrap();
+        assert!(get_mesh(&scenegraph, "child").is_none());
+        assert_eq!(scene_objects(&scenegraph), 1);
+        assert_eq!(scenegraph.undo().unwrap(), "remove");
+        assert_eq!(get_mesh(&scenegraph, "child").unwrap().get_parent(), "parent");
+        assert_eq!(scene_objects(&scenegraph), 3);
+
+        // a failed undo or redo keeps the transaction in the history
+        let mut transaction = Transaction::new("missing");
+        transaction.set_transform("missing", Default::default());
+        scenegraph.undo_stack_.push(transaction.clone());
+        scenegraph.redo_stack_.push(transaction);
+        assert!(scenegraph.undo().is_err());
+        assert_eq!(scenegraph.get_undo_names(), ["missing"]);
+        assert!(scenegraph.redo().is_err());
+        assert_eq!(scenegraph.get_redo_names(), ["remove", "missing"]);
+    }
+
+    #[test]
+    fn reload_changed_elements(){
+        let mut scenegraph = GlobalScenegraph::default();
+        let moved = mesh("moved", "").replace("current_state = {0.0", "current_state = {1.0");
+        load_file(&mut scenegraph, &world("scene", &[mesh("kept", ""), mesh("moved", "")]), "world.csl", true);
+        let reloaded = world("scene", &[mesh("kept", ""), moved]);
+
+        // unchanged elements are taken out of the pending ones
+        let mut interpreter = interpret(&reloaded).unwrap();
+        let unchanged = take_unchanged(&mut interpreter.get_data().objects_, &scenegraph.objects_, |a, b| same_object(a.as_ref(), b.as_ref()));
+        assert_eq!(unchanged, ["kept"]);
+        assert!(!interpreter.get_data().objects_.contains_name("kept"));
+        assert!(interpreter.get_data().objects_.contains_name("moved"));
+
+        // the record of the file is replaced, so names do not pile up over several reloads
+        for _ in 0..2 {
+            let mut interpreter = interpret(&reloaded).unwrap();
+            interpreter.get_data().source = "world.csl".into();
+            scenegraph.add_reloaded(interpreter, 0);
+            scenegraph.update(false);
+        }
+        let elements = &scenegraph.worlds_["world.csl"];
+        assert_eq!((elements.scenes.len(), elements.objects.len()), (1, 2));
+        assert_eq!(get_mesh(&scenegraph, "moved").unwrap().get_data().pos[0], 1.0);
+        assert!(get_mesh(&scenegraph, "kept").is_some());
+    }
+}
@@ -4,6 +4,7 @@ use super::object_trait::*;
 use super::world::*;
 use super::scene::*;
 use super::material::*;
+use super::texture::Texture;
 use super::viewport::*;
 //use super::polygonstoragetrait::*;
 use super::basecontainer::*;
@@ -20,12 +21,14 @@ pub struct GlobalScenegraphChanged {
     //pub polygons_  : ElementSnapshot<RendererPolygonStorage>,
     pub materials_ : ElementSnapshot<Material>,
     pub viewports_ : ElementSnapshot<ViewPort>,
+    pub textures_  : ElementSnapshot<Texture>,
 }
 
 impl GlobalScenegraphChanged{
     pub fn is_empty(&self) -> bool{
         self.objects_.get_data().is_empty() &&
         self.materials_.get_data().is_empty() &&
-        self.viewports_.get_data().is_empty()
+        self.viewports_.get_data().is_empty() &&
+        self.textures_.get_data().is_empty()
     }
 }
\ No newline at end of file
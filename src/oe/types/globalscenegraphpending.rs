@@ -1,4 +1,6 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
 
 use compact_str::CompactString;
 use multi_containers::HashMultiMap;
@@ -7,7 +9,10 @@ use super::object_trait::*;
 use super::world::*;
 use super::scene::*;
 use super::material::*;
+use super::texture::Texture;
 use super::viewport::*;
+use super::animation::AnimationClip;
+use super::prefab::{Prefab, PendingPrefabInstance};
 //use super::polygonstoragetrait::*;
 use super::basecontainer::*;
 use super::elementcontainer::*;
@@ -32,35 +37,63 @@ impl From<(CompactString, CompactString)> for VertexGroupMeshKey{
 // GLOBAL SCENE GRAPH PENDING
 #[derive(Default)]
 pub struct GlobalScenegraphPending{
+    // file the elements were loaded from, empty for elements added on the fly
+    pub source     : CompactString,
     pub world_     : Option<World>,
     pub scenes_    : PendingElements<Scene>,
     pub objects_   : PendingElements<Box<dyn ObjectTrait>>,
     //polygons_  : EPendingElements<Box<dyn PolygonStorageTrait>>,
     pub materials_ : PendingElements<Material>,
     pub viewports_ : PendingElements<ViewPort>,
+    pub textures_  : PendingElements<Texture>,
 
     pub object2viewport      : HashMultiMap<CompactString, CompactString>,
     pub object2scene         : HashMultiMap<CompactString, CompactString>,
     pub object2object        : HashMultiMap<CompactString, CompactString>,
     pub material2scene       : HashMultiMap<CompactString, CompactString>,
     pub material2vertexgroup : HashMultiMap<CompactString, VertexGroupMeshKey>,
+
+    pub animations : Vec<AnimationClip>,
+
+    pub prefabs : HashMap<CompactString, Prefab>,
+    pub prefab_instances : Vec<PendingPrefabInstance>,
+
+    // shared with the asset manager to report the loading progress
+    pub elements_interpreted : Arc<AtomicUsize>,
 }
 
 impl GlobalScenegraphPending{
     pub fn new_object(&mut self, id : usize, element : Arc<SingleElement<Box<dyn ObjectTrait>>>, name : CompactString, scene_name : &str){
+        self.elements_interpreted.fetch_add(1, Ordering::Relaxed);
         self.object2scene.insert(name.clone(), scene_name.into());
         self.objects_.insert_str(id, element, name);
     }
+    // the vertex groups of a mesh lose their materials when the materials are removed
+    pub fn link_vgroup_materials(&mut self, name : &str, object : &mut dyn ObjectTrait){
+        let Some(mesh) = object.get_mesh_mut() else {return;};
+        for vgroup in mesh.get_polygonstorage_unlocked().0.get_vgroups(){
+            if let Some(material) = &vgroup.material{
+                self.material2vertexgroup.insert(material.clone(), (vgroup.name.clone(), name.into()).into());
+            }
+        }
+    }
     pub fn new_material(&mut self, id : usize, element : Arc<SingleElement<Material>>, name : CompactString, scene_name : &str){
+        self.elements_interpreted.fetch_add(1, Ordering::Relaxed);
         self.material2scene.insert(name.clone(), scene_name.into());
         self.materials_.insert_str(id, element, name);
     }
     pub fn new_scene(&mut self, id : usize, element : Arc<SingleElement<Scene>>, name : CompactString){
+        self.elements_interpreted.fetch_add(1, Ordering::Relaxed);
         self.scenes_.insert_str(id, element, name);
     }
     pub fn new_viewport(&mut self, id : usize, element : Arc<SingleElement<ViewPort>>, name : CompactString){
+        self.elements_interpreted.fetch_add(1, Ordering::Relaxed);
         self.viewports_.insert_str(id, element, name);
     }
+    pub fn new_texture(&mut self, id : usize, element : Arc<SingleElement<Texture>>, name : CompactString){
+        self.elements_interpreted.fetch_add(1, Ordering::Relaxed);
+        self.textures_.insert_str(id, element, name);
+    }
 }
 
 use std::fmt;
@@ -68,16 +101,21 @@ impl std::fmt::Debug for GlobalScenegraphPending{
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut output_string = String::new(); 
         
+        output_string.push_str(&format!("___source {:?}\n", &self.source));
         output_string.push_str(&format!("___world_ {:?}\n", &self.world_));
         output_string.push_str(&format!("___scenes_ {:?}\n", &self.scenes_));
         output_string.push_str(&format!("___objects_ {:?}\n", &self.objects_));
         output_string.push_str(&format!("___materials_ {:?}\n", &self.materials_));
         output_string.push_str(&format!("___viewports_ {:?}\n", &self.viewports_));
+        output_string.push_str(&format!("___textures_ {:?}\n", &self.textures_));
         output_string.push_str(&format!("___object2viewport {:?}\n", &self.object2viewport));
         output_string.push_str(&format!("___object2scene {:?}\n", &self.object2scene));
         output_string.push_str(&format!("___object2object {:?}\n", &self.object2object));
         output_string.push_str(&format!("___material2scene {:?}\n", &self.material2scene));
         output_string.push_str(&format!("___material2vertexgroup {:?}\n", &self.material2vertexgroup));
+        output_string.push_str(&format!("___animations {:?}\n", &self.animations));
+        output_string.push_str(&format!("___prefabs {:?}\n", &self.prefabs));
+        output_string.push_str(&format!("___prefab_instances {:?}\n", &self.prefab_instances));
          write!(f, "Global Pending Scenegraph\n{}", output_string)
     }
 } 
\ No newline at end of file
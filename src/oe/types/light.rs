@@ -1,6 +1,7 @@
 use super::object_trait::*;
 use super::camera::*;
 use super::mesh::*;
+use crate::oe::math::{DQuat, DVec4};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
@@ -9,8 +10,10 @@ pub enum LightType {
     CustomLight = 0,
     Point = 1,
     Sun = 2,
+    // spot light of older files, same as Spot
     Lamp = 3,
-
+    Spot = 4,
+    Area = 5,
 }
 
 impl From<i32> for LightType {
@@ -19,20 +22,116 @@ impl From<i32> for LightType {
             _ if value == LightType::Point as i32 => LightType::Point,
             _ if value == LightType::Sun as i32 => LightType::Sun,
             _ if value == LightType::Lamp as i32 => LightType::Lamp,
+            _ if value == LightType::Spot as i32 => LightType::Spot,
+            _ if value == LightType::Area as i32 => LightType::Area,
             _ if value == LightType::CustomLight as i32 => LightType::CustomLight,
             _ => LightType::CustomLight,
         }
     }
 }
 
+// falloff over the distance d, the light never reaches further than its range r
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Attenuation {
+    // constant up to the range
+    None,
+    // 1 - d / r
+    Linear,
+    // 1 / (1 + d^2), faded out smoothly with (1 - (d / r)^4)^2
+    #[default]
+    InverseSquare,
+    // 1 / (constant + linear * d + quadratic * d^2)
+    Custom{constant : f32, linear : f32, quadratic : f32},
+}
+
+impl Attenuation {
+    // value of attenuation in csl lights, custom coefficients are separate assignments
+    pub fn name(&self) -> &'static str{
+        match self {
+            Attenuation::None => "none",
+            Attenuation::Linear => "linear",
+            Attenuation::InverseSquare => "inverse_square",
+            Attenuation::Custom{..} => "custom",
+        }
+    }
+    pub fn get_factor(&self, distance : f32, range : f32) -> f32{
+        if distance >= range {
+            return 0.0;
+        }
+        match *self {
+            Attenuation::None => 1.0,
+            Attenuation::Linear => 1.0 - distance / range,
+            Attenuation::InverseSquare => {
+                let window = (1.0 - (distance / range).powi(4)).powi(2);
+                window / (1.0 + distance * distance)
+            }
+            Attenuation::Custom{constant, linear, quadratic} => {
+                1.0 / (constant + linear * distance + quadratic * distance * distance).max(f32::EPSILON)
+            }
+        }
+    }
+    // model followed by the constant, linear and quadratic coefficients
+    pub fn get_renderer_data(&self) -> [f32; 4]{
+        match *self {
+            Attenuation::None => [0.0, 1.0, 0.0, 0.0],
+            Attenuation::Linear => [1.0, 0.0, 0.0, 0.0],
+            Attenuation::InverseSquare => [2.0, 1.0, 0.0, 1.0],
+            Attenuation::Custom{constant, linear, quadratic} => [3.0, constant, linear, quadratic],
+        }
+    }
+}
+
+// area lights emit from the local xy plane towards -z, the size is scaled by the object
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaShape {
+    Rectangle{width : f32, height : f32},
+    Disc{radius : f32},
+}
+
+impl Default for AreaShape {
+    fn default() -> Self {
+        AreaShape::Rectangle{width : 1.0, height : 1.0}
+    }
+}
+
+// shadows of sun lights are split into cascades, point lights render into cube maps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    // size of every cascade or cube face, rounded up to a power of two
+    pub resolution : u32,
+    pub bias : f32,
+    // offset along the surface normal in texels
+    pub normal_bias : f32,
+    pub cascades : u32,
+    // blend between uniform (0) and logarithmic (1) cascade splits
+    pub split_lambda : f32,
+    // sun shadows end at this view depth or the far plane of the camera
+    pub max_distance : f32,
+    // near plane of the cube faces of point lights
+    pub near : f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings{resolution : 1024, bias : 0.005, normal_bias : 1.0, cascades : 4, split_lambda : 0.75, max_distance : 100.0, near : 0.05}
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Light {
     data_ : CommonObjectData,
     type_ : LightType,
     pub intensity : f32,
+    // full angle of the spot light cone
     pub fov : f32,
+    // full angle of the fully lit inner cone, the light fades out towards the fov
+    pub inner_fov : f32,
     pub range : f32,
     pub color : [f32; 3],
+    pub attenuation : Attenuation,
+    pub area_shape : AreaShape,
+    // only sun and point lights cast shadows
+    pub shadow : Option<ShadowSettings>,
     pub priority : u32
 }
 
@@ -43,19 +142,49 @@ impl Light {
             type_,
             intensity,
             fov,
+            inner_fov : fov,
             range,
             color,
+            attenuation : Default::default(),
+            area_shape : Default::default(),
+            shadow : None,
             priority : 0
         }
     }
     pub fn get_type(&self) -> LightType{
         self.type_
     }
+    pub fn is_spot(&self) -> bool{
+        matches!(self.type_, LightType::Spot | LightType::Lamp)
+    }
+    // spot and area lights shine along the local -z axis
+    pub fn get_direction(&self) -> [f64; 3]{
+        let direction = DQuat::new(&self.data_.rot).to_mat4x4() * DVec4::new(&[0.0, 0.0, -1.0, 0.0]);
+        [direction[0], direction[1], direction[2]]
+    }
+    // cosines of the half angles of the inner and outer cone
+    pub fn get_cone_cosines(&self) -> (f32, f32){
+        let outer = self.fov * 0.5;
+        ((self.inner_fov.min(self.fov) * 0.5).cos(), outer.cos())
+    }
+    // brightness factor of a point in the given distance and direction from the light
+    pub fn get_attenuation(&self, distance : f32, direction : [f64; 3]) -> f32{
+        let mut output = self.attenuation.get_factor(distance, self.range);
+        if self.is_spot() {
+            let (inner, outer) = self.get_cone_cosines();
+            let forward = self.get_direction();
+            let length = direction.iter().map(|x| x * x).sum::<f64>().sqrt().max(f64::EPSILON);
+            let cosine = (forward.iter().zip(direction.iter()).map(|(a, b)| a * b).sum::<f64>() / length) as f32;
+            output *= ((cosine - outer) / (inner - outer).max(f32::EPSILON)).clamp(0.0, 1.0);
+        }
+        output
+    }
 }
 
 impl ObjectTrait for Light {
     fn get_camera(&self) -> Option<Camera> {None}
     fn get_light(&self) -> Option<Light> {Some(self.clone())}
+    fn get_light_mut(&mut self) -> Option<&mut Light> {Some(self)}
     fn get_mesh(&self) -> Option<Mesh> {None}
     fn get_mesh_mut(&mut self) -> Option<&mut Mesh> {None}
     fn get_data(&self) -> &CommonObjectData {
@@ -64,7 +193,30 @@ impl ObjectTrait for Light {
     fn get_data_mut(&mut self) -> &mut CommonObjectData {
         &mut self.data_
     }
-    fn update(&mut self){
-        
+    fn update(&mut self) -> bool{
+        false
+    }
+}
+#[cfg(test)]
+pub mod lighttest{
+    use super::*;
+
+    #[test]
+    fn attenuation_factor(){
+        let custom = Attenuation::Custom{constant : 1.0, linear : 0.5, quadratic : 0.25};
+        for attenuation in [Attenuation::None, Attenuation::Linear, Attenuation::InverseSquare, custom]{
+            // nothing is lit at or beyond the range
+            assert_eq!(attenuation.get_factor(10.0, 10.0), 0.0);
+            assert_eq!(attenuation.get_factor(20.0, 10.0), 0.0);
+            assert_eq!(attenuation.get_factor(0.0, 10.0), 1.0);
+            assert!(attenuation.get_factor(2.0, 10.0) >= attenuation.get_factor(3.0, 10.0));
+        }
+        assert_eq!(Attenuation::None.get_factor(9.0, 10.0), 1.0);
+        assert_eq!(Attenuation::Linear.get_factor(2.5, 10.0), 0.75);
+        assert!((Attenuation::InverseSquare.get_factor(1.0, 10.0) - 0.5 * (1.0 - 1e-4f32).powi(2)).abs() < 1e-6);
+        assert!(Attenuation::InverseSquare.get_factor(9.99, 10.0) < 1e-4);
+        assert_eq!(custom.get_factor(2.0, 10.0), 1.0 / 3.0);
+        // coefficients of 0 do not divide by 0
+        assert!(Attenuation::Custom{constant : 0.0, linear : 0.0, quadratic : 0.0}.get_factor(0.0, 10.0).is_finite());
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,97 @@
 use std::sync::atomic::{Ordering, AtomicUsize};
+use compact_str::CompactString;
 
-#[derive(Default, Debug, Clone, Copy)]
+use super::texture::{TextureSlot, TextureSlotType, TEXTURE_SLOTS};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode{
+    #[default]
+    Opaque,
+    // fragments with an alpha below the cutoff are discarded
+    Mask,
+    Blend,
+}
+
+impl AlphaMode{
+    // value of alpha_mode in csl materials
+    pub fn name(&self) -> &'static str{
+        match self{
+            AlphaMode::Opaque => "opaque",
+            AlphaMode::Mask => "mask",
+            AlphaMode::Blend => "blend",
+        }
+    }
+    pub fn from_name(name : &str) -> Option<AlphaMode>{
+        [AlphaMode::Opaque, AlphaMode::Mask, AlphaMode::Blend].into_iter().find(|x| x.name() == name)
+    }
+}
+
+// metallic-roughness parameters, the textures of the material slots are shared with the legacy model
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial{
+    pub base_color : [f32; 4],
+    pub metallic : f32,
+    pub roughness : f32,
+    pub emissive : [f32; 3],
+    // strength of the ambient occlusion
+    pub occlusion : f32,
+    pub alpha_mode : AlphaMode,
+    pub alpha_cutoff : f32,
+    pub double_sided : bool,
+}
+
+impl Default for PbrMaterial{
+    fn default() -> Self{
+        PbrMaterial{
+            base_color : [1.0; 4],
+            metallic : 0.0,
+            roughness : 1.0,
+            emissive : [0.0; 3],
+            occlusion : 1.0,
+            alpha_mode : AlphaMode::Opaque,
+            alpha_cutoff : 0.5,
+            double_sided : false,
+        }
+    }
+}
+
+impl PbrMaterial{
+    // conversion of the legacy fields:
+    // base_color = dif rgb with alpha as its alpha, the legacy model has no metals so metallic = 0,
+    // roughness = (2 / (specular_hardness + 2))^(1/4) which matches the highlight size of the blinn-phong exponent,
+    // a specular_intensity of 0 gives roughness = 1, emissive = dif rgb * illuminosity,
+    // alpha below 1 blends and translucent materials are lit from both sides
+    pub fn from_legacy(material : &Material) -> PbrMaterial{
+        let roughness = if material.specular_intensity > 0.0 {(2.0 / (material.specular_hardness.max(0.0) + 2.0)).powf(0.25)} else {1.0};
+        PbrMaterial{
+            base_color : [material.dif_[0], material.dif_[1], material.dif_[2], material.alpha],
+            metallic : 0.0,
+            roughness,
+            emissive : [0, 1, 2].map(|i| material.dif_[i] * material.illuminosity),
+            occlusion : 1.0,
+            alpha_mode : if material.alpha < 1.0 {AlphaMode::Blend} else {AlphaMode::Opaque},
+            alpha_cutoff : 0.5,
+            double_sided : material.translucency > 0.0,
+        }
+    }
+
+    // base_color, emissive + occlusion, metallic + roughness + alpha_cutoff + alpha_mode, double_sided + padding
+    pub fn get_renderer_data(&self) -> Vec<f32>{
+        let mut output = Vec::with_capacity(16);
+        output.extend_from_slice(&self.base_color);
+        output.extend_from_slice(&self.emissive);
+        output.push(self.occlusion);
+        output.push(self.metallic);
+        output.push(self.roughness);
+        output.push(self.alpha_cutoff);
+        output.push(self.alpha_mode as u8 as f32);
+        output.push(self.double_sided as u8 as f32);
+        output.extend_from_slice(&[0.0; 3]);
+        output
+    }
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct Material {
     id_ : usize,
     pub dif_ : [f32; 4],
@@ -10,6 +101,10 @@ pub struct Material {
     pub illuminosity : f32,
     pub specular_intensity : f32,
     pub specular_hardness : f32,
+    // indexed by TextureSlotType
+    pub textures : [Option<TextureSlot>; TEXTURE_SLOTS],
+    // the legacy fields are ignored by the renderer if set
+    pub pbr : Option<PbrMaterial>,
 }
 
 impl Material{
@@ -20,10 +115,41 @@ impl Material{
         output
     }
 
+    // copy with its own id
+    pub fn duplicate(&self) -> Material{
+        Material{id_ : Material::new().id_, ..self.clone()}
+    }
+
     pub fn id(&self) -> usize{
         self.id_
     }
+    pub fn get_texture(&self, slot : TextureSlotType) -> Option<&TextureSlot>{
+        self.textures[slot as usize].as_ref()
+    }
+    pub fn set_texture(&mut self, slot : TextureSlotType, texture : Option<TextureSlot>){
+        self.textures[slot as usize] = texture;
+    }
+    pub fn get_texture_names(&self) -> impl Iterator<Item = &CompactString>{
+        self.textures.iter().flatten().map(|x| &x.texture)
+    }
+
+    // the legacy fields stay as they are
+    pub fn convert_to_pbr(&mut self){
+        self.pbr = Some(PbrMaterial::from_legacy(self));
+    }
+    pub fn is_pbr(&self) -> bool{
+        self.pbr.is_some()
+    }
+
+    // 16 floats for pbr materials, see PbrMaterial::get_renderer_data
     pub fn get_renderer_data(&self) -> Vec<f32>{
+        match &self.pbr{
+            Some(pbr) => pbr.get_renderer_data(),
+            None => self.get_legacy_renderer_data(),
+        }
+    }
+    // dif, scol, alpha, specular_intensity, specular_hardness, translucency, illuminosity
+    pub fn get_legacy_renderer_data(&self) -> Vec<f32>{
         let mut output = Vec::with_capacity(12);
         output.extend_from_slice(&self.dif_);
         output.extend_from_slice(&self.scol);
@@ -34,4 +160,70 @@ impl Material{
         output.push(self.illuminosity);
         output
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub mod materialtest{
+    use super::*;
+
+    fn legacy() -> Material{
+        Material{
+            dif_ : [0.8, 0.4, 0.2, 1.0],
+            scol : [1.0; 3],
+            alpha : 1.0,
+            illuminosity : 0.5,
+            specular_intensity : 0.5,
+            specular_hardness : 30.0,
+            ..Material::new()
+        }
+    }
+
+    #[test]
+    fn from_legacy(){
+        let pbr = PbrMaterial::from_legacy(&legacy());
+        assert_eq!(pbr.base_color, [0.8, 0.4, 0.2, 1.0]);
+        assert_eq!((pbr.metallic, pbr.occlusion, pbr.alpha_cutoff), (0.0, 1.0, 0.5));
+        // (2 / 32)^(1/4)
+        assert!((pbr.roughness - 0.5).abs() < 1e-6);
+        assert_eq!(pbr.emissive, [0.4, 0.2, 0.1]);
+        assert_eq!((pbr.alpha_mode, pbr.double_sided), (AlphaMode::Opaque, false));
+
+        let pbr = PbrMaterial::from_legacy(&Material{alpha : 0.5, translucency : 0.1, specular_intensity : 0.0, ..legacy()});
+        assert_eq!(pbr.base_color[3], 0.5);
+        assert_eq!(pbr.roughness, 1.0);
+        assert_eq!((pbr.alpha_mode, pbr.double_sided), (AlphaMode::Blend, true));
+
+        // the legacy fields stay as they are
+        let mut material = legacy();
+        material.convert_to_pbr();
+        assert!(material.is_pbr());
+        assert_eq!(material.get_legacy_renderer_data(), legacy().get_legacy_renderer_data());
+    }
+
+    #[test]
+    fn packed_layout(){
+        let material = legacy();
+        assert_eq!(material.get_renderer_data(), [0.8, 0.4, 0.2, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5, 30.0, 0.0, 0.5]);
+
+        let pbr = PbrMaterial{
+            base_color : [0.1, 0.2, 0.3, 0.4],
+            metallic : 0.5,
+            roughness : 0.6,
+            emissive : [0.7, 0.8, 0.9],
+            occlusion : 0.25,
+            alpha_mode : AlphaMode::Mask,
+            alpha_cutoff : 0.3,
+            double_sided : true,
+        };
+        let data = Material{pbr : Some(pbr), ..legacy()}.get_renderer_data();
+        assert_eq!(data, [0.1, 0.2, 0.3, 0.4, 0.7, 0.8, 0.9, 0.25, 0.5, 0.6, 0.3, 1.0, 1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(PbrMaterial::default().get_renderer_data()[12], AlphaMode::Opaque as u8 as f32);
+    }
+
+    #[test]
+    fn alpha_mode_names(){
+        for mode in [AlphaMode::Opaque, AlphaMode::Mask, AlphaMode::Blend]{
+            assert_eq!(AlphaMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(AlphaMode::from_name("cutout"), None);
+    }
+}
@@ -5,21 +5,125 @@ use super::camera::*;
 use super::light::*;
 
 use super::object_trait::*;
-use super::polygonstorage::{DynamicPolygonStorage, StaticPolygonStorage};
+use super::polygonstorage::{DynamicPolygonStorage, StaticPolygonStorage, SoftbodyPolygonStorage};
 use super::polygonstoragetrait::*;
+use super::armature::*;
+use super::morphtarget::*;
 
 #[derive(Debug, Clone)]
 pub struct Mesh {
     data_ : CommonObjectData,
     pub polygon_storage_ : (CompactString, Arc<Mutex<(Box<dyn PolygonStorageTrait>, bool)>>),
+    pub armature_ : Option<Box<Armature>>,
+    pub morph_targets_ : Option<Box<MorphTargets>>,
+    // name of the mesh whose polygon storage is shared, resolved when the mesh is added to the scenegraph
+    pub instance_of_ : Option<CompactString>,
+    // vertex group materials were reassigned since the last update
+    vgroups_changed_ : bool,
 }
 
 impl Mesh {
     pub fn new_static(positions : Vec<f32>, normals : Vec<f32>, uvmaps : Vec<UVMapData>, indices : Vec<u32>, vgroups : Vec<VertexGroup>, polygons_name : &str) -> Mesh{
+        Mesh::from_storage(DynamicPolygonStorage::new(positions, normals, uvmaps, indices, vgroups), PolygonStorageType::Static, polygons_name)
+    }
+
+    pub fn new_softbody(positions : Vec<f32>, normals : Vec<f32>, uvmaps : Vec<UVMapData>, indices : Vec<u32>, vgroups : Vec<VertexGroup>, polygons_name : &str) -> Mesh{
+        Mesh::from_storage(DynamicPolygonStorage::new(positions, normals, uvmaps, indices, vgroups), PolygonStorageType::SoftBody, polygons_name)
+    }
+
+    // converts the storage to the requested type
+    pub fn from_storage(storage : DynamicPolygonStorage, polygons_type : PolygonStorageType, polygons_name : &str) -> Mesh{
+        let storage : Box<dyn PolygonStorageTrait> = match polygons_type{
+            PolygonStorageType::Static => Box::new(StaticPolygonStorage::new(storage)),
+            PolygonStorageType::SoftBody => Box::new(SoftbodyPolygonStorage::new(storage)),
+            PolygonStorageType::Dynamic => Box::new(storage),
+        };
+        Mesh::from_polygon_storage(storage, polygons_name)
+    }
+
+    pub fn from_polygon_storage(storage : Box<dyn PolygonStorageTrait>, polygons_name : &str) -> Mesh{
         Mesh{
             data_ : CommonObjectData::new(ObjectType::Mesh),
-            polygon_storage_ : (polygons_name.into(), Arc::new(Mutex::new((Box::new(StaticPolygonStorage::new(DynamicPolygonStorage::new(positions, normals, uvmaps, indices, vgroups))), true))))
+            polygon_storage_ : (polygons_name.into(), Arc::new(Mutex::new((storage, true)))),
+            armature_ : None,
+            morph_targets_ : None,
+            instance_of_ : None,
+            vgroups_changed_ : false,
+        }
+    }
+
+    // shares the polygon storage of this mesh, deformed meshes can not be instanced
+    pub fn new_instance(&self) -> Option<Mesh>{
+        if self.armature_.is_some() || self.morph_targets_.is_some() || self.instance_of_.is_some() {
+            return None;
+        }
+        if matches!(self.get_polygonstorage_unlocked().0.get_type(), PolygonStorageType::SoftBody) {
+            return None;
+        }
+        Some(Mesh{
+            data_ : CommonObjectData::new(ObjectType::Mesh),
+            polygon_storage_ : self.polygon_storage_.clone(),
+            armature_ : None,
+            morph_targets_ : None,
+            instance_of_ : None,
+            vgroups_changed_ : false,
+        })
+    }
+
+    // placeholder for a mesh that is not loaded yet, the scenegraph resolves it by name
+    pub fn new_instance_of(source_name : &str) -> Mesh{
+        let mut output = Mesh::from_storage(DynamicPolygonStorage::new(vec![], vec![], vec![], vec![], vec![]), PolygonStorageType::Dynamic, source_name);
+        output.instance_of_ = Some(source_name.into());
+        output
+    }
+
+    pub fn resolve_instance(&mut self, source : &Mesh) -> bool{
+        let Some(instance) = source.new_instance() else {return false;};
+        self.polygon_storage_ = instance.polygon_storage_;
+        self.instance_of_ = None;
+        true
+    }
+
+    // copy with its own object id, the polygon storage stays shared
+    pub fn duplicate(&self) -> Mesh{
+        let mut output = self.clone();
+        output.data_.id_ = CommonObjectData::new(ObjectType::Mesh).id_;
+        output
+    }
+
+    // stops sharing the polygon storage with other meshes
+    pub fn copy_polygonstorage(&mut self){
+        let storage = self.get_polygonstorage_unlocked().0.box_clone();
+        self.polygon_storage_.1 = Arc::new(Mutex::new((storage, true)));
+    }
+
+    // the renderer reads the vertex groups again after the next update
+    pub fn set_vgroups_changed(&mut self){
+        self.vgroups_changed_ = true;
+    }
+
+    pub fn shares_polygonstorage(&self, other : &Mesh) -> bool{
+        Arc::ptr_eq(&self.polygon_storage_.1, &other.polygon_storage_.1)
+    }
+
+    // the armature deforms the rest geometry of a softbody polygon storage
+    pub fn set_armature(&mut self, mut armature : Armature) -> bool{
+        let mut polygons = self.polygon_storage_.1.lock().unwrap();
+        let Some(storage) = polygons.0.get_softbody_mut() else {return false;};
+        armature.bind(storage);
+        drop(polygons);
+        self.armature_ = Some(Box::new(armature));
+        true
+    }
+
+    // fails if the polygon storage can not be deformed or the target is out of range
+    pub fn add_morph_target(&mut self, target : MorphTarget, weight : f32) -> bool{
+        if self.morph_targets_.is_none(){
+            let mut polygons = self.polygon_storage_.1.lock().unwrap();
+            let Some((positions, normals)) = polygons.0.get_vertex_data_mut() else {return false;};
+            self.morph_targets_ = Some(Box::new(MorphTargets::new(positions.clone(), normals.clone())));
         }
+        self.morph_targets_.as_mut().unwrap().add_target(target, weight)
     }
 
     pub fn vertexgroup_names(&self) -> Vec<CompactString>{
@@ -42,8 +146,38 @@ impl ObjectTrait for Mesh {
     fn get_data_mut(&mut self) -> &mut CommonObjectData {
         &mut self.data_
     }
-    fn update(&mut self){
+    fn update(&mut self) -> bool{
         let mut polygons = self.polygon_storage_.1.lock().unwrap();
-        polygons.1 = false;
+        // edits of dynamic polygon storages since the last update, instances sharing the storage see the flag of the first one
+        let vgroups_changed = std::mem::take(&mut self.vgroups_changed_);
+        let edited = polygons.0.is_changed_and_reset() || polygons.1 || vgroups_changed;
+        polygons.1 = edited;
+
+        let morphs_changed = self.morph_targets_.as_mut().map(|x| x.is_changed_and_reset()).unwrap_or(false);
+        let pose_changed = self.armature_.as_mut().map(|x| x.update()).unwrap_or(false);
+        let cpu_skinning = self.armature_.as_ref().map(|x| x.skinning_mode == SkinningMode::Cpu).unwrap_or(false);
+        if !(morphs_changed || pose_changed) {
+            return edited;
+        }
+        if !(morphs_changed || cpu_skinning) {
+            // only the bone matrices changed
+            return true;
+        }
+
+        // deform pipeline: rest geometry -> morph targets -> skinning
+        let Some((positions, normals)) = polygons.0.get_vertex_data_mut() else {return true;};
+        match (&self.morph_targets_, &self.armature_){
+            (Some(morph_targets), Some(armature)) if cpu_skinning => {
+                morph_targets.apply(positions, normals);
+                let (morphed_positions, morphed_normals) = (positions.clone(), normals.clone());
+                armature.skin_vertices(&morphed_positions, &morphed_normals, positions, normals);
+            }
+            (Some(morph_targets), _) => morph_targets.apply(positions, normals),
+            (None, Some(armature)) => armature.skin_vertices(&armature.skin.rest_positions, &armature.skin.rest_normals, positions, normals),
+            (None, None) => {}
+        }
+        polygons.0.regenerate_data();
+        polygons.1 = true;
+        true
     }
 }
\ No newline at end of file
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+use compact_str::CompactString;
+
+// sparse deltas, only the moved positions and normals are stored
+#[derive(Clone, Debug, Default)]
+pub struct MorphTarget{
+    pub name : CompactString,
+    pub position_deltas : Vec<(u32, [f32; 3])>,
+    pub normal_deltas : Vec<(u32, [f32; 3])>,
+}
+
+impl MorphTarget{
+    // deltas are flattened, three per index
+    pub fn new(name : &str, positions : &[u32], position_deltas : &[f32], normals : &[u32], normal_deltas : &[f32]) -> Option<MorphTarget>{
+        if position_deltas.len() != positions.len()*3 || normal_deltas.len() != normals.len()*3 {
+            return None;
+        }
+        let to_deltas = |ids : &[u32], deltas : &[f32]| -> Vec<(u32, [f32; 3])>{
+            ids.iter().zip(deltas.chunks(3)).map(|(id, delta)| (*id, [delta[0], delta[1], delta[2]])).collect()
+        };
+        Some(MorphTarget{
+            name : name.into(),
+            position_deltas : to_deltas(positions, position_deltas),
+            normal_deltas : to_deltas(normals, normal_deltas),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MorphTargets{
+    targets : Vec<Arc<MorphTarget>>,
+    weights : Vec<f32>,
+    // undeformed geometry, shared between all copies of the mesh
+    rest : Arc<(Vec<f32>, Vec<f32>)>,
+    changed : bool,
+}
+
+impl MorphTargets{
+    pub fn new(rest_positions : Vec<f32>, rest_normals : Vec<f32>) -> MorphTargets{
+        MorphTargets{
+            rest : Arc::new((rest_positions, rest_normals)),
+            changed : true,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_target(&mut self, target : MorphTarget, weight : f32) -> bool{
+        let num_positions = self.rest.0.len()/3;
+        let num_normals = self.rest.1.len()/3;
+        if target.position_deltas.iter().any(|x| x.0 as usize >= num_positions) || target.normal_deltas.iter().any(|x| x.0 as usize >= num_normals){
+            return false;
+        }
+        if let Some(id) = self.get_target_id(&target.name){
+            self.targets[id] = Arc::new(target);
+            self.weights[id] = weight;
+        }
+        else {
+            self.targets.push(Arc::new(target));
+            self.weights.push(weight);
+        }
+        self.changed = true;
+        true
+    }
+
+    pub fn get_target_id(&self, name : &str) -> Option<usize>{
+        self.targets.iter().position(|x| x.name == name)
+    }
+    pub fn get_target_names(&self) -> Vec<CompactString>{
+        self.targets.iter().map(|x| x.name.clone()).collect()
+    }
+    pub fn get_weights(&self) -> &Vec<f32>{
+        &self.weights
+    }
+    pub fn get_weight(&self, name : &str) -> Option<f32>{
+        Some(self.weights[self.get_target_id(name)?])
+    }
+    pub fn set_weight(&mut self, name : &str, weight : f32) -> bool{
+        let Some(id) = self.get_target_id(name) else {return false;};
+        if self.weights[id] != weight {
+            self.weights[id] = weight;
+            self.changed = true;
+        }
+        true
+    }
+    pub fn is_changed_and_reset(&mut self) -> bool{
+        std::mem::replace(&mut self.changed, false)
+    }
+    pub fn get_rest(&self) -> &(Vec<f32>, Vec<f32>){
+        &self.rest
+    }
+
+    // writes the rest geometry with all weighted deltas applied
+    pub fn apply(&self, positions : &mut Vec<f32>, normals : &mut Vec<f32>){
+        positions.clone_from(&self.rest.0);
+        normals.clone_from(&self.rest.1);
+
+        let mut normals_changed = false;
+        for (target, weight) in self.targets.iter().zip(self.weights.iter()){
+            if *weight == 0.0 {continue;}
+            for (id, delta) in &target.position_deltas{
+                let id = *id as usize*3;
+                for axis in 0..3{
+                    positions[id+axis] += delta[axis]*weight;
+                }
+            }
+            for (id, delta) in &target.normal_deltas{
+                let id = *id as usize*3;
+                for axis in 0..3{
+                    normals[id+axis] += delta[axis]*weight;
+                }
+            }
+            normals_changed |= !target.normal_deltas.is_empty();
+        }
+
+        if normals_changed {
+            for normal in normals.chunks_mut(3){
+                let length = (normal[0]*normal[0] + normal[1]*normal[1] + normal[2]*normal[2]).sqrt();
+                if length > 1e-12 {
+                    normal.iter_mut().for_each(|x| *x /= length);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod morphtargettest{
+    use super::*;
+
+    fn assert_close(a : &[f32], b : &[f32]){
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn apply_weighted_deltas(){
+        assert!(MorphTarget::new("broken", &[0, 1], &[1.0; 3], &[], &[]).is_none());
+
+        let mut targets = MorphTargets::new(vec![0.0; 6], vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert!(targets.is_changed_and_reset());
+        assert!(!targets.add_target(MorphTarget::new("outside", &[2], &[1.0, 0.0, 0.0], &[], &[]).unwrap(), 1.0));
+        assert!(!targets.is_changed_and_reset());
+        assert!(targets.add_target(MorphTarget::new("up", &[1], &[0.0, 2.0, 0.0], &[1], &[1.0, 0.0, -1.0]).unwrap(), 0.5));
+        assert!(targets.add_target(MorphTarget::new("right", &[0, 1], &[1.0, 0.0, 0.0, 1.0, 0.0, 0.0], &[], &[]).unwrap(), 0.0));
+        assert!(targets.is_changed_and_reset());
+
+        let (mut positions, mut normals) = (vec![], vec![]);
+        targets.apply(&mut positions, &mut normals);
+        assert_close(&positions, &[0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        // the moved normal is renormalized
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(&normals, &[0.0, 0.0, 1.0, half, 0.0, half]);
+
+        // weights add up, setting the same weight again is not a change
+        assert!(targets.set_weight("right", -1.0));
+        assert!(targets.is_changed_and_reset());
+        assert!(targets.set_weight("right", -1.0) && !targets.is_changed_and_reset());
+        assert!(!targets.set_weight("missing", 1.0));
+        targets.apply(&mut positions, &mut normals);
+        assert_close(&positions, &[-1.0, 0.0, 0.0, -1.0, 1.0, 0.0]);
+
+        // a target with the same name replaces the old one
+        assert!(targets.add_target(MorphTarget::new("up", &[0], &[0.0, 0.0, 4.0], &[], &[]).unwrap(), 0.25));
+        assert_eq!(targets.get_target_names(), vec!["up", "right"]);
+        assert_eq!(targets.get_weight("up"), Some(0.25));
+        targets.apply(&mut positions, &mut normals);
+        assert_close(&positions, &[-1.0, 0.0, 1.0, -1.0, 0.0, 0.0]);
+        assert_close(&normals, &[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(targets.get_rest().0, vec![0.0; 6]);
+    }
+}
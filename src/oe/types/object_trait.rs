@@ -62,8 +62,11 @@ pub trait ObjectTrait : Send + std::fmt::Debug{
     fn get_light(&self) -> Option<Light>;
     fn get_mesh(&self) -> Option<Mesh>;
     fn get_mesh_mut(&mut self) -> Option<&mut Mesh>;
+    fn get_camera_mut(&mut self) -> Option<&mut Camera> {None}
+    fn get_light_mut(&mut self) -> Option<&mut Light> {None}
     fn get_linked_objects(&self) -> HashSet<CompactString>{ Default::default()}
-    fn update(&mut self);
+    // returns true if the object changed itself
+    fn update(&mut self) -> bool;
     // trait functions with default automatic implementations
     fn id(&self) -> usize {
         self.get_data().id_
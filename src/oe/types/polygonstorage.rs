@@ -1,7 +1,13 @@
 //use crate::oe::TraitWrapper;
 
 //use std::collections::HashSet;
+use std::collections::HashMap;
 use super::polygonstoragetrait::*;
+use super::tangentspace::*;
+use super::vertexcache::{optimize, CacheOptimizationReport};
+use super::quantization::*;
+use super::simplification::generate_lods;
+use std::borrow::Cow;
 use nohash_hasher::{IntMap, IntSet};
 //use std::ops::Index;
 
@@ -24,6 +30,9 @@ impl PolygonStorageTrait for RendererPolygonStorage {
     fn get_type(&self) -> PolygonStorageType{
         PolygonStorageType::Static
     }
+    fn box_clone(&self) -> Box<dyn PolygonStorageTrait> {
+        Box::new(self.clone())
+    }
     
     // only useful for dynamic meshes
     fn regenerate_data(&mut self) {
@@ -35,14 +44,44 @@ impl PolygonStorageTrait for RendererPolygonStorage {
 #[derive(Default, Clone, Debug)]
 pub struct StaticPolygonStorage{
     pub data : PolygonStorageData,
+    // replaces the float vertex buffer
+    compressed : Option<QuantizedVertexBuffer>,
+    cache_report : Option<CacheOptimizationReport>,
 }
 
 impl StaticPolygonStorage{
     pub fn new(dynamic_data : DynamicPolygonStorage) -> StaticPolygonStorage{
         StaticPolygonStorage{
-            data : dynamic_data.get_data().unwrap().clone()
+            data : dynamic_data.get_data().unwrap().clone(),
+            compressed : None,
+            cache_report : None,
+        }
+    }
+    // a float format brings back the float vertex buffer, with the precision of the previous format
+    // tangents and levels of detail can not be generated afterwards
+    pub fn compress(&mut self, format : VertexFormat){
+        if let Some(compressed) = self.compressed.take() {
+            self.data.vertex_buffer_ = compressed.dequantize();
+        }
+        if !format.is_float() {
+            self.compressed = Some(QuantizedVertexBuffer::new(&self.data, format));
+            self.data.vertex_buffer_ = vec![];
         }
     }
+    pub fn get_vertex_format(&self) -> VertexFormat{
+        self.compressed.as_ref().map(|x| x.format).unwrap_or_default()
+    }
+    // reorders triangles and vertices for the vertex cache, overdraw and vertex fetch
+    pub fn new_optimized(dynamic_data : DynamicPolygonStorage) -> StaticPolygonStorage{
+        let mut output = StaticPolygonStorage::new(dynamic_data);
+        output.cache_report = Some(optimize(&mut output.data));
+        output.data.indices_changed();
+        output
+    }
+    // ACMR before and after the optimization, None if the storage was not optimized
+    pub fn get_cache_report(&self) -> Option<CacheOptimizationReport>{
+        self.cache_report
+    }
 }
 
 impl PolygonStorageTrait for StaticPolygonStorage {
@@ -55,10 +94,105 @@ impl PolygonStorageTrait for StaticPolygonStorage {
     fn get_type(&self) -> PolygonStorageType{
         PolygonStorageType::Static
     }
+    fn box_clone(&self) -> Box<dyn PolygonStorageTrait> {
+        Box::new(self.clone())
+    }
     // only useful for dynamic meshes
     fn regenerate_data(&mut self) {
         
     }
+    fn get_dequantized_vertex_buffer(&self) -> Cow<'_, [f32]> {
+        match &self.compressed{
+            Some(compressed) => Cow::Owned(compressed.dequantize()),
+            None => Cow::Borrowed(&self.data.vertex_buffer_),
+        }
+    }
+    fn get_quantized_vertex_buffer(&self) -> Option<&QuantizedVertexBuffer> {
+        self.compressed.as_ref()
+    }
+    fn get_vertex_layout(&self) -> VertexLayout {
+        match &self.compressed{
+            Some(compressed) => compressed.layout.clone(),
+            None => VertexLayout::new(VertexFormat::default(), self.data.num_of_uvs, self.data.tangent_uvmap.is_some()),
+        }
+    }
+    fn len(&self) -> usize {
+        match &self.compressed{
+            Some(compressed) => compressed.len(),
+            None => self.data.vertex_buffer_.len()/self.data.vertex_length(),
+        }
+    }
+    fn set_tangents(&mut self, uvmap : Option<u8>) -> bool {
+        self.compressed.is_none() && self.data.set_tangents(uvmap)
+    }
+    fn generate_lods(&mut self, ratios : &[f32]) -> bool {
+        if self.compressed.is_some() {
+            return false;
+        }
+        self.data.lods = generate_lods(&self.data, ratios);
+        self.data.indices_changed();
+        true
+    }
+    // vertices with the same position are smoothed together, vertices that end up with several normals are split
+    fn generate_normals(&mut self, mode : NormalMode, crease_angle : Option<f32>) -> bool {
+        if self.compressed.is_some() {
+            return false;
+        }
+        let tangent_uvmap = self.data.tangent_uvmap;
+        self.data.set_tangents(None);
+        let stride = self.data.vertex_length();
+
+        let mut position_ids : HashMap<[u32; 3], u32> = HashMap::new();
+        let mut positions = vec![];
+        let vertex_positions : Vec<u32> = self.data.vertex_buffer_.chunks(stride).map(|vertex| {
+            let position = [vertex[0], vertex[1], vertex[2]];
+            *position_ids.entry(position.map(|x| (x + 0.0).to_bits())).or_insert_with(|| {
+                positions.extend_from_slice(&position);
+                (positions.len()/3 - 1) as u32
+            })
+        }).collect();
+        // position and vertex of every corner
+        let corners : Vec<u32> = get_corners(&self.data).into_iter().flat_map(|vertex| [vertex_positions[vertex as usize], vertex]).collect();
+        let (normals, normal_ids) = generate_normals(&positions, &corners, 2, mode, crease_angle);
+
+        // vertices that only differed in their normals are merged again
+        let mut vertex_buffer = Vec::with_capacity(self.data.vertex_buffer_.len());
+        let mut vertex_ids : HashMap<Vec<u32>, u32> = HashMap::new();
+        let corner_vertices : Vec<u32> = corners.chunks(2).zip(normal_ids).map(|(corner, normal)| {
+            let old = &self.data.vertex_buffer_[corner[1] as usize*stride..(corner[1] as usize+1)*stride];
+            let vertex : Vec<f32> = old[..3].iter().chain(&normals[normal as usize*3..normal as usize*3+3]).chain(&old[6..]).copied().collect();
+            *vertex_ids.entry(vertex.iter().map(|x| (x + 0.0).to_bits()).collect()).or_insert_with(|| {
+                vertex_buffer.extend_from_slice(&vertex);
+                (vertex_buffer.len()/stride - 1) as u32
+            })
+        }).collect();
+        self.data.vertex_buffer_ = vertex_buffer;
+        set_corners(&mut self.data, &corner_vertices);
+        self.data.set_tangents(tangent_uvmap);
+        let ratios : Vec<f32> = self.data.lods.iter().map(|x| x.ratio).collect();
+        self.data.lods = generate_lods(&self.data, &ratios);
+        self.data.indices_changed();
+        true
+    }
+}
+
+// vertex of every corner of every vertex group, ordered by the vertex group ids
+fn get_corners(data : &PolygonStorageData) -> Vec<u32>{
+    let mut vgroups : Vec<&usize> = data.index_buffers_.keys().collect();
+    vgroups.sort();
+    vgroups.into_iter().flat_map(|id| data.index_buffers_[id].iter().copied()).collect()
+}
+// replaces the index buffers, the corners are in the order of get_corners
+fn set_corners(data : &mut PolygonStorageData, corners : &[u32]){
+    let mut vgroups : Vec<usize> = data.index_buffers_.keys().copied().collect();
+    vgroups.sort();
+    let mut offset = 0;
+    for id in vgroups{
+        let index_buffer = data.index_buffers_.get_mut(&id).unwrap();
+        let len = index_buffer.len();
+        index_buffer.copy_from_slice(&corners[offset..offset+len]);
+        offset += len;
+    }
 }
 
 // SOFT BODY MAP (suitable for soft bodies)
@@ -100,6 +234,72 @@ impl SoftbodyPolygonStorage{
             regenerated_data : true,
         }
     }
+
+    // triangles of all vertex groups as indices into positions
+    pub fn get_position_triangles(&self) -> Vec<[u32; 3]>{
+        let mut output = vec![];
+        for id in 0..self.data.vgroups.len(){
+            for tri in self.data.index_buffers_[&id].chunks(3){
+                output.push([self.vertex_buffer_[tri[0] as usize][0], self.vertex_buffer_[tri[1] as usize][0], self.vertex_buffer_[tri[2] as usize][0]]);
+            }
+        }
+        output
+    }
+
+    pub fn get_vgroup_positions(&self, vgroup_name : &str) -> Vec<u32>{
+        let mut output : IntSet<u32> = Default::default();
+        for (id, vgroup) in self.data.vgroups.iter().enumerate(){
+            if vgroup.name != vgroup_name {continue;}
+            for vertex in self.data.index_buffers_[&id].iter(){
+                output.insert(self.vertex_buffer_[*vertex as usize][0]);
+            }
+        }
+        output.into_iter().collect()
+    }
+
+    // position paired with every vertex of the vertex buffer
+    pub fn get_vertex_positions(&self) -> Vec<u32>{
+        self.vertex_buffer_.iter().map(|x| x[0]).collect()
+    }
+
+    // a position paired with every normal
+    pub fn get_normal_positions(&self) -> Vec<u32>{
+        let mut output = vec![0; self.normals.len()/3];
+        for vertex in self.vertex_buffer_.iter(){
+            output[vertex[1] as usize] = vertex[0];
+        }
+        output
+    }
+
+    // smooth area weighted normals, written to every normal a position is paired with
+    pub fn recompute_normals(&mut self){
+        let mut position_normals = vec![0.0f32; self.positions.len()];
+        for tri in self.get_position_triangles(){
+            let p : Vec<[f32; 3]> = tri.iter().map(|id| {
+                let id = *id as usize*3;
+                [self.positions[id], self.positions[id+1], self.positions[id+2]]
+            }).collect();
+            let e1 = [p[1][0]-p[0][0], p[1][1]-p[0][1], p[1][2]-p[0][2]];
+            let e2 = [p[2][0]-p[0][0], p[2][1]-p[0][1], p[2][2]-p[0][2]];
+            let normal = [e1[1]*e2[2]-e1[2]*e2[1], e1[2]*e2[0]-e1[0]*e2[2], e1[0]*e2[1]-e1[1]*e2[0]];
+            for id in tri{
+                for axis in 0..3{
+                    position_normals[id as usize*3+axis] += normal[axis];
+                }
+            }
+        }
+        for normal in position_normals.chunks_mut(3){
+            let length = (normal[0]*normal[0] + normal[1]*normal[1] + normal[2]*normal[2]).sqrt();
+            if length > 1e-12 {
+                normal.iter_mut().for_each(|x| *x /= length);
+            }
+        }
+        for vertex in self.vertex_buffer_.iter(){
+            let init_pos = vertex[0] as usize*3;
+            let init_nor = vertex[1] as usize*3;
+            self.normals[init_nor..init_nor+3].copy_from_slice(&position_normals[init_pos..init_pos+3]);
+        }
+    }
 }
 
 impl PolygonStorageTrait for SoftbodyPolygonStorage {
@@ -109,9 +309,43 @@ impl PolygonStorageTrait for SoftbodyPolygonStorage {
     fn get_type(&self) -> PolygonStorageType{
         PolygonStorageType::SoftBody
     }
+    fn box_clone(&self) -> Box<dyn PolygonStorageTrait> {
+        Box::new(self.clone())
+    }
     fn get_data_mut(&mut self) -> Option<&mut PolygonStorageData> {
         Some(&mut self.data)
     }
+    fn get_softbody_mut(&mut self) -> Option<&mut SoftbodyPolygonStorage> {
+        Some(self)
+    }
+    fn get_vertex_data_mut(&mut self) -> Option<(&mut Vec<f32>, &mut Vec<f32>)> {
+        Some((&mut self.positions, &mut self.normals))
+    }
+    // vertices that end up with several normals are split
+    fn generate_normals(&mut self, mode : NormalMode, crease_angle : Option<f32>) -> bool {
+        let stride = 2+self.uvmaps.len();
+        let corners : Vec<u32> = get_corners(&self.data).into_iter().flat_map(|vertex| self.vertex_buffer_[vertex as usize].iter().copied()).collect();
+        let (normals, normal_ids) = generate_normals(&self.positions, &corners, stride, mode, crease_angle);
+
+        let mut vertex_buffer : Vec<Box<[u32]>> = vec![];
+        let mut vertex_ids : HashMap<Box<[u32]>, u32> = HashMap::new();
+        let corner_vertices : Vec<u32> = corners.chunks(stride).zip(normal_ids).map(|(corner, normal)| {
+            let mut key : Box<[u32]> = corner.into();
+            key[1] = normal;
+            *vertex_ids.entry(key.clone()).or_insert_with(|| {
+                vertex_buffer.push(key);
+                (vertex_buffer.len() - 1) as u32
+            })
+        }).collect();
+        self.vertex_buffer_ = vertex_buffer;
+        self.normals = normals;
+        set_corners(&mut self.data, &corner_vertices);
+        self.regenerate_data();
+        let ratios : Vec<f32> = self.data.lods.iter().map(|x| x.ratio).collect();
+        self.data.lods = generate_lods(&self.data, &ratios);
+        self.data.indices_changed();
+        true
+    }
     // only useful for dynamic meshes
     fn regenerate_data(&mut self) {
         let vbo_offset = 6+self.uvmaps.len()*2;
@@ -129,9 +363,23 @@ impl PolygonStorageTrait for SoftbodyPolygonStorage {
                 self.data.vertex_buffer_.push(uvmap.elements[vertex[2+uv_id] as usize*2+1]);
             }
         }
+        self.data.append_tangents();
     }
 }
 
+// lookup tables for incremental edits, built on the first edit after the data is regenerated
+#[derive(Default, Clone, Debug)]
+struct EditCache{
+    // polygon vertex key of every vertex in the vertex buffer
+    vertex_ids : HashMap<Box<[u32]>, u32>,
+    // vertices in the vertex buffer using each position
+    position_vertices : Vec<Vec<u32>>,
+    // triangles using each vertex in the vertex buffer, listed once per vertex group like in the index buffers
+    vertex_triangles : Vec<Vec<u32>>,
+    // vertices whose tangents have to be recomputed
+    touched : IntSet<u32>,
+}
+
 // DYNAMIC MAP (suitable for new mesh generation and new/altered triangles on the fly)
 // Also suitable for soft bodies but slow in comparison to SOFTBODY MAP
 #[derive(Default, Clone, Debug)]
@@ -144,6 +392,9 @@ pub struct DynamicPolygonStorage{
 
     data : PolygonStorageData,
     regenerated_data : bool,
+    edit_cache : Option<EditCache>,
+    // the data changed since the last update of the mesh
+    changed : bool,
     pub max_index : usize,
 }
 
@@ -157,7 +408,8 @@ impl DynamicPolygonStorage{
             indices : TriangleIndices::new(indices, uvmaps.len()),
             uvmaps,
             regenerated_data : false,
-            
+            edit_cache : None,
+            changed : false,
         };
         output.regenerate_data();
         output
@@ -165,10 +417,428 @@ impl DynamicPolygonStorage{
     pub fn get_max_index(&self) -> usize {
         self.max_index
     }
+
+    pub fn num_of_triangles(&self) -> usize {
+        self.indices.len()/(3*self.stride())
+    }
+    // position, normal and uv indices of the three corners
+    pub fn get_triangle(&self, id : u32) -> Option<&[u32]> {
+        let size = 3*self.stride();
+        self.indices.data.get(id as usize*size..(id as usize+1)*size)
+    }
+
+    // EDITING
+    // the vertex and index buffers are updated in place, vertices that are no longer used stay in the vertex buffer until the data is regenerated
+
+    pub fn add_vertex(&mut self, position : [f32; 3]) -> u32 {
+        self.positions.extend_from_slice(&position);
+        (self.positions.len()/3 - 1) as u32
+    }
+    pub fn add_normal(&mut self, normal : [f32; 3]) -> u32 {
+        self.normals.extend_from_slice(&normal);
+        (self.normals.len()/3 - 1) as u32
+    }
+    pub fn add_uv(&mut self, uvmap : usize, uv : [f32; 2]) -> Option<u32> {
+        let elements = &mut self.uvmaps.get_mut(uvmap)?.elements;
+        elements.extend_from_slice(&uv);
+        Some((elements.len()/2 - 1) as u32)
+    }
+
+    pub fn move_vertex(&mut self, id : u32, position : [f32; 3]) -> bool {
+        if id as usize >= self.positions.len()/3 {
+            return false;
+        }
+        self.positions[id as usize*3..id as usize*3+3].copy_from_slice(&position);
+        let stride = self.data.vertex_length();
+        let vertices = self.edit_cache().position_vertices.get(id as usize).cloned().unwrap_or_default();
+        for vertex in vertices{
+            self.data.vertex_buffer_[vertex as usize*stride..vertex as usize*stride+3].copy_from_slice(&position);
+            // the tangents of all neighbours depend on the moved position
+            let triangles = self.edit_cache().vertex_triangles[vertex as usize].clone();
+            for tri in triangles{
+                self.touch_triangle(tri);
+            }
+        }
+        self.finish_edit(false);
+        true
+    }
+
+    // corners contains the position, normal and uv indices of all three corners, returns the id of the new triangle
+    pub fn add_triangle(&mut self, corners : &[u32], vgroup : usize) -> Option<u32> {
+        if corners.len() != 3*self.stride() || vgroup >= self.data.vgroups.len() || !corners.chunks(self.stride()).all(|x| self.is_valid_corner(x)) {
+            return None;
+        }
+        let id = self.num_of_triangles() as u32;
+        self.edit_cache();
+        self.indices.data.extend_from_slice(corners);
+        self.add_to_vgroup(id, vgroup);
+        self.finish_edit(true);
+        Some(id)
+    }
+
+    // the last triangle takes over the id of the removed one
+    pub fn remove_triangle(&mut self, id : u32) -> bool {
+        let num_of_triangles = self.num_of_triangles();
+        if id as usize >= num_of_triangles {
+            return false;
+        }
+        self.edit_cache();
+        for vgroup in 0..self.data.vgroups.len(){
+            self.remove_from_vgroup(id, vgroup);
+        }
+        let size = 3*self.stride();
+        let last = num_of_triangles as u32 - 1;
+        if id != last {
+            for vertex in self.triangle_vertices(last){
+                self.edit_cache().vertex_triangles[vertex as usize].iter_mut().filter(|x| **x == last).for_each(|x| *x = id);
+            }
+            self.indices.data.copy_within(last as usize*size.., id as usize*size);
+            for vgroup in self.data.vgroups.iter_mut(){
+                vgroup.polygons.iter_mut().filter(|x| **x == last).for_each(|x| *x = id);
+            }
+        }
+        self.indices.data.truncate(last as usize*size);
+        self.finish_edit(true);
+        true
+    }
+
+    // inserts a new position on the edge between two positions and splits all triangles using that edge
+    // factor 0 is at position a, normals and uvs are interpolated, returns the new position
+    pub fn split_edge(&mut self, a : u32, b : u32, factor : f32) -> Option<u32> {
+        let stride = self.stride();
+        let mut edges = vec![];
+        for tri in 0..self.num_of_triangles() as u32{
+            for corner in 0..3{
+                let next = (corner+1)%3;
+                let ends = (self.indices[(tri as usize, corner)][0], self.indices[(tri as usize, next)][0]);
+                if ends == (a, b) || ends == (b, a) {
+                    edges.push((tri, corner));
+                }
+            }
+        }
+        if edges.is_empty() || a == b {
+            return None;
+        }
+        self.edit_cache();
+
+        let lerp = |x : &[f32], y : &[f32]| -> Vec<f32> {x.iter().zip(y).map(|(x, y)| x + (y - x)*factor).collect()};
+        let position = lerp(&self.positions[a as usize*3..a as usize*3+3], &self.positions[b as usize*3..b as usize*3+3]);
+        let new_position = self.add_vertex([position[0], position[1], position[2]]);
+        // interpolated normals and uvs are shared between triangles with the same attributes on both ends
+        let mut created : HashMap<(usize, u32, u32), u32> = HashMap::new();
+
+        for (tri, corner) in edges{
+            let next = (corner+1)%3;
+            let first = self.indices[(tri as usize, corner)].to_vec();
+            let second = self.indices[(tri as usize, next)].to_vec();
+            let (key_a, key_b) = if first[0] == a {(&first, &second)} else {(&second, &first)};
+
+            let mut new_corner = vec![new_position; stride];
+            for attribute in 1..stride{
+                let (id_a, id_b) = (key_a[attribute], key_b[attribute]);
+                new_corner[attribute] = if id_a == id_b {id_a} else {
+                    *created.entry((attribute, id_a, id_b)).or_insert_with(|| {
+                        if attribute == 1 {
+                            let normal = lerp(&self.normals[id_a as usize*3..id_a as usize*3+3], &self.normals[id_b as usize*3..id_b as usize*3+3]);
+                            let length = normal.iter().map(|x| x*x).sum::<f32>().sqrt().max(1e-12);
+                            self.normals.extend(normal.iter().map(|x| x/length));
+                            (self.normals.len()/3 - 1) as u32
+                        }
+                        else {
+                            let elements = &mut self.uvmaps[attribute-2].elements;
+                            let uv = lerp(&elements[id_a as usize*2..id_a as usize*2+2], &elements[id_b as usize*2..id_b as usize*2+2]);
+                            elements.extend(uv);
+                            (elements.len()/2 - 1) as u32
+                        }
+                    })
+                };
+            }
+
+            // (first, second, third) becomes (first, new, third) and (new, second, third)
+            let third = self.indices[(tri as usize, (corner+2)%3)].to_vec();
+            let new_tri = self.num_of_triangles() as u32;
+            self.indices.data.extend(new_corner.iter().chain(second.iter()).chain(third.iter()));
+            self.set_corner(tri, next, &new_corner);
+            for vgroup in 0..self.data.vgroups.len(){
+                if self.data.vgroups[vgroup].polygons.contains(&tri) {
+                    self.add_to_vgroup(new_tri, vgroup);
+                }
+            }
+        }
+        self.finish_edit(true);
+        Some(new_position)
+    }
+
+    // merges positions closer than the distance, triangles that collapse are removed and the data is regenerated
+    // merged positions are left unused so the ids of all other positions stay the same, returns the number of merged positions
+    pub fn weld_vertices(&mut self, distance : f32) -> usize {
+        let distance = distance.max(f32::EPSILON);
+        let cell = |p : &[f32]| -> [i64; 3] {[0, 1, 2].map(|axis| (p[axis]/distance).floor() as i64)};
+        let mut grid : HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+        let mut remap : Vec<u32> = Vec::with_capacity(self.positions.len()/3);
+        for (id, p) in self.positions.chunks(3).enumerate(){
+            let base = cell(p);
+            let mut target = None;
+            'search: for offset in 0..27{
+                let neighbour = [base[0] + offset%3 - 1, base[1] + offset/3%3 - 1, base[2] + offset/9 - 1];
+                for other in grid.get(&neighbour).map(|x| x.as_slice()).unwrap_or_default(){
+                    let q = &self.positions[*other as usize*3..*other as usize*3+3];
+                    if (0..3).map(|axis| (p[axis]-q[axis])*(p[axis]-q[axis])).sum::<f32>() <= distance*distance {
+                        target = Some(*other);
+                        break 'search;
+                    }
+                }
+            }
+            remap.push(target.unwrap_or_else(|| {
+                grid.entry(base).or_default().push(id as u32);
+                id as u32
+            }));
+        }
+        let merged = remap.iter().enumerate().filter(|(id, x)| *id as u32 != **x).count();
+        if merged == 0 {
+            return 0;
+        }
+
+        let stride = self.stride();
+        let mut triangles : Vec<Option<u32>> = Vec::with_capacity(self.num_of_triangles());
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for tri in self.indices.data.chunks(3*stride){
+            let ids = [0, 1, 2].map(|corner| remap[tri[corner*stride] as usize]);
+            if ids[0] == ids[1] || ids[1] == ids[2] || ids[0] == ids[2] {
+                triangles.push(None);
+                continue;
+            }
+            triangles.push(Some((indices.len()/(3*stride)) as u32));
+            for (corner, data) in tri.chunks(stride).enumerate(){
+                indices.push(ids[corner]);
+                indices.extend_from_slice(&data[1..]);
+            }
+        }
+        self.indices.data = indices;
+        for vgroup in self.data.vgroups.iter_mut(){
+            vgroup.polygons = vgroup.polygons.iter().filter_map(|x| triangles[*x as usize]).collect();
+        }
+        self.data.lods.clear();
+        self.regenerate_data();
+        merged
+    }
+
+    // reverses the winding of all triangles and inverts all normals
+    pub fn flip_normals(&mut self) {
+        let stride = self.stride();
+        self.edit_cache();
+        self.normals.iter_mut().for_each(|x| *x = -*x);
+        for tri in self.indices.data.chunks_mut(3*stride){
+            let (second, third) = tri[stride..].split_at_mut(stride);
+            second.swap_with_slice(third);
+        }
+        let vertex_length = self.data.vertex_length();
+        for vertex in self.data.vertex_buffer_.chunks_mut(vertex_length){
+            vertex[3..6].iter_mut().for_each(|x| *x = -*x);
+        }
+        let data = &mut self.data;
+        for index_buffer in data.index_buffers_.values_mut().chain(data.lods.iter_mut().flat_map(|x| x.index_buffers.values_mut())){
+            index_buffer.chunks_mut(3).for_each(|x| x.swap(1, 2));
+        }
+        // every tangent changes
+        let num_of_vertices = self.data.vertex_buffer_.len()/vertex_length;
+        self.edit_cache().touched.extend(0..num_of_vertices as u32);
+        self.finish_edit(false);
+    }
+
+    // returns the number of triangles that were moved, triangles not in the first vertex group are skipped
+    pub fn move_triangles(&mut self, triangles : &[u32], from : usize, to : usize) -> usize {
+        if from == to || from >= self.data.vgroups.len() || to >= self.data.vgroups.len() {
+            return 0;
+        }
+        let mut moved = 0;
+        for tri in triangles{
+            if !self.remove_from_vgroup(*tri, from) {
+                continue;
+            }
+            if !self.data.vgroups[to].polygons.contains(tri) {
+                self.add_to_vgroup(*tri, to);
+            }
+            moved += 1;
+        }
+        if moved > 0 {
+            self.finish_edit(true);
+        }
+        moved
+    }
+
+    fn stride(&self) -> usize {
+        2+self.uvmaps.len()
+    }
+
+    fn is_valid_corner(&self, corner : &[u32]) -> bool {
+        (corner[0] as usize) < self.positions.len()/3
+            && (corner[1] as usize) < self.normals.len()/3
+            && self.uvmaps.iter().zip(&corner[2..]).all(|(uvmap, id)| (*id as usize) < uvmap.elements.len()/2)
+    }
+
+    // has to be built before the first edit changes the indices
+    fn edit_cache(&mut self) -> &mut EditCache {
+        if self.edit_cache.is_none() {
+            // same order as in regenerate_data
+            let mut cache = EditCache::default();
+            for key in self.indices.data.chunks(self.stride()){
+                if !cache.vertex_ids.contains_key(key) {
+                    let id = cache.vertex_ids.len() as u32;
+                    cache.vertex_ids.insert(key.into(), id);
+                    if key[0] as usize >= cache.position_vertices.len() {
+                        cache.position_vertices.resize(key[0] as usize+1, vec![]);
+                    }
+                    cache.position_vertices[key[0] as usize].push(id);
+                }
+            }
+            cache.vertex_triangles = vec![vec![]; cache.vertex_ids.len()];
+            for (id, vgroup) in self.data.vgroups.iter().enumerate(){
+                let Some(index_buffer) = self.data.index_buffers_.get(&id) else {continue;};
+                for (tri, vertices) in vgroup.polygons.iter().zip(index_buffer.chunks(3)){
+                    vertices.iter().for_each(|x| cache.vertex_triangles[*x as usize].push(*tri));
+                }
+            }
+            self.edit_cache = Some(cache);
+        }
+        self.edit_cache.as_mut().unwrap()
+    }
+
+    // appends the vertex to the vertex buffer if no other corner uses the same attributes
+    fn get_or_add_vertex(&mut self, key : &[u32]) -> u32 {
+        if let Some(id) = self.edit_cache().vertex_ids.get(key) {
+            return *id;
+        }
+        let buffer = &mut self.data.vertex_buffer_;
+        buffer.extend_from_slice(&self.positions[key[0] as usize*3..key[0] as usize*3+3]);
+        buffer.extend_from_slice(&self.normals[key[1] as usize*3..key[1] as usize*3+3]);
+        for (uvmap, id) in self.uvmaps.iter().zip(&key[2..]){
+            buffer.extend_from_slice(&uvmap.elements[*id as usize*2..*id as usize*2+2]);
+        }
+        // placeholder until the tangents are regenerated
+        if self.data.tangent_uvmap.is_some() {
+            buffer.extend_from_slice(&[1.0, 0.0, 0.0, 1.0]);
+        }
+        let id = (self.data.vertex_buffer_.len()/self.data.vertex_length() - 1) as u32;
+        let cache = self.edit_cache();
+        cache.vertex_ids.insert(key.into(), id);
+        if key[0] as usize >= cache.position_vertices.len() {
+            cache.position_vertices.resize(key[0] as usize+1, vec![]);
+        }
+        cache.position_vertices[key[0] as usize].push(id);
+        cache.vertex_triangles.push(vec![]);
+        id
+    }
+
+    fn add_to_vgroup(&mut self, tri : u32, vgroup : usize) {
+        let vertices = [0, 1, 2].map(|corner| self.indices[(tri as usize, corner)].to_vec()).map(|key| self.get_or_add_vertex(&key));
+        self.data.vgroups[vgroup].polygons.push(tri);
+        self.data.index_buffers_.entry(vgroup).or_default().extend_from_slice(&vertices);
+        let cache = self.edit_cache();
+        for vertex in vertices{
+            cache.vertex_triangles[vertex as usize].push(tri);
+        }
+        self.touch_triangle(tri);
+    }
+
+    // the last triangle of the vertex group takes the place of the removed one, like in the index buffer
+    fn remove_from_vgroup(&mut self, tri : u32, vgroup : usize) -> bool {
+        self.edit_cache();
+        let polygons = &mut self.data.vgroups[vgroup].polygons;
+        let Some(slot) = polygons.iter().position(|x| *x == tri) else {return false;};
+        polygons.swap_remove(slot);
+        let index_buffer = self.data.index_buffers_.get_mut(&vgroup).unwrap();
+        let vertices = [index_buffer[slot*3], index_buffer[slot*3+1], index_buffer[slot*3+2]];
+        let last = index_buffer.len()/3 - 1;
+        index_buffer.copy_within(last*3..last*3+3, slot*3);
+        index_buffer.truncate(last*3);
+        let cache = self.edit_cache();
+        for vertex in vertices{
+            let triangles = &mut cache.vertex_triangles[vertex as usize];
+            if let Some(position) = triangles.iter().position(|x| *x == tri) {
+                triangles.swap_remove(position);
+            }
+            cache.touched.insert(vertex);
+        }
+        true
+    }
+
+    fn set_corner(&mut self, tri : u32, corner : usize, key : &[u32]) {
+        let stride = self.stride();
+        let start = (tri as usize*3 + corner)*stride;
+        // the old vertex loses the triangle before the corner is replaced
+        self.touch_triangle(tri);
+        let vertex = self.get_or_add_vertex(key);
+        let old_vertex = self.triangle_vertices(tri)[corner];
+        self.indices.data[start..start+stride].copy_from_slice(key);
+        for (id, vgroup) in self.data.vgroups.iter().enumerate(){
+            if let Some(slot) = vgroup.polygons.iter().position(|x| *x == tri) {
+                self.data.index_buffers_.get_mut(&id).unwrap()[slot*3+corner] = vertex;
+                let cache = self.edit_cache.as_mut().unwrap();
+                let triangles = &mut cache.vertex_triangles[old_vertex as usize];
+                if let Some(position) = triangles.iter().position(|x| *x == tri) {
+                    triangles.swap_remove(position);
+                }
+                cache.vertex_triangles[vertex as usize].push(tri);
+            }
+        }
+        self.touch_triangle(tri);
+    }
+
+    // vertex buffer ids of the three corners
+    fn triangle_vertices(&mut self, tri : u32) -> [u32; 3] {
+        let keys = [0, 1, 2].map(|corner| self.indices[(tri as usize, corner)].to_vec());
+        let cache = self.edit_cache();
+        keys.map(|key| cache.vertex_ids[key.as_slice()])
+    }
+
+    fn touch_triangle(&mut self, tri : u32) {
+        let vertices = self.triangle_vertices(tri);
+        self.edit_cache().touched.extend(vertices);
+    }
+
+    // the levels of detail no longer match after the triangles changed, only the tangents of touched vertices are recomputed
+    fn finish_edit(&mut self, triangles_changed : bool) {
+        if triangles_changed {
+            self.data.lods.clear();
+        }
+        let touched = std::mem::take(&mut self.edit_cache().touched);
+        if let Some(uvmap) = self.data.tangent_uvmap {
+            let cache = self.edit_cache.as_ref().unwrap();
+            let vertices = touched.into_iter().map(|vertex| {
+                let triangles = cache.vertex_triangles[vertex as usize].iter().map(|tri| {
+                    [0, 1, 2].map(|corner| cache.vertex_ids[&self.indices[(*tri as usize, corner)]])
+                }).collect();
+                (vertex, triangles)
+            });
+            update_tangents(&mut self.data, uvmap as usize, vertices);
+        }
+        self.data.indices_changed();
+        self.changed = true;
+    }
 }
 
 
 impl PolygonStorageTrait for DynamicPolygonStorage{
+    fn get_dynamic_mut(&mut self) -> Option<&mut DynamicPolygonStorage> {
+        Some(self)
+    }
+    fn get_vertex_data_mut(&mut self) -> Option<(&mut Vec<f32>, &mut Vec<f32>)> {
+        Some((&mut self.positions, &mut self.normals))
+    }
+    fn is_changed_and_reset(&mut self) -> bool {
+        std::mem::replace(&mut self.changed, false)
+    }
+    fn generate_normals(&mut self, mode : NormalMode, crease_angle : Option<f32>) -> bool {
+        let stride = 2+self.uvmaps.len();
+        let (normals, normal_ids) = generate_normals(&self.positions, &self.indices.data, stride, mode, crease_angle);
+        for (corner, id) in normal_ids.into_iter().enumerate(){
+            self.indices.data[corner*stride+1] = id;
+        }
+        self.normals = normals;
+        self.regenerate_data();
+        true
+    }
     fn get_data(&self) -> Option<&PolygonStorageData> {
         if self.regenerated_data {
             Some(&self.data)
@@ -188,6 +858,9 @@ impl PolygonStorageTrait for DynamicPolygonStorage{
     fn get_type(&self) -> PolygonStorageType{
         PolygonStorageType::Dynamic
     }
+    fn box_clone(&self) -> Box<dyn PolygonStorageTrait> {
+        Box::new(self.clone())
+    }
     
     // only useful for dynamic meshes
     fn regenerate_data(&mut self){
@@ -250,15 +923,20 @@ impl PolygonStorageTrait for DynamicPolygonStorage{
         }
         //after = time::Instant::now();
         //println!("gen index buffer {:?}", (after-before).as_secs_f64());
+        self.data.append_tangents();
+        self.data.indices_changed();
         self.regenerated_data = true;
+        self.edit_cache = None;
+        self.changed = true;
     }
 }
 
 #[cfg(test)]
 pub mod polygonstoragetest{
 
-    use super::{DynamicPolygonStorage, SoftbodyPolygonStorage, UVMapData};
+    use super::{DynamicPolygonStorage, SoftbodyPolygonStorage, StaticPolygonStorage, UVMapData};
     use super::super::polygonstoragetrait::*;
+    use super::super::tangentspace::NormalMode;
 
     #[test]
     fn test_dynamic_polygon_storage(){
@@ -314,6 +992,171 @@ pub mod polygonstoragetest{
         println!("{:?}", softbody_polygons.get_vertex_buffer());
         assert!(*softbody_polygons.get_vertex_buffer() == vbo_out);
     }
+
+    // geometry of every triangle per vertex group, independent of the vertex and triangle order
+    fn triangle_geometry(polygons : &DynamicPolygonStorage) -> Vec<Vec<Vec<f32>>>{
+        let stride = polygons.get_data().unwrap().vertex_length();
+        let vertex = |id : &u32| polygons.get_vertex_buffer()[*id as usize*stride..(*id as usize+1)*stride].to_vec();
+        (0..polygons.get_vgroups().len()).map(|vgroup| {
+            let mut output : Vec<Vec<f32>> = polygons.get_index_buffer(vgroup).chunks(3).map(|tri| tri.iter().flat_map(vertex).collect()).collect();
+            output.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            output
+        }).collect()
+    }
+
+    #[test]
+    fn test_dynamic_polygon_storage_editing(){
+        let positions = vec![0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  1.0, 1.0, 0.0,  0.0, 1.0, 0.0];
+        let normals = vec![0.0, 0.0, 1.0];
+        let uvmaps = vec![UVMapData{elements : vec![0.0, 0.0,  1.0, 0.0,  1.0, 1.0,  0.0, 1.0]}];
+        let indices = vec![0, 0, 0,  1, 0, 1,  2, 0, 2,
+                           0, 0, 0,  2, 0, 2,  3, 0, 3];
+        let vgroups = vec![VertexGroup{id:0,polygons : vec![0, 1], name:"".into(),material:None}, VertexGroup{id:1,polygons : vec![], name:"".into(), material:None}];
+        let mut polygons = DynamicPolygonStorage::new(positions, normals, uvmaps, indices, vgroups);
+
+        let top = polygons.add_vertex([0.5, 0.5, 1.0]);
+        let tri = polygons.add_triangle(&[0, 0, 0,  1, 0, 1,  top, 0, 2], 1).unwrap();
+        polygons.move_vertex(top, [0.5, 0.5, 2.0]);
+        assert!(polygons.move_triangles(&[0], 0, 1) == 1);
+        assert!(polygons.split_edge(0, 2, 0.5).is_some());
+        assert!(polygons.remove_triangle(tri));
+        polygons.flip_normals();
+        assert!(polygons.num_of_triangles() == 4);
+        assert!(polygons.get_index_buffer(0).len() == 6 && polygons.get_index_buffer(1).len() == 6);
+
+        // the buffers updated in place have to describe the same triangles as fully regenerated ones
+        let mut regenerated = polygons.clone();
+        regenerated.regenerate_data();
+        assert!(triangle_geometry(&polygons) == triangle_geometry(&regenerated));
+
+        let duplicate = polygons.add_vertex([1.0, 0.0, 0.0001]);
+        polygons.add_triangle(&[duplicate, 0, 1,  2, 0, 2,  3, 0, 3], 0).unwrap();
+        assert!(polygons.weld_vertices(0.001) == 1);
+        assert!(polygons.get_triangle(polygons.num_of_triangles() as u32 - 1).unwrap()[0] == 1);
+    }
+
+    #[test]
+    fn test_incremental_tangents(){
+        let positions = vec![0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  1.0, 1.0, 0.0,  0.0, 1.0, 0.0];
+        let normals = vec![0.0, 0.0, 1.0,  0.0, 0.6, 0.8];
+        let uvmaps = vec![UVMapData{elements : vec![0.0, 0.0,  1.0, 0.0,  1.0, 1.0,  0.0, 1.0]}];
+        let indices = vec![0, 0, 0,  1, 0, 1,  2, 1, 2,
+                           0, 0, 0,  2, 1, 2,  3, 0, 3];
+        let vgroups = vec![VertexGroup{id:0,polygons : vec![0, 1], name:"".into(),material:None}, VertexGroup{id:1,polygons : vec![1], name:"".into(), material:None}];
+        let mut polygons = DynamicPolygonStorage::new(positions, normals, uvmaps, indices, vgroups);
+        assert!(polygons.set_tangents(Some(0)));
+
+        // after every edit the tangents have to match a full recompute of the same vertex buffer
+        let check = |polygons : &DynamicPolygonStorage| {
+            let mut recomputed = polygons.clone();
+            assert!(recomputed.set_tangents(Some(0)));
+            let (vertices, expected) = (polygons.get_vertex_buffer(), recomputed.get_vertex_buffer());
+            assert_eq!(vertices.len(), expected.len());
+            assert!(vertices.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", vertices, expected);
+        };
+        let top = polygons.add_vertex([0.5, 0.5, 1.0]);
+        let tri = polygons.add_triangle(&[0, 0, 0,  1, 0, 1,  top, 1, 2], 1).unwrap();
+        check(&polygons);
+        polygons.move_vertex(top, [0.5, -0.5, 2.0]);
+        check(&polygons);
+        polygons.move_vertex(0, [-0.5, 0.0, 0.0]);
+        check(&polygons);
+        assert!(polygons.split_edge(0, 2, 0.25).is_some());
+        check(&polygons);
+        assert!(polygons.move_triangles(&[1], 0, 1) == 1);
+        check(&polygons);
+        assert!(polygons.remove_triangle(tri));
+        check(&polygons);
+        polygons.flip_normals();
+        check(&polygons);
+        assert!(polygons.remove_triangle(0));
+        check(&polygons);
+    }
+
+    #[test]
+    fn test_16bit_index_data_cache(){
+        let positions = vec![0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  1.0, 1.0, 0.0,  0.0, 1.0, 0.0];
+        let mut polygons = shared_positions(positions, &[[0, 1, 2], [0, 2, 3]]);
+        let first = polygons.get_16bit_index_data();
+        assert!(std::sync::Arc::ptr_eq(&first, &polygons.get_16bit_index_data()));
+        assert_eq!(first.index_buffers[0][&0], vec![0, 1, 2, 0, 2, 3]);
+
+        // every edit of the index buffers drops the cached split
+        assert!(polygons.remove_triangle(0));
+        let second = polygons.get_16bit_index_data();
+        assert!(!std::sync::Arc::ptr_eq(&first, &second));
+        assert_eq!(second.index_buffers[0][&0].len(), 3);
+        assert!(polygons.generate_lods(&[0.5]));
+        assert!(!std::sync::Arc::ptr_eq(&second, &polygons.get_16bit_index_data()));
+        assert_eq!(polygons.get_16bit_index_data().index_buffers.len(), 2);
+    }
+
+    // one uv map and a placeholder normal, the positions are shared by all triangles
+    fn shared_positions(positions : Vec<f32>, triangles : &[[u32; 3]]) -> DynamicPolygonStorage{
+        let indices = triangles.iter().flat_map(|tri| tri.iter().flat_map(|id| [*id, 0, 0])).collect();
+        let vgroups = vec![VertexGroup{id:0, polygons : (0..triangles.len() as u32).collect(), name:"".into(), material:None}];
+        DynamicPolygonStorage::new(positions, vec![1.0, 0.0, 0.0], vec![UVMapData{elements : vec![0.0, 0.0]}], indices, vgroups)
+    }
+
+    // number of vertices, checks that every corner has the given normal or the normal of its triangle
+    fn check_normals(polygons : &dyn PolygonStorageTrait, expected : Option<[f32; 3]>) -> usize{
+        let stride = polygons.get_data().unwrap().vertex_length();
+        let vertices = polygons.get_vertex_buffer();
+        let vertex = |id : u32| &vertices[id as usize*stride..id as usize*stride+6];
+        for tri in polygons.get_index_buffer(0).chunks(3){
+            let [a, b, c] = [0, 1, 2].map(|corner| vertex(tri[corner]));
+            let (ab, ac) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+            let face = [ab[1]*ac[2] - ab[2]*ac[1], ab[2]*ac[0] - ab[0]*ac[2], ab[0]*ac[1] - ab[1]*ac[0]];
+            let length = (face[0]*face[0] + face[1]*face[1] + face[2]*face[2]).sqrt();
+            let expected = expected.unwrap_or(face.map(|x| x/length));
+            for corner in [a, b, c]{
+                assert!((0..3).all(|i| (corner[3+i] - expected[i]).abs() < 1e-6), "{:?} != {:?}", &corner[3..6], expected);
+            }
+        }
+        vertices.len()/stride
+    }
+
+    #[test]
+    fn test_generate_normals(){
+        let quad = || shared_positions(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0], &[[0, 1, 2], [0, 2, 3]]);
+        let cube = || shared_positions(vec![
+            -1.0, -1.0, -1.0,  1.0, -1.0, -1.0,  1.0, 1.0, -1.0,  -1.0, 1.0, -1.0,
+            -1.0, -1.0, 1.0,  1.0, -1.0, 1.0,  1.0, 1.0, 1.0,  -1.0, 1.0, 1.0,
+        ], &[
+            [0, 2, 1], [0, 3, 2], [4, 5, 6], [4, 6, 7],
+            [0, 1, 5], [0, 5, 4], [1, 2, 6], [1, 6, 5],
+            [2, 3, 7], [2, 7, 6], [3, 0, 4], [3, 4, 7],
+        ]);
+        let storages = |polygons : DynamicPolygonStorage| -> Vec<Box<dyn PolygonStorageTrait>>{
+            let mut static_polygons = StaticPolygonStorage::new(polygons.clone());
+            static_polygons.set_tangents(Some(0));
+            vec![Box::new(polygons.clone()), Box::new(static_polygons), Box::new(SoftbodyPolygonStorage::new(polygons))]
+        };
+
+        for mut polygons in storages(quad()){
+            for mode in [NormalMode::Flat, NormalMode::Smooth, NormalMode::AngleWeighted]{
+                assert!(polygons.generate_normals(mode, None));
+                assert_eq!(check_normals(polygons.as_ref(), Some([0.0, 0.0, 1.0])), 4);
+            }
+        }
+        for mut polygons in storages(cube()){
+            let has_tangents = polygons.has_tangents();
+            // every corner of a hard edged cube gets the normal of its face, so each position is split into three vertices
+            assert!(polygons.generate_normals(NormalMode::AngleWeighted, Some(30.0)));
+            assert_eq!(check_normals(polygons.as_ref(), None), 24);
+            // the two triangles of a side share their flat normal
+            assert!(polygons.generate_normals(NormalMode::Flat, None));
+            assert_eq!(check_normals(polygons.as_ref(), None), 24);
+            assert_eq!(polygons.has_tangents(), has_tangents);
+
+            // smooth normals point away from the center
+            assert!(polygons.generate_normals(NormalMode::Smooth, None));
+            let stride = polygons.get_data().unwrap().vertex_length();
+            let vertices = polygons.get_vertex_buffer();
+            assert_eq!(vertices.len()/stride, 8);
+            assert!(vertices.chunks(stride).all(|x| (0..3).all(|i| x[i]*x[3+i] > 0.1)));
+        }
+    }
 }
 
 pub fn test_dynamic_polygon_storage_large(triangles_num : usize){
@@ -1,10 +1,17 @@
 //use std::sync::{Arc, Mutex};
 use std::sync::atomic::{Ordering, AtomicUsize};
+use std::sync::{Arc, OnceLock};
 //use std::sync::{Arc, Mutex};
 //use super::material::Material;
 use compact_str::CompactString;
 use nohash_hasher::IntMap;
 use std::ops::Index;
+use super::polygonstorage::{DynamicPolygonStorage, SoftbodyPolygonStorage};
+use super::tangentspace::*;
+use super::simplification::*;
+use super::drawrange::*;
+use super::quantization::*;
+use std::borrow::Cow;
 //use super::material::*;
 
 #[repr(C)]
@@ -116,8 +123,14 @@ pub struct PolygonStorageData{
     pub vertex_buffer_ : Vec<f32>,
     pub index_buffers_ : IntMap<usize, Vec<u32>>,
     pub num_of_uvs : u8,
+    // uv map the tangents are generated from, they are appended to every vertex after the uvs
+    pub tangent_uvmap : Option<u8>,
+    // level 0 are the index buffers above
+    pub lods : Vec<LodLevel>,
     pub vgroups : Vec<VertexGroup>,
     pub max_index : usize,
+    // split for targets without 32 bit indices, built on first use
+    index_data_16bit : OnceLock<Arc<IndexData16Bit>>,
 }
 
 impl PolygonStorageData{
@@ -128,6 +141,41 @@ impl PolygonStorageData{
         output.vgroups = vgroups;
         output
     }
+
+    pub fn vertex_length(&self) -> usize{
+        6 + self.num_of_uvs as usize*2 + if self.tangent_uvmap.is_some() {4} else {0}
+    }
+
+    // has to be called whenever the index buffers or levels of detail change
+    pub fn indices_changed(&mut self){
+        self.index_data_16bit.take();
+    }
+
+    // has to be called whenever the vertex buffer is regenerated without tangents
+    pub fn append_tangents(&mut self){
+        let Some(uvmap) = self.tangent_uvmap else {return;};
+        let tangents = generate_tangents(self, uvmap as usize);
+        let stride = 6 + self.num_of_uvs as usize*2;
+        let mut output = Vec::with_capacity(self.vertex_buffer_.len() + tangents.len());
+        for (vertex, tangent) in self.vertex_buffer_.chunks(stride).zip(tangents.chunks(4)){
+            output.extend_from_slice(vertex);
+            output.extend_from_slice(tangent);
+        }
+        self.vertex_buffer_ = output;
+    }
+
+    pub fn set_tangents(&mut self, uvmap : Option<u8>) -> bool{
+        if uvmap.is_some_and(|x| x >= self.num_of_uvs) {
+            return false;
+        }
+        if self.tangent_uvmap.is_some() {
+            let stride = self.vertex_length();
+            self.vertex_buffer_ = self.vertex_buffer_.chunks(stride).flat_map(|x| x[..stride-4].iter().copied()).collect();
+        }
+        self.tangent_uvmap = uvmap;
+        self.append_tangents();
+        true
+    }
 }
 
 pub trait PolygonStorageTrait : Send + std::fmt::Debug{
@@ -135,7 +183,16 @@ pub trait PolygonStorageTrait : Send + std::fmt::Debug{
     fn get_data(&self) -> Option<&PolygonStorageData>;
     fn get_data_mut(&mut self) -> Option<&mut PolygonStorageData>;
     fn get_type(&self) -> PolygonStorageType;
+    fn box_clone(&self) -> Box<dyn PolygonStorageTrait>;
     fn regenerate_data(&mut self);
+    fn get_softbody_mut(&mut self) -> Option<&mut SoftbodyPolygonStorage> {None}
+    fn get_dynamic_mut(&mut self) -> Option<&mut DynamicPolygonStorage> {None}
+    // only storages that can be edited track their changes
+    fn is_changed_and_reset(&mut self) -> bool {false}
+    // positions and normals of storages that can be deformed
+    fn get_vertex_data_mut(&mut self) -> Option<(&mut Vec<f32>, &mut Vec<f32>)> {None}
+    // quantized and renderer storages can not regenerate their normals
+    fn generate_normals(&mut self, _mode : NormalMode, _crease_angle : Option<f32>) -> bool {false}
 
     // derived functions
     fn get_max_index(&self) -> Option<usize> {
@@ -156,19 +213,66 @@ pub trait PolygonStorageTrait : Send + std::fmt::Debug{
     fn get_num_uvs(&self) -> u8{
         self.get_data().unwrap().num_of_uvs
     }
+    fn has_tangents(&self) -> bool{
+        self.get_data().unwrap().tangent_uvmap.is_some()
+    }
+    // tangents are generated from the given uv map, None removes them
+    fn set_tangents(&mut self, uvmap : Option<u8>) -> bool{
+        self.get_data_mut().map(|x| x.set_tangents(uvmap)).unwrap_or(false)
+    }
     fn id(&self) -> usize{
         self.get_data().unwrap().id_
     }
     fn get_vertex_buffer(&self) -> &Vec<f32>{
         &self.get_data().unwrap().vertex_buffer_
     }
+    // software path, compressed storages have no float vertex buffer
+    fn get_dequantized_vertex_buffer(&self) -> Cow<'_, [f32]>{
+        Cow::Borrowed(self.get_vertex_buffer())
+    }
+    fn get_quantized_vertex_buffer(&self) -> Option<&QuantizedVertexBuffer> {None}
+    fn get_vertex_layout(&self) -> VertexLayout{
+        VertexLayout::new(VertexFormat::default(), self.get_num_uvs(), self.has_tangents())
+    }
     fn get_index_buffer(&self, id : usize) -> &Vec<u32>{
         &self.get_data().unwrap().index_buffers_[&id]
     }
+    // replaces all existing levels of detail
+    fn generate_lods(&mut self, ratios : &[f32]) -> bool{
+        let Some(data) = self.get_data_mut() else {return false;};
+        data.lods = generate_lods(data, ratios);
+        data.indices_changed();
+        true
+    }
+    fn get_lods(&self) -> &Vec<LodLevel>{
+        &self.get_data().unwrap().lods
+    }
+    fn get_lod_index_buffer(&self, lod : usize, id : usize) -> &Vec<u32>{
+        match lod{
+            0 => self.get_index_buffer(id),
+            _ => &self.get_data().unwrap().lods[lod-1].index_buffers[&id]
+        }
+    }
+    // axis aligned bounding box of all vertices
+    fn get_bounds(&self) -> ([f32; 3], [f32; 3]){
+        let vertex_length = self.get_data().unwrap().vertex_length();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in self.get_dequantized_vertex_buffer().chunks(vertex_length){
+            for axis in 0..3{
+                min[axis] = min[axis].min(vertex[axis]);
+                max[axis] = max[axis].max(vertex[axis]);
+            }
+        }
+        if min[0] > max[0] {
+            return ([0.0; 3], [0.0; 3]);
+        }
+        (min, max)
+    }
 
     fn len(&self) -> usize{
-        let total_length = self.get_vertex_buffer().len();
-        total_length / (6+self.get_num_uvs() as usize*2)
+        let total_length = self.get_dequantized_vertex_buffer().len();
+        total_length / self.get_data().unwrap().vertex_length()
     }
     fn get_16bit_index_buffer(&self, id : usize) -> Option<Vec<u16>>{
         if self.len() < 65536 {
@@ -179,9 +283,15 @@ pub trait PolygonStorageTrait : Send + std::fmt::Debug{
             None
         }
     }
+    // works for any number of vertices, oversized vertex groups are split into draw ranges
+    // cached until the index buffers change
+    fn get_16bit_index_data(&self) -> Arc<IndexData16Bit>{
+        let data = self.get_data().unwrap();
+        data.index_data_16bit.get_or_init(|| Arc::new(split_16bit(data, self.len(), MAX_16BIT_VERTICES))).clone()
+    }
     fn get_positions(&self) -> Vec<f32>{
-        let vertices = self.get_vertex_buffer();
-        let vertex_length = self.get_num_uvs() as usize*2 + 6;
+        let vertices = self.get_dequantized_vertex_buffer();
+        let vertex_length = self.get_data().unwrap().vertex_length();
         let mut output = Vec::with_capacity(vertices.len()/vertex_length * 3);
         for index in 0..vertices.len()/vertex_length{
             output.push(vertices[index*vertex_length]);
@@ -191,8 +301,8 @@ pub trait PolygonStorageTrait : Send + std::fmt::Debug{
         output
     }
     fn get_normals(&self) -> Vec<f32>{
-        let vertices = self.get_vertex_buffer();
-        let vertex_length = self.get_num_uvs() as usize*2 + 6;
+        let vertices = self.get_dequantized_vertex_buffer();
+        let vertex_length = self.get_data().unwrap().vertex_length();
         let mut output = Vec::with_capacity(vertices.len()/vertex_length * 3);
         for index in 0..vertices.len()/vertex_length{
             output.push(vertices[index*vertex_length+3]);
@@ -202,8 +312,8 @@ pub trait PolygonStorageTrait : Send + std::fmt::Debug{
         output
     }
     fn get_uvs(&self, id : usize) -> Option<Vec<f32>>{
-        let vertices = self.get_vertex_buffer();
-        let vertex_length = self.get_num_uvs() as usize*2 + 6;
+        let vertices = self.get_dequantized_vertex_buffer();
+        let vertex_length = self.get_data().unwrap().vertex_length();
         if id as u8 >= self.get_num_uvs(){
             return None;
         }
@@ -214,6 +324,18 @@ pub trait PolygonStorageTrait : Send + std::fmt::Debug{
         }
         Some(output)
     }
+    fn get_tangents(&self) -> Option<Vec<f32>>{
+        if !self.has_tangents() {
+            return None;
+        }
+        let vertices = self.get_dequantized_vertex_buffer();
+        let vertex_length = self.get_data().unwrap().vertex_length();
+        let mut output = Vec::with_capacity(vertices.len()/vertex_length * 4);
+        for vertex in vertices.chunks(vertex_length){
+            output.extend_from_slice(&vertex[vertex_length-4..]);
+        }
+        Some(output)
+    }
     fn get_vgroup_index_buffer(&self, id : usize) -> Vec<u32>{
         //let vgroup = &self.get_vgroups()[id];
         let ibo = self.get_index_buffer(id);
@@ -0,0 +1,152 @@
+use std::collections::{HashMap, HashSet};
+use compact_str::{CompactString, format_compact};
+use nalgebra_glm as glm;
+
+use super::object_trait::*;
+use super::material::Material;
+use super::armature::BoneTransform;
+use super::polygonstoragetrait::PolygonStorageType;
+
+// names of instanced elements are prefixed with the instance name
+pub fn prefixed_name(instance_name : &str, name : &str) -> CompactString{
+    format_compact!("{}/{}", instance_name, name)
+}
+
+// objects and materials of one instance with their prefixed names
+pub type PrefabElements = (Vec<(CompactString, Box<dyn ObjectTrait>)>, Vec<(CompactString, Material)>);
+
+// changes of one object of a single instance
+#[derive(Clone, Debug, Default)]
+pub struct ObjectOverride{
+    // replaces the transform inside the prefab
+    pub transform : Option<BoneTransform>,
+    pub visible : Option<bool>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PrefabInstanceOptions{
+    // every object of the prefab is placed relative to it
+    pub transform : BoneTransform,
+    pub overrides : HashMap<CompactString, ObjectOverride>,
+    // replace the prefab materials of the same name
+    pub materials : HashMap<CompactString, Material>,
+}
+
+// instance that is added to the scenegraph at the start of the next frame
+#[derive(Clone, Debug, Default)]
+pub struct PendingPrefabInstance{
+    pub name : CompactString,
+    pub prefab : CompactString,
+    pub scene : CompactString,
+    pub options : PrefabInstanceOptions,
+}
+
+// names of the elements that belong to an instance, so it can be despawned as a whole
+#[derive(Clone, Debug, Default)]
+pub struct PrefabInstance{
+    pub prefab : CompactString,
+    pub scene : CompactString,
+    pub objects : Vec<CompactString>,
+    pub materials : Vec<CompactString>,
+}
+
+// objects and materials that are defined once and instantiated many times
+#[derive(Debug, Default)]
+pub struct Prefab{
+    objects : Vec<(CompactString, Box<dyn ObjectTrait>)>,
+    materials : Vec<(CompactString, Material)>,
+}
+
+fn apply_transform(root : &BoneTransform, data : &mut CommonObjectData){
+    let root_rot = glm::quat_normalize(&glm::make_quat(&root.rot));
+    let scaled = glm::make_vec3(&data.pos).component_mul(&glm::make_vec3(&root.sca));
+    let pos = glm::make_vec3(&root.pos) + glm::quat_rotate_vec3(&root_rot, &scaled);
+    let rot = root_rot * glm::make_quat(&data.rot);
+    data.pos = [pos.x, pos.y, pos.z];
+    data.rot = [rot.coords.x, rot.coords.y, rot.coords.z, rot.coords.w];
+    data.sca = [data.sca[0]*root.sca[0], data.sca[1]*root.sca[1], data.sca[2]*root.sca[2]];
+}
+
+impl Prefab{
+    pub fn new() -> Prefab{
+        Default::default()
+    }
+
+    // parents have to be objects of the prefab or of the scene it is instantiated into
+    pub fn add_object(&mut self, name : &str, object : Box<dyn ObjectTrait>){
+        self.objects.retain(|x| x.0 != name);
+        self.objects.push((name.into(), object));
+    }
+    pub fn add_material(&mut self, name : &str, material : Material){
+        self.materials.retain(|x| x.0 != name);
+        self.materials.push((name.into(), material));
+    }
+    pub fn object_names(&self) -> Vec<CompactString>{
+        self.objects.iter().map(|x| x.0.clone()).collect()
+    }
+    pub fn material_names(&self) -> Vec<CompactString>{
+        self.materials.iter().map(|x| x.0.clone()).collect()
+    }
+
+    // copies of all elements with prefixed names, meshes get their own polygon storage unless it is static and only uses scene materials
+    pub fn instantiate(&self, instance_name : &str, options : &PrefabInstanceOptions) -> PrefabElements{
+        let object_names : HashSet<&str> = self.objects.iter().map(|x| x.0.as_str()).collect();
+        let material_names : HashSet<&str> = self.materials.iter().map(|x| x.0.as_str()).collect();
+
+        let materials = self.materials.iter().map(|(name, material)| {
+            (prefixed_name(instance_name, name), options.materials.get(name).unwrap_or(material).duplicate())
+        }).collect();
+
+        let mut objects = Vec::with_capacity(self.objects.len());
+        for (name, object) in &self.objects{
+            let mut output : Box<dyn ObjectTrait> = if let Some(mesh) = object.get_mesh(){
+                let mut mesh = mesh.duplicate();
+                let (uses_prefab_materials, per_object) = {
+                    let polygons = mesh.get_polygonstorage_unlocked();
+                    (polygons.0.get_vgroups().iter().any(|x| x.material.as_ref().is_some_and(|x| material_names.contains(x.as_str()))),
+                     !matches!(polygons.0.get_type(), PolygonStorageType::Static))
+                };
+                if uses_prefab_materials || per_object {
+                    mesh.copy_polygonstorage();
+                }
+                for vgroup in mesh.get_polygonstorage_unlocked().0.get_vgroups_mut(){
+                    if let Some(material) = vgroup.material.as_mut().filter(|x| material_names.contains(x.as_str())){
+                        *material = prefixed_name(instance_name, material);
+                    }
+                }
+                if let Some(source) = mesh.instance_of_.as_mut().filter(|x| object_names.contains(x.as_str())){
+                    *source = prefixed_name(instance_name, source);
+                }
+                Box::new(mesh)
+            }
+            else if let Some(mut light) = object.get_light(){
+                light.get_data_mut().id_ = CommonObjectData::new(ObjectType::Light).id_;
+                Box::new(light)
+            }
+            else if let Some(mut camera) = object.get_camera(){
+                camera.get_data_mut().id_ = CommonObjectData::new(ObjectType::Camera).id_;
+                Box::new(camera)
+            }
+            else {
+                continue;
+            };
+
+            let data = output.get_data_mut();
+            if !data.parent.is_empty() && object_names.contains(data.parent.as_str()) {
+                data.parent = prefixed_name(instance_name, &data.parent);
+            }
+            // the engine does not propagate transforms to children, so every object is placed relative to the root
+            if let Some(object_override) = options.overrides.get(name){
+                if let Some(transform) = &object_override.transform{
+                    (data.pos, data.rot, data.sca) = (transform.pos, transform.rot, transform.sca);
+                }
+                if let Some(visible) = object_override.visible{
+                    data.visible = visible;
+                }
+            }
+            apply_transform(&options.transform, data);
+            objects.push((prefixed_name(instance_name, name), output));
+        }
+        (objects, materials)
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use compact_str::CompactString;
+
+use super::polygonstorage::DynamicPolygonStorage;
+use super::polygonstoragetrait::*;
+
+// all primitives are centered at the origin with +Z pointing up and
+// contain a single vertex group with one UV map
+
+// positions and normals share their indices, UVs are indexed separately so seams do not split the positions
+#[derive(Default)]
+struct PrimitiveBuilder{
+    positions : Vec<f32>,
+    normals : Vec<f32>,
+    uvs : Vec<f32>,
+    indices : Vec<u32>,
+    num_of_triangles : u32,
+    vertex_ids : HashMap<[u32; 6], u32>,
+    uv_ids : HashMap<[u32; 2], u32>,
+}
+
+impl PrimitiveBuilder{
+    fn add_vertex(&mut self, pos : [f32; 3], normal : [f32; 3]) -> u32{
+        // + 0.0 turns -0.0 into 0.0
+        let key = [pos[0], pos[1], pos[2], normal[0], normal[1], normal[2]].map(|x| (x + 0.0).to_bits());
+        let next_id = self.vertex_ids.len() as u32;
+        let id = *self.vertex_ids.entry(key).or_insert(next_id);
+        if id == next_id {
+            self.positions.extend_from_slice(&pos);
+            self.normals.extend_from_slice(&normal);
+        }
+        id
+    }
+    fn add_uv(&mut self, uv : [f32; 2]) -> u32{
+        let next_id = self.uv_ids.len() as u32;
+        let id = *self.uv_ids.entry(uv.map(|x| (x + 0.0).to_bits())).or_insert(next_id);
+        if id == next_id {
+            self.uvs.extend_from_slice(&uv);
+        }
+        id
+    }
+
+    // counter clockwise, (vertex, uv) for each corner, triangles collapsed at poles or tips are skipped
+    fn add_triangle(&mut self, corners : [(u32, u32); 3]){
+        let pos = |id : u32| &self.positions[id as usize*3..id as usize*3+3];
+        if pos(corners[0].0) == pos(corners[1].0) || pos(corners[1].0) == pos(corners[2].0) || pos(corners[2].0) == pos(corners[0].0) {
+            return;
+        }
+        for (vertex, uv) in corners{
+            self.indices.extend_from_slice(&[vertex, vertex, uv]);
+        }
+        self.num_of_triangles += 1;
+    }
+    fn add_quad(&mut self, corners : [(u32, u32); 4]){
+        self.add_triangle([corners[0], corners[1], corners[2]]);
+        self.add_triangle([corners[0], corners[2], corners[3]]);
+    }
+
+    // the closure maps (u, v) in 0.0 - 1.0 to a position and normal, u and v increase counter clockwise around the normal
+    fn add_grid(&mut self, cols : u32, rows : u32, wrap_u : bool, wrap_v : bool, vertex : impl Fn(f32, f32) -> ([f32; 3], [f32; 3])){
+        let mut ids = Vec::with_capacity(((cols+1)*(rows+1)) as usize);
+        for j in 0..=rows{
+            for i in 0..=cols{
+                let (u, v) = (i as f32/cols as f32, j as f32/rows as f32);
+                // the last row and column have to end up exactly on the first ones
+                let pos_u = if wrap_u && i == cols {0.0} else {u};
+                let pos_v = if wrap_v && j == rows {0.0} else {v};
+                let (pos, normal) = vertex(pos_u, pos_v);
+                ids.push((self.add_vertex(pos, normal), self.add_uv([u, v])));
+            }
+        }
+        let id = |i : u32, j : u32| ids[(j*(cols+1)+i) as usize];
+        for j in 0..rows{
+            for i in 0..cols{
+                self.add_quad([id(i, j), id(i+1, j), id(i+1, j+1), id(i, j+1)]);
+            }
+        }
+    }
+
+    // triangle fan around the center, the ring is given counter clockwise around the normal
+    fn add_disk(&mut self, center : [f32; 3], normal : [f32; 3], ring : &[([f32; 3], [f32; 2])]){
+        let center_id = (self.add_vertex(center, normal), self.add_uv([0.5, 0.5]));
+        let ids : Vec<(u32, u32)> = ring.iter().map(|(pos, uv)| (self.add_vertex(*pos, normal), self.add_uv(*uv))).collect();
+        for i in 0..ids.len(){
+            self.add_triangle([center_id, ids[i], ids[(i+1)%ids.len()]]);
+        }
+    }
+
+    fn build(self) -> DynamicPolygonStorage{
+        let mut vgroup = VertexGroup::new();
+        vgroup.name = CompactString::new("default");
+        vgroup.polygons = (0..self.num_of_triangles).collect();
+        DynamicPolygonStorage::new(self.positions, self.normals, vec![UVMapData{elements : self.uvs}], self.indices, vec![vgroup])
+    }
+}
+
+fn normalize(v : [f32; 3]) -> [f32; 3]{
+    let length = (v[0]*v[0] + v[1]*v[1] + v[2]*v[2]).sqrt();
+    if length > 1e-12 {v.map(|x| x/length)} else {v}
+}
+
+fn ring(radius : f32, z : f32, segments : u32, clockwise : bool) -> Vec<([f32; 3], [f32; 2])>{
+    (0..segments).map(|i| {
+        let angle = if clockwise {-1.0} else {1.0} * 2.0*PI*i as f32/segments as f32;
+        ([radius*angle.cos(), radius*angle.sin(), z], [0.5 + 0.5*angle.cos(), 0.5 + 0.5*angle.sin()])
+    }).collect()
+}
+
+// subdivisions are the number of cells along each side
+pub fn plane(size : f32, subdivisions : u32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    let subdivisions = subdivisions.max(1);
+    builder.add_grid(subdivisions, subdivisions, false, false, |u, v| ([(u - 0.5)*size, (v - 0.5)*size, 0.0], [0.0, 0.0, 1.0]));
+    builder.build()
+}
+
+pub fn cube(size : f32, subdivisions : u32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    let subdivisions = subdivisions.max(1);
+    // normal, u and v axes of every face
+    let faces : [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 1.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ];
+    for (normal, axis_u, axis_v) in faces{
+        builder.add_grid(subdivisions, subdivisions, false, false, |u, v| {
+            let pos = [0, 1, 2].map(|i| (normal[i]*0.5 + axis_u[i]*(u - 0.5) + axis_v[i]*(v - 0.5))*size);
+            (pos, normal)
+        });
+    }
+    builder.build()
+}
+
+pub fn uv_sphere(radius : f32, segments : u32, rings : u32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    builder.add_grid(segments, rings, true, false, |u, v| {
+        // exact poles so their triangles collapse
+        let normal = match v{
+            0.0 => [0.0, 0.0, -1.0],
+            1.0 => [0.0, 0.0, 1.0],
+            _ => {
+                let (theta, phi) = (2.0*PI*u, PI*(1.0 - v));
+                [phi.sin()*theta.cos(), phi.sin()*theta.sin(), phi.cos()]
+            }
+        };
+        (normal.map(|x| x*radius), normal)
+    });
+    builder.build()
+}
+
+// subdivisions of 0 give an icosahedron, every subdivision quadruples the number of triangles
+pub fn icosphere(radius : f32, subdivisions : u32) -> DynamicPolygonStorage{
+    let t = (1.0 + 5.0f32.sqrt())/2.0;
+    let mut vertices : Vec<[f32; 3]> = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ].map(normalize).to_vec();
+    let mut triangles : Vec<[usize; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions{
+        let mut midpoints : HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a : usize, b : usize| -> usize{
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                vertices.push(normalize([0, 1, 2].map(|i| vertices[a][i] + vertices[b][i])));
+                vertices.len() - 1
+            })
+        };
+        triangles = triangles.iter().flat_map(|[a, b, c]| {
+            let (ab, bc, ca) = (midpoint(*a, *b), midpoint(*b, *c), midpoint(*c, *a));
+            [[*a, ab, ca], [*b, bc, ab], [*c, ca, bc], [ab, bc, ca]]
+        }).collect();
+    }
+
+    let mut builder = PrimitiveBuilder::default();
+    let ids : Vec<u32> = vertices.iter().map(|normal| builder.add_vertex(normal.map(|x| x*radius), *normal)).collect();
+    for tri in triangles{
+        // spherical mapping, the UVs of triangles crossing the seam are wrapped and poles take the u of their neighbours
+        let mut uvs = tri.map(|id| {
+            let normal = vertices[id];
+            [0.5 + normal[1].atan2(normal[0])/(2.0*PI), 0.5 + normal[2].clamp(-1.0, 1.0).asin()/PI]
+        });
+        let us = uvs.map(|x| x[0]);
+        if us.iter().cloned().fold(f32::MIN, f32::max) - us.iter().cloned().fold(f32::MAX, f32::min) > 0.5 {
+            uvs.iter_mut().filter(|x| x[0] < 0.5).for_each(|x| x[0] += 1.0);
+        }
+        for corner in 0..3{
+            if vertices[tri[corner]][2].abs() > 0.9999 {
+                uvs[corner][0] = (uvs[(corner+1)%3][0] + uvs[(corner+2)%3][0])/2.0;
+            }
+        }
+        let uv_ids = uvs.map(|uv| builder.add_uv(uv));
+        builder.add_triangle([0, 1, 2].map(|corner| (ids[tri[corner]], uv_ids[corner])));
+    }
+    builder.build()
+}
+
+pub fn cylinder(radius : f32, depth : f32, segments : u32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    let segments = segments.max(3);
+    builder.add_grid(segments, 1, true, false, |u, v| {
+        let angle = 2.0*PI*u;
+        ([radius*angle.cos(), radius*angle.sin(), (v - 0.5)*depth], [angle.cos(), angle.sin(), 0.0])
+    });
+    builder.add_disk([0.0, 0.0, depth/2.0], [0.0, 0.0, 1.0], &ring(radius, depth/2.0, segments, false));
+    builder.add_disk([0.0, 0.0, -depth/2.0], [0.0, 0.0, -1.0], &ring(radius, -depth/2.0, segments, true));
+    builder.build()
+}
+
+pub fn cone(radius : f32, depth : f32, segments : u32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    let segments = segments.max(3);
+    builder.add_grid(segments, 1, true, false, |u, v| {
+        let angle = 2.0*PI*u;
+        let normal = normalize([depth*angle.cos(), depth*angle.sin(), radius]);
+        let ring_radius = radius*(1.0 - v);
+        // exact tip so the top triangles collapse
+        let z = if v == 1.0 {depth/2.0} else {(v - 0.5)*depth};
+        ([ring_radius*angle.cos(), ring_radius*angle.sin(), z], normal)
+    });
+    builder.add_disk([0.0, 0.0, -depth/2.0], [0.0, 0.0, -1.0], &ring(radius, -depth/2.0, segments, true));
+    builder.build()
+}
+
+pub fn torus(major_radius : f32, minor_radius : f32, major_segments : u32, minor_segments : u32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    builder.add_grid(major_segments.max(3), minor_segments.max(3), true, true, |u, v| {
+        let (theta, phi) = (2.0*PI*u, 2.0*PI*v);
+        let normal = [phi.cos()*theta.cos(), phi.cos()*theta.sin(), phi.sin()];
+        let ring_radius = major_radius + minor_radius*phi.cos();
+        ([ring_radius*theta.cos(), ring_radius*theta.sin(), minor_radius*phi.sin()], normal)
+    });
+    builder.build()
+}
+
+// the height closure takes (u, v) in 0.0 - 1.0 across the grid
+pub fn heightmap(size : [f32; 2], subdivisions : u32, height : impl Fn(f32, f32) -> f32) -> DynamicPolygonStorage{
+    let mut builder = PrimitiveBuilder::default();
+    let subdivisions = subdivisions.max(1);
+    let num = subdivisions as usize + 1;
+    let heights : Vec<f32> = (0..num*num).map(|id| height((id%num) as f32/subdivisions as f32, (id/num) as f32/subdivisions as f32)).collect();
+    let cell = [size[0]/subdivisions as f32, size[1]/subdivisions as f32];
+
+    builder.add_grid(subdivisions, subdivisions, false, false, |u, v| {
+        let (i, j) = ((u*subdivisions as f32).round() as usize, (v*subdivisions as f32).round() as usize);
+        let h = |i : usize, j : usize| heights[j*num + i];
+        // central differences, one sided at the borders
+        let (i0, i1, j0, j1) = (i.saturating_sub(1), (i+1).min(num-1), j.saturating_sub(1), (j+1).min(num-1));
+        let dx = (h(i1, j) - h(i0, j))/((i1 - i0) as f32*cell[0]);
+        let dy = (h(i, j1) - h(i, j0))/((j1 - j0) as f32*cell[1]);
+        ([(u - 0.5)*size[0], (v - 0.5)*size[1], h(i, j)], normalize([-dx, -dy, 1.0]))
+    });
+    builder.build()
+}
+
+#[cfg(test)]
+pub mod primitivestest{
+    use super::*;
+
+    // checks winding and normals of every triangle, returns the number of vertices and triangles
+    fn check(storage : &DynamicPolygonStorage, closed : bool) -> (usize, usize){
+        let pos = |id : u32| -> [f32; 3] {storage.positions[id as usize*3..id as usize*3+3].try_into().unwrap()};
+        let mut edges : HashMap<([u32; 3], [u32; 3]), u32> = HashMap::new();
+        for id in 0..storage.num_of_triangles() as u32{
+            let tri = storage.get_triangle(id).unwrap();
+            let corners = [tri[0], tri[3], tri[6]];
+            let [a, b, c] = corners.map(pos);
+            let (ab, ac) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+            let face_normal = [ab[1]*ac[2] - ab[2]*ac[1], ab[2]*ac[0] - ab[0]*ac[2], ab[0]*ac[1] - ab[1]*ac[0]];
+            for corner in 0..3{
+                let normal = &storage.normals[tri[corner*3+1] as usize*3..tri[corner*3+1] as usize*3+3];
+                assert!((normal.iter().map(|x| x*x).sum::<f32>() - 1.0).abs() < 1e-5);
+                assert!((0..3).map(|i| face_normal[i]*normal[i]).sum::<f32>() > 0.0, "triangle {} is not counter clockwise", id);
+                let uv = &storage.uvmaps[0].elements[tri[corner*3+2] as usize*2..tri[corner*3+2] as usize*2+2];
+                // wrapped icosphere UVs go past 1.0 at the seam
+                assert!(uv.iter().all(|x| (0.0..=1.5).contains(x)));
+
+                // positions are compared by value, the same position may appear with several normals
+                let key = |id : u32| pos(id).map(|x| (x*1e4).round() as i32 as u32);
+                *edges.entry((key(corners[corner]), key(corners[(corner+1)%3]))).or_default() += 1;
+            }
+        }
+        if closed {
+            // every directed edge exists once and is matched by its reverse
+            for ((a, b), count) in &edges{
+                assert_eq!((*count, edges.get(&(*b, *a))), (1, Some(&1)));
+            }
+        }
+        (storage.positions.len()/3, storage.num_of_triangles())
+    }
+
+    #[test]
+    fn counts_and_winding(){
+        assert_eq!(check(&plane(2.0, 2), false), (9, 8));
+        assert_eq!(check(&cube(1.0, 1), true), (24, 12));
+        assert_eq!(check(&cube(1.0, 2), true), (54, 48));
+        // the triangles at the poles and tips collapse
+        assert_eq!(check(&uv_sphere(1.0, 8, 4), true), (26, 48));
+        assert_eq!(check(&icosphere(1.0, 0), true), (12, 20));
+        assert_eq!(check(&icosphere(1.0, 1), true), (42, 80));
+        assert_eq!(check(&cylinder(1.0, 2.0, 8), true), (34, 32));
+        assert_eq!(check(&cone(1.0, 2.0, 8), true), (25, 16));
+        assert_eq!(check(&torus(2.0, 0.5, 8, 4), true), (32, 64));
+        assert_eq!(check(&heightmap([2.0, 2.0], 4, |u, v| u*v), false), (25, 32));
+    }
+
+    #[test]
+    fn dimensions(){
+        let extent = |storage : &DynamicPolygonStorage, axis : usize| -> (f32, f32){
+            storage.positions.iter().skip(axis).step_by(3).fold((f32::MAX, f32::MIN), |(min, max), x| (min.min(*x), max.max(*x)))
+        };
+        let cube = cube(2.0, 3);
+        assert!((0..3).all(|axis| extent(&cube, axis) == (-1.0, 1.0)));
+        let sphere = icosphere(3.0, 2);
+        assert!(sphere.positions.chunks(3).all(|x| ((x[0]*x[0] + x[1]*x[1] + x[2]*x[2]).sqrt() - 3.0).abs() < 1e-5));
+        let cylinder = cylinder(0.5, 4.0, 16);
+        assert_eq!(extent(&cylinder, 2), (-2.0, 2.0));
+        assert!(cylinder.positions.chunks(3).all(|x| (x[0]*x[0] + x[1]*x[1]).sqrt() <= 0.5 + 1e-6));
+
+        // a slope rising by one along x over a width of two
+        let slope = heightmap([2.0, 2.0], 2, |u, _| u);
+        assert_eq!(extent(&slope, 2), (0.0, 1.0));
+        let expected = normalize([-0.5, 0.0, 1.0]);
+        assert!(slope.normals.chunks(3).all(|x| (0..3).all(|i| (x[i] - expected[i]).abs() < 1e-6)));
+    }
+}
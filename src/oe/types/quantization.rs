@@ -0,0 +1,453 @@
+use compact_str::{CompactString, format_compact};
+
+use super::polygonstoragetrait::PolygonStorageData;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PositionFormat{
+    #[default]
+    Float,
+    // normalized to the bounding box of the mesh
+    Unorm16,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalFormat{
+    #[default]
+    Float,
+    // octahedral mapping to two snorm16 values, tangents become four snorm16 values
+    Octahedral16,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UvFormat{
+    #[default]
+    Float,
+    Half,
+    // normalized to the range of each uv map
+    Unorm16,
+}
+
+impl PositionFormat{
+    pub fn from_name(name : &str) -> Option<PositionFormat>{
+        match name{
+            "float" => Some(PositionFormat::Float),
+            "unorm16" => Some(PositionFormat::Unorm16),
+            _ => None
+        }
+    }
+}
+impl NormalFormat{
+    pub fn from_name(name : &str) -> Option<NormalFormat>{
+        match name{
+            "float" => Some(NormalFormat::Float),
+            "octahedral" => Some(NormalFormat::Octahedral16),
+            _ => None
+        }
+    }
+}
+impl UvFormat{
+    pub fn from_name(name : &str) -> Option<UvFormat>{
+        match name{
+            "float" => Some(UvFormat::Float),
+            "half" => Some(UvFormat::Half),
+            "unorm16" => Some(UvFormat::Unorm16),
+            _ => None
+        }
+    }
+}
+
+// selected per mesh, all floats is the regular vertex buffer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VertexFormat{
+    pub position : PositionFormat,
+    pub normal : NormalFormat,
+    pub uv : UvFormat,
+}
+
+impl VertexFormat{
+    pub fn quantized() -> VertexFormat{
+        VertexFormat{
+            position : PositionFormat::Unorm16,
+            normal : NormalFormat::Octahedral16,
+            uv : UvFormat::Unorm16,
+        }
+    }
+    pub fn is_float(&self) -> bool{
+        *self == VertexFormat::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComponentType{
+    Float,
+    HalfFloat,
+    UnsignedShort,
+    Short,
+}
+
+impl ComponentType{
+    pub fn size(&self) -> usize{
+        match self{
+            ComponentType::Float => 4,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexAttribute{
+    // position, normal, uv0, uv1... and tangent
+    pub name : CompactString,
+    // in bytes from the start of the vertex
+    pub offset : usize,
+    pub components : u8,
+    pub component_type : ComponentType,
+    // integers are mapped to [0, 1] or [-1, 1]
+    pub normalized : bool,
+}
+
+// describes the interleaved vertex buffer for renderers, together with the values needed to dequantize it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexLayout{
+    // in bytes
+    pub stride : usize,
+    pub attributes : Vec<VertexAttribute>,
+    // position = offset + normalized value * scale
+    pub position_offset : [f32; 3],
+    pub position_scale : [f32; 3],
+    // per uv map, same as the positions
+    pub uv_offsets : Vec<[f32; 2]>,
+    pub uv_scales : Vec<[f32; 2]>,
+}
+
+impl VertexLayout{
+    pub fn new(format : VertexFormat, num_of_uvs : u8, tangents : bool) -> VertexLayout{
+        let mut output = VertexLayout{
+            position_scale : [1.0; 3],
+            uv_offsets : vec![[0.0; 2]; num_of_uvs as usize],
+            uv_scales : vec![[1.0; 2]; num_of_uvs as usize],
+            ..Default::default()
+        };
+        let mut add = |name : CompactString, components : u8, component_type : ComponentType, normalized : bool|{
+            let offset = output.stride;
+            output.attributes.push(VertexAttribute{name, offset, components, component_type, normalized});
+            // every attribute starts 4 byte aligned
+            output.stride += (components as usize*component_type.size()).div_ceil(4)*4;
+        };
+        match format.position{
+            PositionFormat::Float => add("position".into(), 3, ComponentType::Float, false),
+            PositionFormat::Unorm16 => add("position".into(), 3, ComponentType::UnsignedShort, true),
+        }
+        match format.normal{
+            NormalFormat::Float => add("normal".into(), 3, ComponentType::Float, false),
+            NormalFormat::Octahedral16 => add("normal".into(), 2, ComponentType::Short, true),
+        }
+        for uvmap in 0..num_of_uvs{
+            match format.uv{
+                UvFormat::Float => add(format_compact!("uv{}", uvmap), 2, ComponentType::Float, false),
+                UvFormat::Half => add(format_compact!("uv{}", uvmap), 2, ComponentType::HalfFloat, false),
+                UvFormat::Unorm16 => add(format_compact!("uv{}", uvmap), 2, ComponentType::UnsignedShort, true),
+            }
+        }
+        if tangents {
+            match format.normal{
+                NormalFormat::Float => add("tangent".into(), 4, ComponentType::Float, false),
+                NormalFormat::Octahedral16 => add("tangent".into(), 4, ComponentType::Short, true),
+            }
+        }
+        output
+    }
+}
+
+fn to_half(value : f32) -> u16{
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 {0x200} else {0};
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // subnormal
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        return sign | ((mantissa >> shift) + ((mantissa >> (shift - 1)) & 1)) as u16;
+    }
+    // rounding may carry into the exponent, which is still the correct result
+    sign | ((((exponent as u32) << 10) | (mantissa >> 13)) + ((mantissa >> 12) & 1)) as u16
+}
+
+fn from_half(value : u16) -> f32{
+    let sign = if value & 0x8000 != 0 {-1.0} else {1.0};
+    let exponent = ((value >> 10) & 0x1f) as i32;
+    let mantissa = (value & 0x3ff) as f32;
+    sign * match exponent{
+        0 => mantissa * 2f32.powi(-24),
+        0x1f => if mantissa == 0.0 {f32::INFINITY} else {f32::NAN},
+        _ => (1.0 + mantissa/1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn to_unorm16(value : f32) -> u16{
+    (value.clamp(0.0, 1.0)*65535.0).round() as u16
+}
+fn to_snorm16(value : f32) -> i16{
+    (value.clamp(-1.0, 1.0)*32767.0).round() as i16
+}
+fn from_snorm16(value : i16) -> f32{
+    (value as f32/32767.0).max(-1.0)
+}
+
+fn octahedral_encode(normal : &[f32]) -> [i16; 2]{
+    let length = normal[0].abs() + normal[1].abs() + normal[2].abs();
+    if length == 0.0 {
+        return [0, 0];
+    }
+    let (x, y) = (normal[0]/length, normal[1]/length);
+    let sign = |x : f32| if x >= 0.0 {1.0} else {-1.0};
+    // the lower hemisphere is folded over the diagonals
+    let (x, y) = if normal[2] < 0.0 {((1.0 - y.abs())*sign(x), (1.0 - x.abs())*sign(y))} else {(x, y)};
+    [to_snorm16(x), to_snorm16(y)]
+}
+
+fn octahedral_decode(value : [i16; 2]) -> [f32; 3]{
+    let (x, y) = (from_snorm16(value[0]), from_snorm16(value[1]));
+    let z = 1.0 - x.abs() - y.abs();
+    let fold = (-z).max(0.0);
+    let x = if x >= 0.0 {x - fold} else {x + fold};
+    let y = if y >= 0.0 {y - fold} else {y + fold};
+    let length = (x*x + y*y + z*z).sqrt();
+    [x/length, y/length, z/length]
+}
+
+// vertex buffer in a quantized layout, used instead of the float vertex buffer to save memory
+#[derive(Clone, Debug, Default)]
+pub struct QuantizedVertexBuffer{
+    pub format : VertexFormat,
+    pub layout : VertexLayout,
+    pub data : Vec<u8>,
+    num_of_uvs : u8,
+    tangents : bool,
+}
+
+impl QuantizedVertexBuffer{
+    pub fn new(source : &PolygonStorageData, format : VertexFormat) -> QuantizedVertexBuffer{
+        let tangents = source.tangent_uvmap.is_some();
+        let mut layout = VertexLayout::new(format, source.num_of_uvs, tangents);
+        let vertex_length = source.vertex_length();
+        let vertices = &source.vertex_buffer_;
+        let num_of_vertices = vertices.len()/vertex_length;
+
+        // ranges of the normalized values
+        let range = |offset : usize, components : usize| -> (Vec<f32>, Vec<f32>){
+            let mut min = vec![f32::MAX; components];
+            let mut max = vec![f32::MIN; components];
+            for vertex in vertices.chunks(vertex_length){
+                for component in 0..components{
+                    min[component] = min[component].min(vertex[offset+component]);
+                    max[component] = max[component].max(vertex[offset+component]);
+                }
+            }
+            if num_of_vertices == 0 {
+                return (vec![0.0; components], vec![0.0; components]);
+            }
+            let scale = min.iter().zip(&max).map(|(min, max)| (max - min)/65535.0).collect();
+            (min, scale)
+        };
+        if format.position == PositionFormat::Unorm16 {
+            let (offset, scale) = range(0, 3);
+            layout.position_offset = [offset[0], offset[1], offset[2]];
+            layout.position_scale = [scale[0], scale[1], scale[2]];
+        }
+        if format.uv == UvFormat::Unorm16 {
+            for uvmap in 0..source.num_of_uvs as usize{
+                let (offset, scale) = range(6 + uvmap*2, 2);
+                layout.uv_offsets[uvmap] = [offset[0], offset[1]];
+                layout.uv_scales[uvmap] = [scale[0], scale[1]];
+            }
+        }
+        let normalize = |value : f32, offset : f32, scale : f32| if scale > 0.0 {(value - offset)/(scale*65535.0)} else {0.0};
+
+        let mut data = Vec::with_capacity(num_of_vertices*layout.stride);
+        for vertex in vertices.chunks(vertex_length){
+            let start = data.len();
+            let mut attributes = layout.attributes.iter();
+            let mut seek = |data : &mut Vec<u8>| data.resize(start + attributes.next().unwrap().offset, 0);
+
+            seek(&mut data);
+            for (axis, value) in vertex[0..3].iter().enumerate(){
+                match format.position{
+                    PositionFormat::Float => data.extend_from_slice(&value.to_le_bytes()),
+                    PositionFormat::Unorm16 => data.extend_from_slice(&to_unorm16(normalize(*value, layout.position_offset[axis], layout.position_scale[axis])).to_le_bytes()),
+                }
+            }
+            seek(&mut data);
+            match format.normal{
+                NormalFormat::Float => vertex[3..6].iter().for_each(|x| data.extend_from_slice(&x.to_le_bytes())),
+                NormalFormat::Octahedral16 => octahedral_encode(&vertex[3..6]).iter().for_each(|x| data.extend_from_slice(&x.to_le_bytes())),
+            }
+            for uvmap in 0..source.num_of_uvs as usize{
+                seek(&mut data);
+                for component in 0..2{
+                    let value = vertex[6 + uvmap*2 + component];
+                    match format.uv{
+                        UvFormat::Float => data.extend_from_slice(&value.to_le_bytes()),
+                        UvFormat::Half => data.extend_from_slice(&to_half(value).to_le_bytes()),
+                        UvFormat::Unorm16 => data.extend_from_slice(&to_unorm16(normalize(value, layout.uv_offsets[uvmap][component], layout.uv_scales[uvmap][component])).to_le_bytes()),
+                    }
+                }
+            }
+            if tangents {
+                seek(&mut data);
+                for value in &vertex[vertex_length-4..]{
+                    match format.normal{
+                        NormalFormat::Float => data.extend_from_slice(&value.to_le_bytes()),
+                        NormalFormat::Octahedral16 => data.extend_from_slice(&to_snorm16(*value).to_le_bytes()),
+                    }
+                }
+            }
+            data.resize(start + layout.stride, 0);
+        }
+
+        QuantizedVertexBuffer{
+            format,
+            layout,
+            data,
+            num_of_uvs : source.num_of_uvs,
+            tangents,
+        }
+    }
+
+    pub fn len(&self) -> usize{
+        self.data.len()/self.layout.stride.max(1)
+    }
+    pub fn is_empty(&self) -> bool{
+        self.data.is_empty()
+    }
+
+    // software path, returns the vertices in the layout of the float vertex buffer
+    pub fn dequantize(&self) -> Vec<f32>{
+        let vertex_length = 6 + self.num_of_uvs as usize*2 + if self.tangents {4} else {0};
+        let mut output = Vec::with_capacity(self.len()*vertex_length);
+        let layout = &self.layout;
+        for vertex in self.data.chunks(layout.stride){
+            let float = |offset : usize| f32::from_le_bytes(vertex[offset..offset+4].try_into().unwrap());
+            let short = |offset : usize| i16::from_le_bytes(vertex[offset..offset+2].try_into().unwrap());
+            let ushort = |offset : usize| u16::from_le_bytes(vertex[offset..offset+2].try_into().unwrap());
+            let mut attributes = layout.attributes.iter().map(|x| x.offset);
+
+            let offset = attributes.next().unwrap();
+            for axis in 0..3{
+                output.push(match self.format.position{
+                    PositionFormat::Float => float(offset + axis*4),
+                    PositionFormat::Unorm16 => layout.position_offset[axis] + ushort(offset + axis*2) as f32*layout.position_scale[axis],
+                });
+            }
+            let offset = attributes.next().unwrap();
+            match self.format.normal{
+                NormalFormat::Float => output.extend((0..3).map(|axis| float(offset + axis*4))),
+                NormalFormat::Octahedral16 => output.extend_from_slice(&octahedral_decode([short(offset), short(offset + 2)])),
+            }
+            for uvmap in 0..self.num_of_uvs as usize{
+                let offset = attributes.next().unwrap();
+                for component in 0..2{
+                    output.push(match self.format.uv{
+                        UvFormat::Float => float(offset + component*4),
+                        UvFormat::Half => from_half(ushort(offset + component*2)),
+                        UvFormat::Unorm16 => layout.uv_offsets[uvmap][component] + ushort(offset + component*2) as f32*layout.uv_scales[uvmap][component],
+                    });
+                }
+            }
+            if self.tangents {
+                let offset = attributes.next().unwrap();
+                for component in 0..4{
+                    output.push(match self.format.normal{
+                        NormalFormat::Float => float(offset + component*4),
+                        NormalFormat::Octahedral16 => from_snorm16(short(offset + component*2)),
+                    });
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+pub mod quantizationtest{
+    use super::*;
+    use super::super::polygonstorage::StaticPolygonStorage;
+    use super::super::polygonstoragetrait::PolygonStorageTrait;
+    use super::super::primitives;
+
+    #[test]
+    fn half_round_trip(){
+        // exactly representable values survive unchanged
+        for value in [0.0, -0.0, 1.0, -2.0, 0.5, 1024.0, 65504.0, 2f32.powi(-14), 2f32.powi(-24), f32::INFINITY, f32::NEG_INFINITY]{
+            assert_eq!(from_half(to_half(value)), value);
+        }
+        assert!(from_half(to_half(f32::NAN)).is_nan());
+        // out of range values become infinite, tiny ones zero
+        assert_eq!(from_half(to_half(1e6)), f32::INFINITY);
+        assert_eq!(from_half(to_half(1e-9)), 0.0);
+
+        // normal values are within half an ulp of the 11 bit mantissa
+        for step in 0..10000{
+            let value = (step as f32*0.37 - 1800.0)*1.013;
+            if value.abs() < 2f32.powi(-14) {continue;}
+            let error = (from_half(to_half(value)) - value).abs();
+            assert!(error <= value.abs()*2f32.powi(-11), "{} {}", value, error);
+        }
+    }
+
+    #[test]
+    fn octahedral_round_trip(){
+        let mut worst : f32 = 1.0;
+        for step in 0..64*64{
+            // evenly spread over the sphere, including the poles and the folded lower hemisphere
+            let (theta, phi) = ((step/64) as f32/63.0*std::f32::consts::PI, (step%64) as f32/64.0*std::f32::consts::TAU);
+            let normal = [theta.sin()*phi.cos(), theta.sin()*phi.sin(), theta.cos()];
+            let decoded = octahedral_decode(octahedral_encode(&normal));
+            assert!(((decoded[0]*decoded[0] + decoded[1]*decoded[1] + decoded[2]*decoded[2]).sqrt() - 1.0).abs() < 1e-5);
+            worst = worst.min(normal[0]*decoded[0] + normal[1]*decoded[1] + normal[2]*decoded[2]);
+        }
+        // less than 0.06 degrees off
+        assert!(worst > (0.06f32).to_radians().cos(), "{}", worst);
+    }
+
+    #[test]
+    fn vertex_buffer_round_trip(){
+        let mut polygons = StaticPolygonStorage::new(primitives::uv_sphere(2.0, 16, 8));
+        assert!(polygons.set_tangents(Some(0)));
+        let original = polygons.get_vertex_buffer().clone();
+        let vertex_length = polygons.get_data().unwrap().vertex_length();
+
+        for format in [VertexFormat::quantized(), VertexFormat{position : PositionFormat::Float, normal : NormalFormat::Float, uv : UvFormat::Half}]{
+            polygons.compress(format);
+            assert_eq!(polygons.get_vertex_format(), format);
+            let quantized = polygons.get_quantized_vertex_buffer().unwrap();
+            assert_eq!(quantized.len(), original.len()/vertex_length);
+            assert_eq!(quantized.data.len(), quantized.len()*quantized.layout.stride);
+
+            let dequantized = polygons.get_dequantized_vertex_buffer().to_vec();
+            assert_eq!(dequantized.len(), original.len());
+            for (a, b) in original.chunks(vertex_length).zip(dequantized.chunks(vertex_length)){
+                // positions within a step of the bounding box, uvs within a step of their range
+                assert!((0..3).all(|i| (a[i] - b[i]).abs() < 4.0/65535.0), "{:?} {:?}", &a[..3], &b[..3]);
+                assert!((3..6).all(|i| (a[i] - b[i]).abs() < 1e-3), "{:?} {:?}", &a[3..6], &b[3..6]);
+                assert!((6..8).all(|i| (a[i] - b[i]).abs() < 1e-3), "{:?} {:?}", &a[6..8], &b[6..8]);
+                assert!((8..12).all(|i| (a[i] - b[i]).abs() < 1e-4), "{:?} {:?}", &a[8..], &b[8..]);
+            }
+        }
+        // going back to floats restores the float vertex buffer
+        polygons.compress(VertexFormat::default());
+        assert!(polygons.get_quantized_vertex_buffer().is_none());
+        assert_eq!(polygons.get_vertex_buffer().len(), original.len());
+    }
+}
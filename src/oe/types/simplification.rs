@@ -0,0 +1,326 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use nohash_hasher::IntMap;
+
+use super::polygonstoragetrait::PolygonStorageData;
+
+// index buffers of a simplified version of the mesh, they share the vertex buffer with the full mesh
+#[derive(Clone, Debug, Default)]
+pub struct LodLevel{
+    // fraction of the original triangles that was targeted
+    pub ratio : f32,
+    // largest projected size, relative to the viewport height, this level is used at
+    pub max_screen_size : f32,
+    pub index_buffers : IntMap<usize, Vec<u32>>,
+}
+
+// symmetric 4x4 matrix, a² ab ac ad b² bc bd c² cd d²
+type Quadric = [f64; 10];
+
+fn plane_quadric(n : [f64; 3], d : f64, weight : f64) -> Quadric{
+    let (a, b, c) = (n[0], n[1], n[2]);
+    [a*a, a*b, a*c, a*d, b*b, b*c, b*d, c*c, c*d, d*d].map(|x| x*weight)
+}
+fn add_quadrics(a : &Quadric, b : &Quadric) -> Quadric{
+    std::array::from_fn(|i| a[i] + b[i])
+}
+fn quadric_error(q : &Quadric, p : [f64; 3]) -> f64{
+    let (x, y, z) = (p[0], p[1], p[2]);
+    q[0]*x*x + 2.0*q[1]*x*y + 2.0*q[2]*x*z + 2.0*q[3]*x
+        + q[4]*y*y + 2.0*q[5]*y*z + 2.0*q[6]*y
+        + q[7]*z*z + 2.0*q[8]*z
+        + q[9]
+}
+
+fn sub(a : [f64; 3], b : [f64; 3]) -> [f64; 3] {[a[0]-b[0], a[1]-b[1], a[2]-b[2]]}
+fn dot(a : [f64; 3], b : [f64; 3]) -> f64 {a[0]*b[0] + a[1]*b[1] + a[2]*b[2]}
+fn cross(a : [f64; 3], b : [f64; 3]) -> [f64; 3] {[a[1]*b[2]-a[2]*b[1], a[2]*b[0]-a[0]*b[2], a[0]*b[1]-a[1]*b[0]]}
+
+#[derive(PartialEq)]
+struct Collapse{
+    cost : f64,
+    from : u32,
+    to : u32,
+    // versions of both vertices when the cost was computed
+    versions : (u32, u32),
+}
+
+impl Eq for Collapse {}
+impl PartialOrd for Collapse{
+    fn partial_cmp(&self, other : &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Collapse{
+    // cheapest collapse first
+    fn cmp(&self, other : &Self) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+// quadric error metric simplification restricted to the existing vertices,
+// vertices on uv or normal seams, open borders and vertex group boundaries are never moved
+struct Simplifier{
+    positions : Vec<[f64; 3]>,
+    triangles : Vec<[u32; 3]>,
+    triangle_vgroups : Vec<usize>,
+    alive : Vec<bool>,
+    num_of_alive : usize,
+    vertex_triangles : Vec<Vec<u32>>,
+    quadrics : Vec<Quadric>,
+    locked : Vec<bool>,
+    versions : Vec<u32>,
+    heap : BinaryHeap<Collapse>,
+}
+
+impl Simplifier{
+    fn new(data : &PolygonStorageData) -> Simplifier{
+        let stride = data.vertex_length();
+        let positions : Vec<[f64; 3]> = data.vertex_buffer_.chunks(stride).map(|x| [x[0] as f64, x[1] as f64, x[2] as f64]).collect();
+        let num_of_vertices = positions.len();
+
+        let mut vgroup_ids : Vec<usize> = data.index_buffers_.keys().copied().collect();
+        vgroup_ids.sort();
+        let mut triangles = vec![];
+        let mut triangle_vgroups = vec![];
+        for vgroup in vgroup_ids{
+            for tri in data.index_buffers_[&vgroup].chunks(3){
+                triangles.push([tri[0], tri[1], tri[2]]);
+                triangle_vgroups.push(vgroup);
+            }
+        }
+
+        let mut locked = vec![false; num_of_vertices];
+        // seams split a position into several vertices
+        let mut position_count : HashMap<[u64; 3], u32> = HashMap::new();
+        for pos in &positions{
+            *position_count.entry(pos.map(|x| (x + 0.0).to_bits())).or_default() += 1;
+        }
+        for (id, pos) in positions.iter().enumerate(){
+            locked[id] |= position_count[&pos.map(|x| (x + 0.0).to_bits())] > 1;
+        }
+        let mut vertex_vgroups : Vec<Option<usize>> = vec![None; num_of_vertices];
+        let mut edge_count : HashMap<(u32, u32), u32> = HashMap::new();
+        let mut vertex_triangles = vec![vec![]; num_of_vertices];
+        for (id, tri) in triangles.iter().enumerate(){
+            for corner in 0..3{
+                let (a, b) = (tri[corner], tri[(corner+1)%3]);
+                *edge_count.entry((a.min(b), a.max(b))).or_default() += 1;
+                vertex_triangles[a as usize].push(id as u32);
+                match vertex_vgroups[a as usize]{
+                    Some(vgroup) if vgroup != triangle_vgroups[id] => locked[a as usize] = true,
+                    _ => vertex_vgroups[a as usize] = Some(triangle_vgroups[id]),
+                }
+            }
+        }
+        for ((a, b), count) in &edge_count{
+            if *count == 1 {
+                locked[*a as usize] = true;
+                locked[*b as usize] = true;
+            }
+        }
+
+        let mut quadrics = vec![[0.0; 10]; num_of_vertices];
+        for tri in &triangles{
+            let p = tri.map(|x| positions[x as usize]);
+            let normal = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+            let length = dot(normal, normal).sqrt();
+            if length < 1e-12 {continue;}
+            let normal = normal.map(|x| x/length);
+            let quadric = plane_quadric(normal, -dot(normal, p[0]), length/2.0);
+            for id in tri{
+                quadrics[*id as usize] = add_quadrics(&quadrics[*id as usize], &quadric);
+            }
+        }
+
+        let mut output = Simplifier{
+            positions,
+            alive : vec![true; triangles.len()],
+            num_of_alive : triangles.len(),
+            triangles,
+            triangle_vgroups,
+            vertex_triangles,
+            quadrics,
+            locked,
+            versions : vec![0; num_of_vertices],
+            heap : BinaryHeap::new(),
+        };
+        for (a, b) in edge_count.into_keys(){
+            output.push_collapse(a, b);
+            output.push_collapse(b, a);
+        }
+        output
+    }
+
+    fn push_collapse(&mut self, from : u32, to : u32){
+        if self.locked[from as usize] {
+            return;
+        }
+        let quadric = add_quadrics(&self.quadrics[from as usize], &self.quadrics[to as usize]);
+        self.heap.push(Collapse{
+            cost : quadric_error(&quadric, self.positions[to as usize]),
+            from,
+            to,
+            versions : (self.versions[from as usize], self.versions[to as usize]),
+        });
+    }
+
+    fn alive_triangles(&self, vertex : u32) -> impl Iterator<Item=u32> + '_{
+        self.vertex_triangles[vertex as usize].iter().copied().filter(|x| self.alive[*x as usize])
+    }
+
+    // rejects collapses that flip triangles or make the mesh non manifold
+    fn is_valid(&self, from : u32, to : u32) -> bool{
+        let mut shared = 0;
+        let mut from_neighbours = HashSet::new();
+        for tri_id in self.alive_triangles(from){
+            let tri = self.triangles[tri_id as usize];
+            if tri.contains(&to) {
+                shared += 1;
+                continue;
+            }
+            from_neighbours.extend(tri.iter().copied().filter(|x| *x != from));
+
+            let p = tri.map(|x| self.positions[x as usize]);
+            let moved = tri.map(|x| self.positions[if x == from {to} else {x} as usize]);
+            let before = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+            let after = cross(sub(moved[1], moved[0]), sub(moved[2], moved[0]));
+            let (before_length, after_length) = (dot(before, before).sqrt(), dot(after, after).sqrt());
+            if after_length < 1e-12 || dot(before, after) < 0.2*before_length*after_length {
+                return false;
+            }
+        }
+        if shared == 0 {
+            return false;
+        }
+        // link condition, only the opposite vertices of the shared triangles may be common neighbours
+        let mut common = HashSet::new();
+        for tri_id in self.alive_triangles(to){
+            let tri = self.triangles[tri_id as usize];
+            if tri.contains(&from) {continue;}
+            common.extend(tri.iter().copied().filter(|x| from_neighbours.contains(x)));
+        }
+        common.len() <= shared
+    }
+
+    fn collapse(&mut self, from : u32, to : u32){
+        let from_triangles : Vec<u32> = self.alive_triangles(from).collect();
+        for tri_id in from_triangles{
+            let tri = &mut self.triangles[tri_id as usize];
+            if tri.contains(&to) {
+                self.alive[tri_id as usize] = false;
+                self.num_of_alive -= 1;
+            }
+            else {
+                tri.iter_mut().filter(|x| **x == from).for_each(|x| *x = to);
+                self.vertex_triangles[to as usize].push(tri_id);
+            }
+        }
+        self.quadrics[to as usize] = add_quadrics(&self.quadrics[to as usize], &self.quadrics[from as usize]);
+        self.versions[from as usize] += 1;
+        self.versions[to as usize] += 1;
+        self.vertex_triangles[to as usize].retain(|x| self.alive[*x as usize]);
+
+        let neighbours : HashSet<u32> = self.alive_triangles(to).flat_map(|x| self.triangles[x as usize]).filter(|x| *x != to).collect();
+        for neighbour in neighbours{
+            self.push_collapse(to, neighbour);
+            self.push_collapse(neighbour, to);
+        }
+    }
+
+    fn simplify(&mut self, target : usize){
+        while self.num_of_alive > target {
+            let Some(collapse) = self.heap.pop() else {break;};
+            let (from, to) = (collapse.from as usize, collapse.to as usize);
+            if collapse.versions != (self.versions[from], self.versions[to]) {continue;}
+            if self.is_valid(collapse.from, collapse.to) {
+                self.collapse(collapse.from, collapse.to);
+            }
+        }
+    }
+
+    fn get_index_buffers(&self, vgroup_ids : impl Iterator<Item=usize>) -> IntMap<usize, Vec<u32>>{
+        let mut output : IntMap<usize, Vec<u32>> = vgroup_ids.map(|x| (x, vec![])).collect();
+        for (id, tri) in self.triangles.iter().enumerate(){
+            if self.alive[id] {
+                output.get_mut(&self.triangle_vgroups[id]).unwrap().extend_from_slice(tri);
+            }
+        }
+        output
+    }
+}
+
+// every level continues simplifying the previous one, ratios should therefore be decreasing
+pub fn generate_lods(data : &PolygonStorageData, ratios : &[f32]) -> Vec<LodLevel>{
+    let mut simplifier = Simplifier::new(data);
+    let num_of_triangles = simplifier.triangles.len();
+    let mut output = Vec::with_capacity(ratios.len());
+    for ratio in ratios{
+        let ratio = ratio.clamp(0.0, 1.0);
+        simplifier.simplify((num_of_triangles as f32*ratio) as usize);
+        output.push(LodLevel{
+            ratio,
+            // the number of triangles on screen grows with the projected area
+            max_screen_size : ratio.sqrt(),
+            index_buffers : simplifier.get_index_buffers(data.index_buffers_.keys().copied()),
+        });
+    }
+    output
+}
+
+#[cfg(test)]
+pub mod simplificationtest{
+    use super::*;
+    use super::super::polygonstorage::DynamicPolygonStorage;
+    use super::super::polygonstoragetrait::PolygonStorageTrait;
+    use super::super::primitives;
+
+    // the same geometry without uv seams, so that no vertex is locked except on open borders
+    fn without_uvs(polygons : DynamicPolygonStorage) -> DynamicPolygonStorage{
+        let indices = (0..polygons.num_of_triangles() as u32).flat_map(|id| {
+            let tri = polygons.get_triangle(id).unwrap();
+            [tri[0], tri[1], tri[3], tri[4], tri[6], tri[7]]
+        }).collect();
+        let vgroups = polygons.get_vgroups().clone();
+        DynamicPolygonStorage::new(polygons.positions, polygons.normals, vec![], indices, vgroups)
+    }
+
+    // face normal of every triangle of the level
+    fn face_normals(data : &PolygonStorageData, lod : &LodLevel) -> Vec<([f64; 3], [f64; 3])>{
+        let stride = data.vertex_length();
+        let position = |id : u32| [0, 1, 2].map(|i| data.vertex_buffer_[id as usize*stride+i] as f64);
+        lod.index_buffers[&0].chunks(3).map(|tri| {
+            let p = [tri[0], tri[1], tri[2]].map(position);
+            let center = [0, 1, 2].map(|i| (p[0][i] + p[1][i] + p[2][i])/3.0);
+            (cross(sub(p[1], p[0]), sub(p[2], p[0])), center)
+        }).collect()
+    }
+
+    #[test]
+    fn reaches_target_without_flips(){
+        let sphere = without_uvs(primitives::icosphere(1.0, 3));
+        let data = sphere.get_data().unwrap();
+        assert_eq!(sphere.num_of_triangles(), 1280);
+
+        let lods = generate_lods(data, &[0.5, 0.25, 0.1]);
+        for lod in &lods{
+            // every collapse on a closed mesh removes two triangles
+            let target = (1280.0*lod.ratio) as usize;
+            let count = lod.index_buffers[&0].len()/3;
+            assert!(count <= target && count + 2 > target, "{} triangles for a target of {}", count, target);
+            for (normal, center) in face_normals(data, lod){
+                assert!(dot(normal, normal) > 1e-12);
+                assert!(dot(normal, center) > 0.0, "flipped triangle at {:?}", center);
+            }
+        }
+        assert_eq!(lods[1].max_screen_size, 0.5);
+
+        // the open border of a plane is kept, the inside is simplified without flipping
+        let plane = without_uvs(primitives::plane(2.0, 16));
+        let lods = generate_lods(plane.get_data().unwrap(), &[0.5]);
+        assert!(lods[0].index_buffers[&0].len()/3 <= 256);
+        for (normal, _) in face_normals(plane.get_data().unwrap(), &lods[0]){
+            assert!(normal[2] > 1e-6);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use compact_str::CompactString;
+
+use super::armature::BoneTransform;
+use super::light::{Light, Attenuation, AreaShape, ShadowSettings};
+use super::material::{Material, PbrMaterial, AlphaMode};
+use super::super::carbon::parser::{parse_string, Element};
+
+// files of other versions are rejected
+pub const SNAPSHOT_VERSION : i32 = 2;
+
+#[derive(Clone, Debug, Default)]
+pub struct ObjectState{
+    pub transform : BoneTransform,
+    pub visible : bool,
+}
+
+// parameters of a light without its type
+#[derive(Clone, Debug, Default)]
+pub struct LightState{
+    pub intensity : f32,
+    pub fov : f32,
+    pub inner_fov : f32,
+    pub range : f32,
+    pub color : [f32; 3],
+    pub attenuation : Attenuation,
+    pub area_shape : AreaShape,
+    pub shadow : Option<ShadowSettings>,
+    pub priority : u32,
+}
+
+impl LightState{
+    pub fn new(light : &Light) -> LightState{
+        LightState{
+            intensity : light.intensity,
+            fov : light.fov,
+            inner_fov : light.inner_fov,
+            range : light.range,
+            color : light.color,
+            attenuation : light.attenuation,
+            area_shape : light.area_shape,
+            shadow : light.shadow,
+            priority : light.priority,
+        }
+    }
+    pub fn apply(&self, light : &mut Light){
+        (light.intensity, light.fov, light.inner_fov, light.range, light.color) = (self.intensity, self.fov, self.inner_fov, self.range, self.color);
+        (light.attenuation, light.area_shape, light.shadow, light.priority) = (self.attenuation, self.area_shape, self.shadow, self.priority);
+    }
+}
+
+// parameters of a material without its id
+#[derive(Clone, Debug, Default)]
+pub struct MaterialState{
+    pub dif : [f32; 4],
+    pub scol : [f32; 3],
+    pub alpha : f32,
+    pub translucency : f32,
+    pub illuminosity : f32,
+    pub specular_intensity : f32,
+    pub specular_hardness : f32,
+    pub pbr : Option<PbrMaterial>,
+}
+
+impl MaterialState{
+    pub fn new(material : &Material) -> MaterialState{
+        MaterialState{
+            dif : material.dif_,
+            scol : material.scol,
+            alpha : material.alpha,
+            translucency : material.translucency,
+            illuminosity : material.illuminosity,
+            specular_intensity : material.specular_intensity,
+            specular_hardness : material.specular_hardness,
+            pbr : material.pbr.clone(),
+        }
+    }
+    pub fn apply(&self, material : &mut Material){
+        material.dif_ = self.dif;
+        material.scol = self.scol;
+        material.alpha = self.alpha;
+        material.translucency = self.translucency;
+        material.illuminosity = self.illuminosity;
+        material.specular_intensity = self.specular_intensity;
+        material.specular_hardness = self.specular_hardness;
+        material.pbr = self.pbr.clone();
+    }
+}
+
+// dynamic state of the scenegraph, restored on top of the same loaded worlds
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot{
+    pub worlds : Vec<CompactString>,
+    pub loaded_scene : Option<CompactString>,
+    pub loaded_viewport : Option<CompactString>,
+    pub objects : Vec<(CompactString, ObjectState)>,
+    pub lights : Vec<(CompactString, LightState)>,
+    pub materials : Vec<(CompactString, MaterialState)>,
+    pub user_data : Vec<(CompactString, CompactString)>,
+}
+
+// the lexer only knows floats with a decimal point
+fn float_str(value : f64) -> String{
+    let output = value.to_string();
+    if output.contains('.') || !value.is_finite() {output} else {output + ".0"}
+}
+fn float_list(values : impl Iterator<Item = f64>) -> String{
+    values.map(float_str).collect::<Vec<String>>().join("; ")
+}
+fn escape(value : &str) -> String{
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+fn unescape(value : &str) -> CompactString{
+    let mut output = CompactString::default();
+    // the lexer keeps the quotes of empty strings
+    if value == "\"\"" {
+        return output;
+    }
+    let mut chars = value.chars();
+    while let Some(c) = chars.next(){
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next(){
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some(other) => output.push(other),
+            None => {}
+        }
+    }
+    output
+}
+
+fn get_name(element : &Element) -> Result<CompactString, String>{
+    element.attributes_ref().get("name").and_then(|x| x.get_str()).map(unescape).ok_or("Snapshot element without a name.".to_string())
+}
+fn get_floats<const N : usize>(element : &Element, key : &str) -> Result<[f32; N], String>{
+    let values = element.assignments_ref().get(key).and_then(|x| x.get_float_list()).filter(|x| x.len() >= N);
+    let values = values.ok_or(format!("Snapshot element is missing \"{}\".", key))?;
+    Ok(std::array::from_fn(|i| values[i] as f32))
+}
+fn get_float(element : &Element, key : &str) -> Result<f32, String>{
+    let value = element.assignments_ref().get(key).and_then(|x| x.get_float());
+    Ok(value.ok_or(format!("Snapshot element is missing \"{}\".", key))? as f32)
+}
+fn get_u32(element : &Element, key : &str) -> Result<u32, String>{
+    let value = element.assignments_ref().get(key).and_then(|x| x.get_int()).and_then(|x| u32::try_from(x).ok());
+    value.ok_or(format!("Snapshot element is missing \"{}\".", key))
+}
+fn get_str<'a>(element : &'a Element, key : &str) -> Result<&'a str, String>{
+    element.assignments_ref().get(key).and_then(|x| x.get_str()).ok_or(format!("Snapshot element is missing \"{}\".", key))
+}
+
+fn get_light(element : &Element) -> Result<LightState, String>{
+    let attenuation = match get_str(element, "attenuation")?{
+        "none" => Attenuation::None,
+        "linear" => Attenuation::Linear,
+        "inverse_square" => Attenuation::InverseSquare,
+        "custom" => Attenuation::Custom{
+            constant : get_float(element, "attenuation_constant")?,
+            linear : get_float(element, "attenuation_linear")?,
+            quadratic : get_float(element, "attenuation_quadratic")?,
+        },
+        other => return Err(format!("Snapshot light has unknown attenuation \"{}\".", other)),
+    };
+    let area_shape = match get_str(element, "area_shape")?{
+        "rectangle" => AreaShape::Rectangle{width : get_float(element, "area_width")?, height : get_float(element, "area_height")?},
+        "disc" => AreaShape::Disc{radius : get_float(element, "area_radius")?},
+        other => return Err(format!("Snapshot light has unknown area shape \"{}\".", other)),
+    };
+    let shadow = match element.assignments_ref().get("shadow").and_then(|x| x.get_int()).unwrap_or(0) != 0 {
+        true => Some(ShadowSettings{
+            resolution : get_u32(element, "shadow_resolution")?,
+            bias : get_float(element, "shadow_bias")?,
+            normal_bias : get_float(element, "shadow_normal_bias")?,
+            cascades : get_u32(element, "shadow_cascades")?,
+            split_lambda : get_float(element, "shadow_split_lambda")?,
+            max_distance : get_float(element, "shadow_distance")?,
+            near : get_float(element, "shadow_near")?,
+        }),
+        false => None,
+    };
+    Ok(LightState{
+        intensity : get_float(element, "intensity")?,
+        fov : get_float(element, "fov")?,
+        inner_fov : get_float(element, "inner_fov")?,
+        range : get_float(element, "range")?,
+        color : get_floats(element, "color")?,
+        attenuation,
+        area_shape,
+        shadow,
+        priority : get_u32(element, "priority")?,
+    })
+}
+// same assignments as the lights of world files
+fn light_csl(state : &LightState) -> String{
+    let mut output = format!("intensity = {} fov = {} inner_fov = {} range = {} color = {{{}}} priority = {} attenuation = \"{}\" ",
+        float_str(state.intensity as f64), float_str(state.fov as f64), float_str(state.inner_fov as f64), float_str(state.range as f64),
+        float_list(state.color.iter().map(|x| *x as f64)), state.priority, state.attenuation.name());
+    if let Attenuation::Custom{constant, linear, quadratic} = state.attenuation {
+        output += &format!("attenuation_constant = {} attenuation_linear = {} attenuation_quadratic = {} ",
+            float_str(constant as f64), float_str(linear as f64), float_str(quadratic as f64));
+    }
+    output += &match state.area_shape {
+        AreaShape::Rectangle{width, height} => format!("area_shape = \"rectangle\" area_width = {} area_height = {} ", float_str(width as f64), float_str(height as f64)),
+        AreaShape::Disc{radius} => format!("area_shape = \"disc\" area_radius = {} ", float_str(radius as f64)),
+    };
+    if let Some(shadow) = &state.shadow {
+        output += &format!("shadow = 1 shadow_resolution = {} shadow_bias = {} shadow_normal_bias = {} shadow_cascades = {} shadow_split_lambda = {} shadow_distance = {} shadow_near = {} ",
+            shadow.resolution, float_str(shadow.bias as f64), float_str(shadow.normal_bias as f64), shadow.cascades,
+            float_str(shadow.split_lambda as f64), float_str(shadow.max_distance as f64), float_str(shadow.near as f64));
+    }
+    output
+}
+
+fn get_pbr(element : &Element) -> Result<Option<PbrMaterial>, String>{
+    if element.assignments_ref().get("model").and_then(|x| x.get_str()) != Some("pbr") {
+        return Ok(None);
+    }
+    let alpha_mode = element.assignments_ref().get("alpha_mode").and_then(|x| x.get_str()).and_then(AlphaMode::from_name);
+    Ok(Some(PbrMaterial{
+        base_color : get_floats(element, "base_color")?,
+        metallic : get_float(element, "metallic")?,
+        roughness : get_float(element, "roughness")?,
+        emissive : get_floats(element, "emissive")?,
+        occlusion : get_float(element, "occlusion")?,
+        alpha_mode : alpha_mode.ok_or("Snapshot material is missing \"alpha_mode\".".to_string())?,
+        alpha_cutoff : get_float(element, "alpha_cutoff")?,
+        double_sided : element.assignments_ref().get("double_sided").and_then(|x| x.get_int()).unwrap_or(0) != 0,
+    }))
+}
+
+impl Snapshot{
+    pub fn to_csl(&self) -> String{
+        let mut output = format!("<Snapshot version={}>\n", SNAPSHOT_VERSION);
+        if !self.worlds.is_empty() {
+            let worlds : Vec<String> = self.worlds.iter().map(|x| format!("\"{}\"", escape(x))).collect();
+            output += &format!("worlds = {{{}}}\n", worlds.join("; "));
+        }
+        if let Some(scene) = &self.loaded_scene {
+            output += &format!("loaded_scene = \"{}\"\n", escape(scene));
+        }
+        if let Some(viewport) = &self.loaded_viewport {
+            output += &format!("loaded_viewport = \"{}\"\n", escape(viewport));
+        }
+        for (name, state) in &self.objects{
+            output += &format!("<Object name=\"{}\" visible={}> current_state = {{{}}} </Object>\n",
+                escape(name), state.visible as i32, float_list(state.transform.to_state().into_iter()));
+        }
+        for (name, state) in &self.lights{
+            output += &format!("<Light name=\"{}\"> {}</Light>\n", escape(name), light_csl(state));
+        }
+        for (name, state) in &self.materials{
+            let pbr = state.pbr.as_ref().map(|pbr| format!("model = \"pbr\" base_color = {{{}}} metallic = {} roughness = {} emissive = {{{}}} occlusion = {} alpha_mode = \"{}\" alpha_cutoff = {} double_sided = {} ",
+                float_list(pbr.base_color.iter().map(|x| *x as f64)), float_str(pbr.metallic as f64), float_str(pbr.roughness as f64),
+                float_list(pbr.emissive.iter().map(|x| *x as f64)), float_str(pbr.occlusion as f64), pbr.alpha_mode.name(),
+                float_str(pbr.alpha_cutoff as f64), pbr.double_sided as i32)).unwrap_or_default();
+            output += &format!("<Material name=\"{}\"> dif = {{{}}} scol = {{{}}} alpha = {} translucency = {} illuminosity = {} specular_intensity = {} specular_hardness = {} {}</Material>\n",
+                escape(name), float_list(state.dif.iter().map(|x| *x as f64)), float_list(state.scol.iter().map(|x| *x as f64)),
+                float_str(state.alpha as f64), float_str(state.translucency as f64), float_str(state.illuminosity as f64),
+                float_str(state.specular_intensity as f64), float_str(state.specular_hardness as f64), pbr);
+        }
+        for (name, value) in &self.user_data{
+            output += &format!("<UserData name=\"{}\"> value = \"{}\" </UserData>\n", escape(name), escape(value));
+        }
+        output + "</Snapshot>\n"
+    }
+
+    pub fn from_csl(input_str : &str) -> Result<Snapshot, String>{
+        let element = parse_string(input_str)?;
+        let version = element.attributes_ref().get("version").and_then(|x| x.get_int());
+        if version != Some(SNAPSHOT_VERSION) {
+            return Err(format!("Snapshot version {:?} is not supported, expected {}.", version, SNAPSHOT_VERSION));
+        }
+
+        let mut output = Snapshot{
+            worlds : element.assignments_ref().get("worlds").and_then(|x| x.get_str_list()).map(|x| x.iter().map(|x| unescape(x)).collect()).unwrap_or_default(),
+            loaded_scene : element.assignments_ref().get("loaded_scene").and_then(|x| x.get_str()).map(unescape),
+            loaded_viewport : element.assignments_ref().get("loaded_viewport").and_then(|x| x.get_str()).map(unescape),
+            ..Default::default()
+        };
+        for object_e in element.get_elements("Object").iter().filter_map(|x| x.get()){
+            let state = object_e.assignments_ref().get("current_state").and_then(|x| x.get_float_list()).and_then(|x| BoneTransform::from_state(x));
+            output.objects.push((get_name(object_e)?, ObjectState{
+                transform : state.ok_or("Snapshot object is missing \"current_state\".".to_string())?,
+                visible : object_e.attributes_ref().get("visible").and_then(|x| x.get_int()).unwrap_or(1) != 0,
+            }));
+        }
+        for light_e in element.get_elements("Light").iter().filter_map(|x| x.get()){
+            output.lights.push((get_name(light_e)?, get_light(light_e)?));
+        }
+        for material_e in element.get_elements("Material").iter().filter_map(|x| x.get()){
+            output.materials.push((get_name(material_e)?, MaterialState{
+                dif : get_floats(material_e, "dif")?,
+                scol : get_floats(material_e, "scol")?,
+                alpha : get_float(material_e, "alpha")?,
+                translucency : get_float(material_e, "translucency")?,
+                illuminosity : get_float(material_e, "illuminosity")?,
+                specular_intensity : get_float(material_e, "specular_intensity")?,
+                specular_hardness : get_float(material_e, "specular_hardness")?,
+                pbr : get_pbr(material_e)?,
+            }));
+        }
+        for data_e in element.get_elements("UserData").iter().filter_map(|x| x.get()){
+            let value = data_e.assignments_ref().get("value").and_then(|x| x.get_str()).map(unescape).unwrap_or_default();
+            output.user_data.push((get_name(data_e)?, value));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+pub mod snapshottest{
+    use super::*;
+
+    fn snapshot() -> Snapshot{
+        let shadow = ShadowSettings{resolution : 512, cascades : 2, ..Default::default()};
+        Snapshot{
+            worlds : vec!["world.csl".into()],
+            loaded_scene : Some("scene".into()),
+            loaded_viewport : None,
+            objects : vec![("object \"quoted\"".into(), ObjectState{
+                transform : BoneTransform{pos : [1.0, -2.5, 3.0], rot : [0.0, 0.0, 0.0, 1.0], sca : [2.0; 3]},
+                visible : false,
+            })],
+            lights : vec![
+                ("sun".into(), LightState{intensity : 3.0, fov : 0.5, inner_fov : 0.25, range : 100.0, color : [1.0, 0.5, 0.25],
+                    attenuation : Attenuation::Custom{constant : 1.0, linear : 0.5, quadratic : 0.125}, area_shape : AreaShape::Disc{radius : 2.0},
+                    shadow : Some(shadow), priority : 3}),
+                ("lamp".into(), LightState{intensity : 1.0, range : 10.0, attenuation : Attenuation::Linear, ..Default::default()}),
+            ],
+            materials : vec![("material".into(), MaterialState{dif : [0.5; 4], alpha : 1.0, ..Default::default()})],
+            user_data : vec![("key".into(), "line\nbreak".into())],
+        }
+    }
+
+    #[test]
+    fn save_load_round_trip(){
+        let saved = snapshot();
+        let loaded = Snapshot::from_csl(&saved.to_csl()).unwrap();
+        assert_eq!(loaded.to_csl(), saved.to_csl());
+
+        assert_eq!(loaded.objects[0].0, "object \"quoted\"");
+        assert_eq!(loaded.objects[0].1.transform.pos, [1.0, -2.5, 3.0]);
+        assert!(!loaded.objects[0].1.visible);
+        let (sun, lamp) = (&loaded.lights[0].1, &loaded.lights[1].1);
+        assert_eq!(sun.inner_fov, 0.25);
+        assert_eq!(sun.attenuation, Attenuation::Custom{constant : 1.0, linear : 0.5, quadratic : 0.125});
+        assert_eq!(sun.area_shape, AreaShape::Disc{radius : 2.0});
+        assert_eq!(sun.shadow, saved.lights[0].1.shadow);
+        assert_eq!(sun.priority, 3);
+        assert_eq!(lamp.attenuation, Attenuation::Linear);
+        assert!(lamp.shadow.is_none());
+        assert_eq!(loaded.user_data[0].1, "line\nbreak");
+    }
+
+    #[test]
+    fn reject_invalid_input(){
+        let saved = snapshot().to_csl();
+        let invalid = [
+            String::new(),
+            "\n\n".to_string(),
+            "no snapshot".to_string(),
+            "<Snapshot version=2>".to_string(),
+            "<Snapshot>\n</Snapshot>\n".to_string(),
+            saved.replace(&format!("version={}", SNAPSHOT_VERSION), "version=1"),
+            saved.replace("priority = 3", "priority = -3"),
+            saved.replace("\"custom\"", "\"cubic\""),
+            saved[..saved.len() / 2].to_string(),
+        ];
+        for input in invalid{
+            assert!(Snapshot::from_csl(&input).is_err(), "{}", input);
+        }
+    }
+}
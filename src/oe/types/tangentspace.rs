@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use super::polygonstoragetrait::PolygonStorageData;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalMode{
+    // one normal per triangle
+    Flat,
+    // weighted by triangle area
+    #[default]
+    Smooth,
+    // weighted by the angle of the triangle at the vertex
+    AngleWeighted,
+}
+
+impl NormalMode{
+    pub fn from_name(name : &str) -> Option<NormalMode>{
+        match name{
+            "flat" => Some(NormalMode::Flat),
+            "smooth" => Some(NormalMode::Smooth),
+            "angle" => Some(NormalMode::AngleWeighted),
+            _ => None
+        }
+    }
+}
+
+type Vec3 = [f32; 3];
+
+fn sub(a : Vec3, b : Vec3) -> Vec3 {[a[0]-b[0], a[1]-b[1], a[2]-b[2]]}
+fn dot(a : Vec3, b : Vec3) -> f32 {a[0]*b[0] + a[1]*b[1] + a[2]*b[2]}
+fn cross(a : Vec3, b : Vec3) -> Vec3 {[a[1]*b[2]-a[2]*b[1], a[2]*b[0]-a[0]*b[2], a[0]*b[1]-a[1]*b[0]]}
+fn normalize(a : Vec3) -> Vec3{
+    let length = dot(a, a).sqrt();
+    if length > 1e-12 {a.map(|x| x/length)} else {[0.0; 3]}
+}
+fn corner_angle(a : Vec3, b : Vec3, c : Vec3) -> f32{
+    dot(normalize(sub(b, a)), normalize(sub(c, a))).clamp(-1.0, 1.0).acos()
+}
+fn get_vec3(data : &[f32], id : usize) -> Vec3{
+    [data[id*3], data[id*3+1], data[id*3+2]]
+}
+
+// indices are laid out like the triangle indices of the polygon storages, stride values per corner starting with the position index
+// returns the normals and the normal index of every corner, corners whose faces differ more than the crease angle (in degrees) are not smoothed together
+pub fn generate_normals(positions : &[f32], indices : &[u32], stride : usize, mode : NormalMode, crease_angle : Option<f32>) -> (Vec<f32>, Vec<u32>){
+    let num_of_corners = indices.len()/stride;
+    let position_id = |corner : usize| indices[corner*stride] as usize;
+
+    let mut face_normals = Vec::with_capacity(num_of_corners/3);
+    let mut corner_weights = Vec::with_capacity(num_of_corners);
+    for tri in 0..num_of_corners/3{
+        let p = [0, 1, 2].map(|corner| get_vec3(positions, position_id(tri*3 + corner)));
+        let normal = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+        face_normals.push(normalize(normal));
+        for corner in 0..3{
+            corner_weights.push(match mode{
+                NormalMode::AngleWeighted => corner_angle(p[corner], p[(corner+1)%3], p[(corner+2)%3]),
+                // the length of the cross product is twice the area
+                _ => dot(normal, normal).sqrt(),
+            });
+        }
+    }
+
+    let mut normals : Vec<f32> = vec![];
+    let mut normal_ids = vec![0; num_of_corners];
+    if mode == NormalMode::Flat {
+        // coplanar triangles share their normal
+        let mut created : HashMap<[u32; 3], u32> = HashMap::new();
+        for (tri, normal) in face_normals.iter().enumerate(){
+            let id = *created.entry(normal.map(|x| (x + 0.0).to_bits())).or_insert_with(|| {
+                normals.extend_from_slice(normal);
+                (normals.len()/3 - 1) as u32
+            });
+            normal_ids[tri*3..tri*3+3].fill(id);
+        }
+        return (normals, normal_ids);
+    }
+
+    let mut position_corners : Vec<Vec<usize>> = vec![vec![]; positions.len()/3];
+    for corner in 0..num_of_corners{
+        position_corners[position_id(corner)].push(corner);
+    }
+    let min_cos = crease_angle.map(|x| x.to_radians().cos()).unwrap_or(-1.0);
+    for corners in position_corners{
+        // normals already created for this position, equal normals are shared
+        let mut created : Vec<(Vec3, u32)> = vec![];
+        for corner in &corners{
+            let face = face_normals[corner/3];
+            let mut normal = [0.0; 3];
+            for other in &corners{
+                let other_face = face_normals[other/3];
+                if other == corner || dot(face, other_face) >= min_cos {
+                    for axis in 0..3{
+                        normal[axis] += other_face[axis]*corner_weights[*other];
+                    }
+                }
+            }
+            let mut normal = normalize(normal);
+            if normal == [0.0; 3] {
+                normal = if face == [0.0; 3] {[0.0, 0.0, 1.0]} else {face};
+            }
+            let id = match created.iter().find(|x| x.0 == normal){
+                Some((_, id)) => *id,
+                None => {
+                    let id = (normals.len()/3) as u32;
+                    normals.extend_from_slice(&normal);
+                    created.push((normal, id));
+                    id
+                }
+            };
+            normal_ids[*corner] = id;
+        }
+    }
+    (normals, normal_ids)
+}
+
+// tangent of every vertex in the vertex buffer, xyz is orthogonal to the normal and w is the sign of the bitangent like in MikkTSpace
+// the vertex buffer must not contain tangents yet
+pub fn generate_tangents(data : &PolygonStorageData, uvmap : usize) -> Vec<f32>{
+    let stride = 6 + data.num_of_uvs as usize*2;
+    let num_of_vertices = data.vertex_buffer_.len()/stride;
+
+    let mut sums = vec![([0.0f32; 3], [0.0f32; 3]); num_of_vertices];
+    for index_buffer in data.index_buffers_.values(){
+        for tri in index_buffer.chunks(3){
+            let ids = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let Some(corners) = triangle_tangents(&data.vertex_buffer_, stride, uvmap, ids) else {continue;};
+            for (id, (tangent, bitangent)) in ids.into_iter().zip(corners){
+                for axis in 0..3{
+                    sums[id].0[axis] += tangent[axis];
+                    sums[id].1[axis] += bitangent[axis];
+                }
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(num_of_vertices*4);
+    for (id, (tangent, bitangent)) in sums.into_iter().enumerate(){
+        output.extend_from_slice(&finish_tangent(&data.vertex_buffer_[id*stride+3..id*stride+6], tangent, bitangent));
+    }
+    output
+}
+
+// recomputes the tangents of the given vertices in a vertex buffer that already contains tangents,
+// the triangles of a vertex are given as vertex ids and have to be listed once per index buffer that contains them
+pub fn update_tangents(data : &mut PolygonStorageData, uvmap : usize, vertices : impl Iterator<Item=(u32, Vec<[u32; 3]>)>){
+    let stride = data.vertex_length();
+    let mut output = vec![];
+    for (vertex, triangles) in vertices{
+        let (mut tangent, mut bitangent) = ([0.0f32; 3], [0.0f32; 3]);
+        for tri in triangles{
+            let ids = tri.map(|x| x as usize);
+            let Some(corners) = triangle_tangents(&data.vertex_buffer_, stride, uvmap, ids) else {continue;};
+            let Some(corner) = tri.iter().position(|x| *x == vertex) else {continue;};
+            for axis in 0..3{
+                tangent[axis] += corners[corner].0[axis];
+                bitangent[axis] += corners[corner].1[axis];
+            }
+        }
+        let id = vertex as usize*stride;
+        output.push((id, finish_tangent(&data.vertex_buffer_[id+3..id+6], tangent, bitangent)));
+    }
+    // tangents come last in every vertex
+    for (id, tangent) in output{
+        data.vertex_buffer_[id+stride-4..id+stride].copy_from_slice(&tangent);
+    }
+}
+
+// angle weighted tangent and bitangent of every corner projected onto the plane of its normal, None if the uvs are degenerate
+fn triangle_tangents(vertices : &[f32], stride : usize, uvmap : usize, ids : [usize; 3]) -> Option<[(Vec3, Vec3); 3]>{
+    let position = |id : usize| -> Vec3 {[vertices[id*stride], vertices[id*stride+1], vertices[id*stride+2]]};
+    let normal = |id : usize| -> Vec3 {[vertices[id*stride+3], vertices[id*stride+4], vertices[id*stride+5]]};
+    let uv = |id : usize| -> [f32; 2] {[vertices[id*stride+6+uvmap*2], vertices[id*stride+7+uvmap*2]]};
+
+    let p = ids.map(position);
+    let t = ids.map(uv);
+    let (e1, e2) = (sub(p[1], p[0]), sub(p[2], p[0]));
+    let (du1, dv1, du2, dv2) = (t[1][0]-t[0][0], t[1][1]-t[0][1], t[2][0]-t[0][0], t[2][1]-t[0][1]);
+    let det = du1*dv2 - du2*dv1;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let face_tangent = [0, 1, 2].map(|axis| (e1[axis]*dv2 - e2[axis]*dv1)/det);
+    let face_bitangent = [0, 1, 2].map(|axis| (e2[axis]*du1 - e1[axis]*du2)/det);
+
+    Some([0, 1, 2].map(|corner| {
+        let weight = corner_angle(p[corner], p[(corner+1)%3], p[(corner+2)%3]);
+        let n = normal(ids[corner]);
+        let tangent = normalize(sub(face_tangent, n.map(|x| x*dot(n, face_tangent))));
+        let bitangent = normalize(sub(face_bitangent, n.map(|x| x*dot(n, face_bitangent))));
+        (tangent.map(|x| x*weight), bitangent.map(|x| x*weight))
+    }))
+}
+
+fn finish_tangent(normal : &[f32], tangent : Vec3, bitangent : Vec3) -> [f32; 4]{
+    let n = [normal[0], normal[1], normal[2]];
+    let mut tangent = normalize(sub(tangent, n.map(|x| x*dot(n, tangent))));
+    if tangent == [0.0; 3] {
+        // no usable UVs, any direction orthogonal to the normal
+        let axis = if n[0].abs() < 0.9 {[1.0, 0.0, 0.0]} else {[0.0, 1.0, 0.0]};
+        tangent = normalize(cross(axis, n));
+    }
+    let sign = if dot(cross(n, tangent), bitangent) < 0.0 {-1.0} else {1.0};
+    [tangent[0], tangent[1], tangent[2], sign]
+}
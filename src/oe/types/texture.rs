@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::sync::atomic::{Ordering, AtomicUsize};
+use compact_str::CompactString;
+
+use super::super::carbon::image::Image;
+
+// the image levels are shared, so copies of the texture are cheap
+#[derive(Default, Debug, Clone)]
+pub struct Texture {
+    id_ : usize,
+    // file the image was decoded from, empty for generated images
+    pub path : CompactString,
+    pub mipmaps : bool,
+    levels_ : Arc<Vec<Image>>,
+}
+
+impl Texture{
+    pub fn new() -> Texture{
+        static ID_COUNT : AtomicUsize = AtomicUsize::new(1);
+        Texture{
+            id_ : ID_COUNT.fetch_add(1, Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+
+    pub fn id(&self) -> usize{
+        self.id_
+    }
+
+    pub fn set_image(&mut self, image : Image){
+        self.levels_ = Arc::new(if self.mipmaps {image.generate_mipmaps()} else {vec![image]});
+    }
+    // the full image followed by the mipmaps if they are generated
+    pub fn get_levels(&self) -> Arc<Vec<Image>>{
+        self.levels_.clone()
+    }
+    pub fn get_size(&self) -> (u32, u32){
+        self.levels_.first().map(|x| (x.width, x.height)).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureSlotType{
+    Diffuse,
+    Normal,
+    Specular,
+    Emissive,
+}
+
+pub const TEXTURE_SLOTS : usize = 4;
+
+impl TextureSlotType{
+    pub const ALL : [TextureSlotType; TEXTURE_SLOTS] = [TextureSlotType::Diffuse, TextureSlotType::Normal, TextureSlotType::Specular, TextureSlotType::Emissive];
+
+    // prefix of the slot assignments of csl materials
+    pub fn name(&self) -> &'static str{
+        match self{
+            TextureSlotType::Diffuse => "diffuse",
+            TextureSlotType::Normal => "normal",
+            TextureSlotType::Specular => "specular",
+            TextureSlotType::Emissive => "emissive",
+        }
+    }
+}
+
+// texture of a material slot with the uv map of the mesh it is sampled with
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextureSlot{
+    pub texture : CompactString,
+    pub uvmap : u8,
+}
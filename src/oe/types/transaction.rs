@@ -0,0 +1,49 @@
+use std::sync::{Arc, Mutex};
+use compact_str::CompactString;
+
+use super::object_trait::*;
+use super::viewport::ViewPort;
+use super::armature::BoneTransform;
+use super::elementcontainer::SingleElement;
+
+// a single scenegraph edit, applying it gives the edits that revert it
+#[derive(Debug, Clone)]
+pub enum EditOperation{
+    AddObject{name : CompactString, scenes : Vec<CompactString>, object : Arc<SingleElement<Box<dyn ObjectTrait>>>},
+    // children of the object are removed as well and added again when it is undone
+    RemoveObject{name : CompactString},
+    SetTransform{name : CompactString, transform : BoneTransform},
+    // None removes the material from the vertex group
+    SetMaterial{mesh : CompactString, vgroup : CompactString, material : Option<CompactString>},
+    // replaces cameras, layers and split screen settings, the viewport keeps its id
+    SetViewport{name : CompactString, viewport : ViewPort},
+}
+
+// edits that are applied, undone and redone together
+#[derive(Debug, Default, Clone)]
+pub struct Transaction{
+    pub name : CompactString,
+    pub operations : Vec<EditOperation>,
+}
+
+impl Transaction{
+    pub fn new(name : &str) -> Transaction{
+        Transaction{name : name.into(), operations : vec![]}
+    }
+
+    pub fn add_object(&mut self, scene_name : &str, name : &str, object : Box<dyn ObjectTrait>){
+        self.operations.push(EditOperation::AddObject{name : name.into(), scenes : vec![scene_name.into()], object : Arc::new(Mutex::new((object, true)))});
+    }
+    pub fn remove_object(&mut self, name : &str){
+        self.operations.push(EditOperation::RemoveObject{name : name.into()});
+    }
+    pub fn set_transform(&mut self, name : &str, transform : BoneTransform){
+        self.operations.push(EditOperation::SetTransform{name : name.into(), transform});
+    }
+    pub fn set_material(&mut self, mesh_name : &str, vgroup_name : &str, material_name : Option<&str>){
+        self.operations.push(EditOperation::SetMaterial{mesh : mesh_name.into(), vgroup : vgroup_name.into(), material : material_name.map(|x| x.into())});
+    }
+    pub fn set_viewport(&mut self, name : &str, viewport : ViewPort){
+        self.operations.push(EditOperation::SetViewport{name : name.into(), viewport});
+    }
+}
@@ -0,0 +1,298 @@
+use super::polygonstoragetrait::PolygonStorageData;
+
+// cache size used to measure the ACMR, common hardware is at least this large
+pub const ACMR_CACHE_SIZE : usize = 16;
+
+// constants from Tom Forsyth's "Linear-Speed Vertex Cache Optimisation"
+const FORSYTH_CACHE_SIZE : usize = 32;
+const CACHE_DECAY_POWER : f32 = 1.5;
+const LAST_TRIANGLE_SCORE : f32 = 0.75;
+const VALENCE_BOOST_SCALE : f32 = 2.0;
+const VALENCE_BOOST_POWER : f32 = 0.5;
+
+// average cache miss ratio, the number of transformed vertices per triangle, before and after optimizing
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheOptimizationReport{
+    pub acmr_before : f32,
+    pub acmr_after : f32,
+}
+
+// simulates a FIFO post transform cache, returns the cache misses of every triangle
+fn simulate_fifo(indices : &[u32], cache_size : usize) -> Vec<u8>{
+    let mut cache : std::collections::VecDeque<u32> = Default::default();
+    indices.chunks(3).map(|tri| {
+        let mut misses = 0;
+        for vertex in tri{
+            if !cache.contains(vertex) {
+                misses += 1;
+                cache.push_back(*vertex);
+                if cache.len() > cache_size {
+                    cache.pop_front();
+                }
+            }
+        }
+        misses
+    }).collect()
+}
+
+pub fn compute_acmr(indices : &[u32], cache_size : usize) -> f32{
+    if indices.len() < 3 {
+        return 0.0;
+    }
+    simulate_fifo(indices, cache_size).iter().map(|x| *x as f32).sum::<f32>()/(indices.len()/3) as f32
+}
+
+fn vertex_score(cache_position : Option<usize>, remaining_valence : u32) -> f32{
+    if remaining_valence == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position{
+        // the vertices of the last triangle get a fixed score so triangles are not picked from the same fan over and over
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => (1.0 - (position - 3) as f32/(FORSYTH_CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER),
+        None => 0.0,
+    };
+    cache_score + VALENCE_BOOST_SCALE*(remaining_valence as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+// reorders the triangles of an index buffer for post transform cache locality
+pub fn optimize_vertex_cache(indices : &[u32], num_of_vertices : usize) -> Vec<u32>{
+    let num_of_triangles = indices.len()/3;
+    let mut vertex_triangles : Vec<Vec<usize>> = vec![vec![]; num_of_vertices];
+    for (tri, vertices) in indices.chunks(3).enumerate(){
+        for vertex in vertices{
+            vertex_triangles[*vertex as usize].push(tri);
+        }
+    }
+    let mut cache_positions : Vec<Option<usize>> = vec![None; num_of_vertices];
+    let mut vertex_scores : Vec<f32> = vertex_triangles.iter().map(|x| vertex_score(None, x.len() as u32)).collect();
+    let mut triangle_scores : Vec<f32> = indices.chunks(3).map(|tri| tri.iter().map(|x| vertex_scores[*x as usize]).sum()).collect();
+    let mut added = vec![false; num_of_triangles];
+    let mut cache : Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(indices.len());
+    // when no vertex in the cache has triangles left, the most recently used vertices that still have some are tried,
+    // then the first triangle in input order, so dead ends do not need a scan over all triangles
+    let mut dead_end : Vec<u32> = vec![];
+    let mut input_cursor = 0;
+
+    let mut next = (0..num_of_triangles).max_by(|a, b| triangle_scores[*a].total_cmp(&triangle_scores[*b]));
+
+    while let Some(tri) = next{
+        added[tri] = true;
+        let vertices = &indices[tri*3..tri*3+3];
+        output.extend_from_slice(vertices);
+        dead_end.extend_from_slice(vertices);
+
+        for vertex in vertices{
+            vertex_triangles[*vertex as usize].retain(|x| *x != tri);
+        }
+        // most recently used vertices first
+        let mut new_cache : Vec<u32> = vertices.to_vec();
+        new_cache.extend(cache.iter().filter(|x| !vertices.contains(x)));
+        let evicted : Vec<u32> = new_cache.split_off(new_cache.len().min(FORSYTH_CACHE_SIZE));
+        for vertex in &evicted{
+            cache_positions[*vertex as usize] = None;
+        }
+        cache = new_cache;
+        for (position, vertex) in cache.iter().enumerate(){
+            cache_positions[*vertex as usize] = Some(position);
+        }
+
+        // rescore everything that was touched and pick the best triangle around the cache
+        next = None;
+        let mut best_score = f32::MIN;
+        for vertex in cache.iter().chain(evicted.iter()){
+            let vertex = *vertex as usize;
+            let new_score = vertex_score(cache_positions[vertex], vertex_triangles[vertex].len() as u32);
+            let difference = new_score - vertex_scores[vertex];
+            vertex_scores[vertex] = new_score;
+            for other in &vertex_triangles[vertex]{
+                triangle_scores[*other] += difference;
+            }
+        }
+        for vertex in &cache{
+            for other in &vertex_triangles[*vertex as usize]{
+                if triangle_scores[*other] > best_score {
+                    best_score = triangle_scores[*other];
+                    next = Some(*other);
+                }
+            }
+        }
+        while next.is_none() {
+            let Some(vertex) = dead_end.pop() else {break;};
+            next = vertex_triangles[vertex as usize].iter().copied().max_by(|a, b| triangle_scores[*a].total_cmp(&triangle_scores[*b]));
+        }
+        if next.is_none() {
+            while input_cursor < num_of_triangles && added[input_cursor] {
+                input_cursor += 1;
+            }
+            next = (input_cursor < num_of_triangles).then_some(input_cursor);
+        }
+    }
+    output
+}
+
+// sorts clusters of triangles so outward facing ones are drawn first, clusters start where the cache restarts so the ACMR barely changes
+pub fn optimize_overdraw(indices : &[u32], positions : impl Fn(u32) -> [f32; 3]) -> Vec<u32>{
+    let misses = simulate_fifo(indices, ACMR_CACHE_SIZE);
+    let mut clusters : Vec<(usize, usize)> = vec![];
+    for (tri, miss) in misses.iter().enumerate(){
+        if *miss == 3 || clusters.is_empty() {
+            clusters.push((tri, tri+1));
+        }
+        else {
+            clusters.last_mut().unwrap().1 = tri+1;
+        }
+    }
+
+    let triangle_data : Vec<([f32; 3], [f32; 3])> = indices.chunks(3).map(|tri| {
+        let p = [positions(tri[0]), positions(tri[1]), positions(tri[2])];
+        let e1 = [p[1][0]-p[0][0], p[1][1]-p[0][1], p[1][2]-p[0][2]];
+        let e2 = [p[2][0]-p[0][0], p[2][1]-p[0][1], p[2][2]-p[0][2]];
+        let normal = [e1[1]*e2[2]-e1[2]*e2[1], e1[2]*e2[0]-e1[0]*e2[2], e1[0]*e2[1]-e1[1]*e2[0]];
+        let centroid = [0, 1, 2].map(|axis| (p[0][axis] + p[1][axis] + p[2][axis])/3.0);
+        (centroid, normal)
+    }).collect();
+    let num_of_triangles = triangle_data.len().max(1) as f32;
+    let mesh_centroid = [0, 1, 2].map(|axis| triangle_data.iter().map(|x| x.0[axis]).sum::<f32>()/num_of_triangles);
+
+    let mut keys : Vec<(f32, (usize, usize))> = clusters.into_iter().map(|(start, end)| {
+        let mut centroid = [0.0; 3];
+        let mut normal = [0.0; 3];
+        for (tri_centroid, tri_normal) in &triangle_data[start..end]{
+            for axis in 0..3{
+                centroid[axis] += tri_centroid[axis]/(end - start) as f32;
+                normal[axis] += tri_normal[axis];
+            }
+        }
+        let length = (normal[0]*normal[0] + normal[1]*normal[1] + normal[2]*normal[2]).sqrt().max(1e-12);
+        let key = (0..3).map(|axis| (centroid[axis] - mesh_centroid[axis])*normal[axis]/length).sum::<f32>();
+        (key, (start, end))
+    }).collect();
+    keys.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keys.iter().flat_map(|(_, (start, end))| indices[start*3..end*3].iter().copied()).collect()
+}
+
+// optimizes all index buffers including the levels of detail and then orders the vertex buffer by first use
+pub fn optimize(data : &mut PolygonStorageData) -> CacheOptimizationReport{
+    let stride = data.vertex_length();
+    let num_of_vertices = data.vertex_buffer_.len()/stride;
+    let mut vgroup_ids : Vec<usize> = data.index_buffers_.keys().copied().collect();
+    vgroup_ids.sort();
+
+    let acmr = |data : &PolygonStorageData| -> f32{
+        let (misses, triangles) = vgroup_ids.iter().map(|id| {
+            let indices = &data.index_buffers_[id];
+            (compute_acmr(indices, ACMR_CACHE_SIZE)*(indices.len()/3) as f32, indices.len()/3)
+        }).fold((0.0, 0), |acc, x| (acc.0 + x.0, acc.1 + x.1));
+        if triangles == 0 {0.0} else {misses/triangles as f32}
+    };
+    let acmr_before = acmr(data);
+
+    let vertices = &data.vertex_buffer_;
+    let position = |id : u32| -> [f32; 3] {let id = id as usize*stride; [vertices[id], vertices[id+1], vertices[id+2]]};
+    let optimize_buffer = |indices : &Vec<u32>| -> Vec<u32>{
+        optimize_overdraw(&optimize_vertex_cache(indices, num_of_vertices), position)
+    };
+    let index_buffers : Vec<(usize, Vec<u32>)> = vgroup_ids.iter().map(|id| (*id, optimize_buffer(&data.index_buffers_[id]))).collect();
+    let lods : Vec<Vec<(usize, Vec<u32>)>> = data.lods.iter().map(|lod| {
+        vgroup_ids.iter().filter(|id| lod.index_buffers.contains_key(id)).map(|id| (*id, optimize_buffer(&lod.index_buffers[id]))).collect()
+    }).collect();
+    for (id, indices) in index_buffers{
+        data.index_buffers_.insert(id, indices);
+    }
+    for (lod, buffers) in data.lods.iter_mut().zip(lods){
+        for (id, indices) in buffers{
+            lod.index_buffers.insert(id, indices);
+        }
+    }
+
+    // vertex fetch order, unused vertices go last
+    let mut remap : Vec<Option<u32>> = vec![None; num_of_vertices];
+    let mut order : Vec<u32> = Vec::with_capacity(num_of_vertices);
+    let all_buffers = vgroup_ids.iter().map(|id| &data.index_buffers_[id])
+        .chain(data.lods.iter().flat_map(|lod| vgroup_ids.iter().filter_map(|id| lod.index_buffers.get(id))));
+    for indices in all_buffers{
+        for vertex in indices{
+            if remap[*vertex as usize].is_none() {
+                remap[*vertex as usize] = Some(order.len() as u32);
+                order.push(*vertex);
+            }
+        }
+    }
+    for (vertex, new_id) in remap.iter_mut().enumerate(){
+        if new_id.is_none() {
+            *new_id = Some(order.len() as u32);
+            order.push(vertex as u32);
+        }
+    }
+    data.vertex_buffer_ = order.iter().flat_map(|x| data.vertex_buffer_[*x as usize*stride..(*x as usize+1)*stride].iter().copied()).collect();
+    let apply_remap = |indices : &mut Vec<u32>| indices.iter_mut().for_each(|x| *x = remap[*x as usize].unwrap());
+    data.index_buffers_.values_mut().for_each(apply_remap);
+    data.lods.iter_mut().for_each(|lod| lod.index_buffers.values_mut().for_each(apply_remap));
+
+    CacheOptimizationReport{
+        acmr_before,
+        acmr_after : acmr(data),
+    }
+}
+
+#[cfg(test)]
+pub mod vertexcachetest{
+    use super::*;
+    use super::super::polygonstorage::StaticPolygonStorage;
+    use super::super::primitives;
+
+    // every triangle of the input is in the output exactly once
+    fn assert_same_triangles(a : &[u32], b : &[u32]){
+        let sorted = |indices : &[u32]| {
+            let mut output : Vec<[u32; 3]> = indices.chunks(3).map(|x| [x[0], x[1], x[2]]).collect();
+            output.sort();
+            output
+        };
+        assert_eq!(sorted(a), sorted(b));
+    }
+
+    #[test]
+    fn acmr(){
+        assert_eq!(compute_acmr(&[0, 1, 2, 2, 1, 3], 16), 2.0);
+        assert_eq!(compute_acmr(&[0, 1, 2, 3, 4, 5], 16), 3.0);
+        // the first vertex is evicted by the time it is used again
+        assert_eq!(compute_acmr(&[0, 1, 2, 3, 4, 5, 0, 6, 7], 4), 3.0);
+        assert_eq!(compute_acmr(&[], 16), 0.0);
+    }
+
+    #[test]
+    fn grid_acmr_does_not_increase(){
+        let grid = primitives::plane(1.0, 64);
+        let original = StaticPolygonStorage::new(grid.clone());
+        let optimized = StaticPolygonStorage::new_optimized(grid);
+        let report = optimized.get_cache_report().unwrap();
+        assert!(report.acmr_after <= report.acmr_before, "{:?}", report);
+        assert!(report.acmr_after < 1.0, "{:?}", report);
+        assert_eq!(original.data.vertex_buffer_.len(), optimized.data.vertex_buffer_.len());
+
+        // the reordered vertices still form the same triangles
+        let stride = original.data.vertex_length();
+        let key = |vertex : &[f32]| -> Vec<u32> {vertex.iter().map(|x| x.to_bits()).collect()};
+        let original_ids : std::collections::HashMap<Vec<u32>, u32> = original.data.vertex_buffer_.chunks(stride).enumerate().map(|(id, x)| (key(x), id as u32)).collect();
+        let corners = |data : &PolygonStorageData| -> Vec<u32>{
+            data.index_buffers_[&0].iter().map(|id| original_ids[&key(&data.vertex_buffer_[*id as usize*stride..*id as usize*stride+stride])]).collect()
+        };
+        assert_same_triangles(&corners(&original.data), &corners(&optimized.data));
+    }
+
+    #[test]
+    fn dead_ends(){
+        // separate triangles leave nothing in the cache to continue from
+        let soup : Vec<u32> = (0..3000).collect();
+        let output = optimize_vertex_cache(&soup, 3000);
+        assert_same_triangles(&soup, &output);
+        assert_eq!(compute_acmr(&output, ACMR_CACHE_SIZE), 3.0);
+
+        let indices = StaticPolygonStorage::new(primitives::icosphere(1.0, 3)).data.index_buffers_[&0].clone();
+        let output = optimize_vertex_cache(&indices, *indices.iter().max().unwrap() as usize + 1);
+        assert_same_triangles(&indices, &output);
+        assert!(compute_acmr(&output, ACMR_CACHE_SIZE) < compute_acmr(&indices, ACMR_CACHE_SIZE));
+    }
+}
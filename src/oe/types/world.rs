@@ -13,3 +13,14 @@ pub struct World{
     pub loaded_scene : CompactString,
     pub loaded_viewport : CompactString,
 }
+
+// names of the elements that were loaded from one file, so the world can be unloaded as a whole
+#[derive(Default, Debug, Clone)]
+pub struct WorldElements{
+    pub scenes : Vec<CompactString>,
+    pub objects : Vec<CompactString>,
+    pub materials : Vec<CompactString>,
+    pub viewports : Vec<CompactString>,
+    pub textures : Vec<CompactString>,
+    pub prefab_instances : Vec<CompactString>,
+}
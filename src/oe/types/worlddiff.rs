@@ -0,0 +1,136 @@
+use super::object_trait::*;
+use super::mesh::Mesh;
+use super::material::Material;
+use super::scene::Scene;
+use super::viewport::ViewPort;
+use super::texture::Texture;
+
+// elements of a reloaded file are compared by content, ids are different after every load
+
+fn same_common_data(a : &CommonObjectData, b : &CommonObjectData) -> bool{
+    a.parent == b.parent && a.visible == b.visible && a.type_ == b.type_ && a.pos == b.pos && a.rot == b.rot && a.sca == b.sca
+}
+
+// deformed meshes are always treated as changed
+fn same_mesh(a : &Mesh, b : &Mesh) -> bool{
+    if a.armature_.is_some() || b.armature_.is_some() || a.morph_targets_.is_some() || b.morph_targets_.is_some() {
+        return false;
+    }
+    if a.instance_of_ != b.instance_of_ || a.polygon_storage_.0 != b.polygon_storage_.0 {
+        return false;
+    }
+    let (a_polygons, b_polygons) = (a.get_polygonstorage_unlocked(), b.get_polygonstorage_unlocked());
+    let (a_storage, b_storage) = (a_polygons.0.as_ref(), b_polygons.0.as_ref());
+    if std::mem::discriminant(&a_storage.get_type()) != std::mem::discriminant(&b_storage.get_type()) {
+        return false;
+    }
+    if a_storage.get_vertex_layout() != b_storage.get_vertex_layout() || a_storage.get_dequantized_vertex_buffer() != b_storage.get_dequantized_vertex_buffer() {
+        return false;
+    }
+    let (a_lods, b_lods) = (a_storage.get_lods(), b_storage.get_lods());
+    if a_lods.len() != b_lods.len() || a_lods.iter().zip(b_lods).any(|(x, y)| x.ratio != y.ratio || x.max_screen_size != y.max_screen_size) {
+        return false;
+    }
+    let (a_vgroups, b_vgroups) = (a_storage.get_vgroups(), b_storage.get_vgroups());
+    a_vgroups.len() == b_vgroups.len() && a_vgroups.iter().zip(b_vgroups).all(|(x, y)| {
+        x.name == y.name && x.material == y.material && x.polygons == y.polygons && a_storage.get_data().and_then(|data| data.index_buffers_.get(&x.id)) == b_storage.get_data().and_then(|data| data.index_buffers_.get(&y.id))
+    })
+}
+
+pub fn same_object(a : &dyn ObjectTrait, b : &dyn ObjectTrait) -> bool{
+    if !same_common_data(a.get_data(), b.get_data()) {
+        return false;
+    }
+    match a.get_type(){
+        ObjectType::Mesh => same_mesh(&a.get_mesh().unwrap(), &b.get_mesh().unwrap()),
+        ObjectType::Light => {
+            let (a, b) = (a.get_light().unwrap(), b.get_light().unwrap());
+            a.get_type() as i32 == b.get_type() as i32 && a.intensity == b.intensity && a.fov == b.fov && a.range == b.range && a.color == b.color
+                && a.inner_fov == b.inner_fov && a.attenuation == b.attenuation && a.area_shape == b.area_shape && a.priority == b.priority
+                && a.shadow == b.shadow
+        }
+        ObjectType::Camera => {
+            let (a, b) = (a.get_camera().unwrap(), b.get_camera().unwrap());
+            a.aspect_ratio == b.aspect_ratio && a.fov == b.fov && a.near == b.near && a.far == b.far
+        }
+        ObjectType::Custom => false,
+    }
+}
+
+pub fn same_material(a : &Material, b : &Material) -> bool{
+    a.get_legacy_renderer_data() == b.get_legacy_renderer_data() && a.pbr == b.pbr && a.textures == b.textures
+}
+
+pub fn same_texture(a : &Texture, b : &Texture) -> bool{
+    a.path == b.path && a.mipmaps == b.mipmaps && a.get_levels() == b.get_levels()
+}
+
+pub fn same_scene(a : &Scene, b : &Scene) -> bool{
+    a.objects == b.objects && a.materials == b.materials
+}
+
+pub fn same_viewport(a : &ViewPort, b : &ViewPort) -> bool{
+    a.layers_ == b.layers_ && a.cameras_ == b.cameras_ && a.layer_combine_modes_ == b.layer_combine_modes_ && a.split_screen_positions_ == b.split_screen_positions_
+}
+
+#[cfg(test)]
+pub mod worlddifftest{
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use super::super::super::carbon::interpreter::interpret;
+
+    const STATE : &str = "current_state = {0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0} parent = \"\"";
+
+    // the only object of a world with a single scene
+    fn object(element : &str) -> Arc<Mutex<(Box<dyn ObjectTrait>, bool)>>{
+        let input = format!("<World> loaded_scene = \"s\" loaded_viewport = \"\"\n<Scene name = \"s\">\n{}\n</Scene>\n</World>", element);
+        let mut interpreter = interpret(&input).unwrap();
+        let objects = &interpreter.get_data().objects_;
+        objects.get(objects.keys().next().unwrap()).unwrap().clone()
+    }
+    fn mesh(vertices : &str, content : &str) -> Arc<Mutex<(Box<dyn ObjectTrait>, bool)>>{
+        object(&format!("<Mesh name = \"m\" visible = 1> vertices = {{{}}} {}\n<Triangle> v1 = {{0; 0}} v2 = {{1; 0}} v3 = {{2; 0}} </Triangle>\n{}</Mesh>", vertices, STATE, content))
+    }
+    fn light(parameters : &str) -> Arc<Mutex<(Box<dyn ObjectTrait>, bool)>>{
+        object(&format!("<Light name = \"l\" visible = 1> light_type = 1 fov = 1.0 range = 10.0 intensity = 1.0 color = {{1.0; 1.0; 1.0}} {} {} </Light>", STATE, parameters))
+    }
+    fn same(a : &Arc<Mutex<(Box<dyn ObjectTrait>, bool)>>, b : &Arc<Mutex<(Box<dyn ObjectTrait>, bool)>>) -> bool{
+        same_object(a.lock().unwrap().0.as_ref(), b.lock().unwrap().0.as_ref())
+    }
+
+    #[test]
+    fn objects(){
+        let vertices = "0.0; 0.0; 0.0; 1.0; 0.0; 0.0; 0.0; 1.0; 0.0";
+        assert!(same(&mesh(vertices, ""), &mesh(vertices, "")));
+        assert!(!same(&mesh(vertices, ""), &mesh("0.0; 0.0; 0.0; 2.0; 0.0; 0.0; 0.0; 1.0; 0.0", "")));
+        // deformed meshes are never the same
+        let armature = "<Armature name = \"a\">\n<Bone name = \"b\"> rest_state = {0.0; 0.0; 0.0; 0.0; 0.0; 0.0; 1.0; 1.0; 1.0; 1.0} </Bone>\n</Armature>\n";
+        assert!(!same(&mesh(vertices, armature), &mesh(vertices, armature)));
+
+        assert!(same(&light("attenuation = \"linear\""), &light("attenuation = \"linear\"")));
+        assert!(!same(&light("attenuation = \"linear\""), &light("attenuation = \"none\"")));
+        assert!(!same(&light(""), &light("shadow = 1")));
+        // objects of different types never match
+        let camera = || object(&format!("<Camera name = \"c\" visible = 1> aspect_ratio = 1.0 fov = 1.0 near = 0.1 far = 100.0 {} </Camera>", STATE));
+        assert!(same(&camera(), &camera()));
+        assert!(!same(&camera(), &light("")));
+    }
+
+    #[test]
+    fn materials_scenes_viewports(){
+        let (a, mut b) = (Material::new(), Material::new());
+        assert!(same_material(&a, &b));
+        b.alpha = 0.5;
+        assert!(!same_material(&a, &b));
+
+        let (a, mut b) = (Scene::new(), Scene::new());
+        assert!(same_scene(&a, &b));
+        b.objects.insert("object".into());
+        assert!(!same_scene(&a, &b));
+
+        let (a, mut b) = (ViewPort::new(), ViewPort::new());
+        assert!(same_viewport(&a, &b));
+        b.cameras_.push("camera".into());
+        assert!(!same_viewport(&a, &b));
+    }
+}